@@ -0,0 +1,148 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::{ConnectInfo, State},
+    http::{Request, header},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Target for access events, so they can be filtered on their own via `RUST_LOG`
+/// (e.g. `RUST_LOG=info,rustybin::access=warn` keeps only failed requests).
+pub const ACCESS_LOG_TARGET: &str = "rustybin::access";
+
+/// Emit one event per completed request: info for 2xx/3xx, warn for 4xx, error for 5xx.
+/// Successes on `quiet_paths` (e.g. health probes) are logged at debug instead.
+///
+/// Installed as the outermost layer so responses produced by other middleware
+/// (rate limiting, auth, CORS) are logged too.
+pub async fn access_log(State(quiet_paths): State<Arc<HashSet<String>>>, req: Request<Body>, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let quiet = quiet_paths.contains(&path);
+    let client_ip = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|connect_info| connect_info.0.ip().to_string())
+        .unwrap_or_else(|| "-".to_string());
+
+    let response = next.run(req).await;
+
+    let status = response.status().as_u16();
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    // Streamed bodies have no exact size up front; fall back to Content-Length
+    let bytes_sent = response.body().size_hint().exact().or_else(|| {
+        response
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+    });
+
+    macro_rules! access_event {
+        ($level:ident) => {
+            tracing::$level!(
+                target: ACCESS_LOG_TARGET,
+                method = %method,
+                path = %path,
+                status,
+                latency_ms,
+                client_ip = %client_ip,
+                bytes_sent,
+                "request completed"
+            )
+        };
+    }
+
+    match status {
+        500.. => access_event!(error),
+        400..=499 => access_event!(warn),
+        _ if quiet => access_event!(debug),
+        _ => access_event!(info),
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::StatusCode, middleware, routing::get};
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+    use tower::ServiceExt;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+
+    type AccessEvent = (tracing::Level, BTreeMap<String, String>);
+
+    // Level and fields of each access event seen while it's the default subscriber
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<AccessEvent>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Captured {
+        fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+            struct Fields<'a>(&'a mut BTreeMap<String, String>);
+            impl tracing::field::Visit for Fields<'_> {
+                fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                    self.0.insert(field.name().to_string(), format!("{:?}", value));
+                }
+            }
+
+            if event.metadata().target() == ACCESS_LOG_TARGET {
+                let mut fields = BTreeMap::new();
+                event.record(&mut Fields(&mut fields));
+                self.0.lock().unwrap().push((*event.metadata().level(), fields));
+            }
+        }
+    }
+
+    fn app(quiet_paths: &[&str]) -> Router {
+        let quiet_paths = Arc::new(quiet_paths.iter().map(|path| path.to_string()).collect());
+        Router::new()
+            .route("/v1/pastes/{id}", get(|| async { "hello" }))
+            .route("/v1/health", get(|| async { "ok" }))
+            .route("/v1/limited", get(|| async { StatusCode::TOO_MANY_REQUESTS }))
+            .route("/v1/broken", get(|| async { StatusCode::INTERNAL_SERVER_ERROR }))
+            .layer(middleware::from_fn_with_state(quiet_paths, access_log))
+    }
+
+    async fn events_for(app: Router, path: &str) -> Vec<AccessEvent> {
+        let captured = Captured::default();
+        let _default = tracing::subscriber::set_default(tracing_subscriber::registry().with(captured.clone()));
+        let mut request = Request::get(path).body(Body::empty()).unwrap();
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 7], 4000))));
+        app.oneshot(request).await.unwrap();
+        captured.0.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn a_request_logs_exactly_one_event_with_its_fields() {
+        let events = events_for(app(&[]), "/v1/pastes/abc").await;
+        assert_eq!(events.len(), 1, "{:?}", events);
+        let (level, fields) = &events[0];
+        assert_eq!(*level, tracing::Level::INFO);
+        assert_eq!(fields["method"], "GET");
+        assert_eq!(fields["path"], "/v1/pastes/abc");
+        assert_eq!(fields["status"], "200");
+        assert_eq!(fields["client_ip"], "192.0.2.7");
+        assert_eq!(fields["bytes_sent"], "5");
+        assert!(fields["latency_ms"].parse::<f64>().is_ok(), "{:?}", fields);
+    }
+
+    #[tokio::test]
+    async fn level_follows_the_status_and_quiet_paths() {
+        let cases = [
+            ("/v1/limited", tracing::Level::WARN),
+            ("/v1/broken", tracing::Level::ERROR),
+            ("/v1/health", tracing::Level::DEBUG),
+        ];
+        for (path, level) in cases {
+            let events = events_for(app(&["/v1/health"]), path).await;
+            assert_eq!(events.iter().map(|(level, _)| *level).collect::<Vec<_>>(), [level], "{}", path);
+        }
+    }
+}
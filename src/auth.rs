@@ -1,6 +1,6 @@
 use axum::{
     extract::Request,
-    http::StatusCode,
+    http::{Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
     Json,
@@ -72,8 +72,13 @@ fn extract_cookie(cookie_header: &str, name: &str) -> Option<String> {
 
 /// Axum middleware that requires a valid admin JWT in the `admin_token` cookie.
 ///
-/// Returns 401 Unauthorized if the token is missing, invalid, or expired.
+/// Returns 401 Unauthorized if the token is missing, invalid, or expired. `OPTIONS`
+/// passes through: no admin route serves it, so it only reaches the router's 405.
 pub async fn require_admin_auth(req: Request, next: Next) -> Result<Response, Response> {
+    if req.method() == Method::OPTIONS {
+        return Ok(next.run(req).await);
+    }
+
     let admin_secret = env::var("ADMIN_SECRET").unwrap_or_default();
 
     let cookie_header = req
@@ -0,0 +1,197 @@
+// Stored paste data is compressed in the LZ4 block format: sequences of literal
+// bytes followed by a copy of earlier output, each led by a token whose high
+// nibble is the literal count and low nibble the copy length less 4. A nibble of
+// 15 continues in bytes of 255 until a smaller one. Simple byte matching, no
+// entropy coding, so it's quick both ways; text shrinks, ciphertext barely does.
+
+// Shortest copy worth encoding, and the copy length a zero low nibble stands for
+const MIN_MATCH: usize = 4;
+
+// Copies reach at most this far back, since offsets are two bytes
+const MAX_OFFSET: usize = u16::MAX as usize;
+
+// The block format ends on at least 5 literals, and the last copy starts at least
+// 12 bytes before the end
+const LAST_LITERALS: usize = 5;
+const MATCH_LIMIT: usize = 12;
+
+// Positions remembered by a hash of the 4 bytes there, 4096 of them
+const HASH_BITS: u32 = 12;
+
+/// Compress `input` into an LZ4 block.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2);
+    // Position + 1 of the latest occurrence of each hash, 0 for none
+    let mut table = vec![0usize; 1 << HASH_BITS];
+    let match_end = input.len().saturating_sub(LAST_LITERALS);
+    let mut anchor = 0;
+    let mut i = 0;
+
+    while i + MATCH_LIMIT <= input.len() {
+        let word = &input[i..i + MIN_MATCH];
+        let slot = hash(word);
+        let candidate = table[slot];
+        table[slot] = i + 1;
+
+        if let Some(start) = candidate.checked_sub(1)
+            && i - start <= MAX_OFFSET
+            && &input[start..start + MIN_MATCH] == word
+        {
+            let mut len = MIN_MATCH;
+            while i + len < match_end && input[start + len] == input[i + len] {
+                len += 1;
+            }
+            push_sequence(&mut out, &input[anchor..i], Some((i - start, len)));
+            i += len;
+            anchor = i;
+        } else {
+            i += 1;
+        }
+    }
+
+    push_sequence(&mut out, &input[anchor..], None);
+    out
+}
+
+/// Decompress an LZ4 block that `compress` made from `len` bytes, or None when it
+/// is malformed or doesn't come out at exactly `len` bytes.
+pub fn decompress(input: &[u8], len: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(len);
+    let mut pos = 0;
+
+    loop {
+        let token = *input.get(pos)?;
+        pos += 1;
+
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_length(input, &mut pos)?;
+        }
+        let end = pos.checked_add(literals)?;
+        if out.len() + literals > len {
+            return None;
+        }
+        out.extend_from_slice(input.get(pos..end)?);
+        pos = end;
+        if pos == input.len() {
+            break;
+        }
+
+        let offset = u16::from_le_bytes([*input.get(pos)?, *input.get(pos + 1)?]) as usize;
+        pos += 2;
+        let mut copy = (token & 0x0F) as usize + MIN_MATCH;
+        if token & 0x0F == 15 {
+            copy += read_length(input, &mut pos)?;
+        }
+        if offset == 0 || offset > out.len() || out.len() + copy > len {
+            return None;
+        }
+        // Byte by byte, since a copy may overlap the bytes it produces
+        let start = out.len() - offset;
+        for k in 0..copy {
+            out.push(out[start + k]);
+        }
+    }
+
+    (out.len() == len).then_some(out)
+}
+
+fn hash(word: &[u8]) -> usize {
+    let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+    (word.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+// One sequence: its literals, then the copy of `len` bytes from `offset` back,
+// which only the last sequence goes without
+fn push_sequence(out: &mut Vec<u8>, literals: &[u8], copy: Option<(usize, usize)>) {
+    let copy_nibble = copy.map_or(0, |(_, len)| (len - MIN_MATCH).min(15));
+    out.push(((literals.len().min(15) as u8) << 4) | copy_nibble as u8);
+    if literals.len() >= 15 {
+        push_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+
+    if let Some((offset, len)) = copy {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if len - MIN_MATCH >= 15 {
+            push_length(out, len - MIN_MATCH - 15);
+        }
+    }
+}
+
+fn push_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn read_length(input: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut len = 0usize;
+    loop {
+        let byte = *input.get(*pos)?;
+        *pos += 1;
+        len = len.checked_add(byte as usize)?;
+        if byte != 255 {
+            return Some(len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(input: &[u8]) -> Vec<u8> {
+        let compressed = compress(input);
+        assert_eq!(decompress(&compressed, input.len()).as_deref(), Some(input), "{} bytes", input.len());
+        compressed
+    }
+
+    #[test]
+    fn round_trips_text_and_edge_cases() {
+        round_trip(b"");
+        round_trip(b"a");
+        round_trip(b"exactly twelve");
+        round_trip(&[b'a'; 100_000]);
+
+        let log: String = (0..5_000).map(|line| format!("{line}: GET /v1/pastes/abc123 200\n")).collect();
+        let compressed = round_trip(log.as_bytes());
+        assert!(compressed.len() < log.len() / 4, "{} of {} bytes", compressed.len(), log.len());
+    }
+
+    #[test]
+    fn round_trips_bytes_that_dont_compress() {
+        // A fixed pseudo-random sequence, standing in for ciphertext
+        let mut state = 0x2545_F491u32;
+        let noise: Vec<u8> = (0..70_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        round_trip(&noise);
+    }
+
+    #[test]
+    fn encodes_a_run_as_the_block_format_specifies() {
+        // A literal, a copy of 26 from one byte back (nibble 15 plus 7), then the last 5 literals
+        let compressed = compress(&[b'a'; 32]);
+        assert_eq!(compressed, [0x1F, b'a', 0x01, 0x00, 0x07, 0x50, b'a', b'a', b'a', b'a', b'a']);
+    }
+
+    #[test]
+    fn rejects_malformed_blocks() {
+        let compressed = compress(b"hello hello hello hello hello");
+        assert_eq!(decompress(&compressed, 28), None, "wrong length");
+        assert_eq!(decompress(&compressed[..compressed.len() - 1], 29), None, "truncated");
+        // A copy reaching back before the start of the output
+        assert_eq!(decompress(&[0x10, b'a', 0x05, 0x00, 0x00], 5), None);
+        assert_eq!(decompress(&[], 0), None);
+        // A huge declared length stops at the expected size rather than allocating it
+        assert_eq!(decompress(&[0x1F, b'a', 0x01, 0x00, 255, 255, 255, 0], 64), None);
+    }
+}
@@ -0,0 +1,863 @@
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::content_policy::BannedContentPolicy;
+use crate::db::{DEFAULT_DATA_DIR, DEFAULT_ID_LENGTH, IdCharset, MAX_ID_LENGTH, MIN_ID_LENGTH, OpenRetry, PasteOptions};
+use crate::language;
+use crate::net::IpNetwork;
+use crate::operations::EnabledOperations;
+use crate::security_headers::DEFAULT_CONTENT_SECURITY_POLICY;
+
+// Room in a request body for fields and framing beyond the paste data itself
+const BODY_OVERHEAD_BYTES: usize = 64 * 1024;
+
+// Configuration error type
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config file {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+
+    #[error("Failed to parse config file {0}: {1}")]
+    Parse(PathBuf, serde_json::Error),
+
+    #[error("Invalid value for {0}: {1}")]
+    InvalidEnv(&'static str, String),
+
+    #[error("Missing value for --config")]
+    MissingConfigPath,
+
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+}
+
+/// A route with its own per-minute rate limit, from a `route_rate_limits` entry
+/// such as `GET /v1/pastes/:id/raw`. Without a method it covers every method on
+/// the route.
+#[derive(Debug, Clone)]
+pub struct RouteRateLimit {
+    pub method: Option<String>,
+    /// The route as the router matches it, with `{name}` parameters.
+    pub route: String,
+    pub limit: u32,
+}
+
+// Methods a route limit may name; HEAD is counted with GET
+const ROUTE_LIMIT_METHODS: [&str; 4] = ["GET", "POST", "PUT", "DELETE"];
+
+impl RouteRateLimit {
+    fn parse(entry: &str, limit: u32) -> Result<Self, String> {
+        let (method, route) = match entry.trim().split_once(char::is_whitespace) {
+            Some((method, route)) => (Some(method.to_ascii_uppercase()), route.trim()),
+            None => (None, entry.trim()),
+        };
+        if let Some(method) = &method
+            && !ROUTE_LIMIT_METHODS.contains(&method.as_str())
+        {
+            return Err(format!("has method {}, expected one of {}", method, ROUTE_LIMIT_METHODS.join(", ")));
+        }
+        if !route.starts_with("/v1/") {
+            return Err("must name a route under /v1/".to_string());
+        }
+        if limit == 0 {
+            return Err("must allow at least one request; ENABLE_* turns operations off".to_string());
+        }
+
+        // Accept the README's `:id` spelling for the router's `{id}`
+        let route = route
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => format!("{{{}}}", name),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        Ok(Self { method, route, limit })
+    }
+
+    /// The rate limiter's key for this route: `METHOD /route`, or just the route.
+    pub fn key(&self) -> String {
+        match &self.method {
+            Some(method) => format!("{} {}", method, self.route),
+            None => self.route.clone(),
+        }
+    }
+}
+
+/// Server configuration.
+///
+/// Loaded from an optional JSON file (`--config <path>` or `RUSTYBIN_CONFIG`),
+/// then overridden by environment variables of the same name in upper case
+/// (e.g. `read_rate_limit` is overridden by `READ_RATE_LIMIT`).
+///
+/// Serializing (and `Debug`) shows secrets as `***`, so the whole config is safe to log.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: u16,
+    pub rust_env: String,
+    pub cors_allowed_origins: Vec<String>,
+    pub cors_max_age_secs: u64,
+    pub read_rate_limit: u32,
+    pub create_rate_limit: u32,
+    pub update_rate_limit: u32,
+    pub delete_rate_limit: u32,
+    pub max_concurrent_per_ip: u32,
+    pub rate_limit_ipv4_prefix: u8,
+    pub rate_limit_ipv6_prefix: u8,
+    pub rate_limit_max_tracked_ips: usize,
+    pub rate_limit_allowlist: Vec<String>,
+    pub rate_limit_exempt_paths: Vec<String>,
+    pub route_rate_limits: BTreeMap<String, u32>,
+    pub quiet_paths: Vec<String>,
+    pub trusted_proxies: Vec<String>,
+    pub admin_login_rate_limit: u32,
+    pub admin_read_rate_limit: u32,
+    pub admin_delete_rate_limit: u32,
+    pub max_paste_bytes: usize,
+    pub max_paste_lines: usize,
+    pub max_request_bytes: Option<usize>,
+    pub max_upload_bytes: Option<usize>,
+    pub data_dir: PathBuf,
+    pub db_open_attempts: u32,
+    pub db_open_retry_delay_ms: u64,
+    pub db_busy_timeout_ms: u64,
+    pub read_only_mode: bool,
+    pub enable_create: bool,
+    pub enable_delete: bool,
+    pub enable_read: bool,
+    pub allow_plaintext_pastes: bool,
+    pub strip_bom: bool,
+    pub strip_trailing_whitespace: bool,
+    pub hide_forbidden: bool,
+    pub hide_expiry_distinction: bool,
+    pub max_tags_per_paste: usize,
+    pub paste_id_length: usize,
+    pub paste_id_charset: IdCharset,
+    pub max_total_pastes: u64,
+    pub daily_create_quota: u64,
+    pub report_rate_limit: u32,
+    pub create_min_interval_ms: u64,
+    pub health_min_free_disk_mb: u64,
+    pub health_max_tracked_clients: usize,
+    pub max_concurrent_maintenance: usize,
+    pub purge_interval_secs: u64,
+    pub trash_retention_secs: u64,
+    pub checkpoint_interval_secs: u64,
+    pub view_flush_interval_secs: u64,
+    pub idempotency_ttl_secs: u64,
+    pub max_ttl_seconds: u64,
+    pub default_ttl_seconds: u64,
+    pub clamp_ttl: bool,
+    pub slow_query_ms: Option<u64>,
+    pub server_timing: bool,
+    pub access_log_enabled: bool,
+    pub compress_pastes_over: usize,
+    pub default_language: String,
+    pub allow_put_create: bool,
+    pub public_base_url: Option<String>,
+    #[serde(serialize_with = "redact")]
+    pub read_token_secret: Option<String>,
+    #[serde(serialize_with = "redact")]
+    pub creator_hash_secret: Option<String>,
+    pub request_timeout_secs: u64,
+    pub paste_cache_max_age_secs: u64,
+    pub content_security_policy: String,
+    pub content_banned_substrings: Vec<String>,
+    pub content_banned_patterns: Vec<String>,
+    pub theme_hints_file: Option<PathBuf>,
+    #[serde(skip)]
+    pub theme_hints: HashMap<String, String>, // Loaded from theme_hints_file
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+}
+
+// Write a set secret as `***`; an unset one stays null so it's clear it isn't configured
+fn redact<S: Serializer>(secret: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    match secret {
+        Some(_) => serializer.serialize_str("***"),
+        None => serializer.serialize_none(),
+    }
+}
+
+// Same fields and redaction as the serialized form, rather than a derive that would print secrets
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_string(self) {
+            Ok(json) => write!(f, "Config {}", json),
+            Err(_) => f.write_str("Config { .. }"),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 3000,
+            rust_env: "development".to_string(),
+            cors_allowed_origins: [
+                "https://rustybin.net",
+                "https://rustyb.in",
+                "http://localhost:8080",
+                "http://localhost:5173",
+                "https://api.rustybin.net",
+                "https://api.rustyb.in",
+            ]
+            .iter()
+            .map(|origin| origin.to_string())
+            .collect(),
+            cors_max_age_secs: 600,
+            read_rate_limit: 45,
+            create_rate_limit: 15,
+            update_rate_limit: 15,
+            delete_rate_limit: 15,
+            max_concurrent_per_ip: 10,
+            rate_limit_ipv4_prefix: 32,
+            rate_limit_ipv6_prefix: 64,
+            rate_limit_max_tracked_ips: 200_000,
+            rate_limit_allowlist: Vec::new(),
+            rate_limit_exempt_paths: Vec::new(),
+            route_rate_limits: BTreeMap::new(),
+            quiet_paths: Vec::new(),
+            trusted_proxies: Vec::new(),
+            admin_login_rate_limit: 5,
+            admin_read_rate_limit: 60,
+            admin_delete_rate_limit: 20,
+            max_paste_bytes: 200000,
+            max_paste_lines: 0,
+            max_request_bytes: None,
+            max_upload_bytes: None,
+            data_dir: PathBuf::from(DEFAULT_DATA_DIR),
+            db_open_attempts: 5,
+            db_open_retry_delay_ms: 500,
+            db_busy_timeout_ms: 5000,
+            read_only_mode: false,
+            enable_create: true,
+            enable_delete: true,
+            enable_read: true,
+            allow_plaintext_pastes: false,
+            strip_bom: false,
+            strip_trailing_whitespace: false,
+            hide_forbidden: false,
+            hide_expiry_distinction: false,
+            max_tags_per_paste: 5,
+            paste_id_length: DEFAULT_ID_LENGTH,
+            paste_id_charset: IdCharset::Alphanumeric,
+            max_total_pastes: 0,
+            daily_create_quota: 0,
+            report_rate_limit: 5,
+            create_min_interval_ms: 0,
+            health_min_free_disk_mb: 100,
+            health_max_tracked_clients: 100_000,
+            max_concurrent_maintenance: 1,
+            purge_interval_secs: 300,
+            trash_retention_secs: 0,
+            checkpoint_interval_secs: 300,
+            view_flush_interval_secs: 5,
+            idempotency_ttl_secs: 86400,
+            max_ttl_seconds: 7 * 24 * 60 * 60,
+            default_ttl_seconds: 0,
+            clamp_ttl: false,
+            slow_query_ms: None,
+            server_timing: false,
+            access_log_enabled: false,
+            compress_pastes_over: 0,
+            default_language: language::AUTO.to_string(),
+            allow_put_create: false,
+            public_base_url: None,
+            read_token_secret: None,
+            creator_hash_secret: None,
+            request_timeout_secs: 30,
+            paste_cache_max_age_secs: 31_536_000,
+            content_security_policy: DEFAULT_CONTENT_SECURITY_POLICY.to_string(),
+            content_banned_substrings: Vec::new(),
+            content_banned_patterns: Vec::new(),
+            theme_hints_file: None,
+            theme_hints: HashMap::new(),
+            tls_cert_path: None,
+            tls_key_path: None,
+        }
+    }
+}
+
+// Override a value from an environment variable, if set
+fn env_override<T>(name: &'static str, target: &mut T) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(value) = env::var(name) {
+        *target = value
+            .trim()
+            .parse()
+            .map_err(|e: T::Err| ConfigError::InvalidEnv(name, format!("{:?} ({})", value, e)))?;
+    }
+    Ok(())
+}
+
+// Override an optional value from an environment variable, if set and non-empty
+fn env_override_opt<T: for<'a> From<&'a str>>(name: &'static str, target: &mut Option<T>) {
+    if let Ok(value) = env::var(name) {
+        let value = value.trim();
+        *target = (!value.is_empty()).then(|| T::from(value));
+    }
+}
+
+// Override an optional parsed value from an environment variable, if set; an empty
+// value clears it
+fn env_override_parsed_opt<T>(name: &'static str, target: &mut Option<T>) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(value) = env::var(name) {
+        let value = value.trim();
+        *target = if value.is_empty() {
+            None
+        } else {
+            Some(value.parse().map_err(|e: T::Err| ConfigError::InvalidEnv(name, format!("{:?} ({})", value, e)))?)
+        };
+    }
+    Ok(())
+}
+
+// Override a comma-separated list from an environment variable, if set
+fn env_override_list(name: &'static str, target: &mut Vec<String>) {
+    if let Ok(value) = env::var(name) {
+        *target = value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect();
+    }
+}
+
+// Override a map from comma-separated `key=value` entries in an environment variable,
+// if set
+fn env_override_map<T>(name: &'static str, target: &mut BTreeMap<String, T>) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(value) = env::var(name) {
+        let mut map = BTreeMap::new();
+        for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let parsed = entry
+                .rsplit_once('=')
+                .ok_or_else(|| "expected key=value".to_string())
+                .and_then(|(key, value)| Ok((key.trim().to_string(), value.trim().parse().map_err(|e: T::Err| e.to_string())?)));
+            let (key, value) = parsed.map_err(|e| ConfigError::InvalidEnv(name, format!("{:?} ({})", entry, e)))?;
+            map.insert(key, value);
+        }
+        *target = map;
+    }
+    Ok(())
+}
+
+// Override a newline-separated list from an environment variable, if set; for
+// values such as regexes that may contain commas
+fn env_override_lines(name: &'static str, target: &mut Vec<String>) {
+    if let Ok(value) = env::var(name) {
+        *target = value
+            .lines()
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect();
+    }
+}
+
+// Find the config file path from `--config <path>`, `--config=<path>` or RUSTYBIN_CONFIG
+fn config_path() -> Result<Option<PathBuf>, ConfigError> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args
+                .next()
+                .map(|path| Some(PathBuf::from(path)))
+                .ok_or(ConfigError::MissingConfigPath);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Ok(Some(PathBuf::from(path)));
+        }
+    }
+
+    Ok(env::var("RUSTYBIN_CONFIG").ok().map(PathBuf::from))
+}
+
+impl Config {
+    /// Load the configuration from the config file (if any) and environment.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match config_path()? {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+        config.theme_hints = config.load_theme_hints()?;
+
+        Ok(config)
+    }
+
+    // Read theme_hints_file, a JSON object of language to theme, keyed by lowercased language
+    fn load_theme_hints(&self) -> Result<HashMap<String, String>, ConfigError> {
+        let Some(path) = &self.theme_hints_file else {
+            return Ok(HashMap::new());
+        };
+        let contents = fs::read_to_string(path).map_err(|e| ConfigError::Read(path.clone(), e))?;
+        let hints: HashMap<String, String> =
+            serde_json::from_str(&contents).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+
+        Ok(hints
+            .into_iter()
+            .map(|(language, theme)| (language.trim().to_ascii_lowercase(), theme))
+            .collect())
+    }
+
+    fn from_file(path: PathBuf) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(&path).map_err(|e| ConfigError::Read(path.clone(), e))?;
+        serde_json::from_str(&contents).map_err(|e| ConfigError::Parse(path, e))
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        env_override("PORT", &mut self.port)?;
+        env_override("RUST_ENV", &mut self.rust_env)?;
+        env_override_list("CORS_ALLOWED_ORIGINS", &mut self.cors_allowed_origins);
+        env_override("CORS_MAX_AGE_SECS", &mut self.cors_max_age_secs)?;
+        env_override("READ_RATE_LIMIT", &mut self.read_rate_limit)?;
+        env_override("CREATE_RATE_LIMIT", &mut self.create_rate_limit)?;
+        env_override("UPDATE_RATE_LIMIT", &mut self.update_rate_limit)?;
+        env_override("DELETE_RATE_LIMIT", &mut self.delete_rate_limit)?;
+        env_override("MAX_CONCURRENT_PER_IP", &mut self.max_concurrent_per_ip)?;
+        env_override("RATE_LIMIT_IPV4_PREFIX", &mut self.rate_limit_ipv4_prefix)?;
+        env_override("RATE_LIMIT_IPV6_PREFIX", &mut self.rate_limit_ipv6_prefix)?;
+        env_override("RATE_LIMIT_MAX_TRACKED_IPS", &mut self.rate_limit_max_tracked_ips)?;
+        env_override_list("RATE_LIMIT_ALLOWLIST", &mut self.rate_limit_allowlist);
+        env_override_list("RATE_LIMIT_EXEMPT_PATHS", &mut self.rate_limit_exempt_paths);
+        env_override_map("ROUTE_RATE_LIMITS", &mut self.route_rate_limits)?;
+        env_override_list("QUIET_PATHS", &mut self.quiet_paths);
+        env_override_list("TRUSTED_PROXIES", &mut self.trusted_proxies);
+        env_override("ADMIN_LOGIN_RATE_LIMIT", &mut self.admin_login_rate_limit)?;
+        env_override("ADMIN_READ_RATE_LIMIT", &mut self.admin_read_rate_limit)?;
+        env_override("ADMIN_DELETE_RATE_LIMIT", &mut self.admin_delete_rate_limit)?;
+        env_override("MAX_PASTE_BYTES", &mut self.max_paste_bytes)?;
+        env_override("MAX_PASTE_LINES", &mut self.max_paste_lines)?;
+        env_override_parsed_opt("MAX_REQUEST_BYTES", &mut self.max_request_bytes)?;
+        env_override_parsed_opt("MAX_UPLOAD_BYTES", &mut self.max_upload_bytes)?;
+        env_override("DATA_DIR", &mut self.data_dir)?;
+        env_override("DB_OPEN_ATTEMPTS", &mut self.db_open_attempts)?;
+        env_override("DB_OPEN_RETRY_DELAY_MS", &mut self.db_open_retry_delay_ms)?;
+        env_override("DB_BUSY_TIMEOUT_MS", &mut self.db_busy_timeout_ms)?;
+        env_override("READ_ONLY_MODE", &mut self.read_only_mode)?;
+        env_override("ENABLE_CREATE", &mut self.enable_create)?;
+        env_override("ENABLE_DELETE", &mut self.enable_delete)?;
+        env_override("ENABLE_READ", &mut self.enable_read)?;
+        env_override("ALLOW_PLAINTEXT_PASTES", &mut self.allow_plaintext_pastes)?;
+        env_override("STRIP_BOM", &mut self.strip_bom)?;
+        env_override("STRIP_TRAILING_WHITESPACE", &mut self.strip_trailing_whitespace)?;
+        env_override("HIDE_FORBIDDEN", &mut self.hide_forbidden)?;
+        env_override("HIDE_EXPIRY_DISTINCTION", &mut self.hide_expiry_distinction)?;
+        env_override("MAX_TAGS_PER_PASTE", &mut self.max_tags_per_paste)?;
+        env_override("PASTE_ID_LENGTH", &mut self.paste_id_length)?;
+        env_override("PASTE_ID_CHARSET", &mut self.paste_id_charset)?;
+        env_override("MAX_TOTAL_PASTES", &mut self.max_total_pastes)?;
+        env_override("DAILY_CREATE_QUOTA", &mut self.daily_create_quota)?;
+        env_override("REPORT_RATE_LIMIT", &mut self.report_rate_limit)?;
+        env_override("CREATE_MIN_INTERVAL_MS", &mut self.create_min_interval_ms)?;
+        env_override("HEALTH_MIN_FREE_DISK_MB", &mut self.health_min_free_disk_mb)?;
+        env_override("HEALTH_MAX_TRACKED_CLIENTS", &mut self.health_max_tracked_clients)?;
+        env_override("MAX_CONCURRENT_MAINTENANCE", &mut self.max_concurrent_maintenance)?;
+        env_override("PURGE_INTERVAL_SECS", &mut self.purge_interval_secs)?;
+        env_override("TRASH_RETENTION_SECS", &mut self.trash_retention_secs)?;
+        env_override("CHECKPOINT_INTERVAL_SECS", &mut self.checkpoint_interval_secs)?;
+        env_override("VIEW_FLUSH_INTERVAL_SECS", &mut self.view_flush_interval_secs)?;
+        env_override("IDEMPOTENCY_TTL_SECS", &mut self.idempotency_ttl_secs)?;
+        env_override("MAX_TTL_SECONDS", &mut self.max_ttl_seconds)?;
+        env_override("DEFAULT_TTL_SECONDS", &mut self.default_ttl_seconds)?;
+        env_override("CLAMP_TTL", &mut self.clamp_ttl)?;
+        env_override_parsed_opt("SLOW_QUERY_MS", &mut self.slow_query_ms)?;
+        env_override("SERVER_TIMING", &mut self.server_timing)?;
+        env_override("ACCESS_LOG_ENABLED", &mut self.access_log_enabled)?;
+        env_override("COMPRESS_PASTES_OVER", &mut self.compress_pastes_over)?;
+        env_override("DEFAULT_LANGUAGE", &mut self.default_language)?;
+        env_override("ALLOW_PUT_CREATE", &mut self.allow_put_create)?;
+        env_override_opt("PUBLIC_BASE_URL", &mut self.public_base_url);
+        env_override_opt("READ_TOKEN_SECRET", &mut self.read_token_secret);
+        env_override_opt("CREATOR_HASH_SECRET", &mut self.creator_hash_secret);
+        env_override("REQUEST_TIMEOUT_SECS", &mut self.request_timeout_secs)?;
+        env_override("PASTE_CACHE_MAX_AGE_SECS", &mut self.paste_cache_max_age_secs)?;
+        env_override("CONTENT_SECURITY_POLICY", &mut self.content_security_policy)?;
+        env_override_list("CONTENT_BANNED_SUBSTRINGS", &mut self.content_banned_substrings);
+        env_override_lines("CONTENT_BANNED_PATTERNS", &mut self.content_banned_patterns);
+        env_override_opt("THEME_HINTS_FILE", &mut self.theme_hints_file);
+        env_override_opt("TLS_CERT_PATH", &mut self.tls_cert_path);
+        env_override_opt("TLS_KEY_PATH", &mut self.tls_key_path);
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.port == 0 {
+            return Err(ConfigError::Invalid("port must be greater than 0".to_string()));
+        }
+
+        let limits = [
+            ("read_rate_limit", self.read_rate_limit),
+            ("create_rate_limit", self.create_rate_limit),
+            ("update_rate_limit", self.update_rate_limit),
+            ("delete_rate_limit", self.delete_rate_limit),
+            ("admin_login_rate_limit", self.admin_login_rate_limit),
+            ("admin_read_rate_limit", self.admin_read_rate_limit),
+            ("admin_delete_rate_limit", self.admin_delete_rate_limit),
+        ];
+        if let Some((name, _)) = limits.iter().find(|(_, limit)| *limit == 0) {
+            return Err(ConfigError::Invalid(format!("{} must be greater than 0", name)));
+        }
+
+        if self.rate_limit_ipv4_prefix > 32 {
+            return Err(ConfigError::Invalid("rate_limit_ipv4_prefix must be at most 32".to_string()));
+        }
+
+        if self.rate_limit_ipv6_prefix > 128 {
+            return Err(ConfigError::Invalid("rate_limit_ipv6_prefix must be at most 128".to_string()));
+        }
+
+        if let Some(Err(e)) = self.rate_limit_allowlist.iter().map(|entry| entry.parse::<IpNetwork>()).find(Result::is_err) {
+            return Err(ConfigError::Invalid(format!("rate_limit_allowlist: {}", e)));
+        }
+
+        // Paths are matched against the request path exactly, which always starts with '/'
+        for (name, paths) in [("rate_limit_exempt_paths", &self.rate_limit_exempt_paths), ("quiet_paths", &self.quiet_paths)] {
+            if let Some(path) = paths.iter().find(|path| !path.starts_with('/')) {
+                return Err(ConfigError::Invalid(format!("{}: {:?} must start with '/'", name, path)));
+            }
+        }
+
+        // `:id` and `{id}` spell the same route, so two entries may still collide
+        let mut route_keys = HashSet::new();
+        for (route, &limit) in &self.route_rate_limits {
+            let parsed = RouteRateLimit::parse(route, limit)
+                .map_err(|e| ConfigError::Invalid(format!("route_rate_limits: {:?} {}", route, e)))?;
+            if !route_keys.insert(parsed.key()) {
+                return Err(ConfigError::Invalid(format!("route_rate_limits: {:?} is listed twice", parsed.key())));
+            }
+        }
+
+        if let Some(Err(e)) = self.trusted_proxies.iter().map(|entry| entry.parse::<IpNetwork>()).find(Result::is_err) {
+            return Err(ConfigError::Invalid(format!("trusted_proxies: {}", e)));
+        }
+
+        if self.max_paste_bytes == 0 {
+            return Err(ConfigError::Invalid("max_paste_bytes must be greater than 0".to_string()));
+        }
+
+        // A body cap below the paste limit would reject pastes the limit allows
+        let body_limits = [
+            ("max_request_bytes", self.max_request_bytes),
+            ("max_upload_bytes", self.max_upload_bytes),
+        ];
+        for (name, limit) in body_limits {
+            if limit.is_some_and(|limit| limit < self.max_paste_bytes) {
+                return Err(ConfigError::Invalid(format!("{} must be at least max_paste_bytes", name)));
+            }
+        }
+
+        if !(MIN_ID_LENGTH..=MAX_ID_LENGTH).contains(&self.paste_id_length) {
+            return Err(ConfigError::Invalid(format!(
+                "paste_id_length must be between {} and {} (got {})",
+                MIN_ID_LENGTH, MAX_ID_LENGTH, self.paste_id_length
+            )));
+        }
+
+        if self.default_language.trim().is_empty() {
+            return Err(ConfigError::Invalid("default_language must not be empty (use \"auto\" to detect)".to_string()));
+        }
+
+        if self.max_ttl_seconds < 60 {
+            return Err(ConfigError::Invalid("max_ttl_seconds must be at least 60".to_string()));
+        }
+
+        if self.default_ttl_seconds > self.max_ttl_seconds {
+            return Err(ConfigError::Invalid("default_ttl_seconds must be at most max_ttl_seconds".to_string()));
+        }
+
+        if self.max_concurrent_maintenance == 0 {
+            return Err(ConfigError::Invalid("max_concurrent_maintenance must be greater than 0".to_string()));
+        }
+
+        if self.cors_allowed_origins.is_empty() {
+            return Err(ConfigError::Invalid("cors_allowed_origins must not be empty".to_string()));
+        }
+
+        // TLS needs a rustls-based listener that this build doesn't include; refuse
+        // to start rather than silently serve plain HTTP to an operator expecting HTTPS
+        if self.tls_cert_path.is_some() || self.tls_key_path.is_some() {
+            return Err(ConfigError::Invalid(
+                "tls_cert_path/tls_key_path are set, but this build has no TLS support; \
+                 terminate TLS at a reverse proxy instead"
+                    .to_string(),
+            ));
+        }
+
+        if let Some(url) = &self.public_base_url
+            && !(url.starts_with("http://") || url.starts_with("https://"))
+        {
+            return Err(ConfigError::Invalid(format!(
+                "public_base_url must start with http:// or https:// (got {:?})",
+                url
+            )));
+        }
+
+        if let Err(e) = BannedContentPolicy::new(&self.content_banned_substrings, &self.content_banned_patterns) {
+            return Err(ConfigError::Invalid(format!("content_banned_patterns: {}", e)));
+        }
+
+        if axum::http::HeaderValue::from_str(&self.content_security_policy).is_err() {
+            return Err(ConfigError::Invalid(
+                "content_security_policy must be a valid header value (no control characters)".to_string(),
+            ));
+        }
+
+        if self.data_dir.as_os_str().is_empty() {
+            return Err(ConfigError::Invalid("data_dir must not be empty".to_string()));
+        }
+
+        if self.db_open_attempts == 0 {
+            return Err(ConfigError::Invalid("db_open_attempts must be greater than 0".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_production(&self) -> bool {
+        self.rust_env == "production"
+    }
+
+    /// The `Content-Security-Policy` header for SPA pages; `None` when disabled (empty).
+    pub fn content_security_policy(&self) -> Option<axum::http::HeaderValue> {
+        let policy = self.content_security_policy.trim();
+        if policy.is_empty() {
+            None
+        } else {
+            axum::http::HeaderValue::from_str(policy).ok()
+        }
+    }
+
+    /// Networks exempt from rate limiting. Entries are checked by `load`.
+    pub fn rate_limit_allowlist(&self) -> Vec<IpNetwork> {
+        self.rate_limit_allowlist.iter().filter_map(|entry| entry.parse().ok()).collect()
+    }
+
+    /// Routes with their own rate limit, parsed (checked by `load`).
+    pub fn route_rate_limits(&self) -> Vec<RouteRateLimit> {
+        self.route_rate_limits
+            .iter()
+            .filter_map(|(route, &limit)| RouteRateLimit::parse(route, limit).ok())
+            .collect()
+    }
+
+    /// Proxies whose forwarding headers name the client, parsed (checked by `load`).
+    pub fn trusted_proxies(&self) -> Vec<IpNetwork> {
+        self.trusted_proxies.iter().filter_map(|entry| entry.parse().ok()).collect()
+    }
+
+    /// The content policy from the banned substrings and patterns, or `None` when
+    /// there are no rules. Patterns are checked by `load`, so they compile here.
+    pub fn content_policy(&self) -> Option<BannedContentPolicy> {
+        BannedContentPolicy::new(&self.content_banned_substrings, &self.content_banned_patterns)
+            .ok()
+            .filter(|policy| !policy.is_empty())
+    }
+
+    /// Largest request body accepted by most routes: `MAX_REQUEST_BYTES`, or by
+    /// default twice `MAX_PASTE_BYTES` (room for JSON escaping) plus 64 KiB.
+    pub fn request_body_limit(&self) -> usize {
+        self.max_request_bytes.unwrap_or(2 * self.max_paste_bytes + BODY_OVERHEAD_BYTES)
+    }
+
+    /// Largest multipart body accepted by the upload route: `MAX_UPLOAD_BYTES`, or
+    /// by default one file at `MAX_PASTE_BYTES` plus 64 KiB for part headers and
+    /// form fields.
+    pub fn upload_body_limit(&self) -> usize {
+        self.max_upload_bytes.unwrap_or(self.max_paste_bytes + BODY_OVERHEAD_BYTES)
+    }
+
+    /// Startup retries for opening the database.
+    pub fn db_open_retry(&self) -> OpenRetry {
+        OpenRetry {
+            attempts: self.db_open_attempts,
+            initial_delay: Duration::from_millis(self.db_open_retry_delay_ms),
+        }
+    }
+
+    /// The public operations turned on by `ENABLE_*`. `READ_ONLY_MODE` refuses
+    /// creates and deletes on its own, whatever these say.
+    pub fn enabled_operations(&self) -> EnabledOperations {
+        EnabledOperations {
+            create: self.enable_create,
+            delete: self.enable_delete,
+            read: self.enable_read,
+        }
+    }
+
+    /// Paste storage options derived from this configuration.
+    pub fn paste_options(&self) -> PasteOptions {
+        PasteOptions {
+            max_paste_bytes: self.max_paste_bytes,
+            max_paste_lines: self.max_paste_lines,
+            allow_plaintext: self.allow_plaintext_pastes,
+            strip_bom: self.strip_bom,
+            strip_trailing_whitespace: self.strip_trailing_whitespace,
+            max_tags: self.max_tags_per_paste,
+            trash_retention_secs: self.trash_retention_secs,
+            id_length: self.paste_id_length,
+            id_charset: self.paste_id_charset,
+            max_total_pastes: self.max_total_pastes,
+            defer_expired_deletes: self.hide_expiry_distinction,
+            buffer_views: self.view_flush_interval_secs > 0,
+            idempotency_ttl_secs: self.idempotency_ttl_secs,
+            max_ttl_secs: self.max_ttl_seconds,
+            default_ttl_secs: self.default_ttl_seconds,
+            clamp_ttl: self.clamp_ttl,
+            default_language: self.default_language.trim().to_string(),
+            slow_query: self.slow_query_ms.map(Duration::from_millis),
+            read_only: self.read_only_mode,
+            theme_hints: self.theme_hints.clone(),
+            busy_timeout: Duration::from_millis(self.db_busy_timeout_ms),
+            access_log: self.access_log_enabled,
+            compress_over: self.compress_pastes_over,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Tests that set environment variables take this, since the environment is shared
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    fn with_env<T>(vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        // SAFETY: every test that touches the environment holds ENV_LOCK
+        unsafe { vars.iter().for_each(|(name, value)| env::set_var(name, value)) };
+        let result = f();
+        unsafe { vars.iter().for_each(|(name, _)| env::remove_var(name)) };
+        result
+    }
+
+    fn config_file(contents: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rustybin-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn sample_config_file_is_read_over_the_defaults() {
+        let path = config_file(
+            r#"{"port": 8080, "create_rate_limit": 3, "cors_allowed_origins": ["https://paste.example"],
+                "max_paste_bytes": 2048, "data_dir": "/srv/rustybin"}"#,
+        );
+        let config = Config::from_file(path.clone()).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.create_rate_limit, 3);
+        assert_eq!(config.cors_allowed_origins, ["https://paste.example"]);
+        assert_eq!(config.max_paste_bytes, 2048);
+        assert_eq!(config.data_dir, PathBuf::from("/srv/rustybin"));
+        assert_eq!(config.read_rate_limit, Config::default().read_rate_limit, "unset fields keep their default");
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_or_mistyped_file_fields_are_refused() {
+        for contents in [r#"{"prot": 8080}"#, r#"{"port": "eighty"}"#] {
+            let path = config_file(contents);
+            let err = Config::from_file(path.clone()).unwrap_err();
+            fs::remove_dir_all(path.parent().unwrap()).unwrap();
+            assert!(matches!(err, ConfigError::Parse(..)), "{}: {}", contents, err);
+        }
+    }
+
+    #[test]
+    fn env_vars_override_file_values() {
+        let path = config_file(r#"{"port": 8080, "read_rate_limit": 10, "create_rate_limit": 3}"#);
+        let mut config = Config::from_file(path.clone()).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        with_env(&[("PORT", "9090"), ("CORS_ALLOWED_ORIGINS", "https://a.example, https://b.example")], || {
+            config.apply_env().unwrap()
+        });
+        assert_eq!(config.port, 9090, "env wins over the file");
+        assert_eq!(config.read_rate_limit, 10, "file value kept without an env var");
+        assert_eq!(config.create_rate_limit, 3);
+        assert_eq!(config.cors_allowed_origins, ["https://a.example", "https://b.example"]);
+    }
+
+    #[test]
+    fn bad_env_values_name_the_variable() {
+        let mut config = Config::default();
+        let err = with_env(&[("CREATE_RATE_LIMIT", "lots")], || config.apply_env().unwrap_err());
+        assert!(matches!(err, ConfigError::InvalidEnv("CREATE_RATE_LIMIT", _)), "{}", err);
+    }
+
+    #[test]
+    fn invalid_settings_fail_with_a_clear_message() {
+        let cases = [
+            (Config { port: 0, ..Config::default() }, "port must be greater than 0"),
+            (Config { create_rate_limit: 0, ..Config::default() }, "create_rate_limit must be greater than 0"),
+            (Config { max_paste_bytes: 0, ..Config::default() }, "max_paste_bytes must be greater than 0"),
+            (Config { data_dir: PathBuf::new(), ..Config::default() }, "data_dir must not be empty"),
+        ];
+        for (config, message) in cases {
+            let err = config.validate().unwrap_err();
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn tls_settings_are_refused() {
+        let cert = Config { tls_cert_path: Some(PathBuf::from("cert.pem")), ..Config::default() };
+        let key = Config { tls_key_path: Some(PathBuf::from("key.pem")), ..Config::default() };
+        for config in [cert, key] {
+            let err = config.validate().unwrap_err();
+            assert!(err.to_string().contains("no TLS support"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn secrets_are_redacted_in_the_logged_config() {
+        let config = Config {
+            read_token_secret: Some("hunter2-read".to_string()),
+            creator_hash_secret: Some("hunter2-creator".to_string()),
+            max_paste_bytes: 12345,
+            ..Config::default()
+        };
+
+        let logged = serde_json::to_value(&config).unwrap();
+        assert_eq!(logged["read_token_secret"], "***");
+        assert_eq!(logged["creator_hash_secret"], "***");
+        assert_eq!(logged["max_paste_bytes"], 12345, "the rest is shown as configured");
+        assert_eq!(serde_json::to_value(Config::default()).unwrap()["read_token_secret"], serde_json::Value::Null, "unset stays null");
+
+        let debug = format!("{:?}", config);
+        assert!(debug.starts_with("Config {") && debug.contains("\"read_token_secret\":\"***\""), "{}", debug);
+        for shown in [logged.to_string(), debug] {
+            assert!(!shown.contains("hunter2"), "{}", shown);
+        }
+    }
+}
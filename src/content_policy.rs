@@ -0,0 +1,71 @@
+use regex::Regex;
+
+/// Outcome of checking paste content against a [`ContentPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDecision {
+    Allow,
+    /// Rejected; `rule` describes the matching rule for the server log only.
+    Block { rule: String },
+}
+
+/// Decides whether plaintext content may be stored, e.g. to honour takedowns.
+///
+/// Only consulted for plaintext pastes: client-encrypted content can't be read
+/// by the server, so it is never evaluated.
+pub trait ContentPolicy: Send + Sync {
+    fn evaluate(&self, data: &str, language: &str) -> PolicyDecision;
+}
+
+/// Blocks content containing any banned substring (case-insensitive) or matching
+/// any banned regex.
+#[derive(Debug)]
+pub struct BannedContentPolicy {
+    substrings: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl BannedContentPolicy {
+    pub fn new(substrings: &[String], patterns: &[String]) -> Result<Self, regex::Error> {
+        Ok(Self {
+            substrings: substrings.iter().map(|substring| substring.to_lowercase()).collect(),
+            patterns: patterns.iter().map(|pattern| Regex::new(pattern)).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Whether there are no rules, so every paste would be allowed.
+    pub fn is_empty(&self) -> bool {
+        self.substrings.is_empty() && self.patterns.is_empty()
+    }
+}
+
+impl ContentPolicy for BannedContentPolicy {
+    fn evaluate(&self, data: &str, _language: &str) -> PolicyDecision {
+        if !self.substrings.is_empty() {
+            let lowered = data.to_lowercase();
+            if let Some(substring) = self.substrings.iter().find(|substring| lowered.contains(substring.as_str())) {
+                return PolicyDecision::Block { rule: format!("substring {:?}", substring) };
+            }
+        }
+
+        match self.patterns.iter().find(|pattern| pattern.is_match(data)) {
+            Some(pattern) => PolicyDecision::Block { rule: format!("pattern {:?}", pattern.as_str()) },
+            None => PolicyDecision::Allow,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_banned_substrings_and_patterns_only() {
+        let policy = BannedContentPolicy::new(&["Leaked Keys".to_string()], &[r"\b\d{4}-\d{4}-\d{4}-\d{4}\b".to_string()]).unwrap();
+        assert!(matches!(policy.evaluate("here are the LEAKED KEYS", "plaintext"), PolicyDecision::Block { .. }), "case-insensitive");
+        assert!(matches!(policy.evaluate("card 1234-5678-9012-3456", "plaintext"), PolicyDecision::Block { .. }));
+        assert_eq!(policy.evaluate("fn main() {}", "rust"), PolicyDecision::Allow);
+
+        assert!(BannedContentPolicy::new(&[], &[]).unwrap().is_empty());
+        assert!(BannedContentPolicy::new(&[], &["(unclosed".to_string()]).is_err());
+    }
+}
@@ -1,12 +1,20 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use rand::{distributions::Alphanumeric, Rng};
-use sqlite::{Connection, State};
+use sqlite::{Connection, OpenFlags, State};
+use std::collections::HashMap;
 use std::fs;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use sha2::{Sha256, Digest};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+
+use crate::compress;
+use crate::content_policy::{ContentPolicy, PolicyDecision};
+use crate::diff::{self, LineDiff};
+use crate::language;
 
 // Define the Paste struct
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,17 +32,64 @@ pub struct Paste {
     pub edit_key_hash: Option<String>, // Only returned for admin listing
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub paste_type: Option<String>, // "paste" or "workspace", populated in admin queries
+    #[serde(default)]
+    pub normalized: bool, // BOM/trailing whitespace was stripped at storage time
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub views: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_views: Option<u64>, // Paste is deleted once it has been read this many times
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>, // Absolute link to the paste, only returned on creation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language_detected: Option<bool>, // Set on creation when "auto" was requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_sha256: Option<String>, // Client-supplied hash, stored and echoed on reads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_sha256: Option<String>, // Hash of the stored data, on creation, reads and updates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<String>, // ID of the paste this one was forked from
+    #[serde(default)]
+    pub pinned: bool, // Exempt from expiry and eviction
+    #[serde(default)]
+    pub visibility: Visibility,
+    #[serde(default)]
+    pub encoding: Encoding,
+    #[serde(default)]
+    pub theme_hint: Option<String>, // Highlight theme suggested for the language, never stored
 }
 
 // Data structure for creating a new paste
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreatePasteData {
     pub data: String,
-    pub language: String,
+    #[serde(default)]
+    pub language: Option<String>, // Missing or empty means the configured default
     #[serde(default)]
     pub burn_after_read: bool,
     #[serde(default)]
     pub expires_in_minutes: Option<u32>,
+    #[serde(default = "default_encryption_version")]
+    pub encryption_version: u8,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub max_views: Option<u64>,
+    #[serde(default)]
+    pub content_sha256: Option<String>, // Hex SHA-256 of `data`, checked on receipt
+    #[serde(default)]
+    pub visibility: Visibility,
+    #[serde(default)]
+    pub encoding: Encoding, // How readers should interpret `data`; base64 is checked to decode
+    #[serde(default)]
+    pub validate_syntax: bool, // Parse plaintext against its language and reject it if malformed
+    #[serde(skip)]
+    pub creator_hash: Option<String>, // Set by the server from the client address, never by clients
+}
+
+fn default_encryption_version() -> u8 {
+    ENCRYPTION_VERSION_CLIENT
 }
 
 // Data structure for updating a paste
@@ -43,6 +98,15 @@ pub struct UpdatePasteData {
     pub data: String,
     pub language: String,
     pub edit_key: String,
+    #[serde(default)]
+    pub content_sha256: Option<String>, // Replaces the stored hash; omitting it clears it
+}
+
+// Data structure for appending to a plaintext paste
+#[derive(Debug, Deserialize)]
+pub struct AppendPasteData {
+    pub data: String,
+    pub edit_key: String,
 }
 
 // Data structure for deleting a paste
@@ -77,26 +141,470 @@ pub enum DbError {
     
     #[error("Paste not found")]
     PasteNotFound,
+
+    #[error("Paste is private")]
+    PastePrivate,
+
+    #[error("Paste has expired")]
+    PasteExpired,
+
+    #[error("Paste is in the trash")]
+    PasteTrashed,
     
     #[error("Failed to generate unique ID after maximum retries")]
     IdGenerationFailed,
+
+    #[error("Plaintext pastes are disabled")]
+    PlaintextNotAllowed,
+
+    #[error("Unsupported encryption version: {0}")]
+    UnsupportedEncryptionVersion(u8),
+
+    #[error("Invalid tag: {0}")]
+    InvalidTag(String),
+
+    #[error("Too many tags: {0} (maximum: {1})")]
+    TooManyTags(usize, usize),
+
+    #[error("Invalid max_views: must be at least 1")]
+    InvalidMaxViews,
+
+    #[error("Invalid content_sha256: must be 64 hex characters")]
+    InvalidContentHash,
+
+    #[error("content_sha256 does not match the received data")]
+    ContentHashMismatch,
+
+    #[error("Paste {0} already exists")]
+    ImportConflict(String),
+
+    #[error("ID conflict: a row with this ID was inserted concurrently")]
+    IdConflict,
+
+    #[error("Batch item {0} failed: {1}")]
+    BatchItemFailed(usize, Box<DbError>),
+
+    #[error("Paste content rejected by the content policy")]
+    ContentRejected,
+
+    #[error("Idempotency key was already used with a different request")]
+    IdempotencyKeyReused,
+
+    #[error("Too many lines: {0} (maximum: {1})")]
+    TooManyLines(usize, usize),
+
+    #[error("Only plaintext pastes can be appended to")]
+    AppendToEncrypted,
+
+    #[error("Only plaintext pastes can be diffed")]
+    DiffOfEncrypted,
+
+    #[error("Content is not valid {0}: {1}")]
+    SyntaxInvalid(&'static str, String),
+
+    #[error("Expiry too long: {0} seconds (maximum: {1})")]
+    TtlTooLong(u64, u64),
+
+    #[error("Content declared as base64 does not decode: {0}")]
+    InvalidBase64(String),
+
+    #[error("Only plaintext pastes can declare an encoding")]
+    EncodingOfEncrypted,
+
+    #[error("This paste was already reported from this address")]
+    AlreadyReported,
+
+    #[error("Too many reports: at most {0} per hour")]
+    ReportLimitExceeded(u32),
+
+    #[error("Stored paste data is corrupt")]
+    CorruptData,
+
+    #[error("{} fields failed validation", .0.len())]
+    ValidationFailed(Vec<FieldError>),
+}
+
+/// One failed check on a create request, with the request field it concerns.
+#[derive(Debug)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub error: DbError,
+}
+
+// Collects the field checks of a create request, so every failure is reported
+// together rather than only the first
+#[derive(Default)]
+struct FieldErrors(Vec<FieldError>);
+
+impl FieldErrors {
+    fn push(&mut self, field: &'static str, error: DbError) {
+        self.0.push(FieldError { field, error });
+    }
+
+    // The check's value, or None after recording its failure against `field`
+    fn check<T>(&mut self, field: &'static str, result: Result<T, DbError>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.push(field, error);
+                None
+            }
+        }
+    }
+
+    fn into_result(self) -> Result<(), DbError> {
+        if self.0.is_empty() { Ok(()) } else { Err(DbError::ValidationFailed(self.0)) }
+    }
+}
+
+/// Operator-configurable behavior for how paste content is accepted and stored.
+#[derive(Debug, Clone)]
+pub struct PasteOptions {
+    /// Maximum size of stored paste data in bytes.
+    pub max_paste_bytes: usize,
+    /// Maximum number of lines in a plaintext paste; `0` means unlimited.
+    pub max_paste_lines: usize,
+    /// Accept pastes with `encryption_version: 0` (server-visible plaintext).
+    pub allow_plaintext: bool,
+    /// Strip a leading UTF-8 BOM from plaintext pastes.
+    pub strip_bom: bool,
+    /// Strip trailing spaces and tabs from each line of plaintext pastes.
+    pub strip_trailing_whitespace: bool,
+    /// Maximum number of tags a paste may carry.
+    pub max_tags: usize,
+    /// How long deleted pastes stay restorable; `0` deletes immediately.
+    pub trash_retention_secs: u64,
+    /// Length of generated paste IDs, before any collision retries.
+    pub id_length: usize,
+    /// Characters generated paste IDs are drawn from.
+    pub id_charset: IdCharset,
+    /// Maximum number of stored records; the oldest unpinned ones are evicted
+    /// past it. `0` means unlimited.
+    pub max_total_pastes: u64,
+    /// Leave expired pastes for the purge instead of deleting them when read, so
+    /// reading one costs the same as a miss.
+    pub defer_expired_deletes: bool,
+    /// Buffer view increments in memory until `flush_views`, instead of writing
+    /// each one. Pastes with `max_views` are always counted in the database.
+    pub buffer_views: bool,
+    /// How long an `Idempotency-Key` replays its original create.
+    pub idempotency_ttl_secs: u64,
+    /// Longest expiry a paste may ask for, in seconds.
+    pub max_ttl_secs: u64,
+    /// Expiry given to pastes that don't ask for one; `0` means they never expire.
+    pub default_ttl_secs: u64,
+    /// Cut expiries past `max_ttl_secs` down to it instead of rejecting them.
+    pub clamp_ttl: bool,
+    /// Language used when a create omits it or sends it empty; `auto` detects it.
+    pub default_language: String,
+    /// How long one operation may hold the connection before it's logged as a slow
+    /// query; `None` turns the warning off.
+    pub slow_query: Option<Duration>,
+    /// Serve reads without writing: views aren't counted, and nothing is deleted
+    /// when read (burn-after-read, `max_views`, expired pastes).
+    pub read_only: bool,
+    /// Highlight theme suggested to clients for each language, keyed by lowercased
+    /// language identifier.
+    pub theme_hints: HashMap<String, String>,
+    /// How long SQLite retries a locked database before failing with `SQLITE_BUSY`;
+    /// zero fails immediately.
+    pub busy_timeout: Duration,
+    /// Record each content read in the `access_log` table.
+    pub access_log: bool,
+    /// Store paste data longer than this many bytes compressed, when that makes it
+    /// smaller; `0` stores everything verbatim.
+    pub compress_over: usize,
+}
+
+/// A locked connection that logs how long it was held when dropped: at debug level
+/// always, and as a warning once that reaches the slow query threshold.
+struct ConnGuard<'a> {
+    guard: MutexGuard<'a, Connection>,
+    op: &'static str,
+    started: Instant,
+    slow_query: Option<Duration>,
+}
+
+impl std::ops::Deref for ConnGuard<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.guard
+    }
+}
+
+impl Drop for ConnGuard<'_> {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        crate::server_timing::record_db_time(elapsed);
+        tracing::debug!("Database {} took {} µs", self.op, elapsed.as_micros());
+        if self.slow_query.is_some_and(|threshold| elapsed >= threshold) {
+            tracing::warn!("Slow query: database {} took {:.1} ms", self.op, elapsed.as_secs_f64() * 1000.0);
+        }
+    }
+}
+
+/// How often to try opening the database at startup before giving up.
+#[derive(Debug, Clone, Copy)]
+pub struct OpenRetry {
+    /// Total attempts, including the first.
+    pub attempts: u32,
+    /// Wait after the first failure; doubled after each later one.
+    pub initial_delay: Duration,
+}
+
+// Longest wait between attempts to open the database
+const MAX_OPEN_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// What a raw read knows about a paste besides its data.
+#[derive(Debug)]
+pub struct RawPasteInfo {
+    pub language: String,
+    pub encoding: Encoding,
+    /// Reads use the paste up: it burns after reading or has a view limit.
+    pub limited_reads: bool,
+    /// When the paste expires; pinned pastes never do.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A raw read's data.
+#[derive(Debug)]
+pub enum RawData {
+    /// Loaded in one piece: small, compressed, base64, or consumed by the read.
+    Whole(String),
+    /// Read a chunk at a time from a snapshot taken with the paste's metadata.
+    Chunks(PasteChunks),
+}
+
+/// Bytes of paste data read per chunk of a streamed raw read.
+pub const RAW_CHUNK_BYTES: u64 = 64 * 1024;
+
+/// A paste's stored data, read `RAW_CHUNK_BYTES` at a time inside one read
+/// transaction on a connection of its own. Every chunk comes from the same
+/// version of the paste, and a slow client doesn't hold the shared connection.
+pub struct PasteChunks {
+    conn: Connection,
+    id: String,
+    offset: u64,
+    size: u64,
+}
+
+impl std::fmt::Debug for PasteChunks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PasteChunks")
+            .field("id", &self.id)
+            .field("offset", &self.offset)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+impl PasteChunks {
+    // Open a snapshot of the database and check the paste is still stored
+    // uncompressed there. None when it isn't, so the caller reads it whole.
+    fn open(uri: &str, busy_timeout: Duration, id: &str) -> Result<Option<Self>, DbError> {
+        let conn = Connection::open_with_flags(uri, OpenFlags::new().with_read_only().with_uri())?;
+        Database::set_busy_timeout(&conn, busy_timeout)?;
+        // The snapshot is taken by the first read and held until the connection closes
+        conn.execute("BEGIN;")?;
+
+        let mut stmt = conn.prepare("SELECT length(CAST(data AS BLOB)), compressed FROM pastes WHERE id = ? AND deleted_at IS NULL")?;
+        stmt.bind((1, id))?;
+        if stmt.next()? != State::Row || stmt.read::<i64, _>(1)? != 0 {
+            return Ok(None);
+        }
+        let size = stmt.read::<i64, _>(0)? as u64;
+        drop(stmt);
+
+        Ok(Some(Self { conn, id: id.to_string(), offset: 0, size }))
+    }
+
+    /// Length of the paste's data in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The next chunk of data, or None once all of it has been read.
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, DbError> {
+        if self.offset >= self.size {
+            return Ok(None);
+        }
+
+        let mut stmt = self.conn.prepare("SELECT substr(CAST(data AS BLOB), ?, ?) FROM pastes WHERE id = ?")?;
+        // substr is 1-based
+        stmt.bind((1, self.offset as i64 + 1))?;
+        stmt.bind((2, RAW_CHUNK_BYTES.min(self.size - self.offset) as i64))?;
+        stmt.bind((3, self.id.as_str()))?;
+        if stmt.next()? != State::Row {
+            return Err(DbError::CorruptData);
+        }
+        let chunk = stmt.read::<Vec<u8>, _>(0)?;
+        if chunk.is_empty() {
+            return Err(DbError::CorruptData);
+        }
+        drop(stmt);
+
+        self.offset += chunk.len() as u64;
+        Ok(Some(chunk))
+    }
+}
+
+/// Outcome of a create carrying an idempotency key.
+#[derive(Debug)]
+pub enum IdempotentCreate {
+    Created(Paste),
+    Replayed(Paste),
+}
+
+/// Character set for generated paste IDs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdCharset {
+    /// `A-Z`, `a-z` and `0-9` (base62).
+    #[default]
+    Alphanumeric,
+    /// Base62 plus `-` and `_`, the URL-safe base64 alphabet.
+    UrlSafe,
+}
+
+impl IdCharset {
+    /// Whether `c` is one of this charset's characters.
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            Self::Alphanumeric => c.is_ascii_alphanumeric(),
+            Self::UrlSafe => c.is_ascii_alphanumeric() || c == '-' || c == '_',
+        }
+    }
+}
+
+impl std::str::FromStr for IdCharset {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "alphanumeric" => Ok(Self::Alphanumeric),
+            "url_safe" => Ok(Self::UrlSafe),
+            other => Err(format!("unknown charset {:?}, expected alphanumeric or url_safe", other)),
+        }
+    }
+}
+
+/// Who may find and read a paste.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    /// Readable by ID and listed wherever pastes are listed.
+    Public,
+    /// Readable by anyone with the ID but never listed, like every paste before
+    /// visibility existed.
+    #[default]
+    Unlisted,
+    /// Readable only with the paste's edit key, and never listed.
+    Private,
+}
+
+impl Visibility {
+    /// Value stored in the `visibility` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Unlisted => "unlisted",
+            Self::Private => "private",
+        }
+    }
+
+    // Parse a stored value. Anything unrecognised is treated as private, so a bad
+    // row is never exposed.
+    fn from_stored(value: &str) -> Self {
+        match value {
+            "public" => Self::Public,
+            "unlisted" => Self::Unlisted,
+            _ => Self::Private,
+        }
+    }
+}
+
+impl std::str::FromStr for Visibility {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "public" => Ok(Self::Public),
+            "unlisted" => Ok(Self::Unlisted),
+            "private" => Ok(Self::Private),
+            other => Err(format!("unknown visibility {:?}, expected public, unlisted or private", other)),
+        }
+    }
+}
+
+/// How a paste's `data` is to be read. The server only acts on it for plaintext
+/// pastes: base64 is checked on every write and decoded by raw reads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// `data` is the content itself.
+    #[default]
+    Utf8,
+    /// `data` is base64 (standard alphabet, padded) of binary content.
+    Base64,
+}
+
+impl Encoding {
+    /// Value stored in the `encoding` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf8",
+            Self::Base64 => "base64",
+        }
+    }
+
+    // Parse a stored value. Anything unrecognised is read as UTF-8, which serves the
+    // data unchanged.
+    fn from_stored(value: &str) -> Self {
+        match value {
+            "base64" => Self::Base64,
+            _ => Self::Utf8,
+        }
+    }
+
+    /// The content bytes `data` stands for. Base64 may be wrapped across lines, as
+    /// `base64` writes it.
+    pub fn decode(self, data: &str) -> Result<Vec<u8>, base64::DecodeError> {
+        match self {
+            Self::Utf8 => Ok(data.as_bytes().to_vec()),
+            Self::Base64 => {
+                let compact: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+                base64::engine::general_purpose::STANDARD.decode(compact)
+            }
+        }
+    }
 }
 
 // Database struct
 #[derive(Clone)]
 pub struct Database {
     connection: Arc<Mutex<Connection>>,
+    // URI opening the database file read-only, for reads that need a snapshot of
+    // their own; None for an in-memory database
+    snapshot_uri: Option<String>,
+    options: PasteOptions,
+    // View increments not yet written to the database, by paste ID. Only touched
+    // while holding `connection`, so reads see the stored count plus this delta.
+    pending_views: Arc<Mutex<std::collections::HashMap<String, u64>>>,
+    content_policy: Option<Arc<dyn ContentPolicy>>,
 }
 
 impl std::fmt::Debug for Database {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Database")
             .field("connection", &"<SQLite Connection>")
+            .field("options", &self.options)
             .finish()
     }
 }
 
 // Internal struct for encrypted paste data
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 struct PasteData {
     title: String,
@@ -121,25 +629,302 @@ pub struct DashboardStats {
     pub pastes_over_time: Vec<DailyPasteStats>,
 }
 
+/// Non-sensitive details of a paste, without its content.
+#[derive(Debug, Serialize)]
+pub struct PasteMeta {
+    pub id: String,
+    pub language: String,
+    pub created_at: DateTime<Utc>,
+    pub views: u64,
+    pub encryption_version: u8,
+    pub size_bytes: u64,
+}
+
+/// One read of a paste recorded in the access log.
+#[derive(Debug, Serialize)]
+pub struct AccessLogEntry {
+    pub accessed_at: DateTime<Utc>,
+    /// Keyed hash of the reader's address, as for `creator_hash`; `None` without a secret.
+    pub ip_hash: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// A live paste with abuse reports against it, for the admin moderation list.
+#[derive(Debug, Serialize)]
+pub struct ReportedPaste {
+    pub paste_id: String,
+    pub reports: u64,
+    pub first_reported_at: DateTime<Utc>,
+    pub last_reported_at: DateTime<Utc>,
+    /// Reasons of the most recent reports, newest first.
+    pub reasons: Vec<String>,
+}
+
+// Most reasons listed per paste in the moderation list
+const MAX_LISTED_REPORT_REASONS: usize = 5;
+
+// Longest User-Agent kept in the access log; longer ones are cut at a char boundary
+const MAX_LOGGED_USER_AGENT_CHARS: usize = 256;
+
+/// Everything stored about a paste or workspace except its content, for admins
+/// reviewing abuse reports. Includes expired and trashed records.
+#[derive(Debug, Serialize)]
+pub struct PasteDetails {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub language: String,
+    pub created_at: DateTime<Utc>,
+    pub encryption_version: u8,
+    pub size_bytes: u64,
+    pub has_edit_key: bool,
+    pub burn_after_read: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub normalized: bool,
+    pub views: u64,
+    pub max_views: Option<u64>,
+    pub tags: Vec<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub pinned: bool,
+    pub content_sha256: Option<String>,
+    pub forked_from: Option<String>,
+    pub creator_hash: Option<String>,
+    pub visibility: Visibility,
+    pub encoding: Encoding,
+}
+
+/// Complete stored record of a paste or workspace, used for backup export/import.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasteRecord {
+    pub id: String,
+    #[serde(rename = "type", default = "default_record_type")]
+    pub record_type: String,
+    pub data: String,
+    pub language: String,
+    pub created_at: DateTime<Utc>,
+    pub encryption_version: u8,
+    #[serde(default)]
+    pub edit_key_hash: Option<String>,
+    #[serde(default)]
+    pub burn_after_read: bool,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub normalized: bool,
+    #[serde(default)]
+    pub views: u64,
+    #[serde(default)]
+    pub max_views: Option<u64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub content_sha256: Option<String>,
+    #[serde(default)]
+    pub forked_from: Option<String>,
+    #[serde(default)]
+    pub creator_hash: Option<String>,
+    #[serde(default)]
+    pub visibility: Visibility,
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+fn default_record_type() -> String {
+    "paste".to_string()
+}
+
+/// What to do when an imported record's ID already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportConflict {
+    Skip,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped: Vec<String>,
+}
+
+/// How aggressively `Database::checkpoint` copies the WAL back into the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointMode {
+    /// Checkpoint what it can without waiting on readers or writers.
+    Passive,
+    /// Wait for a full checkpoint, then truncate the WAL file to zero bytes.
+    Truncate,
+}
+
+impl CheckpointMode {
+    fn as_sql(self) -> &'static str {
+        match self {
+            Self::Passive => "PASSIVE",
+            Self::Truncate => "TRUNCATE",
+        }
+    }
+}
+
+/// Frame counts reported by `PRAGMA wal_checkpoint`.
+#[derive(Debug, Serialize)]
+pub struct CheckpointResult {
+    /// Whether the checkpoint was blocked from completing.
+    pub busy: bool,
+    /// Frames in the WAL file (`-1` when not in WAL mode).
+    pub log_frames: i64,
+    /// Frames copied back into the database (`-1` when not in WAL mode).
+    pub checkpointed_frames: i64,
+}
+
+/// Pastes removed by a purge, by reason.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct PurgeCounts {
+    /// Unpinned pastes past their expiration time.
+    pub expired: usize,
+    /// Trashed pastes past `trash_retention_secs`.
+    pub trashed: usize,
+    /// Oldest unpinned pastes evicted to get back under `max_total_pastes`.
+    pub evicted: usize,
+}
+
+impl PurgeCounts {
+    pub fn total(&self) -> usize {
+        self.expired + self.trashed + self.evicted
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub total_pastes: i64,
+    pub total_bytes: i64,
+    pub oldest_created_at: Option<i64>,
+    pub newest_created_at: Option<i64>,
+    pub by_encryption_version: std::collections::HashMap<u8, i64>,
+}
+
 // Encryption version constants
+pub const ENCRYPTION_VERSION_NONE: u8 = 0;
 const ENCRYPTION_VERSION_CLIENT: u8 = 1;
 
-// Maximum character limit for pastes
-const MAX_PASTE_CHARACTERS: usize = 200000;
+// UTF-8 byte order mark
+const UTF8_BOM: char = '\u{feff}';
+
+// Row values for a paste being inserted
+struct NewPaste<'a> {
+    id: String,
+    data: String,
+    language: String,
+    created_at: DateTime<Utc>,
+    encryption_version: u8,
+    edit_key_hash: String,
+    burn_after_read: bool,
+    expires_at: Option<i64>,
+    record_type: &'a str,
+    normalized: bool,
+    tags: Vec<String>,
+    max_views: Option<u64>,
+    language_detected: Option<bool>,
+    content_sha256: Option<String>,
+    forked_from: Option<String>,
+    creator_hash: Option<String>,
+    visibility: Visibility,
+    encoding: Encoding,
+}
+
+// Maximum length of a single tag
+const MAX_TAG_LENGTH: usize = 32;
+
+// Maximum number of pastes returned when listing by tag
+const MAX_TAG_LISTING: i64 = 100;
+
+/// Default directory holding the database.
+pub const DEFAULT_DATA_DIR: &str = "data";
+
+// Database file name inside the data directory
+const DATABASE_FILE: &str = "pastes.db";
+
+// File written and removed to check that the data directory is writable
+const WRITE_PROBE_FILE: &str = ".write-probe";
 
 // Maximum retries for ID generation
 const MAX_ID_GENERATION_RETRIES: u32 = 10;
 
-// Base ID length
-const BASE_ID_LENGTH: usize = 6;
+/// Default length of generated paste IDs.
+pub const DEFAULT_ID_LENGTH: usize = 6;
+
+/// Shortest configurable paste ID length.
+pub const MIN_ID_LENGTH: usize = 4;
+
+/// Longest configurable paste ID length.
+pub const MAX_ID_LENGTH: usize = 32;
+
+/// Longest ID generation can produce: the longest configurable length, grown by
+/// one on every collision retry.
+pub const MAX_GENERATED_ID_LENGTH: usize = MAX_ID_LENGTH + MAX_ID_GENERATION_RETRIES as usize - 1;
+
+// Alphabet for IdCharset::UrlSafe
+const URL_SAFE_ID_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+// A paste's stored created_at. Rows are written as INTEGER seconds, but older writers
+// could leave TEXT (seconds or RFC 3339) behind; parse those rather than misreading or
+// dropping the row, and warn once so operators know to migrate them.
+fn stored_created_at(value: &sqlite::Value) -> DateTime<Utc> {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+
+    let parsed = match value {
+        sqlite::Value::Integer(seconds) => return DateTime::from_timestamp(*seconds, 0).unwrap_or_else(Utc::now),
+        sqlite::Value::String(text) => text
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+            .or_else(|| DateTime::parse_from_rfc3339(text.trim()).ok().map(|at| at.with_timezone(&Utc))),
+        _ => None,
+    };
+
+    WARNED.call_once(|| {
+        tracing::warn!(
+            "Found a paste with a non-integer created_at ({:?}); rewrite such rows as Unix seconds",
+            value
+        );
+    });
+    parsed.unwrap_or_else(Utc::now)
+}
+
+// A paste's content size in bytes, in SQL, whether or not it's stored compressed
+const DATA_BYTES: &str = "CASE WHEN compressed > 0 THEN compressed ELSE length(CAST(data AS BLOB)) END";
+
+// `data` as it goes in the `data` column, with the `compressed` value beside it:
+// past `compress_over` bytes a compressed block, if that's smaller than the text
+fn data_to_store(data: &str, compress_over: usize) -> (sqlite::Value, i64) {
+    if compress_over > 0 && data.len() > compress_over {
+        let block = compress::compress(data.as_bytes());
+        if block.len() < data.len() {
+            return (sqlite::Value::Binary(block), data.len() as i64);
+        }
+    }
+    (sqlite::Value::String(data.to_string()), 0)
+}
 
-// Maximum expiration time in minutes (1 week)
-const MAX_EXPIRES_IN_MINUTES: u32 = 10080;
+// A paste's content from its `data` and `compressed` columns
+fn stored_data(data: sqlite::Value, compressed: i64) -> Result<String, DbError> {
+    match data {
+        sqlite::Value::String(text) if compressed == 0 => Ok(text),
+        sqlite::Value::Binary(block) if compressed > 0 => compress::decompress(&block, compressed as usize)
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(DbError::CorruptData),
+        _ => Err(DbError::CorruptData),
+    }
+}
 
 impl Database {
     // Helper function to get precise UTF-8 byte count
     fn get_utf8_byte_count(text: &str) -> usize {
-        text.as_bytes().len()
+        text.len()
     }
     
     // Generate a random alphanumeric ID
@@ -150,13 +935,48 @@ impl Database {
             .map(char::from)
             .collect()
     }
+
+    // Generate a random paste ID in the configured charset
+    fn generate_paste_id(&self, length: usize) -> String {
+        match self.options.id_charset {
+            IdCharset::Alphanumeric => Self::generate_id(length),
+            IdCharset::UrlSafe => {
+                let mut rng = rand::thread_rng();
+                (0..length)
+                    .map(|_| char::from(URL_SAFE_ID_CHARS[rng.gen_range(0..URL_SAFE_ID_CHARS.len())]))
+                    .collect()
+            }
+        }
+    }
     
+    // Lowercase hex SHA-256 of paste data, as compared against `content_sha256`
+    fn sha256_hex(data: &str) -> String {
+        format!("{:x}", Sha256::digest(data.as_bytes()))
+    }
+
+    // Check a client-supplied content hash against the data as received, returning
+    // it normalized to lowercase
+    fn verify_content_hash(content_sha256: Option<String>, data: &str) -> Result<Option<String>, DbError> {
+        let Some(expected) = content_sha256 else {
+            return Ok(None);
+        };
+        if expected.len() != 64 || !expected.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(DbError::InvalidContentHash);
+        }
+
+        let expected = expected.to_ascii_lowercase();
+        if expected != Self::sha256_hex(data) {
+            return Err(DbError::ContentHashMismatch);
+        }
+        Ok(Some(expected))
+    }
+
     // Hash an edit key for storage
     fn hash_edit_key(edit_key: &str) -> String {
         let mut hasher = Sha256::new();
         hasher.update(edit_key.as_bytes());
         let result = hasher.finalize();
-        base64::encode(result)
+        BASE64.encode(result)
     }
     
     // Verify an edit key matches the stored hash
@@ -165,9 +985,204 @@ impl Database {
         computed_hash == stored_hash
     }
     
-    // Check if a paste ID already exists
-    fn paste_exists(&self, id: &str) -> Result<bool, DbError> {
-        let conn = self.connection.lock().unwrap();
+    // Strip a leading BOM and/or per-line trailing whitespace, per the configured options.
+    // Returns the (possibly unchanged) content and whether anything was removed.
+    fn normalize_content(&self, data: String) -> (String, bool) {
+        let mut result = data.as_str();
+
+        if self.options.strip_bom {
+            result = result.strip_prefix(UTF8_BOM).unwrap_or(result);
+        }
+
+        let normalized = if self.options.strip_trailing_whitespace {
+            result
+                .split_inclusive('\n')
+                .map(|line| {
+                    let (body, ending) = match line.strip_suffix("\r\n") {
+                        Some(body) => (body, "\r\n"),
+                        None => match line.strip_suffix('\n') {
+                            Some(body) => (body, "\n"),
+                            None => (line, ""),
+                        },
+                    };
+                    format!("{}{}", body.trim_end_matches([' ', '\t']), ending)
+                })
+                .collect::<String>()
+        } else {
+            result.to_string()
+        };
+
+        if normalized.len() == data.len() {
+            (data, false)
+        } else {
+            (normalized, true)
+        }
+    }
+
+    // Consult the content policy, if any, for plaintext; encrypted content is unreadable
+    fn check_content_policy(&self, data: &str, language: &str, encryption_version: u8) -> Result<(), DbError> {
+        let Some(policy) = &self.content_policy else {
+            return Ok(());
+        };
+
+        if encryption_version != ENCRYPTION_VERSION_NONE {
+            tracing::debug!("Content policy skipped for client-encrypted paste");
+            return Ok(());
+        }
+
+        match policy.evaluate(data, language) {
+            PolicyDecision::Allow => Ok(()),
+            PolicyDecision::Block { rule } => {
+                tracing::warn!("Rejected paste content: matched {}", rule);
+                Err(DbError::ContentRejected)
+            }
+        }
+    }
+
+    // Theme hint configured for a language, if any
+    fn theme_hint(&self, language: &str) -> Option<String> {
+        self.options.theme_hints.get(&language.to_ascii_lowercase()).cloned()
+    }
+
+    // Base64 content has to decode, so raw reads can always serve its bytes. Only
+    // plaintext may declare base64: encrypted data is for the client to interpret.
+    fn check_encoding(data: &str, encoding: Encoding, encryption_version: u8) -> Result<(), DbError> {
+        if encoding == Encoding::Utf8 {
+            return Ok(());
+        }
+        if encryption_version != ENCRYPTION_VERSION_NONE {
+            return Err(DbError::EncodingOfEncrypted);
+        }
+        encoding.decode(data).map(|_| ()).map_err(|e| DbError::InvalidBase64(e.to_string()))
+    }
+
+    // Unix time a new paste expires at. Asking for more than max_ttl_secs is
+    // TtlTooLong, or cut down to it with clamp_ttl; asking for nothing gets the
+    // default expiry, if one is set.
+    fn expiry_timestamp(&self, expires_in_minutes: Option<u32>) -> Result<Option<i64>, DbError> {
+        let max = self.options.max_ttl_secs;
+        let ttl_secs = match expires_in_minutes {
+            Some(0) => return Err(DbError::CharacterLimitExceeded(0, (max / 60) as usize)),
+            Some(minutes) if minutes as u64 * 60 > max && !self.options.clamp_ttl => {
+                return Err(DbError::TtlTooLong(minutes as u64 * 60, max));
+            }
+            Some(minutes) => (minutes as u64 * 60).min(max),
+            None if self.options.default_ttl_secs > 0 => self.options.default_ttl_secs,
+            None => return Ok(None),
+        };
+        Ok(Some(Utc::now().timestamp() + ttl_secs as i64))
+    }
+
+    // Parse plaintext in a format we can check. Languages without a parser here
+    // (and encrypted content, which we can't read) pass unchecked.
+    fn check_syntax(data: &str, language: &str, encryption_version: u8) -> Result<(), DbError> {
+        if encryption_version != ENCRYPTION_VERSION_NONE {
+            return Ok(());
+        }
+
+        match language {
+            "json" => serde_json::from_str::<serde::de::IgnoredAny>(data)
+                .map(|_| ())
+                .map_err(|e| DbError::SyntaxInvalid("JSON", e.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    // Enforce the line limit on plaintext; encrypted content only has the byte limit
+    fn check_line_limit(&self, data: &str, encryption_version: u8) -> Result<(), DbError> {
+        if self.options.max_paste_lines == 0 || encryption_version != ENCRYPTION_VERSION_NONE {
+            return Ok(());
+        }
+
+        let lines = data.lines().count();
+        if lines > self.options.max_paste_lines {
+            return Err(DbError::TooManyLines(lines, self.options.max_paste_lines));
+        }
+        Ok(())
+    }
+
+    // Validate the requested encryption version against the configured options
+    fn check_encryption_version(&self, encryption_version: u8) -> Result<(), DbError> {
+        match encryption_version {
+            ENCRYPTION_VERSION_CLIENT => Ok(()),
+            ENCRYPTION_VERSION_NONE if self.options.allow_plaintext => Ok(()),
+            ENCRYPTION_VERSION_NONE => Err(DbError::PlaintextNotAllowed),
+            other => Err(DbError::UnsupportedEncryptionVersion(other)),
+        }
+    }
+
+    // Whether a stored encryption version can be served to clients
+    fn is_readable_version(encryption_version: u8) -> bool {
+        encryption_version == ENCRYPTION_VERSION_CLIENT || encryption_version == ENCRYPTION_VERSION_NONE
+    }
+
+    // Lowercase, deduplicate and validate tags (alphanumeric, '-' or '_', up to 32 chars)
+    fn normalize_tags(&self, tags: Vec<String>) -> Result<Vec<String>, DbError> {
+        let mut normalized: Vec<String> = Vec::new();
+        for tag in tags {
+            let tag = Self::normalize_tag(&tag)?;
+            if !normalized.contains(&tag) {
+                normalized.push(tag);
+            }
+        }
+
+        if normalized.len() > self.options.max_tags {
+            return Err(DbError::TooManyTags(normalized.len(), self.options.max_tags));
+        }
+
+        Ok(normalized)
+    }
+
+    // Normalize and validate a single tag
+    pub fn normalize_tag(tag: &str) -> Result<String, DbError> {
+        let tag = tag.trim().to_lowercase();
+        let valid = !tag.is_empty()
+            && tag.len() <= MAX_TAG_LENGTH
+            && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if valid {
+            Ok(tag)
+        } else {
+            Err(DbError::InvalidTag(tag))
+        }
+    }
+
+    // Read the tags attached to a paste
+    fn read_tags(conn: &Connection, id: &str) -> Result<Vec<String>, DbError> {
+        let mut stmt = conn.prepare("SELECT tag FROM paste_tags WHERE paste_id = ? ORDER BY tag")?;
+        stmt.bind((1, id))?;
+
+        let mut tags = Vec::new();
+        while let State::Row = stmt.next()? {
+            tags.push(stmt.read::<String, _>(0)?);
+        }
+        Ok(tags)
+    }
+
+    /// Path of the database file inside a data directory.
+    pub fn database_path(data_dir: &Path) -> PathBuf {
+        data_dir.join(DATABASE_FILE)
+    }
+
+    /// Check that `data_dir` can be written, creating it if missing, by writing and
+    /// removing a probe file.
+    pub fn check_writable(data_dir: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(data_dir)?;
+        let probe = data_dir.join(WRITE_PROBE_FILE);
+        fs::write(&probe, b"")?;
+        fs::remove_file(&probe)
+    }
+
+    /// Whether `id` is shaped like an ID this server generates: any length from
+    /// `MIN_ID_LENGTH` to `MAX_GENERATED_ID_LENGTH`, so IDs stay readable after
+    /// `PASTE_ID_LENGTH` changes, in the configured charset.
+    pub fn is_valid_id(&self, id: &str) -> bool {
+        (MIN_ID_LENGTH..=MAX_GENERATED_ID_LENGTH).contains(&id.len())
+            && id.chars().all(|c| self.options.id_charset.contains(c))
+    }
+
+    // Check if a paste ID already exists
+    fn paste_exists(&self, id: &str) -> Result<bool, DbError> {
+        let conn = self.conn("paste_exists");
         let mut stmt = conn.prepare("SELECT 1 FROM pastes WHERE id = ? LIMIT 1")?;
         stmt.bind((1, id))?;
         Ok(matches!(stmt.next()?, State::Row))
@@ -177,8 +1192,8 @@ impl Database {
     fn generate_unique_id(&self) -> Result<String, DbError> {
         for retry in 0..MAX_ID_GENERATION_RETRIES {
             // Increase ID length with retries to reduce collision probability
-            let length = BASE_ID_LENGTH + (retry as usize);
-            let id = Self::generate_id(length);
+            let length = self.options.id_length + (retry as usize);
+            let id = self.generate_paste_id(length);
             
             if !self.paste_exists(&id)? {
                 return Ok(id);
@@ -190,133 +1205,645 @@ impl Database {
         Err(DbError::IdGenerationFailed)
     }
 
-    pub fn new() -> Self {
-        // Ensure data directory exists
-        let data_dir = PathBuf::from("data");
-        fs::create_dir_all(&data_dir).expect("Failed to create data directory");
-        
-        // Initialize database connection
-        let db_path = data_dir.join("pastes.db");
-        let connection = Connection::open(db_path).expect("Failed to open database");
-        
+    /// Open the database in the default `data/` directory.
+    #[allow(dead_code)]
+    pub fn new(options: PasteOptions) -> Result<Self, DbError> {
+        Self::new_with_path(DEFAULT_DATA_DIR, options)
+    }
+
+    /// Open (creating if needed) the database in `data_dir`. SQLite keeps the
+    /// WAL and shared-memory files next to the database file, so they land in
+    /// the same directory.
+    pub fn new_with_path(data_dir: impl AsRef<Path>, options: PasteOptions) -> Result<Self, DbError> {
+        let connection = Self::open_connection(data_dir.as_ref(), options.busy_timeout)?;
+        Self::from_connection(connection, options, Some(Self::read_only_uri(data_dir.as_ref(), false)))
+    }
+
+    /// Like `new_with_path`, but retry opening the database with exponential
+    /// backoff, for data volumes that are mounted shortly after the process starts.
+    /// Migration failures aren't retried.
+    pub fn open_with_retry(data_dir: impl AsRef<Path>, options: PasteOptions, retry: OpenRetry) -> Result<Self, DbError> {
+        let data_dir = data_dir.as_ref();
+        let mut delay = retry.initial_delay;
+        let mut attempt = 1;
+        let connection = loop {
+            match Self::open_connection(data_dir, options.busy_timeout) {
+                Ok(connection) => break connection,
+                Err(e) if attempt < retry.attempts => {
+                    tracing::warn!(
+                        "Failed to open database in {} (attempt {}/{}): {}; retrying in {:?}",
+                        data_dir.display(),
+                        attempt,
+                        retry.attempts,
+                        e,
+                        delay
+                    );
+                    std::thread::sleep(delay);
+                    delay = (delay * 2).min(MAX_OPEN_RETRY_DELAY);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        Self::from_connection(connection, options, Some(Self::read_only_uri(data_dir, false)))
+    }
+
+    /// Open an existing database without writing to it or running migrations, for
+    /// `READ_ONLY_MODE`. When the directory itself can't be written, SQLite can't
+    /// create the WAL's shared-memory file either, so the file is opened as
+    /// immutable; that assumes nothing else changes it while the server runs.
+    pub fn open_read_only(data_dir: impl AsRef<Path>, options: PasteOptions, immutable: bool) -> Result<Self, DbError> {
+        let path = Self::database_path(data_dir.as_ref());
+        if !path.is_file() {
+            return Err(DbError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} does not exist", path.display()),
+            )));
+        }
+
+        let uri = Self::read_only_uri(data_dir.as_ref(), immutable);
+        let connection = Connection::open_with_flags(&uri, OpenFlags::new().with_read_only().with_uri())?;
+        connection.execute("PRAGMA foreign_keys = ON;")?;
+        Self::set_busy_timeout(&connection, options.busy_timeout)?;
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            snapshot_uri: Some(uri),
+            options,
+            pending_views: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            content_policy: None,
+        })
+    }
+
+    // URI opening the database in `data_dir` read-only
+    fn read_only_uri(data_dir: &Path, immutable: bool) -> String {
+        // Escape the characters that would end the path part of the URI
+        let path = Self::database_path(data_dir).to_string_lossy().replace('%', "%25").replace('?', "%3f").replace('#', "%23");
+        format!("file:{}?mode=ro{}", path, if immutable { "&immutable=1" } else { "" })
+    }
+
+    // Create the data directory, open the database file, and set the connection pragmas.
+    // The busy timeout goes first: switching to WAL needs an exclusive lock, which
+    // another process opening the same file at the same moment may hold.
+    fn open_connection(data_dir: &Path, busy_timeout: Duration) -> Result<Connection, DbError> {
+        fs::create_dir_all(data_dir)?;
+        let connection = Connection::open(Self::database_path(data_dir))?;
+        Self::set_busy_timeout(&connection, busy_timeout)?;
+
         // Enable foreign keys and WAL mode
-        connection.execute("PRAGMA foreign_keys = ON;").expect("Failed to set foreign_keys pragma");
-        connection.execute("PRAGMA journal_mode = WAL;").expect("Failed to set journal_mode pragma");
-        
-        // Create tables if they don't exist (includes all columns)
-        connection.execute("
-            CREATE TABLE IF NOT EXISTS pastes (
-                id TEXT PRIMARY KEY,
-                data TEXT NOT NULL,
-                language TEXT NOT NULL,
-                created_at INTEGER NOT NULL,
-                encryption_version INTEGER NOT NULL DEFAULT 0,
-                edit_key_hash TEXT,
-                burn_after_read INTEGER NOT NULL DEFAULT 0,
-                expires_at INTEGER
-            );
-        ").expect("Failed to create pastes table");
-        
-        // Migration: Add columns if they don't exist
-        let _ = connection.execute("ALTER TABLE pastes ADD COLUMN edit_key_hash TEXT;");
-        let _ = connection.execute("ALTER TABLE pastes ADD COLUMN burn_after_read INTEGER NOT NULL DEFAULT 0;");
-        let _ = connection.execute("ALTER TABLE pastes ADD COLUMN expires_at INTEGER;");
-        
-        connection.execute("
-            CREATE INDEX IF NOT EXISTS idx_pastes_created_at ON pastes(created_at DESC);
-        ").expect("Failed to create index");
+        connection.execute("PRAGMA foreign_keys = ON;")?;
+        connection.execute("PRAGMA journal_mode = WAL;")?;
+        Ok(connection)
+    }
+
+    // Have SQLite wait out another connection's lock (e.g. a backup or the sqlite3
+    // shell) instead of failing the statement at once
+    fn set_busy_timeout(connection: &Connection, timeout: Duration) -> Result<(), DbError> {
+        connection.execute(format!("PRAGMA busy_timeout = {};", timeout.as_millis()))?;
+        Ok(())
+    }
 
-        // Migration: Add type column for workspace support
-        let _ = connection.execute("ALTER TABLE pastes ADD COLUMN type TEXT NOT NULL DEFAULT 'paste';");
-        connection.execute("CREATE INDEX IF NOT EXISTS idx_type ON pastes(type);").expect("Failed to create type index");
+    fn from_connection(connection: Connection, options: PasteOptions, snapshot_uri: Option<String>) -> Result<Self, DbError> {
+        // Bring the schema up to date
+        let version = crate::migrations::run(&connection)?;
+        tracing::debug!("Database schema version: {}", version);
 
-        Self {
+        Ok(Self {
             connection: Arc::new(Mutex::new(connection)),
-        }
+            snapshot_uri,
+            options,
+            pending_views: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            content_policy: None,
+        })
+    }
+
+    /// A fresh database held in memory, for tests.
+    #[cfg(test)]
+    pub(crate) fn in_memory(options: PasteOptions) -> Result<Self, DbError> {
+        let connection = Connection::open(":memory:")?;
+        connection.execute("PRAGMA foreign_keys = ON;")?;
+        Self::from_connection(connection, options, None)
+    }
+
+    /// Check new plaintext pastes against `policy` before storing them.
+    pub fn with_content_policy(mut self, policy: Arc<dyn ContentPolicy>) -> Self {
+        self.content_policy = Some(policy);
+        self
     }
     
-    // Store client-encrypted paste with edit key
-    fn store_client_encrypted_paste(
-        &self,
-        id: String,
-        data: String,
-        language: String,
-        created_at: DateTime<Utc>,
-        edit_key_hash: String,
-        burn_after_read: bool,
-        expires_at: Option<i64>,
-        record_type: &str,
-    ) -> Result<Paste, DbError> {
-        let timestamp = created_at.timestamp() as i64;
-        let burn_flag = if burn_after_read { 1 } else { 0 };
+    // Lock the connection for the operation `op`, timing it until the guard drops.
+    // A thread that panicked while holding the lock poisons the mutex; SQLite's own
+    // state is still consistent, so roll back whatever transaction it left open and
+    // carry on instead of failing every later request.
+    fn conn(&self, op: &'static str) -> ConnGuard<'_> {
+        let guard = self.connection.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Database connection lock was poisoned by a panic; recovering");
+            let conn = poisoned.into_inner();
+            // Fails harmlessly when no transaction was open
+            let _ = conn.execute("ROLLBACK");
+            self.connection.clear_poison();
+            conn
+        });
+
+        // Started once the lock is held, so waiting for other operations isn't counted
+        ConnGuard {
+            guard,
+            op,
+            started: Instant::now(),
+            slow_query: self.options.slow_query,
+        }
+    }
+
+    // Store a paste with edit key. The paste and its tags go in one transaction, so
+    // a failed tag insert doesn't leave the paste behind without them.
+    fn store_client_encrypted_paste(&self, new_paste: NewPaste) -> Result<Paste, DbError> {
+        let conn = self.conn("store_client_encrypted_paste");
+        conn.execute("BEGIN IMMEDIATE;")?;
+
+        let mut paste = match Self::insert_paste(&conn, new_paste, self.options.compress_over) {
+            Ok(paste) => paste,
+            Err(e) => {
+                let _ = conn.execute("ROLLBACK;");
+                return Err(e);
+            }
+        };
+        if let Err(e) = conn.execute("COMMIT;") {
+            let _ = conn.execute("ROLLBACK;");
+            return Err(e.into());
+        }
+
+        paste.theme_hint = self.theme_hint(&paste.language);
+        self.enforce_paste_cap(&conn);
+        Ok(paste)
+    }
+
+    // Evict the oldest unpinned records once MAX_TOTAL_PASTES is exceeded. The paste
+    // that was just stored is already committed, so a failure here is only logged.
+    fn enforce_paste_cap(&self, conn: &Connection) {
+        if self.options.max_total_pastes == 0 {
+            return;
+        }
+
+        let result = Self::count_records(conn).and_then(|total| {
+            let excess = total.saturating_sub(self.options.max_total_pastes);
+            if excess == 0 { Ok(0) } else { Self::evict_oldest_locked(conn, excess) }
+        });
+        match result {
+            Ok(0) => {}
+            Ok(evicted) => tracing::info!("Evicted {} oldest paste(s) to stay under MAX_TOTAL_PASTES", evicted),
+            Err(e) => tracing::warn!("Failed to enforce MAX_TOTAL_PASTES: {}", e),
+        }
+    }
+
+    // Number of stored records (pastes and workspaces, including trashed ones)
+    fn count_records(conn: &Connection) -> Result<u64, DbError> {
+        let mut stmt = conn.prepare("SELECT COUNT(*) FROM pastes")?;
+        stmt.next()?;
+        Ok(stmt.read::<i64, _>(0)? as u64)
+    }
+
+    fn unique_violation_as_conflict(err: sqlite::Error) -> DbError {
+        const SQLITE_CONSTRAINT: isize = 19;
+        let is_unique = err.code == Some(SQLITE_CONSTRAINT)
+            && err.message.as_deref().is_some_and(|msg| msg.starts_with("UNIQUE constraint failed"));
+        if is_unique { DbError::IdConflict } else { DbError::Sqlite(err) }
+    }
+
+    // Insert a paste and its tags on an already-locked connection
+    fn insert_paste(conn: &Connection, new_paste: NewPaste, compress_over: usize) -> Result<Paste, DbError> {
+        let NewPaste {
+            id,
+            data,
+            language,
+            created_at,
+            encryption_version,
+            edit_key_hash,
+            burn_after_read,
+            expires_at,
+            record_type,
+            normalized,
+            tags,
+            max_views,
+            language_detected,
+            content_sha256,
+            forked_from,
+            creator_hash,
+            visibility,
+            encoding,
+        } = new_paste;
+
+        let timestamp = created_at.timestamp();
+        let burn_flag = burn_after_read as i64;
+        let normalized_flag = normalized as i64;
+        let (stored, compressed) = data_to_store(&data, compress_over);
 
         // Insert into database
-        let conn = self.connection.lock().unwrap();
         let mut stmt = conn.prepare(
-            "INSERT INTO pastes (id, data, language, created_at, encryption_version, edit_key_hash, burn_after_read, expires_at, type) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            "INSERT INTO pastes (id, data, language, created_at, encryption_version, edit_key_hash, burn_after_read, expires_at, type, normalized, max_views, content_sha256, forked_from, creator_hash, visibility, encoding, compressed) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )?;
 
         // Bind parameters
         stmt.bind((1, id.as_str()))?;
-        stmt.bind((2, data.as_str()))?;
+        stmt.bind((2, &stored))?;
         stmt.bind((3, language.as_str()))?;
-        stmt.bind((4, timestamp.to_string().as_str()))?;
-        stmt.bind((5, ENCRYPTION_VERSION_CLIENT.to_string().as_str()))?;
+        stmt.bind((4, timestamp))?;
+        stmt.bind((5, encryption_version as i64))?;
         stmt.bind((6, edit_key_hash.as_str()))?;
-        stmt.bind((7, burn_flag.to_string().as_str()))?;
-
-        // Handle optional expires_at
-        if let Some(exp) = expires_at {
-            stmt.bind((8, exp.to_string().as_str()))?;
-        } else {
-            stmt.bind((8, sqlite::Value::Null))?;
-        }
-
+        stmt.bind((7, burn_flag))?;
+        stmt.bind((8, expires_at))?;
         stmt.bind((9, record_type))?;
+        stmt.bind((10, normalized_flag))?;
+        stmt.bind((11, max_views.map(|max| max as i64)))?;
+        stmt.bind((12, content_sha256.as_deref()))?;
+        stmt.bind((13, forked_from.as_deref()))?;
+        stmt.bind((14, creator_hash.as_deref()))?;
+        stmt.bind((15, visibility.as_str()))?;
+        stmt.bind((16, encoding.as_str()))?;
+        stmt.bind((17, compressed))?;
 
-        stmt.next()?;
+        // The ID was checked as free before locking; a duplicate here means another insert won the race
+        stmt.next().map_err(Self::unique_violation_as_conflict)?;
+
+        for tag in &tags {
+            let mut tag_stmt = conn.prepare("INSERT INTO paste_tags (paste_id, tag) VALUES (?, ?)")?;
+            tag_stmt.bind((1, id.as_str()))?;
+            tag_stmt.bind((2, tag.as_str()))?;
+            tag_stmt.next()?;
+        }
 
         Ok(Paste {
             id,
             data: String::new(), // Don't return the encrypted data on creation
             language,
-            created_at,
-            encryption_version: ENCRYPTION_VERSION_CLIENT,
+            // Report the stored (whole-second) value so it matches later reads
+            created_at: DateTime::from_timestamp(timestamp, 0).unwrap_or(created_at),
+            encryption_version,
             burn_after_read,
-            expires_at: expires_at.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(|| Utc::now())),
+            expires_at: expires_at.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(Utc::now)),
             edit_key: None, // Will be set by caller
             edit_key_hash: None,
             paste_type: None,
+            normalized,
+            tags,
+            views: 0,
+            max_views,
+            url: None,
+            language_detected,
+            content_sha256,
+            server_sha256: Some(Self::sha256_hex(&data)),
+            forked_from,
+            pinned: false,
+            visibility,
+            encoding,
+            theme_hint: None, // Set by callers, which know the configured hints
         })
     }
 
     pub fn create_paste(&self, paste_data: CreatePasteData) -> Result<Paste, DbError> {
+        let (new_paste, edit_key) = self.prepare_paste(paste_data, None)?;
+
+        // Store the paste with edit key hash and advanced options
+        let mut paste = self.store_client_encrypted_paste(new_paste)?;
+        
+        // Set the edit key on the returned paste (only on creation)
+        paste.edit_key = Some(edit_key);
+
+        Ok(paste)
+    }
+
+    /// Create a paste at most once per `(scope, key)`. Repeating the same request
+    /// within `idempotency_ttl_secs` returns the original response without its
+    /// edit key, which only the first response carries; reusing the key for a
+    /// different request is `IdempotencyKeyReused`.
+    pub fn create_paste_idempotent(&self, paste_data: CreatePasteData, scope: &str, key: &str) -> Result<IdempotentCreate, DbError> {
+        let fingerprint = Self::sha256_hex(&serde_json::to_string(&paste_data)?);
+        if let Some(replay) = self.idempotent_replay(scope, key, &fingerprint)? {
+            return Ok(replay);
+        }
+
+        let (new_paste, edit_key) = self.prepare_paste(paste_data, None)?;
+
+        // Checking the key again under the insert's lock means concurrent replays
+        // see the first request's row instead of each storing a paste
+        let conn = self.conn("create_paste_idempotent");
+        if let Some(replay) = Self::idempotent_replay_locked(&conn, scope, key, &fingerprint, self.idempotency_cutoff())? {
+            return Ok(replay);
+        }
+
+        conn.execute("BEGIN IMMEDIATE;")?;
+        let stored = Self::insert_paste(&conn, new_paste, self.options.compress_over).and_then(|mut paste| {
+            paste.theme_hint = self.theme_hint(&paste.language);
+            // Recorded before the edit key is set, so the key is never stored in plain text
+            Self::insert_idempotency_key(&conn, scope, key, &fingerprint, &paste)?;
+            paste.edit_key = Some(edit_key);
+            Ok(paste)
+        });
+        match stored {
+            Ok(paste) => {
+                conn.execute("COMMIT;")?;
+                self.enforce_paste_cap(&conn);
+                Ok(IdempotentCreate::Created(paste))
+            }
+            Err(e) => {
+                let _ = conn.execute("ROLLBACK;");
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether `(scope, key)` has a create that would still be replayed.
+    pub fn idempotency_key_exists(&self, scope: &str, key: &str) -> Result<bool, DbError> {
+        let conn = self.conn("idempotency_key_exists");
+        let mut stmt = conn.prepare("SELECT 1 FROM idempotency_keys WHERE scope = ? AND key = ? AND created_at > ?")?;
+        stmt.bind((1, scope))?;
+        stmt.bind((2, key))?;
+        stmt.bind((3, self.idempotency_cutoff()))?;
+        Ok(stmt.next()? == State::Row)
+    }
+
+    /// Add `creates` to today's (UTC) create count for `key` unless that would take
+    /// it past `quota`. Returns the new total, or `None` when refused, in which case
+    /// nothing is counted. Counts are kept per day, so they start over at midnight.
+    pub fn increment_daily_quota(&self, key: &str, creates: u64, quota: u64) -> Result<Option<u64>, DbError> {
+        if creates > quota {
+            return Ok(None);
+        }
+
+        let conn = self.conn("increment_daily_quota");
+        let day = Self::quota_day(Utc::now());
+
+        let mut stmt = conn.prepare("
+            INSERT INTO daily_quotas (client, day, creates) VALUES (?, ?, ?)
+            ON CONFLICT (client, day) DO UPDATE SET creates = creates + excluded.creates
+            WHERE creates + excluded.creates <= ?
+        ")?;
+        stmt.bind((1, key))?;
+        stmt.bind((2, day.as_str()))?;
+        stmt.bind((3, creates as i64))?;
+        stmt.bind((4, quota as i64))?;
+        stmt.next()?;
+        if conn.change_count() == 0 {
+            return Ok(None);
+        }
+
+        let mut stmt = conn.prepare("SELECT creates FROM daily_quotas WHERE client = ? AND day = ?")?;
+        stmt.bind((1, key))?;
+        stmt.bind((2, day.as_str()))?;
+        stmt.next()?;
+        Ok(Some(stmt.read::<i64, _>(0)? as u64))
+    }
+
+    // Day a quota count belongs to, as an ISO date so older days sort first
+    fn quota_day(at: DateTime<Utc>) -> String {
+        at.format("%Y-%m-%d").to_string()
+    }
+
+    fn idempotent_replay(&self, scope: &str, key: &str, fingerprint: &str) -> Result<Option<IdempotentCreate>, DbError> {
+        let conn = self.conn("idempotent_replay");
+        Self::idempotent_replay_locked(&conn, scope, key, fingerprint, self.idempotency_cutoff())
+    }
+
+    // Oldest creation time of an idempotency key that still replays
+    fn idempotency_cutoff(&self) -> i64 {
+        Utc::now().timestamp() - self.options.idempotency_ttl_secs as i64
+    }
+
+    fn idempotent_replay_locked(
+        conn: &Connection,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        cutoff: i64,
+    ) -> Result<Option<IdempotentCreate>, DbError> {
+        let mut stmt = conn.prepare(
+            "SELECT fingerprint, response FROM idempotency_keys WHERE scope = ? AND key = ? AND created_at > ?"
+        )?;
+        stmt.bind((1, scope))?;
+        stmt.bind((2, key))?;
+        stmt.bind((3, cutoff))?;
+        if stmt.next()? != State::Row {
+            return Ok(None);
+        }
+
+        if stmt.read::<String, _>(0)? != fingerprint {
+            return Err(DbError::IdempotencyKeyReused);
+        }
+        let paste = serde_json::from_str(&stmt.read::<String, _>(1)?)?;
+        Ok(Some(IdempotentCreate::Replayed(paste)))
+    }
+
+    // Record a create's response under its key, replacing an expired entry
+    fn insert_idempotency_key(conn: &Connection, scope: &str, key: &str, fingerprint: &str, paste: &Paste) -> Result<(), DbError> {
+        let mut stmt = conn.prepare(
+            "INSERT OR REPLACE INTO idempotency_keys (scope, key, fingerprint, paste_id, response, created_at) VALUES (?, ?, ?, ?, ?, ?)"
+        )?;
+        stmt.bind((1, scope))?;
+        stmt.bind((2, key))?;
+        stmt.bind((3, fingerprint))?;
+        stmt.bind((4, paste.id.as_str()))?;
+        stmt.bind((5, serde_json::to_string(paste)?.as_str()))?;
+        stmt.bind((6, Utc::now().timestamp()))?;
+        stmt.next()?;
+        Ok(())
+    }
+
+    /// Copy a paste's content and language into a new paste recording `forked_from`.
+    ///
+    /// The source is read like any other reader would, so the fork counts as a view
+    /// and consumes burn-after-read and `max_views` pastes. The copy goes through the
+    /// usual create checks and gets no expiry, tags or view limit of its own. It
+    /// keeps the source's visibility, so forking a private paste doesn't publish it.
+    pub fn fork_paste(&self, source_id: &str, creator_hash: Option<String>) -> Result<Paste, DbError> {
+        let source = self.get_paste(source_id)?.ok_or(DbError::PasteNotFound)?;
+
+        let paste_data = CreatePasteData {
+            data: source.data,
+            language: Some(source.language),
+            burn_after_read: false,
+            expires_in_minutes: None,
+            encryption_version: source.encryption_version,
+            tags: Vec::new(),
+            max_views: None,
+            content_sha256: None,
+            visibility: source.visibility,
+            encoding: source.encoding,
+            validate_syntax: false,
+            creator_hash,
+        };
+
+        let (mut new_paste, edit_key) = self.prepare_paste(paste_data, None)?;
+        new_paste.forked_from = Some(source.id);
+        // The source's stored hash still describes the copied bytes
+        new_paste.content_sha256 = source.content_sha256;
+
+        let mut paste = self.store_client_encrypted_paste(new_paste)?;
+        paste.edit_key = Some(edit_key);
+        Ok(paste)
+    }
+
+    /// Create several pastes atomically: either every paste is stored or none is.
+    ///
+    /// Failures are reported as `BatchItemFailed` with the index of the offending item.
+    pub fn create_pastes_batch(&self, items: Vec<CreatePasteData>) -> Result<Vec<Paste>, DbError> {
+        // Validate everything up front so a bad item never touches the database
+        let mut prepared = Vec::with_capacity(items.len());
+        for (index, paste_data) in items.into_iter().enumerate() {
+            let item = self
+                .prepare_paste(paste_data, None)
+                .map_err(|e| DbError::BatchItemFailed(index, Box::new(e)))?;
+            prepared.push(item);
+        }
+
+        let conn = self.conn("create_pastes_batch");
+        conn.execute("BEGIN IMMEDIATE;")?;
+
+        let mut pastes = Vec::with_capacity(prepared.len());
+        for (index, (new_paste, edit_key)) in prepared.into_iter().enumerate() {
+            match Self::insert_paste(&conn, new_paste, self.options.compress_over) {
+                Ok(mut paste) => {
+                    paste.edit_key = Some(edit_key);
+                    paste.theme_hint = self.theme_hint(&paste.language);
+                    pastes.push(paste);
+                }
+                Err(e) => {
+                    let _ = conn.execute("ROLLBACK;");
+                    return Err(DbError::BatchItemFailed(index, Box::new(e)));
+                }
+            }
+        }
+
+        if let Err(e) = conn.execute("COMMIT;") {
+            let _ = conn.execute("ROLLBACK;");
+            return Err(e.into());
+        }
+        self.enforce_paste_cap(&conn);
+
+        Ok(pastes)
+    }
+
+    /// Create a plaintext paste at a caller-chosen ID.
+    ///
+    /// Fails with `PasteAlreadyExists` if the ID is taken.
+    pub fn create_paste_with_id(&self, id: &str, data: String, creator_hash: Option<String>) -> Result<Paste, DbError> {
+        let paste_data = CreatePasteData {
+            data,
+            language: Some(language::PLAINTEXT.to_string()),
+            burn_after_read: false,
+            expires_in_minutes: None,
+            encryption_version: ENCRYPTION_VERSION_NONE,
+            tags: Vec::new(),
+            max_views: None,
+            content_sha256: None,
+            visibility: Visibility::default(),
+            encoding: Encoding::default(),
+            validate_syntax: false,
+            creator_hash,
+        };
+
+        let (new_paste, edit_key) = self.prepare_paste(paste_data, Some(id.to_string()))?;
+        let mut paste = self.store_client_encrypted_paste(new_paste)?;
+        paste.edit_key = Some(edit_key);
+
+        Ok(paste)
+    }
+
+    /// Run every create-time check (including custom ID availability) without storing anything.
+    pub fn validate_paste(&self, paste_data: CreatePasteData, custom_id: Option<&str>) -> Result<(), DbError> {
+        self.prepare_paste(paste_data, custom_id.map(str::to_string)).map(|_| ())
+    }
+
+    // Older rows may have been stored with an empty language; report the default instead.
+    // An `auto` default can't be detected after the fact, so those read as plaintext.
+    fn stored_language(&self, language: String) -> String {
+        if !language.trim().is_empty() {
+            language
+        } else if language::is_auto(&self.options.default_language) {
+            language::PLAINTEXT.to_string()
+        } else {
+            self.options.default_language.clone()
+        }
+    }
+
+    // Validate and normalize a create request into row values plus the plain edit key.
+    // A custom ID is used as-is if free; otherwise a unique ID is generated.
+    fn prepare_paste(&self, paste_data: CreatePasteData, custom_id: Option<String>) -> Result<(NewPaste<'static>, String), DbError> {
+        let mut errors = FieldErrors::default();
+        errors.check("encryption_version", self.check_encryption_version(paste_data.encryption_version));
+
         // Check character limit before processing - using explicit UTF-8 byte count
         let byte_count = Self::get_utf8_byte_count(&paste_data.data);
         
         tracing::debug!("Paste data length (bytes): {}", byte_count);
         
-        if byte_count > MAX_PASTE_CHARACTERS {
-            return Err(DbError::CharacterLimitExceeded(byte_count, MAX_PASTE_CHARACTERS));
+        if byte_count > self.options.max_paste_bytes {
+            errors.push("data", DbError::CharacterLimitExceeded(byte_count, self.options.max_paste_bytes));
         }
+
+        // The client hashes what it sent, before any normalization
+        let content_sha256 = errors
+            .check("content_sha256", Self::verify_content_hash(paste_data.content_sha256, &paste_data.data))
+            .flatten();
         
-        if paste_data.data.is_empty() {
-            return Err(DbError::ClientEncryptionRequired);
+        // Normalize plaintext only; encrypted content is never altered
+        let (data, normalized) = if paste_data.encryption_version == ENCRYPTION_VERSION_NONE {
+            self.normalize_content(paste_data.data)
+        } else {
+            (paste_data.data, false)
+        };
+
+        // Checks of the content itself only mean something once there is some
+        if data.is_empty() {
+            errors.push("data", DbError::ClientEncryptionRequired);
+        } else {
+            // Base64 that doesn't decode is the data's fault; base64 on encrypted data is the encoding's
+            let field = if paste_data.encryption_version == ENCRYPTION_VERSION_NONE { "data" } else { "encoding" };
+            errors.check(field, Self::check_encoding(&data, paste_data.encoding, paste_data.encryption_version));
+            errors.check("data", self.check_line_limit(&data, paste_data.encryption_version));
         }
-        
-        // Validate expiration time (max 1 week = 10080 minutes)
-        let expires_at = if let Some(minutes) = paste_data.expires_in_minutes {
-            if minutes == 0 || minutes > MAX_EXPIRES_IN_MINUTES {
-                return Err(DbError::CharacterLimitExceeded(minutes as usize, MAX_EXPIRES_IN_MINUTES as usize));
+
+        let tags = errors.check("tags", self.normalize_tags(paste_data.tags)).unwrap_or_default();
+
+        let requested = paste_data
+            .language
+            .filter(|language| !language.trim().is_empty())
+            .unwrap_or_else(|| self.options.default_language.clone());
+
+        // Only plaintext text can be inspected; encrypted and base64 pastes asking for
+        // detection get plaintext
+        let (language, language_detected) = if !language::is_auto(&requested) {
+            (requested, None)
+        } else if paste_data.encryption_version == ENCRYPTION_VERSION_NONE && paste_data.encoding == Encoding::Utf8 {
+            match language::detect(&data) {
+                Some(detected) => (detected.to_string(), Some(true)),
+                None => (language::PLAINTEXT.to_string(), Some(false)),
             }
-            let expires_timestamp = Utc::now().timestamp() + (minutes as i64 * 60);
-            Some(expires_timestamp)
         } else {
-            None
+            (language::PLAINTEXT.to_string(), Some(false))
         };
+
+        if paste_data.validate_syntax && !data.is_empty() {
+            errors.check("data", Self::check_syntax(&data, &language, paste_data.encryption_version));
+        }
+
+        if paste_data.max_views == Some(0) {
+            errors.push("max_views", DbError::InvalidMaxViews);
+        }
         
-        // Generate unique ID with collision detection
-        let id = self.generate_unique_id()?;
+        let expires_at = errors
+            .check("expires_in_minutes", self.expiry_timestamp(paste_data.expires_in_minutes))
+            .flatten();
+        errors.into_result()?;
+
+        // Policy is only consulted on a request that is otherwise valid
+        self.check_content_policy(&data, &language, paste_data.encryption_version)?;
+        
+        // Use the custom ID if it's free, otherwise generate a unique ID with collision detection
+        let id = match custom_id {
+            Some(id) if self.paste_exists(&id)? => return Err(DbError::PasteAlreadyExists),
+            Some(id) => id,
+            None => self.generate_unique_id()?,
+        };
         
         // Generate edit key (32 bytes, base64 encoded)
         let edit_key = Self::generate_id(32);
@@ -324,133 +1851,553 @@ impl Database {
 
         let now = Utc::now();
         
-        // Store the client-encrypted paste with edit key hash and advanced options
-        let mut paste = self.store_client_encrypted_paste(
+        let new_paste = NewPaste {
             id,
-            paste_data.data,
-            paste_data.language,
-            now,
+            data,
+            language,
+            created_at: now,
+            encryption_version: paste_data.encryption_version,
             edit_key_hash,
-            paste_data.burn_after_read,
+            burn_after_read: paste_data.burn_after_read,
             expires_at,
-            "paste",
-        )?;
-        
-        // Set the edit key on the returned paste (only on creation)
-        paste.edit_key = Some(edit_key);
+            record_type: "paste",
+            normalized,
+            tags,
+            max_views: paste_data.max_views,
+            language_detected,
+            content_sha256,
+            forked_from: None,
+            creator_hash: paste_data.creator_hash,
+            visibility: paste_data.visibility,
+            encoding: paste_data.encoding,
+        };
 
-        Ok(paste)
+        Ok((new_paste, edit_key))
     }
 
-    pub fn get_encrypted_paste(&self, id: &str) -> Option<(String, String, DateTime<Utc>)> {
-        let conn = self.connection.lock().unwrap();
+    #[allow(dead_code)]
+    pub fn get_encrypted_paste(&self, id: &str) -> Result<Option<(String, String, DateTime<Utc>)>, DbError> {
+        let conn = self.conn("get_encrypted_paste");
         
-        let mut stmt = conn.prepare("SELECT data, language, created_at, encryption_version FROM pastes WHERE id = ?")
-            .ok()?;
+        let mut stmt = conn.prepare("SELECT data, language, created_at, encryption_version, compressed FROM pastes WHERE id = ? AND deleted_at IS NULL")?;
             
-        stmt.bind((1, id)).ok()?;
+        stmt.bind((1, id))?;
         
-        if let State::Row = stmt.next().ok()? {
-            let data = stmt.read::<String, _>(0).ok()?;
-            let language = stmt.read::<String, _>(1).ok()?;
-            let created_at = stmt.read::<i64, _>(2).ok()?;
-            let encryption_version = stmt.read::<i64, _>(3).ok().unwrap_or(0) as u8;
+        if let State::Row = stmt.next()? {
+            let data = stored_data(stmt.read::<sqlite::Value, _>(0)?, stmt.read::<i64, _>(4)?)?;
+            let language = stmt.read::<String, _>(1)?;
+            let created_at = stmt.read::<sqlite::Value, _>(2)?;
+            let encryption_version = stmt.read::<i64, _>(3).unwrap_or(0) as u8;
             
             // Only return the encrypted data for client-side decryption
             if encryption_version == ENCRYPTION_VERSION_CLIENT {
-                let timestamp = DateTime::from_timestamp(created_at, 0).unwrap_or_else(|| Utc::now());
-                return Some((data, language, timestamp));
+                let timestamp = stored_created_at(&created_at);
+                return Ok(Some((data, language, timestamp)));
             }
         }
         
-        None
+        Ok(None)
     }
 
-    pub fn get_paste(&self, id: &str) -> Option<Paste> {
-        // First, check if paste exists and get its metadata
-        let (encrypted_data, language, created_at, burn_after_read, expires_at) = {
-            let conn = self.connection.lock().unwrap();
-            let mut stmt = conn.prepare(
-                "SELECT data, language, created_at, encryption_version, burn_after_read, expires_at FROM pastes WHERE id = ?"
-            ).ok()?;
-            
-            stmt.bind((1, id)).ok()?;
-            
-            if let State::Row = stmt.next().ok()? {
-                let data = stmt.read::<String, _>(0).ok()?;
-                let language = stmt.read::<String, _>(1).ok()?;
-                let created_at_ts = stmt.read::<i64, _>(2).ok()?;
-                let encryption_version = stmt.read::<i64, _>(3).ok().unwrap_or(0) as u8;
-                let burn_after_read = stmt.read::<i64, _>(4).ok().unwrap_or(0) != 0;
-                let expires_at: Option<i64> = stmt.read::<Option<i64>, _>(5).ok().flatten();
-                
-                if encryption_version != ENCRYPTION_VERSION_CLIENT {
-                    return None;
-                }
-                
-                let timestamp = DateTime::from_timestamp(created_at_ts, 0).unwrap_or_else(|| Utc::now());
-                (data, language, timestamp, burn_after_read, expires_at)
+    pub fn get_paste(&self, id: &str) -> Result<Option<Paste>, DbError> {
+        self.get_paste_unless(id, |_| false)
+    }
+
+    /// Record a read of `paste_id` in the access log, when it's enabled. Best effort:
+    /// a failed insert is logged and the read goes ahead.
+    pub fn record_access(&self, paste_id: &str, ip_hash: Option<&str>, user_agent: Option<&str>) {
+        if !self.access_log_enabled() {
+            return;
+        }
+
+        let user_agent = user_agent.map(|agent| match agent.char_indices().nth(MAX_LOGGED_USER_AGENT_CHARS) {
+            Some((end, _)) => &agent[..end],
+            None => agent,
+        });
+        let conn = self.conn("record_access");
+        let inserted = conn
+            .prepare("INSERT INTO access_log (paste_id, accessed_at, ip_hash, user_agent) VALUES (?, ?, ?, ?)")
+            .and_then(|mut stmt| {
+                stmt.bind((1, paste_id))?;
+                stmt.bind((2, Utc::now().timestamp()))?;
+                stmt.bind((3, ip_hash))?;
+                stmt.bind((4, user_agent))?;
+                stmt.next()
+            });
+        if let Err(e) = inserted {
+            tracing::warn!("Failed to record access to paste {}: {}", paste_id, e);
+        }
+    }
+
+    /// Whether reads are being recorded in the access log.
+    pub fn access_log_enabled(&self) -> bool {
+        self.options.access_log && !self.options.read_only
+    }
+
+    /// The most recent `limit` recorded reads of `paste_id`, newest first. Entries
+    /// outlive the paste until the next purge.
+    pub fn recent_accesses(&self, paste_id: &str, limit: usize) -> Result<Vec<AccessLogEntry>, DbError> {
+        let conn = self.conn("recent_accesses");
+
+        let mut stmt = conn.prepare("
+            SELECT accessed_at, ip_hash, user_agent FROM access_log
+            WHERE paste_id = ?
+            ORDER BY accessed_at DESC, rowid DESC
+            LIMIT ?
+        ")?;
+        stmt.bind((1, paste_id))?;
+        stmt.bind((2, limit as i64))?;
+
+        let mut entries = Vec::new();
+        while let State::Row = stmt.next()? {
+            entries.push(AccessLogEntry {
+                accessed_at: DateTime::from_timestamp(stmt.read::<i64, _>(0)?, 0).unwrap_or_default(),
+                ip_hash: stmt.read::<Option<String>, _>(1)?,
+                user_agent: stmt.read::<Option<String>, _>(2)?,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// File an abuse report against `paste_id`. Each reporter (a keyed hash of their
+    /// address) may report a paste once, and file at most `hourly_limit` reports an
+    /// hour (`0` means no limit); past either it's `AlreadyReported` or
+    /// `ReportLimitExceeded`.
+    pub fn report_paste(&self, paste_id: &str, reason: &str, reporter_hash: &str, hourly_limit: u32) -> Result<(), DbError> {
+        let conn = self.conn("report_paste");
+        let now = Utc::now().timestamp();
+
+        let mut stmt = conn.prepare("SELECT 1 FROM paste_reports WHERE paste_id = ? AND reporter_hash = ? LIMIT 1")?;
+        stmt.bind((1, paste_id))?;
+        stmt.bind((2, reporter_hash))?;
+        if stmt.next()? == State::Row {
+            return Err(DbError::AlreadyReported);
+        }
+        drop(stmt);
+
+        if hourly_limit > 0 {
+            let mut stmt = conn.prepare("SELECT COUNT(*) FROM paste_reports WHERE reporter_hash = ? AND reported_at > ?")?;
+            stmt.bind((1, reporter_hash))?;
+            stmt.bind((2, now - 3600))?;
+            stmt.next()?;
+            if stmt.read::<i64, _>(0)? >= hourly_limit as i64 {
+                return Err(DbError::ReportLimitExceeded(hourly_limit));
+            }
+        }
+
+        let mut stmt = conn.prepare("INSERT INTO paste_reports (paste_id, reason, reporter_hash, reported_at) VALUES (?, ?, ?, ?)")?;
+        stmt.bind((1, paste_id))?;
+        stmt.bind((2, reason))?;
+        stmt.bind((3, reporter_hash))?;
+        stmt.bind((4, now))?;
+        stmt.next()?;
+        Ok(())
+    }
+
+    /// Live pastes with reports against them, most reported first, up to `limit`.
+    pub fn reported_pastes(&self, limit: usize) -> Result<Vec<ReportedPaste>, DbError> {
+        let conn = self.conn("reported_pastes");
+
+        let mut stmt = conn.prepare("
+            SELECT r.paste_id, COUNT(*), MIN(r.reported_at), MAX(r.reported_at)
+            FROM paste_reports r JOIN pastes p ON p.id = r.paste_id
+            WHERE p.deleted_at IS NULL
+            GROUP BY r.paste_id
+            ORDER BY COUNT(*) DESC, MAX(r.reported_at) DESC
+            LIMIT ?
+        ")?;
+        stmt.bind((1, limit as i64))?;
+
+        let mut reported = Vec::new();
+        while let State::Row = stmt.next()? {
+            reported.push(ReportedPaste {
+                paste_id: stmt.read::<String, _>(0)?,
+                reports: stmt.read::<i64, _>(1)? as u64,
+                first_reported_at: DateTime::from_timestamp(stmt.read::<i64, _>(2)?, 0).unwrap_or_default(),
+                last_reported_at: DateTime::from_timestamp(stmt.read::<i64, _>(3)?, 0).unwrap_or_default(),
+                reasons: Vec::new(),
+            });
+        }
+        drop(stmt);
+
+        let mut stmt = conn.prepare("SELECT reason FROM paste_reports WHERE paste_id = ? ORDER BY reported_at DESC, rowid DESC LIMIT ?")?;
+        for paste in &mut reported {
+            stmt.reset()?;
+            stmt.bind((1, paste.paste_id.as_str()))?;
+            stmt.bind((2, MAX_LISTED_REPORT_REASONS as i64))?;
+            while let State::Row = stmt.next()? {
+                paste.reasons.push(stmt.read::<String, _>(0)?);
+            }
+        }
+
+        Ok(reported)
+    }
+
+    /// Clear the reports against `paste_id` once a moderator has dealt with them,
+    /// returning how many there were.
+    pub fn dismiss_reports(&self, paste_id: &str) -> Result<usize, DbError> {
+        let conn = self.conn("dismiss_reports");
+        let mut stmt = conn.prepare("DELETE FROM paste_reports WHERE paste_id = ?")?;
+        stmt.bind((1, paste_id))?;
+        stmt.next()?;
+        Ok(conn.change_count())
+    }
+
+    /// Metadata for a readable, unexpired paste. `data` is never selected and the
+    /// read doesn't count as a view. Expired and trashed pastes are reported as
+    /// `PasteExpired` and `PasteTrashed`.
+    pub fn get_paste_meta(&self, id: &str) -> Result<Option<PasteMeta>, DbError> {
+        let conn = self.conn("get_paste_meta");
+
+        let mut stmt = conn.prepare(
+            format!("SELECT language, created_at, encryption_version, CASE WHEN pinned = 0 THEN expires_at END, views, {DATA_BYTES}, deleted_at FROM pastes WHERE id = ?")
+        )?;
+        stmt.bind((1, id))?;
+
+        if stmt.next()? != State::Row {
+            return Ok(None);
+        }
+        if stmt.read::<Option<i64>, _>(6)?.is_some() {
+            return Err(DbError::PasteTrashed);
+        }
+
+        let language = self.stored_language(stmt.read::<String, _>(0)?);
+        let created_at = stored_created_at(&stmt.read::<sqlite::Value, _>(1)?);
+        let encryption_version = stmt.read::<i64, _>(2).unwrap_or(0) as u8;
+        let expires_at: Option<i64> = stmt.read::<Option<i64>, _>(3).ok().flatten();
+        let views = stmt.read::<i64, _>(4).unwrap_or(0) as u64 + self.pending_view_count(id);
+        let size_bytes = stmt.read::<i64, _>(5).unwrap_or(0) as u64;
+
+        if !Self::is_readable_version(encryption_version) {
+            return Ok(None);
+        }
+
+        if expires_at.is_some_and(|exp_ts| Utc::now().timestamp() > exp_ts) {
+            return Err(DbError::PasteExpired);
+        }
+
+        Ok(Some(PasteMeta {
+            id: id.to_string(),
+            language,
+            created_at,
+            views,
+            encryption_version,
+            size_bytes,
+        }))
+    }
+
+    // Buffered views for a paste not yet in the database
+    fn pending_view_count(&self, id: &str) -> u64 {
+        self.pending_views.lock().unwrap_or_else(PoisonError::into_inner).get(id).copied().unwrap_or(0)
+    }
+
+    /// Write buffered view counts to the database in one transaction. Returns the
+    /// number of pastes updated; on failure the counts stay buffered.
+    pub fn flush_views(&self) -> Result<usize, DbError> {
+        let conn = self.conn("flush_views");
+        self.flush_views_locked(&conn)
+    }
+
+    // Flush on an already-locked connection
+    fn flush_views_locked(&self, conn: &Connection) -> Result<usize, DbError> {
+        let mut pending_views = self.pending_views.lock().unwrap_or_else(PoisonError::into_inner);
+        if pending_views.is_empty() {
+            return Ok(0);
+        }
+
+        conn.execute("BEGIN IMMEDIATE;")?;
+        let result = pending_views.iter().try_for_each(|(id, count)| {
+            let mut stmt = conn.prepare("UPDATE pastes SET views = views + ? WHERE id = ?")?;
+            stmt.bind((1, *count as i64))?;
+            stmt.bind((2, id.as_str()))?;
+            stmt.next()?;
+            Ok::<_, DbError>(())
+        });
+
+        match result.and_then(|()| conn.execute("COMMIT;").map_err(DbError::from)) {
+            Ok(()) => {
+                let flushed = pending_views.len();
+                pending_views.clear();
+                Ok(flushed)
+            }
+            Err(e) => {
+                let _ = conn.execute("ROLLBACK;");
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether a readable, unexpired paste exists at `id`, via a single indexed lookup
+    /// that never reads `data` or counts a view.
+    pub fn paste_is_live(&self, id: &str) -> Result<bool, DbError> {
+        let conn = self.conn("paste_is_live");
+
+        let mut stmt = conn.prepare("
+            SELECT 1 FROM pastes
+            WHERE id = ? AND deleted_at IS NULL
+              AND encryption_version IN (?, ?)
+              AND (expires_at IS NULL OR expires_at >= ? OR pinned = 1)
+            LIMIT 1
+        ")?;
+        stmt.bind((1, id))?;
+        stmt.bind((2, ENCRYPTION_VERSION_NONE as i64))?;
+        stmt.bind((3, ENCRYPTION_VERSION_CLIENT as i64))?;
+        stmt.bind((4, Utc::now().timestamp()))?;
+
+        Ok(stmt.next()? == State::Row)
+    }
+
+    /// Read a paste, counting the view and applying burn-after-read / max_views.
+    /// Expired and trashed pastes are reported as `PasteExpired` and `PasteTrashed`.
+    ///
+    /// If `skip_view` returns true for the stored data (e.g. the client's cached
+    /// copy is current) the paste is returned without being counted or consumed.
+    /// The whole read-count-delete sequence runs under one connection lock.
+    pub fn get_paste_unless(
+        &self,
+        id: &str,
+        skip_view: impl FnOnce(&str) -> bool,
+    ) -> Result<Option<Paste>, DbError> {
+        let conn = self.conn("get_paste_unless");
+
+        let mut stmt = conn.prepare(
+            "SELECT data, language, created_at, encryption_version, burn_after_read, CASE WHEN pinned = 0 THEN expires_at END, normalized, views, max_views, deleted_at, content_sha256, forked_from, pinned, visibility, encoding, compressed FROM pastes WHERE id = ?"
+        )?;
+        stmt.bind((1, id))?;
+
+        if stmt.next()? != State::Row {
+            return Ok(None);
+        }
+        if stmt.read::<Option<i64>, _>(9)?.is_some() {
+            return Err(DbError::PasteTrashed);
+        }
+
+        let data = stored_data(stmt.read::<sqlite::Value, _>(0)?, stmt.read::<i64, _>(15)?)?;
+        let language = self.stored_language(stmt.read::<String, _>(1)?);
+        let created_at = stored_created_at(&stmt.read::<sqlite::Value, _>(2)?);
+        let encryption_version = stmt.read::<i64, _>(3).unwrap_or(0) as u8;
+        let burn_after_read = stmt.read::<i64, _>(4).unwrap_or(0) != 0;
+        let expires_at: Option<i64> = stmt.read::<Option<i64>, _>(5).ok().flatten();
+        let normalized = stmt.read::<i64, _>(6).unwrap_or(0) != 0;
+        let views = stmt.read::<i64, _>(7).unwrap_or(0) as u64;
+        let max_views = stmt.read::<Option<i64>, _>(8).ok().flatten().map(|max| max as u64);
+        let content_sha256 = stmt.read::<Option<String>, _>(10)?;
+        let forked_from = stmt.read::<Option<String>, _>(11)?;
+        let pinned = stmt.read::<i64, _>(12)? != 0;
+        let visibility = Visibility::from_stored(&stmt.read::<String, _>(13)?);
+        let encoding = Encoding::from_stored(&stmt.read::<String, _>(14)?);
+        drop(stmt);
+
+        if !Self::is_readable_version(encryption_version) {
+            return Ok(None);
+        }
+
+        // Check if paste has expired
+        if let Some(exp_ts) = expires_at
+            && Utc::now().timestamp() > exp_ts
+        {
+            if !self.options.defer_expired_deletes && !self.options.read_only {
+                Self::delete_paste_locked(&conn, id)?;
+            }
+            return Err(DbError::PasteExpired);
+        }
+
+        let tags = Self::read_tags(&conn, id)?;
+
+        let mut pending_views = self.pending_views.lock().unwrap_or_else(PoisonError::into_inner);
+        let pending = pending_views.get(id).copied().unwrap_or(0);
+
+        let views = if self.options.read_only || skip_view(&data) {
+            views + pending
+        } else {
+            let views = views + pending + 1;
+
+            // Delete after reading if burn_after_read is enabled or the view limit is reached
+            if burn_after_read || max_views.is_some_and(|max| views >= max) {
+                pending_views.remove(id);
+                Self::delete_paste_locked(&conn, id)?;
             } else {
-                return None;
+                self.record_view_locked(&conn, &mut pending_views, id, views, max_views)?;
             }
+            views
+        };
+        drop(pending_views);
+
+        let server_sha256 = Some(Self::sha256_hex(&data));
+        let theme_hint = self.theme_hint(&language);
+        Ok(Some(Paste {
+            id: id.to_string(),
+            data,
+            language,
+            created_at,
+            encryption_version,
+            burn_after_read,
+            expires_at: expires_at.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(Utc::now)),
+            edit_key: None, // Never return edit key on get
+            edit_key_hash: None,
+            paste_type: None,
+            normalized,
+            tags,
+            views,
+            max_views,
+            url: None,
+            language_detected: None,
+            content_sha256,
+            server_sha256,
+            forked_from,
+            pinned,
+            visibility,
+            encoding,
+            theme_hint,
+        }))
+    }
+    
+    // Store the count after a view that doesn't consume the paste. `views` includes
+    // the pending count and this view.
+    fn record_view_locked(
+        &self,
+        conn: &Connection,
+        pending_views: &mut std::collections::HashMap<String, u64>,
+        id: &str,
+        views: u64,
+        max_views: Option<u64>,
+    ) -> Result<(), DbError> {
+        if self.options.buffer_views && max_views.is_none() {
+            *pending_views.entry(id.to_string()).or_insert(0) += 1;
+        } else {
+            // View limits are enforced on the stored count, so write it through
+            let mut update_stmt = conn.prepare("UPDATE pastes SET views = ? WHERE id = ?")?;
+            update_stmt.bind((1, views as i64))?;
+            update_stmt.bind((2, id))?;
+            update_stmt.next()?;
+            pending_views.remove(id);
+        }
+        Ok(())
+    }
+
+    // A raw read of a paste that was loaded through `get_paste`
+    fn whole_raw_paste(paste: Paste) -> (RawData, RawPasteInfo) {
+        let info = RawPasteInfo {
+            language: paste.language,
+            encoding: paste.encoding,
+            limited_reads: paste.burn_after_read || paste.max_views.is_some(),
+            expires_at: paste.expires_at,
+        };
+        (RawData::Whole(paste.data), info)
+    }
+
+    /// Read a paste's stored data for the raw endpoints, counting the view like
+    /// `get_paste`, with its language, encoding, and what decides how long the read
+    /// may be cached. Data over `RAW_CHUNK_BYTES` stored as plain text comes back as
+    /// `RawData::Chunks` when the database is a file; either way it's never a mix
+    /// of two versions of the paste.
+    pub fn get_raw_paste(&self, id: &str) -> Result<Option<(RawData, RawPasteInfo)>, DbError> {
+        let conn = self.conn("get_raw_paste");
+
+        let mut stmt = conn.prepare(format!(
+            "SELECT {DATA_BYTES}, encryption_version, burn_after_read, CASE WHEN pinned = 0 THEN expires_at END, views, max_views, deleted_at, language, encoding, compressed FROM pastes WHERE id = ?"
+        ))?;
+        stmt.bind((1, id))?;
+        if stmt.next()? != State::Row {
+            return Ok(None);
+        }
+        if stmt.read::<Option<i64>, _>(6)?.is_some() {
+            return Err(DbError::PasteTrashed);
+        }
+
+        let size = stmt.read::<i64, _>(0)? as u64;
+        let compressed = stmt.read::<i64, _>(9)? != 0;
+        let encryption_version = stmt.read::<i64, _>(1).unwrap_or(0) as u8;
+        let burn_after_read = stmt.read::<i64, _>(2).unwrap_or(0) != 0;
+        let expires_at: Option<i64> = stmt.read::<Option<i64>, _>(3).ok().flatten();
+        let views = stmt.read::<i64, _>(4).unwrap_or(0) as u64;
+        let max_views = stmt.read::<Option<i64>, _>(5).ok().flatten().map(|max| max as u64);
+        let info = RawPasteInfo {
+            language: self.stored_language(stmt.read::<String, _>(7)?),
+            encoding: Encoding::from_stored(&stmt.read::<String, _>(8)?),
+            limited_reads: burn_after_read || max_views.is_some(),
+            expires_at: expires_at.and_then(|exp_ts| DateTime::from_timestamp(exp_ts, 0)),
+        };
+        drop(stmt);
+
+        if !Self::is_readable_version(encryption_version) {
+            return Ok(None);
+        }
+        if expires_at.is_some_and(|exp_ts| Utc::now().timestamp() > exp_ts) {
+            drop(conn);
+            // Let the regular read apply its expiry handling
+            return self.get_paste(id).map(|paste| paste.map(Self::whole_raw_paste));
+        }
+
+        let mut pending_views = self.pending_views.lock().unwrap_or_else(PoisonError::into_inner);
+        let views = views + pending_views.get(id).copied().unwrap_or(0) + 1;
+        if !self.options.read_only && (burn_after_read || max_views.is_some_and(|max| views >= max)) {
+            drop(pending_views);
+            drop(conn);
+            return self.get_paste(id).map(|paste| paste.map(Self::whole_raw_paste));
+        }
+
+        // Read while the lock keeps this process from writing, so a snapshot
+        // holds the same version as the metadata above
+        let streamed = !compressed && info.encoding != Encoding::Base64 && size > RAW_CHUNK_BYTES;
+        let data = match &self.snapshot_uri {
+            Some(uri) if streamed => PasteChunks::open(uri, self.options.busy_timeout, id)?.map(RawData::Chunks),
+            _ => None,
         };
-        
-        // Check if paste has expired
-        if let Some(exp_ts) = expires_at {
-            if Utc::now().timestamp() > exp_ts {
-                // Delete expired paste
-                self.delete_paste(id);
-                return None;
-            }
+        let data = match data {
+            Some(data) => data,
+            None => RawData::Whole(Self::paste_data_locked(&conn, id)?),
+        };
+
+        if !self.options.read_only {
+            self.record_view_locked(&conn, &mut pending_views, id, views, max_views)?;
         }
-        
-        // If burn_after_read is enabled, delete the paste after reading
-        if burn_after_read {
-            self.delete_paste(id);
+        Ok(Some((data, info)))
+    }
+
+    // A paste's whole stored data
+    fn paste_data_locked(conn: &Connection, id: &str) -> Result<String, DbError> {
+        let mut stmt = conn.prepare("SELECT data, compressed FROM pastes WHERE id = ?")?;
+        stmt.bind((1, id))?;
+        if stmt.next()? != State::Row {
+            return Err(DbError::PasteNotFound);
         }
-        
-        Some(Paste {
-            id: id.to_string(),
-            data: encrypted_data,
-            language,
-            created_at,
-            encryption_version: ENCRYPTION_VERSION_CLIENT,
-            burn_after_read,
-            expires_at: expires_at.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(|| Utc::now())),
-            edit_key: None, // Never return edit key on get
-            edit_key_hash: None,
-            paste_type: None,
-        })
+        stored_data(stmt.read::<sqlite::Value, _>(0)?, stmt.read::<i64, _>(1)?)
     }
-    
+
     pub fn update_paste(&self, id: &str, update_data: UpdatePasteData) -> Result<Paste, DbError> {
         // Check character limit
         let byte_count = Self::get_utf8_byte_count(&update_data.data);
-        if byte_count > MAX_PASTE_CHARACTERS {
-            return Err(DbError::CharacterLimitExceeded(byte_count, MAX_PASTE_CHARACTERS));
+        if byte_count > self.options.max_paste_bytes {
+            return Err(DbError::CharacterLimitExceeded(byte_count, self.options.max_paste_bytes));
         }
         
         if update_data.data.is_empty() {
             return Err(DbError::ClientEncryptionRequired);
         }
         
-        let conn = self.connection.lock().unwrap();
+        let conn = self.conn("update_paste");
         
         // First, get the stored edit_key_hash and other metadata
-        let mut stmt = conn.prepare("SELECT edit_key_hash, created_at, burn_after_read, expires_at FROM pastes WHERE id = ?")?;
+        let mut stmt = conn.prepare("SELECT edit_key_hash, created_at, burn_after_read, CASE WHEN pinned = 0 THEN expires_at END, encryption_version, pinned, visibility, encoding FROM pastes WHERE id = ? AND deleted_at IS NULL")?;
         stmt.bind((1, id))?;
         
-        let (stored_hash, created_at, burn_after_read, expires_at) = if let State::Row = stmt.next()? {
+        let (stored_hash, created_at, burn_after_read, expires_at, encryption_version, pinned, visibility, encoding) = if let State::Row = stmt.next()? {
             let hash: Option<String> = stmt.read::<Option<String>, _>(0).ok().flatten();
-            let created_at = stmt.read::<i64, _>(1).unwrap_or(0);
+            let created_at = stmt.read::<sqlite::Value, _>(1).unwrap_or_default();
             let burn_after_read = stmt.read::<i64, _>(2).unwrap_or(0) != 0;
             let expires_at_ts = stmt.read::<Option<i64>, _>(3).unwrap_or(None);
+            let encryption_version = stmt.read::<i64, _>(4).unwrap_or(0) as u8;
+            let pinned = stmt.read::<i64, _>(5).unwrap_or(0) != 0;
+            let visibility = Visibility::from_stored(&stmt.read::<String, _>(6)?);
+            let encoding = Encoding::from_stored(&stmt.read::<String, _>(7)?);
             
-            let timestamp = DateTime::from_timestamp(created_at, 0).unwrap_or_else(|| Utc::now());
-            let expires_at = expires_at_ts.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(|| Utc::now()));
+            let timestamp = stored_created_at(&created_at);
+            let expires_at = expires_at_ts.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(Utc::now));
             
             match hash {
-                Some(h) if !h.is_empty() => (h, timestamp, burn_after_read, expires_at),
+                Some(h) if !h.is_empty() => (h, timestamp, burn_after_read, expires_at, encryption_version, pinned, visibility, encoding),
                 _ => return Err(DbError::InvalidEditKey), // No edit key set for this paste
             }
         } else {
@@ -461,64 +2408,198 @@ impl Database {
         if !Self::verify_edit_key(&update_data.edit_key, &stored_hash) {
             return Err(DbError::InvalidEditKey);
         }
+
+        let content_sha256 = Self::verify_content_hash(update_data.content_sha256, &update_data.data)?;
+
+        // Plaintext pastes are normalized on every write; encrypted content is stored as-is
+        let (data, normalized) = if encryption_version == ENCRYPTION_VERSION_NONE {
+            self.normalize_content(update_data.data)
+        } else {
+            (update_data.data, false)
+        };
+
+        if data.is_empty() {
+            return Err(DbError::ClientEncryptionRequired);
+        }
+        Self::check_encoding(&data, encoding, encryption_version)?;
+        self.check_line_limit(&data, encryption_version)?;
+
+        self.check_content_policy(&data, &update_data.language, encryption_version)?;
         
         // Update the paste
-        let mut update_stmt = conn.prepare("UPDATE pastes SET data = ?, language = ? WHERE id = ?")?;
-        update_stmt.bind((1, update_data.data.as_str()))?;
+        let (stored, compressed) = data_to_store(&data, self.options.compress_over);
+        let mut update_stmt = conn.prepare("UPDATE pastes SET data = ?, language = ?, normalized = ?, content_sha256 = ?, compressed = ? WHERE id = ?")?;
+        update_stmt.bind((1, &stored))?;
         update_stmt.bind((2, update_data.language.as_str()))?;
-        update_stmt.bind((3, id))?;
+        update_stmt.bind((3, if normalized { 1 } else { 0 }))?;
+        update_stmt.bind((4, content_sha256.as_deref()))?;
+        update_stmt.bind((5, compressed))?;
+        update_stmt.bind((6, id))?;
         update_stmt.next()?;
+
+        let tags = Self::read_tags(&conn, id)?;
         
+        let theme_hint = self.theme_hint(&update_data.language);
         Ok(Paste {
             id: id.to_string(),
             data: String::new(),
             language: update_data.language,
             created_at,
-            encryption_version: ENCRYPTION_VERSION_CLIENT,
+            encryption_version,
             burn_after_read,
             expires_at,
             edit_key: None,
             edit_key_hash: None,
             paste_type: None,
+            normalized,
+            tags,
+            views: 0,
+            max_views: None,
+            url: None,
+            language_detected: None,
+            content_sha256,
+            server_sha256: Some(Self::sha256_hex(&data)),
+            forked_from: None,
+            pinned,
+            visibility,
+            encoding,
+            theme_hint,
         })
     }
 
-    pub fn delete_paste(&self, id: &str) -> bool {
-        let conn = self.connection.lock().unwrap();
-        
-        // First check if paste exists
-        let mut check_stmt = match conn.prepare("SELECT 1 FROM pastes WHERE id = ?") {
-            Ok(s) => s,
-            Err(_) => return false,
+    /// Unified diff from paste `id`'s content to `other_id`'s, or `None` if either
+    /// is missing. Both must be plaintext; that is checked on their metadata first,
+    /// so a refused diff reads nothing. The reads then count as views like any other
+    /// (a burn-after-read paste is consumed), and the same ID is only read once.
+    pub fn diff_pastes(&self, id: &str, other_id: &str) -> Result<Option<LineDiff>, DbError> {
+        for id in [id, other_id] {
+            match self.get_paste_meta(id)? {
+                None => return Ok(None),
+                Some(meta) if meta.encryption_version != ENCRYPTION_VERSION_NONE => {
+                    return Err(DbError::DiffOfEncrypted);
+                }
+                Some(_) => {}
+            }
+        }
+
+        let Some(old) = self.get_paste(id)? else {
+            return Ok(None);
         };
-        
-        if check_stmt.bind((1, id)).is_err() {
-            return false;
+        let new_data = if other_id == id {
+            old.data.clone()
+        } else {
+            match self.get_paste(other_id)? {
+                Some(new) => new.data,
+                None => return Ok(None),
+            }
+        };
+
+        Ok(Some(diff::unified(&old.data, &new_data, id, other_id)))
+    }
+
+    /// Add `append_data.data` to the end of a plaintext paste. The combined content
+    /// goes through the same limits, normalization and content policy as an update,
+    /// and the stored `content_sha256` is cleared since it no longer describes it.
+    pub fn append_paste(&self, id: &str, append_data: AppendPasteData) -> Result<Paste, DbError> {
+        // Reading and rewriting under one lock keeps concurrent appends from losing each other
+        let conn = self.conn("append_paste");
+
+        let mut stmt = conn.prepare("SELECT edit_key_hash, data, language, created_at, burn_after_read, CASE WHEN pinned = 0 THEN expires_at END, encryption_version, pinned, visibility, encoding, compressed FROM pastes WHERE id = ? AND deleted_at IS NULL")?;
+        stmt.bind((1, id))?;
+        if stmt.next()? != State::Row {
+            return Err(DbError::PasteNotFound);
         }
-        
-        let exists = matches!(check_stmt.next(), Ok(State::Row));
-        if !exists {
-            return false;
+
+        match stmt.read::<Option<String>, _>("edit_key_hash")? {
+            Some(hash) if !hash.is_empty() && Self::verify_edit_key(&append_data.edit_key, &hash) => {}
+            _ => return Err(DbError::InvalidEditKey),
         }
-        
-        // Delete the paste
-        let mut stmt = match conn.prepare("DELETE FROM pastes WHERE id = ?") {
-            Ok(s) => s,
-            Err(_) => return false,
-        };
-        
-        if stmt.bind((1, id)).is_err() {
-            return false;
+
+        let encryption_version = stmt.read::<i64, _>("encryption_version")? as u8;
+        if encryption_version != ENCRYPTION_VERSION_NONE {
+            return Err(DbError::AppendToEncrypted);
+        }
+
+        let mut combined = stored_data(stmt.read::<sqlite::Value, _>("data")?, stmt.read::<i64, _>("compressed")?)?;
+        combined.push_str(&append_data.data);
+        let language = stmt.read::<String, _>("language")?;
+        let created_at = stored_created_at(&stmt.read::<sqlite::Value, _>("created_at")?);
+        let burn_after_read = stmt.read::<i64, _>("burn_after_read")? != 0;
+        let expires_at = stmt
+            .read::<Option<i64>, _>(5)?
+            .map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(Utc::now));
+        let pinned = stmt.read::<i64, _>("pinned")? != 0;
+        let visibility = Visibility::from_stored(&stmt.read::<String, _>("visibility")?);
+        let encoding = Encoding::from_stored(&stmt.read::<String, _>("encoding")?);
+
+        let byte_count = Self::get_utf8_byte_count(&combined);
+        if byte_count > self.options.max_paste_bytes {
+            return Err(DbError::CharacterLimitExceeded(byte_count, self.options.max_paste_bytes));
         }
+
+        let (data, normalized) = self.normalize_content(combined);
+        Self::check_encoding(&data, encoding, encryption_version)?;
+        self.check_line_limit(&data, encryption_version)?;
+        self.check_content_policy(&data, &language, encryption_version)?;
+
+        let (stored, compressed) = data_to_store(&data, self.options.compress_over);
+        let mut update_stmt = conn.prepare("UPDATE pastes SET data = ?, normalized = ?, content_sha256 = NULL, compressed = ? WHERE id = ?")?;
+        update_stmt.bind((1, &stored))?;
+        update_stmt.bind((2, normalized as i64))?;
+        update_stmt.bind((3, compressed))?;
+        update_stmt.bind((4, id))?;
+        update_stmt.next()?;
+
+        let tags = Self::read_tags(&conn, id)?;
+
+        let theme_hint = self.theme_hint(&language);
+        Ok(Paste {
+            id: id.to_string(),
+            data: String::new(),
+            language,
+            created_at,
+            encryption_version,
+            burn_after_read,
+            expires_at,
+            edit_key: None,
+            edit_key_hash: None,
+            paste_type: None,
+            normalized,
+            tags,
+            views: 0,
+            max_views: None,
+            url: None,
+            language_detected: None,
+            content_sha256: None,
+            server_sha256: Some(Self::sha256_hex(&data)),
+            forked_from: None,
+            pinned,
+            visibility,
+            encoding,
+            theme_hint,
+        })
+    }
+
+    /// Delete a paste by ID. Returns whether a paste was removed.
+    pub fn delete_paste(&self, id: &str) -> Result<bool, DbError> {
+        let conn = self.conn("delete_paste");
+        Self::delete_paste_locked(&conn, id)
+    }
+
+    // Delete a paste on an already-locked connection
+    fn delete_paste_locked(conn: &Connection, id: &str) -> Result<bool, DbError> {
+        let mut stmt = conn.prepare("DELETE FROM pastes WHERE id = ?")?;
+        stmt.bind((1, id))?;
+        stmt.next()?;
         
-        matches!(stmt.next(), Ok(_))
+        Ok(conn.change_count() > 0)
     }
     
     pub fn delete_paste_with_key(&self, id: &str, delete_data: DeletePasteData) -> Result<(), DbError> {
-        let conn = self.connection.lock().unwrap();
+        let conn = self.conn("delete_paste_with_key");
         
         // First get the stored edit_key_hash
-        let mut stmt = conn.prepare("SELECT edit_key_hash FROM pastes WHERE id = ?")?;
+        let mut stmt = conn.prepare("SELECT edit_key_hash FROM pastes WHERE id = ? AND deleted_at IS NULL")?;
         stmt.bind((1, id))?;
         
         if stmt.next()? != State::Row {
@@ -526,20 +2607,104 @@ impl Database {
         }
         
         let stored_hash: String = stmt.read::<String, _>("edit_key_hash")?;
+        drop(stmt);
         
         // Verify the edit key
         if !Self::verify_edit_key(&delete_data.edit_key, &stored_hash) {
             return Err(DbError::InvalidEditKey);
         }
         
-        // Delete the paste
-        let mut delete_stmt = conn.prepare("DELETE FROM pastes WHERE id = ?")?;
-        delete_stmt.bind((1, id))?;
-        delete_stmt.next()?;
+        // Move the paste to the trash when restores are enabled, otherwise delete it
+        if self.options.trash_retention_secs > 0 {
+            let mut trash_stmt = conn.prepare("UPDATE pastes SET deleted_at = ? WHERE id = ?")?;
+            trash_stmt.bind((1, Utc::now().timestamp()))?;
+            trash_stmt.bind((2, id))?;
+            trash_stmt.next()?;
+        } else {
+            Self::delete_paste_locked(&conn, id)?;
+        }
         
         Ok(())
     }
 
+    /// Check an edit key against a live paste without changing anything.
+    ///
+    /// Fails with `PastePrivate` if the paste is private and `edit_key` isn't its
+    /// edit key. Missing pastes pass, so the read that follows reports them as usual.
+    /// Visibility never changes after creation, so checking before the read is safe.
+    pub fn check_read_access(&self, id: &str, edit_key: Option<&str>) -> Result<(), DbError> {
+        let conn = self.conn("check_read_access");
+
+        let mut stmt = conn.prepare("SELECT visibility, edit_key_hash FROM pastes WHERE id = ?")?;
+        stmt.bind((1, id))?;
+        if stmt.next()? != State::Row {
+            return Ok(());
+        }
+
+        if Visibility::from_stored(&stmt.read::<String, _>("visibility")?) != Visibility::Private {
+            return Ok(());
+        }
+        match (edit_key, stmt.read::<Option<String>, _>("edit_key_hash")?) {
+            (Some(edit_key), Some(hash)) if Self::verify_edit_key(edit_key, &hash) => Ok(()),
+            _ => Err(DbError::PastePrivate),
+        }
+    }
+
+    pub fn check_edit_key(&self, id: &str, edit_key: &str) -> Result<(), DbError> {
+        let conn = self.conn("check_edit_key");
+
+        let mut stmt = conn.prepare("SELECT edit_key_hash FROM pastes WHERE id = ? AND deleted_at IS NULL")?;
+        stmt.bind((1, id))?;
+
+        if stmt.next()? != State::Row {
+            return Err(DbError::PasteNotFound);
+        }
+
+        let stored_hash: String = stmt.read::<String, _>("edit_key_hash")?;
+        if !Self::verify_edit_key(edit_key, &stored_hash) {
+            return Err(DbError::InvalidEditKey);
+        }
+
+        Ok(())
+    }
+
+    /// Bring back a paste deleted within the trash retention window.
+    ///
+    /// Fails with `PasteNotFound` if the paste isn't in the trash or the window has passed.
+    pub fn restore_paste_with_key(&self, id: &str, restore_data: DeletePasteData) -> Result<(), DbError> {
+        let conn = self.conn("restore_paste_with_key");
+
+        let mut stmt = conn.prepare(
+            "SELECT edit_key_hash, deleted_at, expires_at FROM pastes WHERE id = ? AND deleted_at IS NOT NULL"
+        )?;
+        stmt.bind((1, id))?;
+
+        if stmt.next()? != State::Row {
+            return Err(DbError::PasteNotFound);
+        }
+
+        let stored_hash = stmt.read::<String, _>(0)?;
+        let deleted_at = stmt.read::<i64, _>(1)?;
+        let expires_at = stmt.read::<Option<i64>, _>(2)?;
+        drop(stmt);
+
+        let now = Utc::now().timestamp();
+        let window_closed = now - deleted_at > self.options.trash_retention_secs as i64;
+        if window_closed || expires_at.is_some_and(|exp_ts| now > exp_ts) {
+            return Err(DbError::PasteNotFound);
+        }
+
+        if !Self::verify_edit_key(&restore_data.edit_key, &stored_hash) {
+            return Err(DbError::InvalidEditKey);
+        }
+
+        let mut restore_stmt = conn.prepare("UPDATE pastes SET deleted_at = NULL WHERE id = ?")?;
+        restore_stmt.bind((1, id))?;
+        restore_stmt.next()?;
+
+        Ok(())
+    }
+
     /// Retrieve dashboard statistics, optionally filtered by time range.
     ///
     /// Supports preset ranges (24h, 7d, 30d, 1y, all) and custom ranges
@@ -551,7 +2716,7 @@ impl Database {
         custom_start: Option<i64>,
         custom_end: Option<i64>,
     ) -> Result<DashboardStats, DbError> {
-        let conn = self.connection.lock().unwrap();
+        let conn = self.conn("get_dashboard_stats");
 
         // Build the time filter WHERE clause for summary cards
         let time_filter = match range {
@@ -569,46 +2734,46 @@ impl Database {
         };
 
         let total_pastes: i64 = conn
-            .prepare(&format!("SELECT COUNT(*) FROM pastes {}", time_filter))?
+            .prepare(format!("SELECT COUNT(*) FROM pastes {}", time_filter))?
             .into_iter()
-            .map(|row| row.unwrap().read::<i64, _>(0))
             .next()
-            .unwrap();
+            .transpose()?
+            .map_or(0, |row| row.read::<i64, _>(0));
 
         let pending_expiration: i64 = conn
-            .prepare(&format!(
-                "SELECT COUNT(*) FROM pastes {} {} expires_at IS NOT NULL",
+            .prepare(format!(
+                "SELECT COUNT(*) FROM pastes {} {} expires_at IS NOT NULL AND pinned = 0",
                 time_filter,
                 if time_filter.is_empty() { "WHERE" } else { "AND" }
             ))?
             .into_iter()
-            .map(|row| row.unwrap().read::<i64, _>(0))
             .next()
-            .unwrap();
+            .transpose()?
+            .map_or(0, |row| row.read::<i64, _>(0));
 
         let unread_pastes: i64 = conn
-            .prepare(&format!(
+            .prepare(format!(
                 "SELECT COUNT(*) FROM pastes {} {} burn_after_read = 1",
                 time_filter,
                 if time_filter.is_empty() { "WHERE" } else { "AND" }
             ))?
             .into_iter()
-            .map(|row| row.unwrap().read::<i64, _>(0))
             .next()
-            .unwrap();
+            .transpose()?
+            .map_or(0, |row| row.read::<i64, _>(0));
 
         let total_size: i64 = conn
-            .prepare(&format!(
+            .prepare(format!(
                 "SELECT COALESCE(SUM(LENGTH(data)), 0) FROM pastes {}",
                 time_filter
             ))?
             .into_iter()
-            .map(|row| row.unwrap().read::<i64, _>(0))
             .next()
-            .unwrap_or(0);
+            .transpose()?
+            .map_or(0, |row| row.read::<i64, _>(0));
 
         let mut language_stats = std::collections::HashMap::new();
-        let lang_stmt = conn.prepare(&format!(
+        let lang_stmt = conn.prepare(format!(
             "SELECT language, COUNT(*) FROM pastes {} GROUP BY language",
             time_filter
         ))?;
@@ -683,11 +2848,12 @@ impl Database {
         })
     }
     
+    #[allow(dead_code)]
     pub fn list_pastes(&self, limit: i64, offset: i64) -> Result<Vec<Paste>, DbError> {
-        let conn = self.connection.lock().unwrap();
+        let conn = self.conn("list_pastes");
         
         let mut stmt = conn.prepare("
-            SELECT id, data, language, created_at, encryption_version, burn_after_read, expires_at, edit_key_hash 
+            SELECT id, data, language, created_at, encryption_version, burn_after_read, expires_at, edit_key_hash, visibility, encoding, compressed
             FROM pastes 
             ORDER BY created_at DESC 
             LIMIT ? OFFSET ?
@@ -701,17 +2867,19 @@ impl Database {
         for row in stmt.into_iter() {
             let row = row?;
             let id = row.read::<&str, _>("id").to_string();
-            let data = row.read::<&str, _>("data").to_string();
+            let data = stored_data(row["data"].clone(), row.read::<i64, _>("compressed"))?;
             let language = row.read::<&str, _>("language").to_string();
-            let created_at_ts = row.read::<i64, _>("created_at");
+            let created_at = stored_created_at(&row["created_at"]);
             let encryption_version = row.read::<i64, _>("encryption_version") as u8;
             let burn_after_read = row.read::<i64, _>("burn_after_read") != 0;
             let expires_at_ts = row.read::<Option<i64>, _>("expires_at");
             let edit_key_hash: Option<String> = row.read::<Option<&str>, _>("edit_key_hash").map(|s| s.to_string());
+            let visibility = Visibility::from_stored(row.read::<&str, _>("visibility"));
+            let encoding = Encoding::from_stored(row.read::<&str, _>("encoding"));
             
-            let created_at = DateTime::from_timestamp(created_at_ts, 0).unwrap_or_else(|| Utc::now());
-            let expires_at = expires_at_ts.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(|| Utc::now()));
+            let expires_at = expires_at_ts.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(Utc::now));
             
+            let theme_hint = self.theme_hint(&language);
             pastes.push(Paste {
                 id,
                 data, // Encrypted data
@@ -723,20 +2891,415 @@ impl Database {
                 edit_key: None,
                 edit_key_hash,
                 paste_type: None,
+                normalized: false,
+                tags: Vec::new(),
+                views: 0,
+                max_views: None,
+                url: None,
+                language_detected: None,
+                content_sha256: None,
+                server_sha256: None,
+                forked_from: None,
+                pinned: false,
+                visibility,
+                encoding,
+                theme_hint,
             });
         }
         
         Ok(pastes)
     }
 
+    /// List the newest public pastes carrying a tag, skipping expired and
+    /// burn-after-read ones. Unlisted pastes are only reachable by their ID.
+    pub fn list_pastes_by_tag(&self, tag: &str) -> Result<Vec<Paste>, DbError> {
+        let conn = self.conn("list_pastes_by_tag");
+
+        let mut stmt = conn.prepare("
+            SELECT p.id, p.language, p.created_at, p.encryption_version, CASE WHEN p.pinned = 0 THEN p.expires_at END AS expires_at, p.visibility, p.encoding
+            FROM pastes p
+            JOIN paste_tags t ON t.paste_id = p.id
+            WHERE t.tag = ?
+              AND p.type = 'paste'
+              AND p.visibility = 'public'
+              AND p.burn_after_read = 0
+              AND p.deleted_at IS NULL
+              AND (p.expires_at IS NULL OR p.expires_at > ? OR p.pinned = 1)
+            ORDER BY p.created_at DESC
+            LIMIT ?
+        ")?;
+
+        stmt.bind((1, tag))?;
+        stmt.bind((2, Utc::now().timestamp()))?;
+        stmt.bind((3, MAX_TAG_LISTING))?;
+
+        let mut pastes = Vec::new();
+
+        for row in stmt.into_iter() {
+            let row = row?;
+            let created_at = stored_created_at(&row["created_at"]);
+            let expires_at_ts = row.read::<Option<i64>, _>("expires_at");
+
+            pastes.push(Paste {
+                id: row.read::<&str, _>("id").to_string(),
+                data: String::new(), // Listings never include content
+                language: row.read::<&str, _>("language").to_string(),
+                created_at,
+                encryption_version: row.read::<i64, _>("encryption_version") as u8,
+                burn_after_read: false,
+                expires_at: expires_at_ts.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(Utc::now)),
+                edit_key: None,
+                edit_key_hash: None,
+                paste_type: None,
+                normalized: false,
+                tags: Vec::new(),
+                views: 0,
+                max_views: None,
+                url: None,
+                language_detected: None,
+                content_sha256: None,
+                server_sha256: None,
+                forked_from: None,
+                pinned: false,
+                visibility: Visibility::from_stored(row.read::<&str, _>("visibility")),
+                encoding: Encoding::from_stored(row.read::<&str, _>("encoding")),
+                theme_hint: self.theme_hint(row.read::<&str, _>("language")),
+            });
+        }
+
+        Ok(pastes)
+    }
+
+    /// Delete up to `count` of the oldest unpinned records. Returns the number removed.
+    #[allow(dead_code)]
+    pub fn evict_oldest(&self, count: u64) -> Result<usize, DbError> {
+        let conn = self.conn("evict_oldest");
+        Self::evict_oldest_locked(&conn, count)
+    }
+
+    // Evict on an already-locked connection; walks idx_pastes_created_at from the old end
+    fn evict_oldest_locked(conn: &Connection, count: u64) -> Result<usize, DbError> {
+        let mut stmt = conn.prepare("
+            DELETE FROM pastes
+            WHERE id IN (
+                SELECT id FROM pastes
+                WHERE pinned = 0
+                ORDER BY created_at ASC
+                LIMIT ?
+            )
+        ")?;
+        stmt.bind((1, count.min(i64::MAX as u64) as i64))?;
+        stmt.next()?;
+
+        Ok(conn.change_count())
+    }
+
+    /// Pin or unpin a record so eviction under `MAX_TOTAL_PASTES` skips it.
+    pub fn set_pinned(&self, id: &str, pinned: bool) -> Result<(), DbError> {
+        let conn = self.conn("set_pinned");
+        let mut stmt = conn.prepare("UPDATE pastes SET pinned = ? WHERE id = ?")?;
+        stmt.bind((1, pinned as i64))?;
+        stmt.bind((2, id))?;
+        stmt.next()?;
+
+        if conn.change_count() > 0 {
+            Ok(())
+        } else {
+            Err(DbError::PasteNotFound)
+        }
+    }
+
     pub fn delete_paste_admin(&self, id: &str) -> Result<(), DbError> {
-        if self.delete_paste(id) {
+        if self.delete_paste(id)? {
             Ok(())
         } else {
             Err(DbError::PasteNotFound)
         }
     }
 
+    /// Full metadata of a record regardless of expiry or trash state, without its content.
+    pub fn admin_get(&self, id: &str) -> Result<Option<PasteDetails>, DbError> {
+        let conn = self.conn("admin_get");
+
+        let mut stmt = conn.prepare(format!("
+            SELECT type, language, created_at, encryption_version, {DATA_BYTES} AS size_bytes,
+                   edit_key_hash IS NOT NULL AS has_edit_key, burn_after_read, expires_at, normalized, views,
+                   max_views, deleted_at, pinned, content_sha256, forked_from, creator_hash, visibility, encoding
+            FROM pastes
+            WHERE id = ?
+        "))?;
+        stmt.bind((1, id))?;
+
+        if stmt.next()? != State::Row {
+            return Ok(None);
+        }
+
+        Ok(Some(PasteDetails {
+            id: id.to_string(),
+            record_type: stmt.read::<String, _>("type")?,
+            language: stmt.read::<String, _>("language")?,
+            created_at: stored_created_at(&stmt.read::<sqlite::Value, _>("created_at")?),
+            encryption_version: stmt.read::<i64, _>("encryption_version")? as u8,
+            size_bytes: stmt.read::<i64, _>("size_bytes")? as u64,
+            has_edit_key: stmt.read::<i64, _>("has_edit_key")? != 0,
+            burn_after_read: stmt.read::<i64, _>("burn_after_read")? != 0,
+            expires_at: stmt
+                .read::<Option<i64>, _>("expires_at")?
+                .and_then(|ts| DateTime::from_timestamp(ts, 0)),
+            normalized: stmt.read::<i64, _>("normalized")? != 0,
+            views: stmt.read::<i64, _>("views")? as u64 + self.pending_view_count(id),
+            max_views: stmt.read::<Option<i64>, _>("max_views")?.map(|max| max as u64),
+            tags: Self::read_tags(&conn, id)?,
+            deleted_at: stmt
+                .read::<Option<i64>, _>("deleted_at")?
+                .and_then(|ts| DateTime::from_timestamp(ts, 0)),
+            pinned: stmt.read::<i64, _>("pinned")? != 0,
+            content_sha256: stmt.read::<Option<String>, _>("content_sha256")?,
+            forked_from: stmt.read::<Option<String>, _>("forked_from")?,
+            creator_hash: stmt.read::<Option<String>, _>("creator_hash")?,
+            visibility: Visibility::from_stored(&stmt.read::<String, _>("visibility")?),
+            encoding: Encoding::from_stored(&stmt.read::<String, _>("encoding")?),
+        }))
+    }
+
+    /// Every stored record (pastes and workspaces) with full metadata, oldest first.
+    pub fn export_all(&self) -> Result<Vec<PasteRecord>, DbError> {
+        let conn = self.conn("export_all");
+        self.flush_views_locked(&conn)?;
+
+        let mut stmt = conn.prepare("
+            SELECT id, type, data, language, created_at, encryption_version, edit_key_hash,
+                   burn_after_read, expires_at, normalized, views, max_views, deleted_at, pinned, content_sha256,
+                   forked_from, creator_hash, visibility, encoding, compressed
+            FROM pastes
+            ORDER BY created_at ASC
+        ")?;
+
+        let mut records = Vec::new();
+        while let State::Row = stmt.next()? {
+            let id = stmt.read::<String, _>("id")?;
+            let tags = Self::read_tags(&conn, &id)?;
+            records.push(PasteRecord {
+                record_type: stmt.read::<String, _>("type")?,
+                data: stored_data(stmt.read::<sqlite::Value, _>("data")?, stmt.read::<i64, _>("compressed")?)?,
+                language: stmt.read::<String, _>("language")?,
+                created_at: stored_created_at(&stmt.read::<sqlite::Value, _>("created_at")?),
+                encryption_version: stmt.read::<i64, _>("encryption_version")? as u8,
+                edit_key_hash: stmt.read::<Option<String>, _>("edit_key_hash")?,
+                burn_after_read: stmt.read::<i64, _>("burn_after_read")? != 0,
+                expires_at: stmt
+                    .read::<Option<i64>, _>("expires_at")?
+                    .and_then(|ts| DateTime::from_timestamp(ts, 0)),
+                normalized: stmt.read::<i64, _>("normalized")? != 0,
+                views: stmt.read::<i64, _>("views")? as u64,
+                max_views: stmt.read::<Option<i64>, _>("max_views")?.map(|max| max as u64),
+                tags,
+                deleted_at: stmt
+                    .read::<Option<i64>, _>("deleted_at")?
+                    .and_then(|ts| DateTime::from_timestamp(ts, 0)),
+                pinned: stmt.read::<i64, _>("pinned")? != 0,
+                content_sha256: stmt.read::<Option<String>, _>("content_sha256")?,
+                forked_from: stmt.read::<Option<String>, _>("forked_from")?,
+                creator_hash: stmt.read::<Option<String>, _>("creator_hash")?,
+                visibility: Visibility::from_stored(&stmt.read::<String, _>("visibility")?),
+                encoding: Encoding::from_stored(&stmt.read::<String, _>("encoding")?),
+                id,
+            });
+        }
+
+        Ok(records)
+    }
+
+    /// Insert exported records as-is (original IDs, timestamps and edit key hashes)
+    /// in one transaction. Existing IDs are skipped or abort the whole import.
+    pub fn import(&self, records: Vec<PasteRecord>, on_conflict: ImportConflict) -> Result<ImportSummary, DbError> {
+        let conn = self.conn("import");
+        conn.execute("BEGIN IMMEDIATE;")?;
+
+        match Self::import_locked(&conn, records, on_conflict, self.options.compress_over) {
+            Ok(summary) => {
+                conn.execute("COMMIT;")?;
+                Ok(summary)
+            }
+            Err(e) => {
+                let _ = conn.execute("ROLLBACK;");
+                Err(e)
+            }
+        }
+    }
+
+    fn import_locked(conn: &Connection, records: Vec<PasteRecord>, on_conflict: ImportConflict, compress_over: usize) -> Result<ImportSummary, DbError> {
+        let mut summary = ImportSummary { imported: 0, skipped: Vec::new() };
+
+        for record in records {
+            let mut exists_stmt = conn.prepare("SELECT 1 FROM pastes WHERE id = ?")?;
+            exists_stmt.bind((1, record.id.as_str()))?;
+            if exists_stmt.next()? == State::Row {
+                match on_conflict {
+                    ImportConflict::Skip => {
+                        summary.skipped.push(record.id);
+                        continue;
+                    }
+                    ImportConflict::Error => return Err(DbError::ImportConflict(record.id)),
+                }
+            }
+
+            let mut stmt = conn.prepare("
+                INSERT INTO pastes (id, type, data, language, created_at, encryption_version, edit_key_hash,
+                                    burn_after_read, expires_at, normalized, views, max_views, deleted_at, pinned,
+                                    content_sha256, forked_from, creator_hash, visibility, encoding, compressed)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ")?;
+            let (stored, compressed) = data_to_store(&record.data, compress_over);
+            stmt.bind((1, record.id.as_str()))?;
+            stmt.bind((2, record.record_type.as_str()))?;
+            stmt.bind((3, &stored))?;
+            stmt.bind((4, record.language.as_str()))?;
+            stmt.bind((5, record.created_at.timestamp()))?;
+            stmt.bind((6, record.encryption_version as i64))?;
+            stmt.bind((7, record.edit_key_hash.as_deref()))?;
+            stmt.bind((8, record.burn_after_read as i64))?;
+            stmt.bind((9, record.expires_at.map(|dt| dt.timestamp())))?;
+            stmt.bind((10, record.normalized as i64))?;
+            stmt.bind((11, record.views as i64))?;
+            stmt.bind((12, record.max_views.map(|max| max as i64)))?;
+            stmt.bind((13, record.deleted_at.map(|dt| dt.timestamp())))?;
+            stmt.bind((14, record.pinned as i64))?;
+            stmt.bind((15, record.content_sha256.as_deref()))?;
+            stmt.bind((16, record.forked_from.as_deref()))?;
+            stmt.bind((17, record.creator_hash.as_deref()))?;
+            stmt.bind((18, record.visibility.as_str()))?;
+            stmt.bind((19, record.encoding.as_str()))?;
+            stmt.bind((20, compressed))?;
+            stmt.next()?;
+
+            for tag in &record.tags {
+                let mut tag_stmt = conn.prepare("INSERT OR IGNORE INTO paste_tags (paste_id, tag) VALUES (?, ?)")?;
+                tag_stmt.bind((1, record.id.as_str()))?;
+                tag_stmt.bind((2, tag.as_str()))?;
+                tag_stmt.next()?;
+            }
+
+            summary.imported += 1;
+        }
+
+        Ok(summary)
+    }
+
+    /// Storage totals across every stored record, read under one connection lock.
+    pub fn storage_stats(&self) -> Result<StorageStats, DbError> {
+        let conn = self.conn("storage_stats");
+
+        let mut stmt = conn.prepare("
+            SELECT COUNT(*), COALESCE(SUM(LENGTH(data)), 0), MIN(created_at), MAX(created_at)
+            FROM pastes
+        ")?;
+        stmt.next()?;
+        let total_pastes = stmt.read::<i64, _>(0)?;
+        let total_bytes = stmt.read::<i64, _>(1)?;
+        let oldest_created_at = stmt.read::<Option<i64>, _>(2)?;
+        let newest_created_at = stmt.read::<Option<i64>, _>(3)?;
+
+        let mut by_encryption_version = std::collections::HashMap::new();
+        let version_stmt = conn.prepare(
+            "SELECT encryption_version, COUNT(*) FROM pastes GROUP BY encryption_version"
+        )?;
+        for row in version_stmt.into_iter() {
+            let row = row?;
+            by_encryption_version.insert(row.read::<i64, _>(0) as u8, row.read::<i64, _>(1));
+        }
+
+        Ok(StorageStats {
+            total_pastes,
+            total_bytes,
+            oldest_created_at,
+            newest_created_at,
+            by_encryption_version,
+        })
+    }
+
+    /// Number of live pastes per language, most used first.
+    pub fn language_counts(&self) -> Result<Vec<(String, u64)>, DbError> {
+        let conn = self.conn("language_counts");
+
+        let mut stmt = conn.prepare("
+            SELECT language, COUNT(*) FROM pastes
+            WHERE type = 'paste' AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > ? OR pinned = 1)
+            GROUP BY language
+            ORDER BY COUNT(*) DESC, language ASC
+        ")?;
+        stmt.bind((1, Utc::now().timestamp()))?;
+
+        let mut counts = Vec::new();
+        while let State::Row = stmt.next()? {
+            counts.push((stmt.read::<String, _>(0)?, stmt.read::<i64, _>(1)? as u64));
+        }
+
+        Ok(counts)
+    }
+
+    /// Delete every paste whose expiration time has passed, plus trashed pastes whose
+    /// restore window has closed, then evict down to `MAX_TOTAL_PASTES` (which catches
+    /// imports and a lowered cap). Returns how many were removed for each reason;
+    /// a trashed paste that has also expired counts as expired.
+    ///
+    /// Every step deletes by condition, so overlapping runs only find less to do.
+    pub fn purge_expired(&self) -> Result<PurgeCounts, DbError> {
+        let conn = self.conn("purge_expired");
+        let now = Utc::now().timestamp();
+        let mut removed = PurgeCounts::default();
+
+        let mut stmt = conn.prepare("DELETE FROM pastes WHERE expires_at IS NOT NULL AND expires_at <= ? AND pinned = 0")?;
+        stmt.bind((1, now))?;
+        stmt.next()?;
+        removed.expired = conn.change_count();
+
+        let mut stmt = conn.prepare("DELETE FROM pastes WHERE deleted_at IS NOT NULL AND deleted_at <= ?")?;
+        stmt.bind((1, now - self.options.trash_retention_secs as i64))?;
+        stmt.next()?;
+        removed.trashed = conn.change_count();
+
+        // Idempotency entries, past quota days and the access log of pastes that are
+        // gone are bookkeeping, not pastes, so they aren't counted
+        let mut stmt = conn.prepare("DELETE FROM idempotency_keys WHERE created_at <= ?")?;
+        stmt.bind((1, self.idempotency_cutoff()))?;
+        stmt.next()?;
+        let mut stmt = conn.prepare("DELETE FROM daily_quotas WHERE day < ?")?;
+        stmt.bind((1, Self::quota_day(Utc::now()).as_str()))?;
+        stmt.next()?;
+        conn.execute("DELETE FROM access_log WHERE paste_id NOT IN (SELECT id FROM pastes);")?;
+
+        if self.options.max_total_pastes > 0 {
+            let excess = Self::count_records(&conn)?.saturating_sub(self.options.max_total_pastes);
+            if excess > 0 {
+                removed.evicted = Self::evict_oldest_locked(&conn, excess)?;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Rebuild the database file to reclaim space left by deleted pastes.
+    pub fn vacuum(&self) -> Result<(), DbError> {
+        let conn = self.conn("vacuum");
+        conn.execute("VACUUM;")?;
+        Ok(())
+    }
+
+    /// Copy WAL contents back into the database file.
+    pub fn checkpoint(&self, mode: CheckpointMode) -> Result<CheckpointResult, DbError> {
+        let conn = self.conn("checkpoint");
+
+        let mut stmt = conn.prepare(format!("PRAGMA wal_checkpoint({});", mode.as_sql()))?;
+        stmt.next()?;
+
+        Ok(CheckpointResult {
+            busy: stmt.read::<i64, _>(0)? != 0,
+            log_frames: stmt.read::<i64, _>(1)?,
+            checkpointed_frames: stmt.read::<i64, _>(2)?,
+        })
+    }
+
     /// List pastes with dynamic filters, sorting, and pagination.
     ///
     /// Returns (pastes, total_count) for pagination metadata.
@@ -746,7 +3309,7 @@ impl Database {
         limit: i64,
         offset: i64,
     ) -> Result<(Vec<Paste>, i64), DbError> {
-        let conn = self.connection.lock().unwrap();
+        let conn = self.conn("list_pastes_filtered");
 
         // Build dynamic WHERE clause
         let mut conditions: Vec<String> = Vec::new();
@@ -760,6 +3323,14 @@ impl Database {
             conditions.push("type = ?".to_string());
             bind_values.push(sqlite::Value::String(pt.clone()));
         }
+        if let Some(ref creator_hash) = params.creator_hash {
+            conditions.push("creator_hash = ?".to_string());
+            bind_values.push(sqlite::Value::String(creator_hash.to_ascii_lowercase()));
+        }
+        if let Some(visibility) = params.visibility {
+            conditions.push("visibility = ?".to_string());
+            bind_values.push(sqlite::Value::String(visibility.as_str().to_string()));
+        }
         if let Some(burn) = params.burn {
             conditions.push("burn_after_read = ?".to_string());
             bind_values.push(sqlite::Value::Integer(if burn { 1 } else { 0 }));
@@ -807,7 +3378,7 @@ impl Database {
             // "size" sorts by data length
             params.sort.as_str()
         } else if params.sort == "size" {
-            "LENGTH(data)"
+            DATA_BYTES
         } else {
             "created_at"
         };
@@ -825,14 +3396,14 @@ impl Database {
         }
         let total: i64 = count_stmt
             .into_iter()
-            .map(|row| row.unwrap().read::<i64, _>(0))
             .next()
-            .unwrap_or(0);
+            .transpose()?
+            .map_or(0, |row| row.read::<i64, _>(0));
 
         // Data query
         let data_sql = format!(
             "SELECT id, data, language, created_at, encryption_version, \
-             burn_after_read, expires_at, edit_key_hash, type \
+             burn_after_read, expires_at, edit_key_hash, type, pinned, visibility, encoding, compressed \
              FROM pastes {} ORDER BY {} {} LIMIT ? OFFSET ?",
             where_clause, sort_col, sort_dir
         );
@@ -849,9 +3420,9 @@ impl Database {
         for row in data_stmt.into_iter() {
             let row = row?;
             let id = row.read::<&str, _>("id").to_string();
-            let data = row.read::<&str, _>("data").to_string();
+            let data = stored_data(row["data"].clone(), row.read::<i64, _>("compressed"))?;
             let language = row.read::<&str, _>("language").to_string();
-            let created_at_ts = row.read::<i64, _>("created_at");
+            let created_at = stored_created_at(&row["created_at"]);
             let enc_ver = row.read::<i64, _>("encryption_version") as u8;
             let burn = row.read::<i64, _>("burn_after_read") != 0;
             let expires_ts = row.read::<Option<i64>, _>("expires_at");
@@ -859,12 +3430,14 @@ impl Database {
                 .read::<Option<&str>, _>("edit_key_hash")
                 .map(|s| s.to_string());
             let ptype = row.read::<&str, _>("type").to_string();
+            let pinned = row.read::<i64, _>("pinned") != 0;
+            let visibility = Visibility::from_stored(row.read::<&str, _>("visibility"));
+            let encoding = Encoding::from_stored(row.read::<&str, _>("encoding"));
 
-            let created_at = DateTime::from_timestamp(created_at_ts, 0)
-                .unwrap_or_else(|| Utc::now());
             let expires_at = expires_ts
-                .map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(|| Utc::now()));
+                .map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(Utc::now));
 
+            let theme_hint = self.theme_hint(&language);
             pastes.push(Paste {
                 id,
                 data,
@@ -876,6 +3449,19 @@ impl Database {
                 edit_key: None,
                 edit_key_hash: edit_hash,
                 paste_type: Some(ptype),
+                normalized: false,
+                tags: Vec::new(),
+                views: 0,
+                max_views: None,
+                url: None,
+                language_detected: None,
+                content_sha256: None,
+                server_sha256: None,
+                forked_from: None,
+                pinned,
+                visibility,
+                encoding,
+                theme_hint,
             });
         }
 
@@ -893,7 +3479,7 @@ impl Database {
         let mut not_found = Vec::new();
 
         for id in ids {
-            if self.delete_paste(id) {
+            if self.delete_paste(id)? {
                 deleted += 1;
             } else {
                 not_found.push(id.clone());
@@ -903,6 +3489,36 @@ impl Database {
         Ok((deleted, not_found))
     }
 
+    /// Delete the records with the given IDs in one transaction; either all of the
+    /// found ones go or none do. Returns the number removed; unknown IDs are skipped.
+    pub fn delete_many(&self, ids: &[String]) -> Result<usize, DbError> {
+        let conn = self.conn("delete_many");
+        conn.execute("BEGIN IMMEDIATE;")?;
+
+        let result = ids.iter().try_fold(0usize, |deleted, id| {
+            Ok::<_, DbError>(deleted + Self::delete_paste_locked(&conn, id)? as usize)
+        });
+
+        match result.and_then(|deleted| conn.execute("COMMIT;").map(|()| deleted).map_err(DbError::from)) {
+            Ok(deleted) => Ok(deleted),
+            Err(e) => {
+                let _ = conn.execute("ROLLBACK;");
+                Err(e)
+            }
+        }
+    }
+
+    /// Delete every unpinned record created before `before`, pastes and workspaces
+    /// alike, in a single statement. Returns the number removed.
+    pub fn delete_before(&self, before: DateTime<Utc>) -> Result<usize, DbError> {
+        let conn = self.conn("delete_before");
+        let mut stmt = conn.prepare("DELETE FROM pastes WHERE created_at < ? AND pinned = 0")?;
+        stmt.bind((1, before.timestamp()))?;
+        stmt.next()?;
+
+        Ok(conn.change_count())
+    }
+
     // ---- Workspace functions ----
 
     pub fn create_workspace(
@@ -913,122 +3529,135 @@ impl Database {
     ) -> Result<Paste, DbError> {
         // Check character limit
         let byte_count = Self::get_utf8_byte_count(&data);
-        if byte_count > MAX_PASTE_CHARACTERS {
-            return Err(DbError::CharacterLimitExceeded(byte_count, MAX_PASTE_CHARACTERS));
+        if byte_count > self.options.max_paste_bytes {
+            return Err(DbError::CharacterLimitExceeded(byte_count, self.options.max_paste_bytes));
         }
 
         if data.is_empty() {
             return Err(DbError::ClientEncryptionRequired);
         }
 
-        // Validate expiration time
-        let expires_at = if let Some(minutes) = expires_in_minutes {
-            if minutes == 0 || minutes > MAX_EXPIRES_IN_MINUTES {
-                return Err(DbError::CharacterLimitExceeded(minutes as usize, MAX_EXPIRES_IN_MINUTES as usize));
-            }
-            let expires_timestamp = Utc::now().timestamp() + (minutes as i64 * 60);
-            Some(expires_timestamp)
-        } else {
-            None
-        };
+        let expires_at = self.expiry_timestamp(expires_in_minutes)?;
 
         let id = self.generate_unique_id()?;
         let edit_key = Self::generate_id(32);
         let edit_key_hash = Self::hash_edit_key(&edit_key);
         let now = Utc::now();
 
-        let mut paste = self.store_client_encrypted_paste(
+        let mut paste = self.store_client_encrypted_paste(NewPaste {
             id,
             data,
-            "workspace".to_string(),
-            now,
+            language: "workspace".to_string(),
+            created_at: now,
+            encryption_version: ENCRYPTION_VERSION_CLIENT,
             edit_key_hash,
             burn_after_read,
             expires_at,
-            "workspace",
-        )?;
+            record_type: "workspace",
+            normalized: false,
+            tags: Vec::new(),
+            max_views: None,
+            language_detected: None,
+            content_sha256: None,
+            forked_from: None,
+            creator_hash: None,
+            visibility: Visibility::default(),
+            encoding: Encoding::default(),
+        })?;
 
         paste.edit_key = Some(edit_key);
         Ok(paste)
     }
 
-    pub fn get_workspace(&self, id: &str) -> Option<Paste> {
+    pub fn get_workspace(&self, id: &str) -> Result<Option<Paste>, DbError> {
         let (encrypted_data, created_at, burn_after_read, expires_at) = {
-            let conn = self.connection.lock().unwrap();
+            let conn = self.conn("get_workspace");
             let mut stmt = conn.prepare(
-                "SELECT data, created_at, encryption_version, burn_after_read, expires_at FROM pastes WHERE id = ? AND type = 'workspace'"
-            ).ok()?;
+                "SELECT data, created_at, encryption_version, burn_after_read, CASE WHEN pinned = 0 THEN expires_at END, compressed FROM pastes WHERE id = ? AND type = 'workspace'"
+            )?;
 
-            stmt.bind((1, id)).ok()?;
+            stmt.bind((1, id))?;
 
-            if let State::Row = stmt.next().ok()? {
-                let data = stmt.read::<String, _>(0).ok()?;
-                let created_at_ts = stmt.read::<i64, _>(1).ok()?;
+            if let State::Row = stmt.next()? {
+                let data = stored_data(stmt.read::<sqlite::Value, _>(0)?, stmt.read::<i64, _>(5)?)?;
+                let timestamp = stored_created_at(&stmt.read::<sqlite::Value, _>(1)?);
                 let encryption_version = stmt.read::<i64, _>(2).ok().unwrap_or(0) as u8;
                 let burn_after_read = stmt.read::<i64, _>(3).ok().unwrap_or(0) != 0;
                 let expires_at: Option<i64> = stmt.read::<Option<i64>, _>(4).ok().flatten();
 
                 if encryption_version != ENCRYPTION_VERSION_CLIENT {
-                    return None;
+                    return Ok(None);
                 }
 
-                let timestamp = DateTime::from_timestamp(created_at_ts, 0).unwrap_or_else(|| Utc::now());
                 (data, timestamp, burn_after_read, expires_at)
             } else {
-                return None;
+                return Ok(None);
             }
         };
 
         // Check if workspace has expired
-        if let Some(exp_ts) = expires_at {
-            if Utc::now().timestamp() > exp_ts {
-                self.delete_paste(id);
-                return None;
-            }
+        if let Some(exp_ts) = expires_at
+            && Utc::now().timestamp() > exp_ts
+        {
+            self.delete_paste(id)?;
+            return Ok(None);
         }
 
         // If burn_after_read is enabled, delete after reading
         if burn_after_read {
-            self.delete_paste(id);
+            self.delete_paste(id)?;
         }
 
-        Some(Paste {
+        Ok(Some(Paste {
             id: id.to_string(),
             data: encrypted_data,
             language: "workspace".to_string(),
             created_at,
             encryption_version: ENCRYPTION_VERSION_CLIENT,
             burn_after_read,
-            expires_at: expires_at.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(|| Utc::now())),
+            expires_at: expires_at.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(Utc::now)),
             edit_key: None,
             edit_key_hash: None,
             paste_type: None,
-        })
+            normalized: false,
+            tags: Vec::new(),
+            views: 0,
+            max_views: None,
+            url: None,
+            language_detected: None,
+            content_sha256: None,
+            server_sha256: None,
+            forked_from: None,
+            pinned: false,
+            visibility: Visibility::default(),
+            encoding: Encoding::default(),
+            theme_hint: None,
+        }))
     }
 
     pub fn update_workspace(&self, id: &str, data: String, edit_key: String) -> Result<Paste, DbError> {
         let byte_count = Self::get_utf8_byte_count(&data);
-        if byte_count > MAX_PASTE_CHARACTERS {
-            return Err(DbError::CharacterLimitExceeded(byte_count, MAX_PASTE_CHARACTERS));
+        if byte_count > self.options.max_paste_bytes {
+            return Err(DbError::CharacterLimitExceeded(byte_count, self.options.max_paste_bytes));
         }
 
         if data.is_empty() {
             return Err(DbError::ClientEncryptionRequired);
         }
 
-        let conn = self.connection.lock().unwrap();
+        let conn = self.conn("update_workspace");
 
         let mut stmt = conn.prepare("SELECT edit_key_hash, created_at, burn_after_read, expires_at FROM pastes WHERE id = ? AND type = 'workspace'")?;
         stmt.bind((1, id))?;
 
         let (stored_hash, created_at, burn_after_read, expires_at) = if let State::Row = stmt.next()? {
             let hash: Option<String> = stmt.read::<Option<String>, _>(0).ok().flatten();
-            let created_at = stmt.read::<i64, _>(1).unwrap_or(0);
+            let created_at = stmt.read::<sqlite::Value, _>(1).unwrap_or_default();
             let burn_after_read = stmt.read::<i64, _>(2).unwrap_or(0) != 0;
             let expires_at_ts = stmt.read::<Option<i64>, _>(3).unwrap_or(None);
 
-            let timestamp = DateTime::from_timestamp(created_at, 0).unwrap_or_else(|| Utc::now());
-            let expires_at = expires_at_ts.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(|| Utc::now()));
+            let timestamp = stored_created_at(&created_at);
+            let expires_at = expires_at_ts.map(|ts| DateTime::from_timestamp(ts, 0).unwrap_or_else(Utc::now));
 
             match hash {
                 Some(h) if !h.is_empty() => (h, timestamp, burn_after_read, expires_at),
@@ -1042,9 +3671,11 @@ impl Database {
             return Err(DbError::InvalidEditKey);
         }
 
-        let mut update_stmt = conn.prepare("UPDATE pastes SET data = ? WHERE id = ? AND type = 'workspace'")?;
-        update_stmt.bind((1, data.as_str()))?;
-        update_stmt.bind((2, id))?;
+        let (stored, compressed) = data_to_store(&data, self.options.compress_over);
+        let mut update_stmt = conn.prepare("UPDATE pastes SET data = ?, compressed = ? WHERE id = ? AND type = 'workspace'")?;
+        update_stmt.bind((1, &stored))?;
+        update_stmt.bind((2, compressed))?;
+        update_stmt.bind((3, id))?;
         update_stmt.next()?;
 
         Ok(Paste {
@@ -1058,11 +3689,24 @@ impl Database {
             edit_key: None,
             edit_key_hash: None,
             paste_type: None,
+            normalized: false,
+            tags: Vec::new(),
+            views: 0,
+            max_views: None,
+            url: None,
+            language_detected: None,
+            content_sha256: None,
+            server_sha256: None,
+            forked_from: None,
+            pinned: false,
+            visibility: Visibility::default(),
+            encoding: Encoding::default(),
+            theme_hint: None,
         })
     }
 
     pub fn delete_workspace_with_key(&self, id: &str, edit_key: &str) -> Result<(), DbError> {
-        let conn = self.connection.lock().unwrap();
+        let conn = self.conn("delete_workspace_with_key");
 
         let mut stmt = conn.prepare("SELECT edit_key_hash FROM pastes WHERE id = ? AND type = 'workspace'")?;
         stmt.bind((1, id))?;
@@ -1083,4 +3727,964 @@ impl Database {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    // Drop the pastes table, so every paste query fails like it would on a broken
+    // database
+    pub(crate) fn break_pastes_table(db: &Database) {
+        db.conn("test").execute("PRAGMA foreign_keys = OFF; DROP TABLE pastes;").unwrap();
+    }
+
+    // Move a paste's expiry into the past
+    pub(crate) fn expire_paste(db: &Database, id: &str) {
+        let conn = db.conn("test");
+        let mut stmt = conn.prepare("UPDATE pastes SET expires_at = ? WHERE id = ?").unwrap();
+        stmt.bind((1, Utc::now().timestamp() - 60)).unwrap();
+        stmt.bind((2, id)).unwrap();
+        stmt.next().unwrap();
+    }
+
+    // Move a paste's creation time `secs` into the past
+    pub(crate) fn backdate_paste(db: &Database, id: &str, secs: i64) {
+        let conn = db.conn("test");
+        let mut stmt = conn.prepare("UPDATE pastes SET created_at = created_at - ? WHERE id = ?").unwrap();
+        stmt.bind((1, secs)).unwrap();
+        stmt.bind((2, id)).unwrap();
+        stmt.next().unwrap();
+    }
+
+    // Move a trashed paste's deletion time `secs` into the past
+    pub(crate) fn backdate_trashed_paste(db: &Database, id: &str, secs: i64) {
+        let conn = db.conn("test");
+        let mut stmt = conn.prepare("UPDATE pastes SET deleted_at = deleted_at - ? WHERE id = ?").unwrap();
+        stmt.bind((1, secs)).unwrap();
+        stmt.bind((2, id)).unwrap();
+        stmt.next().unwrap();
+    }
+
+    // Every value stored in every table, for checking what was persisted
+    pub(crate) fn stored_values(db: &Database) -> Vec<sqlite::Value> {
+        let conn = db.conn("test");
+        let mut tables = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table'").unwrap();
+        let mut values = Vec::new();
+        while let State::Row = tables.next().unwrap() {
+            let table = tables.read::<String, _>(0).unwrap();
+            let mut rows = conn.prepare(format!("SELECT * FROM \"{}\"", table)).unwrap();
+            while let State::Row = rows.next().unwrap() {
+                values.extend((0..rows.column_count()).map(|column| rows.read::<sqlite::Value, _>(column).unwrap()));
+            }
+        }
+        values
+    }
+
+    fn test_db() -> Database {
+        test_db_with(|_| {})
+    }
+
+    fn test_db_with(configure: impl FnOnce(&mut PasteOptions)) -> Database {
+        let mut options = Config::default().paste_options();
+        options.allow_plaintext = true;
+        configure(&mut options);
+        Database::in_memory(options).unwrap()
+    }
+
+    fn plaintext(data: &str, extra: serde_json::Value) -> CreatePasteData {
+        let mut body = serde_json::json!({ "data": data, "encryption_version": 0 });
+        body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn bom_is_stripped_when_enabled() {
+        let db = test_db_with(|options| options.strip_bom = true);
+        let paste = db.create_paste(plaintext("\u{feff}hello", serde_json::json!({}))).unwrap();
+        assert!(paste.normalized);
+
+        let stored = db.get_paste(&paste.id).unwrap().unwrap();
+        assert_eq!(stored.data, "hello");
+        assert!(stored.normalized);
+    }
+
+    #[test]
+    fn bom_is_kept_when_disabled() {
+        let db = test_db_with(|options| options.strip_bom = false);
+        let paste = db.create_paste(plaintext("\u{feff}hello", serde_json::json!({}))).unwrap();
+        assert!(!paste.normalized);
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().data, "\u{feff}hello");
+    }
+
+    #[test]
+    fn failed_tag_insert_stores_nothing() {
+        let db = test_db();
+        db.conn("test")
+            .execute("CREATE TRIGGER reject_tags BEFORE INSERT ON paste_tags BEGIN SELECT RAISE(ABORT, 'no tags'); END;")
+            .unwrap();
+
+        assert!(db.create_paste(plaintext("tagged", serde_json::json!({ "tags": ["rust"] }))).is_err());
+        assert_eq!(Database::count_records(&db.conn("test")).unwrap(), 0);
+    }
+
+    #[test]
+    fn concurrent_opens_of_one_file_both_succeed() {
+        let dir = std::env::temp_dir().join(format!("rustybin-test-{}", uuid::Uuid::new_v4()));
+        let opens: Vec<_> = (0..2)
+            .map(|_| {
+                let dir = dir.clone();
+                std::thread::spawn(move || Database::new_with_path(&dir, Config::default().paste_options()))
+            })
+            .collect();
+
+        for open in opens {
+            assert!(open.join().unwrap().is_ok());
+        }
+        let conn = Connection::open(Database::database_path(&dir)).unwrap();
+        let mut stmt = conn.prepare("PRAGMA user_version;").unwrap();
+        stmt.next().unwrap();
+        assert_eq!(stmt.read::<i64, _>(0).unwrap() as u32, crate::migrations::latest_version());
+        drop(stmt);
+        drop(conn);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn database_failures_are_errors_not_panics() {
+        let db = test_db();
+        let paste = db.create_paste(plaintext("hello", serde_json::json!({}))).unwrap();
+        break_pastes_table(&db);
+
+        assert!(matches!(db.create_paste(plaintext("again", serde_json::json!({}))), Err(DbError::Sqlite(_))));
+        assert!(matches!(db.get_paste(&paste.id), Err(DbError::Sqlite(_))));
+        assert!(matches!(db.delete_paste(&paste.id), Err(DbError::Sqlite(_))));
+    }
+
+    #[test]
+    fn paste_is_gone_after_max_views() {
+        let db = test_db();
+        let paste = db.create_paste(plaintext("twice", serde_json::json!({ "max_views": 2 }))).unwrap();
+
+        for views in 1..=2 {
+            let read = db.get_paste(&paste.id).unwrap().unwrap();
+            assert_eq!(read.data, "twice");
+            assert_eq!(read.views, views);
+        }
+        assert!(db.get_paste(&paste.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn created_at_round_trips_as_an_integer() {
+        let db = test_db();
+        let data = serde_json::from_value(serde_json::json!({ "data": "ciphertext" })).unwrap();
+        let paste = db.create_paste(data).unwrap();
+
+        let (_, _, created_at) = db.get_encrypted_paste(&paste.id).unwrap().unwrap();
+        assert_eq!(created_at, paste.created_at);
+
+        let conn = db.conn("test");
+        let mut stmt = conn.prepare("SELECT typeof(created_at) FROM pastes WHERE id = ?").unwrap();
+        stmt.bind((1, paste.id.as_str())).unwrap();
+        stmt.next().unwrap();
+        assert_eq!(stmt.read::<String, _>(0).unwrap(), "integer");
+    }
+
+    #[test]
+    fn duplicate_id_insert_is_an_id_conflict() {
+        let db = test_db();
+        // Both pass the availability check before either is stored, like a race
+        let (first, _) = db.prepare_paste(plaintext("one", serde_json::json!({})), Some("racedid".to_string())).unwrap();
+        let (second, _) = db.prepare_paste(plaintext("two", serde_json::json!({})), Some("racedid".to_string())).unwrap();
+
+        db.store_client_encrypted_paste(first).unwrap();
+        assert!(matches!(db.store_client_encrypted_paste(second), Err(DbError::IdConflict)));
+        assert_eq!(db.get_paste("racedid").unwrap().unwrap().data, "one");
+    }
+
+    #[test]
+    fn generated_ids_have_the_configured_length_and_charset() {
+        let db = test_db_with(|options| {
+            options.id_length = MAX_ID_LENGTH;
+            options.id_charset = IdCharset::UrlSafe;
+        });
+        for _ in 0..20 {
+            let paste = db.create_paste(plaintext("hello", serde_json::json!({}))).unwrap();
+            assert_eq!(paste.id.len(), MAX_ID_LENGTH);
+            assert!(paste.id.bytes().all(|b| URL_SAFE_ID_CHARS.contains(&b)), "{}", paste.id);
+            assert!(db.is_valid_id(&paste.id));
+        }
+
+        let db = test_db_with(|options| options.id_length = MIN_ID_LENGTH);
+        let paste = db.create_paste(plaintext("hello", serde_json::json!({}))).unwrap();
+        assert_eq!(paste.id.len(), MIN_ID_LENGTH);
+        assert!(paste.id.chars().all(|c| c.is_ascii_alphanumeric()), "{}", paste.id);
+    }
+
+    #[test]
+    fn id_validation_follows_the_length_range_and_charset() {
+        let alphanumeric = test_db();
+        let url_safe = test_db_with(|options| options.id_charset = IdCharset::UrlSafe);
+
+        // Every length generation can reach, including retry growth past MAX_ID_LENGTH
+        for len in [MIN_ID_LENGTH, DEFAULT_ID_LENGTH, MAX_ID_LENGTH, MAX_GENERATED_ID_LENGTH] {
+            assert!(alphanumeric.is_valid_id(&"a".repeat(len)), "length {}", len);
+        }
+        assert!(!alphanumeric.is_valid_id(&"a".repeat(MIN_ID_LENGTH - 1)));
+        assert!(!alphanumeric.is_valid_id(&"a".repeat(MAX_GENERATED_ID_LENGTH + 1)));
+
+        assert!(!alphanumeric.is_valid_id("abc-_def"));
+        assert!(url_safe.is_valid_id("abc-_def"));
+        for id in ["abcdéf", "abc/def", "abc.def"] {
+            assert!(!url_safe.is_valid_id(id), "{}", id);
+        }
+    }
+
+    #[test]
+    fn idempotent_create_replays_the_original_paste() {
+        let db = test_db();
+        let create = || plaintext("once", serde_json::json!({ "tags": ["retry"] }));
+
+        let IdempotentCreate::Created(first) = db.create_paste_idempotent(create(), "client", "key-1").unwrap() else {
+            panic!("first request should create");
+        };
+        let IdempotentCreate::Replayed(replay) = db.create_paste_idempotent(create(), "client", "key-1").unwrap() else {
+            panic!("repeated request should replay");
+        };
+        assert_eq!(replay.id, first.id);
+        assert!(first.edit_key.is_some());
+        assert_eq!(replay.edit_key, None);
+        assert_eq!(replay.created_at, first.created_at);
+        assert_eq!(Database::count_records(&db.conn("test")).unwrap(), 1);
+
+        // The same key from another client is a different create
+        let other = db.create_paste_idempotent(create(), "other-client", "key-1").unwrap();
+        assert!(matches!(other, IdempotentCreate::Created(paste) if paste.id != first.id));
+    }
+
+    #[test]
+    fn idempotency_keys_dont_store_the_edit_key() {
+        let db = test_db();
+        let IdempotentCreate::Created(paste) = db.create_paste_idempotent(plaintext("secret", serde_json::json!({})), "client", "key-1").unwrap() else {
+            panic!("first request should create");
+        };
+        let edit_key = paste.edit_key.unwrap();
+
+        let conn = db.conn("test");
+        let mut stmt = conn.prepare("SELECT response FROM idempotency_keys WHERE scope = 'client' AND key = 'key-1'").unwrap();
+        assert_eq!(stmt.next().unwrap(), State::Row);
+        let response = stmt.read::<String, _>(0).unwrap();
+        assert!(response.contains(&paste.id), "{}", response);
+        assert!(!response.contains(&edit_key), "{}", response);
+        assert!(!response.contains("edit_key"), "{}", response);
+    }
+
+    #[test]
+    fn idempotency_key_reused_for_a_different_request_is_refused() {
+        let db = test_db();
+        db.create_paste_idempotent(plaintext("one", serde_json::json!({})), "client", "key-1").unwrap();
+
+        let reused = db.create_paste_idempotent(plaintext("two", serde_json::json!({})), "client", "key-1");
+        assert!(matches!(reused, Err(DbError::IdempotencyKeyReused)));
+    }
+
+    #[test]
+    fn diff_of_two_plaintext_pastes() {
+        let db = test_db();
+        let old = db.create_paste(plaintext("a\nb\n", serde_json::json!({}))).unwrap();
+        let new = db.create_paste(plaintext("a\nc\n", serde_json::json!({}))).unwrap();
+
+        let diff = db.diff_pastes(&old.id, &new.id).unwrap().unwrap();
+        assert!(diff.unified.contains("-b\n+c\n"), "{}", diff.unified);
+        assert_eq!((diff.added, diff.removed), (1, 1));
+        assert!(db.diff_pastes(&old.id, "doesnotexist").unwrap().is_none());
+    }
+
+    #[test]
+    fn diff_refuses_encrypted_pastes() {
+        let db = test_db();
+        let plain = db.create_paste(plaintext("a\n", serde_json::json!({}))).unwrap();
+        let encrypted = db.create_paste(serde_json::from_value(serde_json::json!({ "data": "ciphertext" })).unwrap()).unwrap();
+
+        assert!(matches!(db.diff_pastes(&plain.id, &encrypted.id), Err(DbError::DiffOfEncrypted)));
+    }
+
+    #[test]
+    fn poisoned_lock_recovers() {
+        let db = test_db();
+        let paste = db.create_paste(plaintext("hello", serde_json::json!({}))).unwrap();
+
+        let connection = Arc::clone(&db.connection);
+        std::thread::spawn(move || {
+            let _guard = connection.lock().unwrap();
+            panic!("poison the connection lock");
+        })
+        .join()
+        .unwrap_err();
+        assert!(db.connection.is_poisoned());
+
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().data, "hello");
+        db.create_paste(plaintext("again", serde_json::json!({}))).unwrap();
+        assert!(!db.connection.is_poisoned());
+    }
+
+    #[test]
+    fn public_and_unlisted_pastes_are_readable_without_a_key() {
+        let db = test_db();
+        for visibility in ["public", "unlisted"] {
+            let paste = db.create_paste(plaintext("hello", serde_json::json!({ "visibility": visibility }))).unwrap();
+            db.check_read_access(&paste.id, None).unwrap();
+            assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().data, "hello");
+        }
+    }
+
+    #[test]
+    fn private_paste_needs_its_edit_key() {
+        let db = test_db();
+        let paste = db.create_paste(plaintext("secret", serde_json::json!({ "visibility": "private" }))).unwrap();
+        let edit_key = paste.edit_key.unwrap();
+
+        assert!(matches!(db.check_read_access(&paste.id, None), Err(DbError::PastePrivate)));
+        assert!(matches!(db.check_read_access(&paste.id, Some("wrong")), Err(DbError::PastePrivate)));
+        db.check_read_access(&paste.id, Some(&edit_key)).unwrap();
+    }
+
+    #[test]
+    fn missing_paste_passes_read_access() {
+        let db = test_db();
+        db.check_read_access("doesnotexist", None).unwrap();
+        assert!(db.get_paste("doesnotexist").unwrap().is_none());
+    }
+
+    #[test]
+    fn tag_listing_shows_only_public_pastes() {
+        let db = test_db();
+        let mut ids = HashMap::new();
+        for visibility in ["public", "unlisted", "private"] {
+            let paste = db
+                .create_paste(plaintext("tagged", serde_json::json!({ "visibility": visibility, "tags": ["rust"] })))
+                .unwrap();
+            ids.insert(visibility, paste.id);
+        }
+
+        let listed: Vec<String> = db.list_pastes_by_tag("rust").unwrap().into_iter().map(|paste| paste.id).collect();
+        assert_eq!(listed, vec![ids["public"].clone()]);
+    }
+
+    #[test]
+    fn tags_are_lowercased_deduplicated_and_stored() {
+        let db = test_db();
+        let paste = db
+            .create_paste(plaintext("tagged", serde_json::json!({ "tags": [" Rust ", "rust", "web-dev", "RUST", "snake_case"] })))
+            .unwrap();
+        assert_eq!(paste.tags, ["rust", "web-dev", "snake_case"]);
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().tags, ["rust", "snake_case", "web-dev"]);
+
+        // Duplicates don't count against the limit
+        let tags = vec!["same"; 10];
+        assert!(db.create_paste(plaintext("tagged", serde_json::json!({ "tags": tags }))).is_ok());
+    }
+
+    #[test]
+    fn invalid_tags_and_too_many_tags_are_refused() {
+        let db = test_db_with(|options| options.max_tags = 2);
+        let refusal = |tags: serde_json::Value| match db.create_paste(plaintext("tagged", serde_json::json!({ "tags": tags }))) {
+            Err(DbError::ValidationFailed(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].field, "tags");
+                errors.into_iter().next().unwrap().error
+            }
+            other => panic!("expected a tags failure, got {:?}", other),
+        };
+
+        let too_long = "a".repeat(MAX_TAG_LENGTH + 1);
+        for tag in ["", "   ", "has space", "slash/tag", "ünïcode", too_long.as_str()] {
+            assert!(matches!(refusal(serde_json::json!([tag])), DbError::InvalidTag(_)), "{:?}", tag);
+        }
+        assert!(db.create_paste(plaintext("tagged", serde_json::json!({ "tags": ["a".repeat(MAX_TAG_LENGTH)] }))).is_ok());
+
+        assert!(matches!(refusal(serde_json::json!(["a", "b", "c"])), DbError::TooManyTags(3, 2)));
+        assert_eq!(Database::count_records(&db.conn("test")).unwrap(), 1);
+    }
+
+    #[test]
+    fn tag_listing_skips_burned_expired_and_trashed_pastes() {
+        let db = test_db_with(|options| options.trash_retention_secs = 3600);
+        let create = |extra: serde_json::Value| {
+            let mut extra = extra;
+            extra["visibility"] = "public".into();
+            extra["tags"] = serde_json::json!(["rust"]);
+            db.create_paste(plaintext("tagged", extra)).unwrap()
+        };
+
+        let older = create(serde_json::json!({}));
+        let newer = create(serde_json::json!({}));
+        {
+            // Creation times are in seconds, so set them apart
+            let conn = db.conn("test");
+            conn.execute(format!("UPDATE pastes SET created_at = created_at - 60 WHERE id = '{}'", older.id)).unwrap();
+        }
+        create(serde_json::json!({ "burn_after_read": true }));
+        let expired = create(serde_json::json!({ "expires_in_minutes": 10 }));
+        db.conn("test").execute(format!("UPDATE pastes SET expires_at = 1 WHERE id = '{}'", expired.id)).unwrap();
+        let trashed = create(serde_json::json!({}));
+        db.delete_paste_with_key(&trashed.id, DeletePasteData { edit_key: trashed.edit_key.clone().unwrap() }).unwrap();
+        db.create_paste(plaintext("other", serde_json::json!({ "visibility": "public", "tags": ["go"] }))).unwrap();
+
+        let listed: Vec<String> = db.list_pastes_by_tag("rust").unwrap().into_iter().map(|paste| paste.id).collect();
+        assert_eq!(listed, [newer.id, older.id], "newest first");
+        assert!(db.list_pastes_by_tag("python").unwrap().is_empty());
+    }
+
+    // The `compressed` column of a paste and the type SQLite stored its data as
+    fn storage_of(db: &Database, id: &str) -> (i64, String) {
+        let conn = db.conn("test");
+        let mut stmt = conn.prepare("SELECT compressed, typeof(data) FROM pastes WHERE id = ?").unwrap();
+        stmt.bind((1, id)).unwrap();
+        assert_eq!(stmt.next().unwrap(), State::Row);
+        (stmt.read::<i64, _>(0).unwrap(), stmt.read::<String, _>(1).unwrap())
+    }
+
+    fn log_lines(count: usize) -> String {
+        (0..count).map(|line| format!("{line}: GET /v1/pastes/abc123 200\n")).collect()
+    }
+
+    #[test]
+    fn compressed_and_verbatim_pastes_read_back_identically() {
+        let db = test_db_with(|options| options.compress_over = 1024);
+        let large = log_lines(2_000);
+        for (data, compressed) in [("short paste\n", false), (large.as_str(), true)] {
+            let paste = db.create_paste(plaintext(data, serde_json::json!({}))).unwrap();
+            let (stored_len, stored_type) = storage_of(&db, &paste.id);
+            if compressed {
+                assert_eq!((stored_len, stored_type.as_str()), (data.len() as i64, "blob"));
+            } else {
+                assert_eq!((stored_len, stored_type.as_str()), (0, "text"));
+            }
+
+            assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().data, data);
+            assert!(matches!(db.get_raw_paste(&paste.id).unwrap().unwrap().0, RawData::Whole(raw) if raw == data));
+            assert_eq!(db.get_paste_meta(&paste.id).unwrap().unwrap().size_bytes, data.len() as u64);
+        }
+
+        let mut exported: Vec<String> = db.export_all().unwrap().into_iter().map(|record| record.data).collect();
+        exported.sort_by_key(String::len);
+        assert!(exported == ["short paste\n".to_string(), large.clone()], "exports carry the decompressed data");
+
+        let copy = test_db_with(|options| options.compress_over = 1024);
+        copy.import(db.export_all().unwrap(), ImportConflict::Error).unwrap();
+        let (id, data) = copy
+            .export_all()
+            .unwrap()
+            .into_iter()
+            .map(|record| (record.id, record.data))
+            .max_by_key(|(_, data)| data.len())
+            .unwrap();
+        assert_eq!(storage_of(&copy, &id).1, "blob");
+        assert_eq!(data, large);
+    }
+
+    #[test]
+    fn encrypted_pastes_decompress_for_the_client() {
+        let db = test_db_with(|options| options.compress_over = 1024);
+        let ciphertext = "c2VjcmV0".repeat(1_000);
+        let paste = db.create_paste(serde_json::from_value(serde_json::json!({ "data": ciphertext })).unwrap()).unwrap();
+        assert_eq!(storage_of(&db, &paste.id).1, "blob");
+        assert_eq!(db.get_encrypted_paste(&paste.id).unwrap().unwrap().0, ciphertext);
+
+        // Ciphertext without repeats doesn't shrink, so it stays as it came
+        let mut state = 0x2545_F491u32;
+        let noise: Vec<u8> = (0..3_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let noise = BASE64.encode(noise);
+        let paste = db.create_paste(serde_json::from_value(serde_json::json!({ "data": noise })).unwrap()).unwrap();
+        assert_eq!(storage_of(&db, &paste.id), (0, "text".to_string()));
+        assert_eq!(db.get_encrypted_paste(&paste.id).unwrap().unwrap().0, noise);
+    }
+
+    #[test]
+    fn updates_and_appends_recompress() {
+        let db = test_db_with(|options| options.compress_over = 1024);
+        let paste = db.create_paste(plaintext("start\n", serde_json::json!({}))).unwrap();
+        let edit_key = paste.edit_key.unwrap();
+
+        let appended = log_lines(100);
+        db.append_paste(&paste.id, AppendPasteData { data: appended.clone(), edit_key: edit_key.clone() }).unwrap();
+        assert_eq!(storage_of(&db, &paste.id).1, "blob");
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().data, format!("start\n{appended}"));
+
+        let update = UpdatePasteData { data: "small again".to_string(), language: "plaintext".to_string(), edit_key, content_sha256: None };
+        db.update_paste(&paste.id, update).unwrap();
+        assert_eq!(storage_of(&db, &paste.id), (0, "text".to_string()));
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().data, "small again");
+    }
+
+    #[test]
+    fn compression_off_stores_verbatim() {
+        let db = test_db();
+        let paste = db.create_paste(plaintext(&log_lines(2_000), serde_json::json!({}))).unwrap();
+        assert_eq!(storage_of(&db, &paste.id), (0, "text".to_string()));
+    }
+
+    #[test]
+    fn corrupt_compressed_data_is_an_error() {
+        let db = test_db_with(|options| options.compress_over = 1024);
+        let paste = db.create_paste(plaintext(&log_lines(2_000), serde_json::json!({}))).unwrap();
+        db.conn("test").execute(format!("UPDATE pastes SET data = X'FF00' WHERE id = '{}'", paste.id)).unwrap();
+        assert!(matches!(db.get_paste(&paste.id), Err(DbError::CorruptData)));
+    }
+
+    #[test]
+    fn batch_create_stores_every_paste_in_order() {
+        let db = test_db();
+        let items = ["first", "second", "third"].into_iter().map(|data| plaintext(data, serde_json::json!({}))).collect();
+        let pastes = db.create_pastes_batch(items).unwrap();
+
+        let data: Vec<_> = pastes.iter().map(|paste| db.get_paste(&paste.id).unwrap().unwrap().data).collect();
+        assert_eq!(data, ["first", "second", "third"]);
+        assert!(pastes.iter().all(|paste| paste.edit_key.is_some()));
+    }
+
+    #[test]
+    fn batch_create_stores_nothing_when_an_item_fails() {
+        let db = test_db_with(|options| options.max_paste_bytes = 16);
+        let items = vec![plaintext("fits", serde_json::json!({})), plaintext(&"x".repeat(17), serde_json::json!({}))];
+        let err = db.create_pastes_batch(items).unwrap_err();
+        assert!(matches!(err, DbError::BatchItemFailed(1, _)), "{}", err);
+        assert_eq!(Database::count_records(&db.conn("test")).unwrap(), 0);
+
+        // An insert failing after earlier items went in rolls those back too
+        db.conn("test")
+            .execute(
+                "CREATE TRIGGER fail_insert BEFORE INSERT ON pastes WHEN NEW.language = 'rust'
+                 BEGIN SELECT RAISE(ABORT, 'insert failed'); END;",
+            )
+            .unwrap();
+        let items = vec![
+            plaintext("one", serde_json::json!({})),
+            plaintext("two", serde_json::json!({})),
+            plaintext("three", serde_json::json!({ "language": "rust" })),
+        ];
+        let err = db.create_pastes_batch(items).unwrap_err();
+        assert!(matches!(err, DbError::BatchItemFailed(2, _)), "{}", err);
+        assert_eq!(Database::count_records(&db.conn("test")).unwrap(), 0);
+    }
+
+    #[test]
+    fn storage_stats_total_every_stored_paste() {
+        let db = test_db();
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 0);
+        assert_eq!(db.storage_stats().unwrap().oldest_created_at, None);
+
+        let pastes = [
+            db.create_paste(plaintext("hello", serde_json::json!({}))).unwrap(),
+            db.create_paste(plaintext("hello world", serde_json::json!({}))).unwrap(),
+            db.create_paste(serde_json::from_value(serde_json::json!({ "data": "Y2lwaGVy" })).unwrap()).unwrap(),
+        ];
+        for (paste, created_at) in pastes.iter().zip([1_700_000_300, 1_700_000_100, 1_700_000_200]) {
+            db.conn("test")
+                .execute(format!("UPDATE pastes SET created_at = {} WHERE id = '{}'", created_at, paste.id))
+                .unwrap();
+        }
+
+        let stats = db.storage_stats().unwrap();
+        assert_eq!(stats.total_pastes, 3);
+        assert_eq!(stats.total_bytes, (5 + 11 + 8) as i64);
+        assert_eq!(stats.oldest_created_at, Some(1_700_000_100));
+        assert_eq!(stats.newest_created_at, Some(1_700_000_300));
+        assert_eq!(stats.by_encryption_version, std::collections::HashMap::from([(0, 2), (1, 1)]));
+    }
+
+    #[test]
+    fn pastes_persist_across_a_reopen_of_a_custom_data_dir() {
+        let root = std::env::temp_dir().join(format!("rustybin-test-{}", uuid::Uuid::new_v4()));
+        let dir = root.join("mounted").join("volume");
+        let options = || {
+            let mut options = Config::default().paste_options();
+            options.allow_plaintext = true;
+            options
+        };
+
+        let db = Database::new_with_path(&dir, options()).unwrap();
+        let paste = db.create_paste(plaintext("kept", serde_json::json!({}))).unwrap();
+        assert!(Database::database_path(&dir).is_file(), "the missing directory is created");
+        for sidecar in ["pastes.db-wal", "pastes.db-shm"] {
+            assert!(dir.join(sidecar).is_file(), "{} is next to the database", sidecar);
+        }
+        drop(db);
+
+        let db = Database::new_with_path(&dir, options()).unwrap();
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().data, "kept");
+        drop(db);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn checkpoints_report_frames_and_truncate_the_wal() {
+        let dir = std::env::temp_dir().join(format!("rustybin-test-{}", uuid::Uuid::new_v4()));
+        let mut options = Config::default().paste_options();
+        options.allow_plaintext = true;
+        let db = Database::new_with_path(&dir, options).unwrap();
+        for line in 0..200 {
+            db.create_paste(plaintext(&format!("paste {line}"), serde_json::json!({}))).unwrap();
+        }
+        let wal = dir.join("pastes.db-wal");
+        assert!(fs::metadata(&wal).unwrap().len() > 0);
+
+        let passive = db.checkpoint(CheckpointMode::Passive).unwrap();
+        assert!(!passive.busy);
+        assert!(passive.log_frames > 0);
+        assert_eq!(passive.checkpointed_frames, passive.log_frames, "nothing else holds the WAL");
+
+        let truncate = db.checkpoint(CheckpointMode::Truncate).unwrap();
+        assert_eq!((truncate.busy, truncate.log_frames, truncate.checkpointed_frames), (false, 0, 0));
+        assert_eq!(fs::metadata(&wal).unwrap().len(), 0);
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 200);
+
+        drop(db);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn deleted_pastes_restore_within_the_window_only() {
+        let db = test_db_with(|options| options.trash_retention_secs = 3600);
+        let paste = db.create_paste(plaintext("oops", serde_json::json!({}))).unwrap();
+        let key = || DeletePasteData { edit_key: paste.edit_key.clone().unwrap() };
+
+        db.delete_paste_with_key(&paste.id, key()).unwrap();
+        assert!(matches!(db.get_paste(&paste.id), Err(DbError::PasteTrashed)));
+        let wrong = DeletePasteData { edit_key: "wrong".to_string() };
+        assert!(matches!(db.restore_paste_with_key(&paste.id, wrong), Err(DbError::InvalidEditKey)));
+        db.restore_paste_with_key(&paste.id, key()).unwrap();
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().data, "oops");
+        assert!(matches!(db.restore_paste_with_key(&paste.id, key()), Err(DbError::PasteNotFound)), "not in the trash");
+
+        // Deleted two hours ago, past the one hour window
+        db.delete_paste_with_key(&paste.id, key()).unwrap();
+        db.conn("test").execute(format!("UPDATE pastes SET deleted_at = deleted_at - 7200 WHERE id = '{}'", paste.id)).unwrap();
+        assert!(matches!(db.restore_paste_with_key(&paste.id, key()), Err(DbError::PasteNotFound)));
+        assert_eq!(db.purge_expired().unwrap().trashed, 1);
+        assert!(db.get_paste(&paste.id).unwrap().is_none(), "the purge deleted it for good");
+    }
+
+    #[test]
+    fn without_a_trash_window_deletes_are_final() {
+        let db = test_db();
+        let paste = db.create_paste(plaintext("gone", serde_json::json!({}))).unwrap();
+        let key = || DeletePasteData { edit_key: paste.edit_key.clone().unwrap() };
+
+        db.delete_paste_with_key(&paste.id, key()).unwrap();
+        assert!(db.get_paste(&paste.id).unwrap().is_none());
+        assert!(matches!(db.restore_paste_with_key(&paste.id, key()), Err(DbError::PasteNotFound)));
+    }
+
+    #[test]
+    fn creates_past_the_cap_evict_the_oldest_unpinned_pastes() {
+        let db = test_db_with(|options| options.max_total_pastes = 3);
+        let create = |data: &str| db.create_paste(plaintext(data, serde_json::json!({}))).unwrap().id;
+        let ids: Vec<String> = ["pinned", "old", "older"].into_iter().map(create).collect();
+        for (id, created_at) in ids.iter().zip([100, 300, 200]) {
+            db.conn("test").execute(format!("UPDATE pastes SET created_at = {} WHERE id = '{}'", created_at, id)).unwrap();
+        }
+        db.set_pinned(&ids[0], true).unwrap();
+        let exists = |id: &str| db.paste_exists(id).unwrap();
+
+        let newer = create("newer");
+        assert!(!exists(&ids[2]), "the oldest unpinned paste went first");
+        assert!(exists(&ids[0]) && exists(&ids[1]) && exists(&newer));
+
+        let newest = create("newest");
+        assert!(!exists(&ids[1]));
+        assert!(exists(&ids[0]) && exists(&newer) && exists(&newest));
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 3);
+
+        assert_eq!(db.evict_oldest(10).unwrap(), 2, "pinned pastes are never evicted");
+        assert!(exists(&ids[0]));
+    }
+
+    #[test]
+    fn auto_language_is_detected_for_readable_plaintext_only() {
+        let db = test_db();
+        let create = |data: &str, extra: serde_json::Value| {
+            let paste = db.create_paste(plaintext(data, extra)).unwrap();
+            (paste.language, paste.language_detected)
+        };
+
+        assert_eq!(create("#!/usr/bin/env python3\nprint(1)\n", serde_json::json!({ "language": "auto" })), ("python".to_string(), Some(true)));
+        assert_eq!(create("{\"a\": [1, 2]}", serde_json::json!({ "language": "" })), ("json".to_string(), Some(true)));
+        assert_eq!(create("just notes", serde_json::json!({ "language": "auto" })), ("plaintext".to_string(), Some(false)));
+        assert_eq!(create("#!/bin/sh\n", serde_json::json!({ "language": "ruby" })), ("ruby".to_string(), None), "an explicit language is kept");
+
+        let encrypted = serde_json::json!({ "data": "IyEvYmluL3No", "language": "auto" });
+        let paste = db.create_paste(serde_json::from_value(encrypted).unwrap()).unwrap();
+        assert_eq!((paste.language.as_str(), paste.language_detected), ("plaintext", Some(false)), "ciphertext can't be inspected");
+    }
+
+    #[test]
+    fn buffered_views_are_reported_and_persist_once_flushed() {
+        let dir = std::env::temp_dir().join(format!("rustybin-test-{}", uuid::Uuid::new_v4()));
+        let options = || {
+            let mut options = Config::default().paste_options();
+            options.allow_plaintext = true;
+            options.buffer_views = true;
+            options
+        };
+        let stored_views = |db: &Database, id: &str| {
+            let conn = db.conn("test");
+            let mut stmt = conn.prepare("SELECT views FROM pastes WHERE id = ?").unwrap();
+            stmt.bind((1, id)).unwrap();
+            stmt.next().unwrap();
+            stmt.read::<i64, _>(0).unwrap()
+        };
+
+        let db = Database::new_with_path(&dir, options()).unwrap();
+        let paste = db.create_paste(plaintext("counted", serde_json::json!({}))).unwrap();
+        let limited = db.create_paste(plaintext("limited", serde_json::json!({ "max_views": 10 }))).unwrap();
+        for views in 1..=3 {
+            assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().views, views);
+        }
+        db.get_paste(&limited.id).unwrap();
+        assert_eq!(stored_views(&db, &paste.id), 0, "still buffered");
+        assert_eq!(stored_views(&db, &limited.id), 1, "view limits write through");
+        assert_eq!(db.get_paste_meta(&paste.id).unwrap().unwrap().views, 3);
+
+        assert_eq!(db.flush_views().unwrap(), 1);
+        assert_eq!(stored_views(&db, &paste.id), 3);
+        assert_eq!(db.flush_views().unwrap(), 0, "nothing left to flush");
+        drop(db);
+
+        let db = Database::new_with_path(&dir, options()).unwrap();
+        assert_eq!(db.get_paste_meta(&paste.id).unwrap().unwrap().views, 3);
+        drop(db);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn open_retries_until_the_data_dir_becomes_usable() {
+        let root = std::env::temp_dir().join(format!("rustybin-test-{}", uuid::Uuid::new_v4()));
+        // A file where a parent directory should be, like a volume not mounted yet
+        let blocker = root.join("volume");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(&blocker, "").unwrap();
+        let dir = blocker.join("data");
+        let options = || Config::default().paste_options();
+
+        let retry = OpenRetry { attempts: 2, initial_delay: Duration::from_millis(1) };
+        assert!(Database::open_with_retry(&dir, options(), retry).is_err(), "gives up after the last attempt");
+
+        let mounted = std::thread::spawn({
+            let blocker = blocker.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(50));
+                fs::remove_file(&blocker).unwrap();
+            }
+        });
+        let retry = OpenRetry { attempts: 10, initial_delay: Duration::from_millis(20) };
+        let db = Database::open_with_retry(&dir, options(), retry).unwrap();
+        mounted.join().unwrap();
+        assert!(Database::database_path(&dir).is_file());
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 0);
+
+        drop(db);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn daily_quota_refuses_past_the_limit_and_starts_over_each_day() {
+        let db = test_db();
+        assert_eq!(db.increment_daily_quota("192.0.2.1", 1, 3).unwrap(), Some(1));
+        assert_eq!(db.increment_daily_quota("192.0.2.1", 2, 3).unwrap(), Some(3));
+        assert_eq!(db.increment_daily_quota("192.0.2.1", 1, 3).unwrap(), None, "past the quota");
+        assert_eq!(db.increment_daily_quota("192.0.2.2", 3, 3).unwrap(), Some(3), "other clients count separately");
+        assert_eq!(db.increment_daily_quota("192.0.2.3", 4, 3).unwrap(), None, "a batch bigger than the quota");
+
+        // Move today's counts to yesterday, as if midnight UTC had passed
+        let yesterday = Database::quota_day(Utc::now() - chrono::Duration::days(1));
+        db.conn("test").execute(format!("UPDATE daily_quotas SET day = '{}'", yesterday)).unwrap();
+        assert_eq!(db.increment_daily_quota("192.0.2.1", 1, 3).unwrap(), Some(1));
+
+        db.purge_expired().unwrap();
+        let conn = db.conn("test");
+        let mut stmt = conn.prepare("SELECT COUNT(*) FROM daily_quotas WHERE day < ?").unwrap();
+        stmt.bind((1, Database::quota_day(Utc::now()).as_str())).unwrap();
+        stmt.next().unwrap();
+        assert_eq!(stmt.read::<i64, _>(0).unwrap(), 0, "the purge drops past days");
+    }
+
+    #[test]
+    fn omitted_languages_get_the_default_and_explicit_ones_pass_through() {
+        let db = test_db_with(|options| options.default_language = "markdown".to_string());
+        for extra in [serde_json::json!({}), serde_json::json!({ "language": null }), serde_json::json!({ "language": "  " })] {
+            let paste = db.create_paste(plaintext("fn main() {}", extra.clone())).unwrap();
+            assert_eq!(paste.language, "markdown", "{}", extra);
+            assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().language, "markdown");
+        }
+        let paste = db.create_paste(plaintext("fn main() {}", serde_json::json!({ "language": "go" }))).unwrap();
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().language, "go");
+
+        // Rows stored before the default existed read back as it
+        db.conn("test").execute(format!("UPDATE pastes SET language = '' WHERE id = '{}'", paste.id)).unwrap();
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().language, "markdown");
+        assert_eq!(db.get_paste_meta(&paste.id).unwrap().unwrap().language, "markdown");
+
+        // Under the `auto` default they read as plaintext, since detection happens on create
+        let db = test_db();
+        let paste = db.create_paste(plaintext("#!/usr/bin/env python3\nprint(1)", serde_json::json!({}))).unwrap();
+        assert_eq!(paste.language, "python");
+        db.conn("test").execute(format!("UPDATE pastes SET language = '' WHERE id = '{}'", paste.id)).unwrap();
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().language, "plaintext");
+    }
+
+    #[test]
+    fn operations_past_the_slow_query_threshold_log_a_warning() {
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+
+        // Level and message of each database event seen while it's the default subscriber
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<(tracing::Level, String)>>>);
+        impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Captured {
+            fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+                struct Message<'a>(&'a mut String);
+                impl tracing::field::Visit for Message<'_> {
+                    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                        if field.name() == "message" {
+                            *self.0 = format!("{:?}", value);
+                        }
+                    }
+                }
+                if event.metadata().target() == "rustybin::db" {
+                    let mut message = String::new();
+                    event.record(&mut Message(&mut message));
+                    self.0.lock().unwrap().push((*event.metadata().level(), message));
+                }
+            }
+        }
+
+        let slow_warnings = |slow_query: Option<Duration>| {
+            let captured = Captured::default();
+            let _default = tracing::subscriber::set_default(tracing_subscriber::registry().with(captured.clone()));
+            let db = test_db_with(|options| options.slow_query = slow_query);
+            db.create_paste(plaintext("timed", serde_json::json!({}))).unwrap();
+            let events = captured.0.lock().unwrap().clone();
+            assert!(events.iter().any(|(level, message)| *level == tracing::Level::DEBUG && message.contains(" µs")), "{:?}", events);
+            events.into_iter().filter(|(level, _)| *level == tracing::Level::WARN).map(|(_, message)| message).collect::<Vec<_>>()
+        };
+
+        let warnings = slow_warnings(Some(Duration::ZERO));
+        assert!(warnings.iter().any(|message| message.starts_with("Slow query: database store_client_encrypted_paste took")), "{:?}", warnings);
+        assert_eq!(slow_warnings(None), Vec::<String>::new(), "off when unset");
+    }
+
+    #[test]
+    fn legacy_text_created_at_reads_back_as_the_time_it_names() {
+        let db = test_db();
+        let paste = db.create_paste(plaintext("legacy", serde_json::json!({}))).unwrap();
+        {
+            let conn = db.conn("test");
+            let mut stmt = conn.prepare("UPDATE pastes SET created_at = ? WHERE id = ?").unwrap();
+            stmt.bind((1, "2023-11-14T22:13:20Z")).unwrap();
+            stmt.bind((2, paste.id.as_str())).unwrap();
+            stmt.next().unwrap();
+            let mut stmt = conn.prepare("SELECT typeof(created_at) FROM pastes WHERE id = ?").unwrap();
+            stmt.bind((1, paste.id.as_str())).unwrap();
+            stmt.next().unwrap();
+            assert_eq!(stmt.read::<String, _>(0).unwrap(), "text");
+        }
+
+        let expected = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(db.get_paste(&paste.id).unwrap().unwrap().created_at, expected);
+        assert_eq!(db.get_paste_meta(&paste.id).unwrap().unwrap().created_at, expected);
+        // Cursor reads used to panic on a TEXT value
+        let listed = db.list_pastes(10, 0).unwrap();
+        assert_eq!(listed.iter().find(|p| p.id == paste.id).unwrap().created_at, expected);
+    }
+
+    #[test]
+    fn theme_hints_follow_the_language_and_are_null_without_an_entry() {
+        let db = test_db_with(|options| {
+            options.theme_hints = HashMap::from([("rust".to_string(), "dark".to_string())]);
+        });
+        let rust = db.create_paste(plaintext("fn main() {}", serde_json::json!({ "language": "rust" }))).unwrap();
+        let python = db.create_paste(plaintext("print(1)", serde_json::json!({ "language": "python" }))).unwrap();
+
+        assert_eq!(rust.theme_hint.as_deref(), Some("dark"));
+        assert_eq!(python.theme_hint, None);
+        assert_eq!(db.get_paste(&rust.id).unwrap().unwrap().theme_hint.as_deref(), Some("dark"));
+        assert_eq!(db.get_paste(&python.id).unwrap().unwrap().theme_hint, None);
+        assert_eq!(serde_json::to_value(&python).unwrap()["theme_hint"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn writes_wait_out_another_connections_lock_up_to_the_busy_timeout() {
+        let dir = std::env::temp_dir().join(format!("rustybin-test-{}", uuid::Uuid::new_v4()));
+        let open = |timeout_ms| {
+            let mut options = Config::default().paste_options();
+            options.allow_plaintext = true;
+            options.busy_timeout = Duration::from_millis(timeout_ms);
+            Database::new_with_path(&dir, options).unwrap()
+        };
+        let lock = || {
+            let other = sqlite::open(Database::database_path(&dir)).unwrap();
+            other.execute("BEGIN IMMEDIATE").unwrap();
+            other
+        };
+
+        let db = open(0);
+        let held = lock();
+        assert!(db.create_paste(plaintext("now", serde_json::json!({}))).is_err(), "no timeout fails at once");
+        drop(held);
+        drop(db);
+
+        // Another process, like a backup, holds a write lock for a moment
+        let db = open(5000);
+        let held = lock();
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            held.execute("COMMIT").unwrap();
+        });
+        let started = Instant::now();
+        assert!(db.create_paste(plaintext("waited", serde_json::json!({}))).is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(150), "{:?}", started.elapsed());
+        release.join().unwrap();
+
+        drop(db);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn expiries_past_the_ceiling_are_rejected_or_clamped_and_omitted_ones_get_the_default() {
+        let expires_in = |paste: &Paste| paste.expires_at.map(|at| (at - Utc::now()).num_seconds());
+        let two_hours = serde_json::json!({ "expires_in_minutes": 120 });
+
+        let db = test_db_with(|options| options.max_ttl_secs = 3600);
+        match db.create_paste(plaintext("long", two_hours.clone())) {
+            Err(DbError::ValidationFailed(errors)) => {
+                assert_eq!(errors[0].field, "expires_in_minutes");
+                assert!(matches!(errors[0].error, DbError::TtlTooLong(7200, 3600)));
+            }
+            other => panic!("expected a ttl failure, got {:?}", other),
+        }
+        let within = db.create_paste(plaintext("short", serde_json::json!({ "expires_in_minutes": 30 }))).unwrap();
+        assert!((1795..=1800).contains(&expires_in(&within).unwrap()));
+        let forever = db.create_paste(plaintext("forever", serde_json::json!({}))).unwrap();
+        assert_eq!(forever.expires_at, None, "no default expiry");
+
+        let clamped = test_db_with(|options| {
+            options.max_ttl_secs = 3600;
+            options.clamp_ttl = true;
+        });
+        let paste = clamped.create_paste(plaintext("long", two_hours)).unwrap();
+        assert!((3595..=3600).contains(&expires_in(&paste).unwrap()));
+
+        let defaulted = test_db_with(|options| options.default_ttl_secs = 600);
+        let paste = defaulted.create_paste(plaintext("default", serde_json::json!({}))).unwrap();
+        assert!((595..=600).contains(&expires_in(&paste).unwrap()));
+    }
+}
@@ -0,0 +1,241 @@
+/// A unified diff of two texts, with the number of lines it adds and removes.
+#[derive(Debug)]
+pub struct LineDiff {
+    pub unified: String,
+    pub added: usize,
+    pub removed: usize,
+}
+
+// Unchanged lines shown around each change, as `diff -u` does
+const CONTEXT_LINES: usize = 3;
+
+// Most edits the Myers search explores before settling for a plain replace of the
+// differing middle. Its trace grows with the square of the edit count, so this
+// bounds memory per request; the output is still a correct diff, just not minimal.
+const MAX_EDIT_DISTANCE: usize = 1000;
+
+#[derive(Debug, Clone, Copy)]
+enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Diff `old` against `new` line by line in unified format, headed `--- a/<old_name>`
+/// and `+++ b/<new_name>`. Identical texts give an empty diff.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> LineDiff {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let edits = edit_script(&old_lines, &new_lines);
+
+    let added = edits.iter().filter(|edit| matches!(edit, Edit::Insert(_))).count();
+    let removed = edits.iter().filter(|edit| matches!(edit, Edit::Delete(_))).count();
+    if added == 0 && removed == 0 {
+        return LineDiff { unified: String::new(), added, removed };
+    }
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", old_name, new_name);
+    for (start, end) in hunks(&edits) {
+        write_hunk(&mut out, &edits[start..end], &old_lines, &new_lines);
+    }
+    LineDiff { unified: out, added, removed }
+}
+
+// The edits turning `old` into `new`: shared ends are matched directly, and only
+// the differing middle goes through the Myers search
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|i| Edit::Equal(i, i)).collect();
+    match myers(old_middle, new_middle) {
+        Some(middle) => edits.extend(middle.into_iter().map(|edit| match edit {
+            Edit::Equal(o, n) => Edit::Equal(o + prefix, n + prefix),
+            Edit::Delete(o) => Edit::Delete(o + prefix),
+            Edit::Insert(n) => Edit::Insert(n + prefix),
+        })),
+        None => {
+            edits.extend((0..old_middle.len()).map(|o| Edit::Delete(o + prefix)));
+            edits.extend((0..new_middle.len()).map(|n| Edit::Insert(n + prefix)));
+        }
+    }
+    edits.extend((0..suffix).map(|i| Edit::Equal(old.len() - suffix + i, new.len() - suffix + i)));
+    edits
+}
+
+// Shortest edit script by Myers' O((N+M)D) algorithm, or None when it would take
+// more than MAX_EDIT_DISTANCE edits. `trace[d]` keeps the furthest x reached on
+// diagonals -d-1..=d+1 before step d, which is all the backtrack needs.
+fn myers(old: &[&str], new: &[&str]) -> Option<Vec<Edit>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let limit = (old.len() + new.len()).min(MAX_EDIT_DISTANCE) as isize;
+    let offset = limit + 1;
+    let mut v = vec![0isize; 2 * limit as usize + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=limit {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let down = k == -d || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize]);
+            let mut x = if down { v[(offset + k + 1) as usize] } else { v[(offset + k - 1) as usize] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(offset + k) as usize] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        // Diagonal k sits at index k + d + 1 of this step's slice
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Equal((x - 1) as usize, (y - 1) as usize));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert((y - 1) as usize));
+            } else {
+                edits.push(Edit::Delete((x - 1) as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    edits.reverse();
+    edits
+}
+
+// Ranges of the edit list to print as hunks: each change with CONTEXT_LINES around
+// it, merging changes whose context would touch
+fn hunks(edits: &[Edit]) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (i, edit) in edits.iter().enumerate() {
+        if matches!(edit, Edit::Equal(..)) {
+            continue;
+        }
+        let start = i.saturating_sub(CONTEXT_LINES);
+        let end = (i + 1 + CONTEXT_LINES).min(edits.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    hunks
+}
+
+fn write_hunk(out: &mut String, edits: &[Edit], old: &[&str], new: &[&str]) {
+    // 1-based first line of the hunk on each side. A side with no lines in the hunk
+    // can only be an empty file, which diff numbers 0.
+    let old_start = edits.iter().find_map(|edit| match edit {
+        Edit::Equal(o, _) | Edit::Delete(o) => Some(o + 1),
+        Edit::Insert(_) => None,
+    });
+    let new_start = edits.iter().find_map(|edit| match edit {
+        Edit::Equal(_, n) | Edit::Insert(n) => Some(n + 1),
+        Edit::Delete(_) => None,
+    });
+    let old_count = edits.iter().filter(|edit| !matches!(edit, Edit::Insert(_))).count();
+    let new_count = edits.iter().filter(|edit| !matches!(edit, Edit::Delete(_))).count();
+
+    out.push_str(&format!("@@ -{} +{} @@\n", range(old_start.unwrap_or(0), old_count), range(new_start.unwrap_or(0), new_count)));
+    for edit in edits {
+        let (marker, line) = match *edit {
+            Edit::Equal(o, _) => (' ', old[o]),
+            Edit::Delete(o) => ('-', old[o]),
+            Edit::Insert(n) => ('+', new[n]),
+        };
+        out.push(marker);
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+// `start,count` as diff writes it, leaving out a count of one
+fn range(start: usize, count: usize) -> String {
+    if count == 1 { start.to_string() } else { format!("{},{}", start, count) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected outputs are what `diff -u` prints for the same inputs
+    #[test]
+    fn changed_and_added_lines() {
+        let diff = unified("a\nb\nc\n", "a\nB\nc\nd\n", "old", "new");
+        assert_eq!(diff.unified, "--- a/old\n+++ b/new\n@@ -1,3 +1,4 @@\n a\n-b\n+B\n c\n+d\n");
+        assert_eq!((diff.added, diff.removed), (2, 1));
+    }
+
+    #[test]
+    fn distant_changes_get_separate_hunks() {
+        let old: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        let new = old.replace("\n2\n", "\ntwo\n").replace("\n18\n", "\neighteen\n");
+        let diff = unified(&old, &new, "old", "new");
+        assert_eq!(
+            diff.unified,
+            "--- a/old\n+++ b/new\n\
+             @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+             @@ -15,6 +15,6 @@\n 15\n 16\n 17\n-18\n+eighteen\n 19\n 20\n"
+        );
+        assert_eq!((diff.added, diff.removed), (2, 2));
+    }
+
+    #[test]
+    fn missing_final_newline_is_marked() {
+        let diff = unified("a", "b", "old", "new");
+        assert_eq!(
+            diff.unified,
+            "--- a/old\n+++ b/new\n@@ -1 +1 @@\n-a\n\\ No newline at end of file\n+b\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn empty_side_is_numbered_zero() {
+        let diff = unified("", "x\n", "old", "new");
+        assert_eq!(diff.unified, "--- a/old\n+++ b/new\n@@ -0,0 +1 @@\n+x\n");
+    }
+
+    #[test]
+    fn identical_texts_give_an_empty_diff() {
+        let diff = unified("same\n", "same\n", "old", "new");
+        assert_eq!((diff.unified.as_str(), diff.added, diff.removed), ("", 0, 0));
+    }
+
+    #[test]
+    fn edits_past_the_search_limit_still_diff_correctly() {
+        let old: String = (0..MAX_EDIT_DISTANCE).map(|n| format!("old {}\n", n)).collect();
+        let new: String = (0..MAX_EDIT_DISTANCE).map(|n| format!("new {}\n", n)).collect();
+        let diff = unified(&old, &new, "old", "new");
+        assert_eq!((diff.added, diff.removed), (MAX_EDIT_DISTANCE, MAX_EDIT_DISTANCE));
+    }
+}
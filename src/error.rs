@@ -1,5 +1,22 @@
-use serde_json;
+use axum::http::StatusCode;
 
 pub fn json_error(message: &str) -> serde_json::Value {
     serde_json::json!({ "error": message })
 }
+
+/// Status and message for an access-denied outcome on a resource.
+///
+/// With `hide_forbidden` enabled the outcome is reported as a plain not-found so
+/// clients can't tell a protected resource exists.
+pub fn access_denied(
+    hide_forbidden: bool,
+    status: StatusCode,
+    message: &str,
+    not_found_message: &str,
+) -> (StatusCode, String) {
+    if hide_forbidden {
+        (StatusCode::NOT_FOUND, not_found_message.to_string())
+    } else {
+        (status, message.to_string())
+    }
+}
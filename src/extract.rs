@@ -0,0 +1,223 @@
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Request, rejection::{BytesRejection, JsonRejection}},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+/// `Json` extractor whose rejections are JSON errors with a stable `code`.
+///
+/// - wrong or missing `Content-Type`: `415`, code `unsupported_media_type`
+/// - body isn't valid JSON: `400`, code `malformed_json`
+/// - JSON doesn't match the expected shape: `400`, code `invalid_field`, plus
+///   `field` when serde names the offending field and `expected` when it says
+///   what type belonged there
+/// - body over the route's size limit: `413`, code `body_too_large`
+pub struct JsonBody<T>(pub T);
+
+impl<S, T> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => Err(json_rejection(rejection)),
+        }
+    }
+}
+
+fn json_rejection(rejection: JsonRejection) -> Response {
+    if let JsonRejection::JsonDataError(_) = &rejection {
+        return data_error(&serde_detail(&rejection));
+    }
+
+    let (status, code, message) = match &rejection {
+        JsonRejection::MissingJsonContentType(_) => (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+            "Content-Type must be application/json".to_string(),
+        ),
+        JsonRejection::JsonSyntaxError(_) => (
+            StatusCode::BAD_REQUEST,
+            "malformed_json",
+            format!("Malformed JSON: {}", serde_detail(&rejection)),
+        ),
+        JsonRejection::BytesRejection(_) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+            return body_too_large();
+        }
+        // Other body read failures keep their own status
+        _ => (rejection.status(), "invalid_body", rejection.body_text()),
+    };
+
+    (status, Json(serde_json::json!({ "error": message, "code": code }))).into_response()
+}
+
+/// Raw request body whose rejections are JSON errors like `JsonBody`'s: `413` with
+/// code `body_too_large` over the route's size limit, else code `invalid_body`.
+pub struct BodyBytes(pub Bytes);
+
+impl<S> FromRequest<S> for BodyBytes
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Bytes::from_request(req, state).await {
+            Ok(bytes) => Ok(Self(bytes)),
+            Err(rejection) => Err(bytes_rejection(rejection)),
+        }
+    }
+}
+
+fn bytes_rejection(rejection: BytesRejection) -> Response {
+    if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        return body_too_large();
+    }
+    let body = serde_json::json!({ "error": rejection.body_text(), "code": "invalid_body" });
+    (rejection.status(), Json(body)).into_response()
+}
+
+fn body_too_large() -> Response {
+    let body = serde_json::json!({ "error": "Request body is too large", "code": "body_too_large" });
+    (StatusCode::PAYLOAD_TOO_LARGE, Json(body)).into_response()
+}
+
+// Well-formed JSON of the wrong shape, e.g. `{"data":123}` becomes
+// "Invalid field `data`: invalid type: integer `123`, expected a string" with
+// `field: "data"` and `expected: "a string"`. The position is left out, since the
+// field path already says where the problem is.
+fn data_error(detail: &str) -> Response {
+    let reason = strip_position(detail);
+    let (path, reason) = match reason.split_once(": ") {
+        Some((path, rest)) if !path.is_empty() && !path.contains(' ') => (Some(path), rest),
+        _ => (None, reason),
+    };
+
+    let message = match path {
+        Some(path) => format!("Invalid field `{}`: {}", path, reason),
+        None => format!("Invalid request body: {}", reason),
+    };
+
+    let mut body = serde_json::json!({ "error": message, "code": "invalid_field" });
+    if let Some(field) = path.map(str::to_string).or_else(|| named_field(reason)) {
+        body["field"] = serde_json::Value::String(field);
+    }
+    if let Some((_, expected)) = reason.rsplit_once(", expected ") {
+        body["expected"] = serde_json::Value::String(expected.to_string());
+    }
+
+    (StatusCode::BAD_REQUEST, Json(body)).into_response()
+}
+
+// The underlying serde message, without axum's "Failed to deserialize..." prefix
+fn serde_detail(rejection: &JsonRejection) -> String {
+    let text = rejection.body_text();
+    match text.split_once(": ") {
+        Some((_, detail)) => detail.to_string(),
+        None => text,
+    }
+}
+
+// serde_json ends its messages with " at line 1 column 11"
+fn strip_position(detail: &str) -> &str {
+    match detail.rsplit_once(" at line ") {
+        Some((reason, position)) if position.contains(" column ") => reason,
+        _ => detail,
+    }
+}
+
+// Missing, unknown and duplicate fields are named in the message ("missing field `name`")
+fn named_field(reason: &str) -> Option<String> {
+    let (_, rest) = reason.split_once("field `")?;
+    let (name, _) = rest.split_once('`')?;
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CreatePasteData;
+    use axum::{Router, body::Body, extract::DefaultBodyLimit, routing::post};
+    use tower::ServiceExt;
+
+    async fn reject(content_type: Option<&str>, body: &str) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::post("/v1/pastes");
+        if let Some(content_type) = content_type {
+            request = request.header("content-type", content_type);
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+        let Err(response) = JsonBody::<CreatePasteData>::from_request(request, &()).await else {
+            panic!("{:?} was accepted", body);
+        };
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn wrong_or_missing_content_type_is_a_415() {
+        for content_type in [None, Some("text/plain"), Some("application/x-www-form-urlencoded")] {
+            let (status, body) = reject(content_type, r#"{"data": "hello"}"#).await;
+            assert_eq!((status, &body["code"]), (StatusCode::UNSUPPORTED_MEDIA_TYPE, &"unsupported_media_type".into()), "{:?}", content_type);
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_json_is_a_400() {
+        let (status, body) = reject(Some("application/json"), r#"{"data": "hello""#).await;
+        assert_eq!((status, &body["code"]), (StatusCode::BAD_REQUEST, &"malformed_json".into()));
+    }
+
+    #[tokio::test]
+    async fn mistyped_and_missing_fields_are_named() {
+        let (status, body) = reject(Some("application/json"), r#"{"data": 123}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "error": "Invalid field `data`: invalid type: integer `123`, expected a string",
+                "code": "invalid_field",
+                "field": "data",
+                "expected": "a string",
+            })
+        );
+
+        let (_, body) = reject(Some("application/json"), r#"{"language": "rust"}"#).await;
+        assert_eq!((&body["code"], &body["field"]), (&"invalid_field".into(), &"data".into()));
+    }
+
+    #[tokio::test]
+    async fn bodies_over_the_limit_are_a_413() {
+        let app = Router::new()
+            .route("/v1/pastes", post(|JsonBody(_): JsonBody<CreatePasteData>| async { StatusCode::CREATED }))
+            .layer(DefaultBodyLimit::max(16));
+        let body = serde_json::json!({ "data": "x".repeat(64) }).to_string();
+        let request = Request::post("/v1/pastes").header("content-type", "application/json").body(Body::from(body)).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["code"], "body_too_large");
+    }
+
+    #[tokio::test]
+    async fn empty_objects_and_nested_fields_are_named() {
+        let (status, body) = reject(Some("application/json"), "{}").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            serde_json::json!({ "error": "Invalid request body: missing field `data`", "code": "invalid_field", "field": "data" })
+        );
+
+        let (status, body) = reject(Some("application/json"), r#"{"data": "x", "tags": ["ok", 7]}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!((&body["field"], &body["expected"]), (&"tags[1]".into(), &"a string".into()));
+        assert!(body["error"].as_str().unwrap().starts_with("Invalid field `tags[1]`: "), "{}", body);
+    }
+}
@@ -1,20 +1,32 @@
 use axum::{
     Json,
     extract::{Path, Query, State},
-    http::{HeaderMap, HeaderValue, StatusCode},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
     response::IntoResponse,
+    Extension,
 };
 use std::env;
 use std::sync::Arc;
 
 use crate::auth::generate_token;
-use crate::db::Database;
+use crate::db::{CheckpointMode, Database, DbError, PasteRecord};
 use crate::error::json_error;
+use crate::extract::BodyBytes;
+use crate::maintenance::{self, Maintenance};
+use crate::models::batch::{BatchItemResult, BatchModeQuery, BatchResults};
 use crate::models::admin::{
-    BulkDeleteRequest, BulkDeleteResponse, DeleteResponse, LoginRequest, PasteFilterParams,
-    PasteListItem, PasteListResponse, StatsQuery, StatsResponse, TimeSeriesPoint,
+    AccessLogQuery, AccessLogResponse, BulkDeleteRequest, BulkDeleteResponse, DeleteManyRequest, DeleteManyResponse, DeleteResponse, DismissReportsResponse, ImportQuery, LoginRequest, PasteFilterParams,
+    PasteListItem, PasteListResponse, ReportedPastesResponse, ReportsQuery, StatsQuery, StatsResponse, StorageStatsResponse,
+    TimeSeriesPoint,
 };
 
+/// Upper bound on an import body; backups carry many pastes, so this is far above
+/// the regular request limit.
+pub const MAX_IMPORT_BYTES: usize = 256 * 1024 * 1024;
+
+// Most IDs a single bulk delete request may name
+const MAX_BULK_DELETE_IDS: usize = 100;
+
 /// POST /v1/admin/login - authenticate with admin secret.
 pub async fn admin_login(Json(body): Json<LoginRequest>) -> impl IntoResponse {
     let admin_secret = env::var("ADMIN_SECRET").unwrap_or_default();
@@ -156,6 +168,42 @@ pub async fn admin_stats(
     }
 }
 
+/// GET /v1/admin/storage - retrieve storage totals for the whole instance.
+pub async fn admin_storage_stats(State(db): State<Arc<Database>>) -> impl IntoResponse {
+    match db.storage_stats() {
+        Ok(stats) => {
+            let to_rfc3339 = |ts: Option<i64>| {
+                ts.and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                    .map(|dt| dt.to_rfc3339())
+            };
+            let response = StorageStatsResponse {
+                total_pastes: stats.total_pastes,
+                total_bytes: stats.total_bytes,
+                oldest_created_at: to_rfc3339(stats.oldest_created_at),
+                newest_created_at: to_rfc3339(stats.newest_created_at),
+                by_encryption_version: stats
+                    .by_encryption_version
+                    .into_iter()
+                    .map(|(version, count)| (version.to_string(), count))
+                    .collect(),
+            };
+            (
+                StatusCode::OK,
+                Json(serde_json::to_value(response).unwrap()),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to fetch storage stats: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to fetch storage statistics")),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// GET /v1/admin/pastes - retrieve filtered, paginated paste list.
 pub async fn admin_list_pastes(
     State(db): State<Arc<Database>>,
@@ -185,6 +233,8 @@ pub async fn admin_list_pastes(
                     has_expiration: p.expires_at.is_some(),
                     expires_at: p.expires_at.map(|dt| dt.to_rfc3339()),
                     encryption_version: p.encryption_version,
+                    pinned: p.pinned,
+                    visibility: p.visibility,
                 })
                 .collect();
 
@@ -213,6 +263,108 @@ pub async fn admin_list_pastes(
     }
 }
 
+/// GET /v1/admin/pastes/:id - full metadata of a single paste, without its content.
+pub async fn admin_get_paste(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match db.admin_get(&id) {
+        Ok(Some(details)) => (StatusCode::OK, Json(serde_json::to_value(details).unwrap())).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, Json(json_error("Paste not found"))).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to fetch paste details {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to fetch paste")),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Most access log entries returned by one request
+const MAX_ACCESS_LOG_ENTRIES: usize = 500;
+
+/// GET /v1/admin/pastes/:id/accesses - recent recorded reads of a paste, newest first.
+pub async fn admin_paste_accesses(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Query(params): Query<AccessLogQuery>,
+) -> impl IntoResponse {
+    match db.recent_accesses(&id, params.limit.clamp(1, MAX_ACCESS_LOG_ENTRIES)) {
+        Ok(accesses) => (
+            StatusCode::OK,
+            Json(
+                serde_json::to_value(AccessLogResponse {
+                    paste_id: id,
+                    enabled: db.access_log_enabled(),
+                    accesses,
+                })
+                .unwrap(),
+            ),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to fetch access log for {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to fetch access log")),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Most reported pastes returned by one request
+const MAX_REPORTED_PASTES: usize = 500;
+
+/// GET /v1/admin/reports - live pastes with abuse reports, most reported first.
+pub async fn admin_reported_pastes(
+    State(db): State<Arc<Database>>,
+    Query(params): Query<ReportsQuery>,
+) -> impl IntoResponse {
+    match db.reported_pastes(params.limit.clamp(1, MAX_REPORTED_PASTES)) {
+        Ok(pastes) => (
+            StatusCode::OK,
+            Json(serde_json::to_value(ReportedPastesResponse { pastes }).unwrap()),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to list reported pastes: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to list reported pastes")),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// DELETE /v1/admin/pastes/:id/reports - dismiss every report against a paste.
+pub async fn admin_dismiss_reports(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match db.dismiss_reports(&id) {
+        Ok(dismissed) => {
+            tracing::info!("Admin dismissed {} reports on paste {}", dismissed, id);
+            (
+                StatusCode::OK,
+                Json(serde_json::to_value(DismissReportsResponse { success: true, dismissed }).unwrap()),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to dismiss reports on {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to dismiss reports")),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// DELETE /v1/admin/pastes/:id - delete a single paste.
 pub async fn admin_delete_paste(
     State(db): State<Arc<Database>>,
@@ -247,17 +399,51 @@ pub async fn admin_delete_paste(
     }
 }
 
+/// PUT /v1/admin/pastes/:id/pin - exclude a paste from `MAX_TOTAL_PASTES` eviction.
+pub async fn admin_pin_paste(State(db): State<Arc<Database>>, Path(id): Path<String>) -> impl IntoResponse {
+    set_pinned(&db, &id, true)
+}
+
+/// DELETE /v1/admin/pastes/:id/pin - make a paste evictable again.
+pub async fn admin_unpin_paste(State(db): State<Arc<Database>>, Path(id): Path<String>) -> impl IntoResponse {
+    set_pinned(&db, &id, false)
+}
+
+fn set_pinned(db: &Database, id: &str, pinned: bool) -> axum::response::Response {
+    match db.set_pinned(id, pinned) {
+        Ok(()) => {
+            tracing::info!("Admin {} paste: {}", if pinned { "pinned" } else { "unpinned" }, id);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(DbError::PasteNotFound) => {
+            (StatusCode::NOT_FOUND, Json(json_error("Paste not found"))).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to update pin on paste {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to update paste")),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// DELETE /v1/admin/pastes - bulk delete multiple pastes.
+///
+/// With `?strict=true|false` the response carries per-item results instead of counts.
 pub async fn admin_bulk_delete(
     State(db): State<Arc<Database>>,
+    Query(mode): Query<BatchModeQuery>,
     Json(body): Json<BulkDeleteRequest>,
 ) -> impl IntoResponse {
-    if body.ids.is_empty() || body.ids.len() > 100 {
+    if body.ids.is_empty() || body.ids.len() > MAX_BULK_DELETE_IDS {
         return (
             StatusCode::BAD_REQUEST,
-            Json(json_error(
-                "Request must include between 1 and 100 paste IDs",
-            )),
+            Json(json_error(&format!(
+                "Request must include between 1 and {} paste IDs",
+                MAX_BULK_DELETE_IDS
+            ))),
         )
             .into_response();
     }
@@ -277,6 +463,21 @@ pub async fn admin_bulk_delete(
                 id_list
             );
 
+            if let Some(strict) = mode.strict {
+                let results = body
+                    .ids
+                    .into_iter()
+                    .map(|id| {
+                        if not_found.contains(&id) {
+                            BatchItemResult::failed(id, StatusCode::NOT_FOUND, "Paste not found")
+                        } else {
+                            BatchItemResult::ok(id, None)
+                        }
+                    })
+                    .collect();
+                return BatchResults { strict, results }.into_response();
+            }
+
             let response = BulkDeleteResponse {
                 success: true,
                 deleted_count,
@@ -299,6 +500,269 @@ pub async fn admin_bulk_delete(
     }
 }
 
+/// POST /v1/admin/pastes/delete - delete a list of pastes, or every unpinned one
+/// created before a time, in one transaction.
+///
+/// A time filter can match any number of pastes, so it also needs `confirm: true`.
+pub async fn admin_delete_many(
+    State(db): State<Arc<Database>>,
+    Json(body): Json<DeleteManyRequest>,
+) -> impl IntoResponse {
+    let result = match (body.ids, body.created_before) {
+        (Some(ids), None) => {
+            if ids.is_empty() || ids.len() > MAX_BULK_DELETE_IDS {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json_error(&format!(
+                        "Request must include between 1 and {} paste IDs",
+                        MAX_BULK_DELETE_IDS
+                    ))),
+                )
+                    .into_response();
+            }
+            db.delete_many(&ids).inspect(|deleted| {
+                tracing::info!("Admin deleted {} pastes (IDs: {})", deleted, ids.join(", "));
+            })
+        }
+        (None, Some(before)) => {
+            if !body.confirm {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json_error("Deleting by created_before requires \"confirm\": true")),
+                )
+                    .into_response();
+            }
+            db.delete_before(before).inspect(|deleted| {
+                tracing::info!("Admin deleted {} pastes created before {}", deleted, before);
+            })
+        }
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json_error("Request must include exactly one of ids or created_before")),
+            )
+                .into_response();
+        }
+    };
+
+    match result {
+        Ok(deleted_count) => (
+            StatusCode::OK,
+            Json(DeleteManyResponse { success: true, deleted_count }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Bulk delete failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to delete pastes")),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// POST /v1/admin/maintenance/purge - run the expired-paste purge now, rather than
+/// waiting for the sweeper.
+pub async fn admin_purge_expired(
+    State(db): State<Arc<Database>>,
+    Extension(maintenance): Extension<Arc<Maintenance>>,
+) -> impl IntoResponse {
+    match maintenance::purge_expired(&maintenance, db).await {
+        Some(Ok(removed)) => {
+            tracing::info!(
+                "Admin purged {} pastes ({} expired, {} trashed, {} evicted)",
+                removed.total(),
+                removed.expired,
+                removed.trashed,
+                removed.evicted
+            );
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "success": true, "purged": removed.total(), "removed": removed })),
+            )
+                .into_response()
+        }
+        Some(Err(e)) => {
+            tracing::error!("Admin purge failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to purge expired pastes")),
+            )
+                .into_response()
+        }
+        None => maintenance_busy().into_response(),
+    }
+}
+
+/// POST /v1/admin/maintenance/vacuum - reclaim space in the database file.
+pub async fn admin_vacuum(
+    State(db): State<Arc<Database>>,
+    Extension(maintenance): Extension<Arc<Maintenance>>,
+) -> impl IntoResponse {
+    match maintenance::vacuum(&maintenance, db).await {
+        Some(Ok(())) => {
+            tracing::info!("Admin vacuumed the database");
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "success": true })),
+            )
+                .into_response()
+        }
+        Some(Err(e)) => {
+            tracing::error!("Admin vacuum failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to vacuum database")),
+            )
+                .into_response()
+        }
+        None => maintenance_busy().into_response(),
+    }
+}
+
+/// POST /v1/admin/checkpoint - checkpoint the WAL and truncate it.
+pub async fn admin_checkpoint(
+    State(db): State<Arc<Database>>,
+    Extension(maintenance): Extension<Arc<Maintenance>>,
+) -> impl IntoResponse {
+    match maintenance::checkpoint(&maintenance, db, CheckpointMode::Truncate).await {
+        Some(Ok(result)) => {
+            tracing::info!(
+                "Admin checkpointed the WAL: {} of {} frames (busy: {})",
+                result.checkpointed_frames,
+                result.log_frames,
+                result.busy
+            );
+            (StatusCode::OK, Json(result)).into_response()
+        }
+        Some(Err(e)) => {
+            tracing::error!("Admin checkpoint failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to checkpoint database")),
+            )
+                .into_response()
+        }
+        None => maintenance_busy().into_response(),
+    }
+}
+
+/// GET /v1/admin/export - every stored record as newline-delimited JSON.
+pub async fn admin_export(State(db): State<Arc<Database>>) -> impl IntoResponse {
+    let records = match db.export_all() {
+        Ok(records) => records,
+        Err(e) => {
+            tracing::error!("Admin export failed: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to export pastes")),
+            )
+                .into_response();
+        }
+    };
+
+    let mut body = String::new();
+    for record in &records {
+        match serde_json::to_string(record) {
+            Ok(line) => {
+                body.push_str(&line);
+                body.push('\n');
+            }
+            Err(e) => {
+                tracing::error!("Failed to serialize paste {} for export: {}", record.id, e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json_error("Failed to export pastes")),
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    tracing::info!("Admin exported {} records", records.len());
+
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"rustybin-export.ndjson\""),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// POST /v1/admin/import - ingest records in the export format.
+pub async fn admin_import(
+    State(db): State<Arc<Database>>,
+    Query(query): Query<ImportQuery>,
+    BodyBytes(body): BodyBytes,
+) -> impl IntoResponse {
+    let Ok(text) = std::str::from_utf8(&body) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Import body must be UTF-8")),
+        )
+            .into_response();
+    };
+
+    let mut records = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<PasteRecord>(line) {
+            Ok(record) => records.push(record),
+            Err(e) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({
+                        "error": format!("Invalid record: {}", e),
+                        "line": index + 1,
+                    })),
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    match db.import(records, query.on_conflict) {
+        Ok(summary) => {
+            tracing::info!(
+                "Admin imported {} records ({} skipped)",
+                summary.imported,
+                summary.skipped.len()
+            );
+            (StatusCode::OK, Json(summary)).into_response()
+        }
+        Err(DbError::ImportConflict(id)) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": format!("Paste {} already exists", id),
+                "id": id,
+            })),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Admin import failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to import pastes")),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// 409 response for a maintenance trigger while another task holds the slot.
+fn maintenance_busy() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::CONFLICT,
+        Json(json_error("Maintenance is already running, try again later")),
+    )
+}
+
 /// Constant-time byte comparison to prevent timing attacks.
 fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
@@ -309,3 +773,303 @@ fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
         .fold(0u8, |acc, (x, y)| acc | (x ^ y))
         == 0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::db::ImportConflict;
+
+    async fn status_and_json(response: impl IntoResponse) -> (StatusCode, serde_json::Value) {
+        let response = response.into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn bulk_delete_reports_mixed_results_in_both_modes() {
+        let config = Config { allow_plaintext_pastes: true, ..Config::default() };
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let create = || {
+            db.create_paste(serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap())
+                .unwrap()
+                .id
+        };
+        let delete = |ids: Vec<String>, strict| {
+            admin_bulk_delete(State(db.clone()), Query(BatchModeQuery { strict }), Json(BulkDeleteRequest { ids }))
+        };
+
+        let id = create();
+        let (status, body) = status_and_json(delete(vec![id.clone(), "missing1".to_string()], Some(true)).await).await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert_eq!(body["results"], serde_json::json!([
+            { "id": id, "status": 200 },
+            { "id": "missing1", "status": 404, "error": "Paste not found" },
+        ]));
+        assert!(db.get_paste(&id).unwrap().is_none());
+
+        let id = create();
+        let (status, body) = status_and_json(delete(vec![id.clone(), "missing1".to_string()], Some(false)).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"][id.as_str()]["status"], 200);
+        assert_eq!(body["results"]["missing1"]["status"], 404);
+
+        // Without a mode the response keeps its counts
+        let id = create();
+        let (status, body) = status_and_json(delete(vec![id, "missing1".to_string()], None).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((&body["deleted_count"], &body["not_found"]), (&1.into(), &serde_json::json!(["missing1"])));
+
+        for ids in [Vec::new(), vec!["abcdef".to_string(); MAX_BULK_DELETE_IDS + 1]] {
+            let (status, _) = status_and_json(delete(ids, Some(true)).await).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn maintenance_triggers_are_a_409_while_a_task_runs() {
+        let db = Arc::new(Database::in_memory(Config::default().paste_options()).unwrap());
+        let maintenance = Arc::new(Maintenance::new(1));
+
+        let slot = maintenance.try_begin("running task").unwrap();
+        for response in [
+            admin_purge_expired(State(db.clone()), Extension(maintenance.clone())).await.into_response(),
+            admin_vacuum(State(db.clone()), Extension(maintenance.clone())).await.into_response(),
+            admin_checkpoint(State(db.clone()), Extension(maintenance.clone())).await.into_response(),
+        ] {
+            assert_eq!(response.status(), StatusCode::CONFLICT);
+        }
+
+        drop(slot);
+        let response = admin_purge_expired(State(db.clone()), Extension(maintenance.clone())).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn storage_stats_report_rfc3339_dates_and_versions() {
+        let config = Config { allow_plaintext_pastes: true, ..Config::default() };
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let (status, body) = status_and_json(admin_storage_stats(State(db.clone())).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((&body["total_pastes"], &body["oldest_created_at"]), (&0.into(), &serde_json::Value::Null));
+
+        db.create_paste(serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap())
+            .unwrap();
+        db.create_paste(serde_json::from_value(serde_json::json!({ "data": "Y2lwaGVy" })).unwrap()).unwrap();
+        let (_, body) = status_and_json(admin_storage_stats(State(db.clone())).await).await;
+        assert_eq!(body["total_pastes"], 2);
+        assert_eq!(body["total_bytes"], 13);
+        assert_eq!(body["by_encryption_version"], serde_json::json!({ "0": 1, "1": 1 }));
+        let oldest = body["oldest_created_at"].as_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(oldest).is_ok(), "{}", oldest);
+
+        crate::db::tests::break_pastes_table(&db);
+        let (status, _) = status_and_json(admin_storage_stats(State(db)).await).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn export_wipe_and_import_round_trips_every_record() {
+        let config = Config { allow_plaintext_pastes: true, ..Config::default() };
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let first = db
+            .create_paste(serde_json::from_value(serde_json::json!({
+                "data": "hello", "encryption_version": 0, "tags": ["notes"], "visibility": "private", "expires_in_minutes": 60,
+            })).unwrap())
+            .unwrap();
+        db.create_paste(serde_json::from_value(serde_json::json!({ "data": "Y2lwaGVy", "language": "rust" })).unwrap()).unwrap();
+        let export = || async {
+            let response = admin_export(State(db.clone())).await.into_response();
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+            axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()
+        };
+        // Records created in the same second may come out in either order
+        let sorted_lines = |export: &[u8]| {
+            let mut lines: Vec<String> = std::str::from_utf8(export).unwrap().lines().map(String::from).collect();
+            lines.sort();
+            lines
+        };
+        let import = |body: axum::body::Bytes, on_conflict| {
+            admin_import(State(db.clone()), Query(ImportQuery { on_conflict }), BodyBytes(body))
+        };
+
+        let exported = export().await;
+        let lines: Vec<serde_json::Value> =
+            std::str::from_utf8(&exported).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        let ids: Vec<String> = lines.iter().map(|line| line["id"].as_str().unwrap().to_string()).collect();
+        assert_eq!(db.delete_many(&ids).unwrap(), 2);
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 0);
+
+        let (status, summary) = status_and_json(import(exported.clone(), ImportConflict::Error).await).await;
+        assert_eq!((status, &summary["imported"]), (StatusCode::OK, &2.into()));
+        assert_eq!(sorted_lines(&export().await), sorted_lines(&exported), "the re-import is identical");
+        assert!(db.check_edit_key(&first.id, first.edit_key.as_deref().unwrap()).is_ok(), "the edit key still works");
+
+        let (status, summary) = status_and_json(import(exported.clone(), ImportConflict::Skip).await).await;
+        assert_eq!((status, &summary["imported"], summary["skipped"].as_array().unwrap().len()), (StatusCode::OK, &0.into(), 2));
+        let (status, body) = status_and_json(import(exported, ImportConflict::Error).await).await;
+        assert_eq!((status, &body["id"]), (StatusCode::CONFLICT, &ids[0].clone().into()));
+
+        let (status, body) = status_and_json(import("{\"id\": \"abc\"}\n".into(), ImportConflict::Skip).await).await;
+        assert_eq!((status, &body["line"]), (StatusCode::BAD_REQUEST, &1.into()));
+    }
+
+    #[tokio::test]
+    async fn checkpoint_returns_the_frame_counts() {
+        let db = Arc::new(Database::in_memory(Config::default().paste_options()).unwrap());
+        let (status, body) = status_and_json(admin_checkpoint(State(db), Extension(Arc::new(Maintenance::new(1)))).await).await;
+        assert_eq!(status, StatusCode::OK);
+        // An in-memory database has no WAL, which SQLite reports as -1 frames
+        assert_eq!(body, serde_json::json!({ "busy": false, "log_frames": -1, "checkpointed_frames": -1 }));
+    }
+
+    #[tokio::test]
+    async fn delete_many_by_ids_or_age_reports_accurate_counts() {
+        let config = Config { allow_plaintext_pastes: true, ..Config::default() };
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let create = || {
+            db.create_paste(serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap())
+                .unwrap()
+                .id
+        };
+        let delete = |body: serde_json::Value| admin_delete_many(State(db.clone()), Json(serde_json::from_value(body).unwrap()));
+
+        let (first, second) = (create(), create());
+        let (status, body) = status_and_json(delete(serde_json::json!({ "ids": [first, second, "nosuch"] })).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["deleted_count"], 2, "the missing id isn't counted");
+
+        // Two pastes from last week, one of them pinned, and one from today
+        let (old, pinned, _recent) = (create(), create(), create());
+        db.set_pinned(&pinned, true).unwrap();
+        for id in [&old, &pinned] {
+            crate::db::tests::backdate_paste(&db, id, 7 * 24 * 60 * 60);
+        }
+        let before = (chrono::Utc::now() - chrono::Duration::days(1)).to_rfc3339();
+
+        let (status, _) = status_and_json(delete(serde_json::json!({ "created_before": before })).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "a filter needs confirm");
+        let (status, body) = status_and_json(delete(serde_json::json!({ "created_before": before, "confirm": true })).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["deleted_count"], 1, "pinned and recent pastes stay");
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 2);
+
+        let too_many: Vec<_> = (0..=MAX_BULK_DELETE_IDS).map(|n| format!("id{n:04}")).collect();
+        for body in [serde_json::json!({ "ids": too_many }), serde_json::json!({ "ids": [] }), serde_json::json!({})] {
+            assert_eq!(status_and_json(delete(body).await).await.0, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn pinned_pastes_survive_the_purge_that_removes_expired_ones() {
+        let config = Config { allow_plaintext_pastes: true, ..Config::default() };
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let create = || {
+            db.create_paste(serde_json::from_value(serde_json::json!({ "data": "docs", "encryption_version": 0, "expires_in_minutes": 60 })).unwrap())
+                .unwrap()
+                .id
+        };
+        let (pinned, unpinned) = (create(), create());
+        let response = admin_pin_paste(State(db.clone()), Path(pinned.clone())).await.into_response();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        for id in [&pinned, &unpinned] {
+            crate::db::tests::expire_paste(&db, id);
+        }
+
+        assert_eq!(db.purge_expired().unwrap().expired, 1);
+        let kept = db.get_paste(&pinned).unwrap().unwrap();
+        assert!(kept.pinned);
+        assert_eq!(kept.expires_at, None, "pinned pastes report no expiry");
+        assert!(db.get_paste(&unpinned).unwrap().is_none(), "the purge deleted it");
+
+        // Unpinned again, its past expiry applies
+        let response = admin_unpin_paste(State(db.clone()), Path(pinned.clone())).await.into_response();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(db.purge_expired().unwrap().expired, 1);
+        let (status, _) = status_and_json(admin_pin_paste(State(db.clone()), Path(pinned)).await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn admin_view_has_the_metadata_the_public_one_hides() {
+        let config = Config { allow_plaintext_pastes: true, ..Config::default() };
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let mut create: crate::db::CreatePasteData = serde_json::from_value(serde_json::json!({
+            "data": "report me",
+            "encryption_version": 0,
+            "tags": ["abuse"],
+            "max_views": 5,
+        }))
+        .unwrap();
+        create.creator_hash = Some("c0ffee".to_string());
+        let paste = db.create_paste(create).unwrap();
+        db.get_paste(&paste.id).unwrap();
+
+        let (status, details) = status_and_json(admin_get_paste(State(db.clone()), Path(paste.id.clone())).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(details["creator_hash"], "c0ffee");
+        assert_eq!(details["has_edit_key"], true);
+        assert_eq!(details["size_bytes"], "report me".len());
+        assert_eq!(details["views"], 1);
+        assert_eq!(details["max_views"], 5);
+        assert_eq!(details["tags"], serde_json::json!(["abuse"]));
+        assert!(details.get("data").is_none() && details.get("edit_key_hash").is_none(), "no content or key hash");
+
+        let public = serde_json::to_value(db.get_paste(&paste.id).unwrap().unwrap()).unwrap();
+        for field in ["creator_hash", "has_edit_key", "size_bytes", "deleted_at"] {
+            assert!(public.get(field).is_none(), "{} is admin-only", field);
+        }
+
+        let (status, _) = status_and_json(admin_get_paste(State(db), Path("nosuch".to_string())).await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn purge_reports_what_it_removed_by_category() {
+        let options = Config { allow_plaintext_pastes: true, trash_retention_secs: 3600, ..Config::default() }.paste_options();
+        let db = Arc::new(Database::in_memory(options).unwrap());
+        let create = || db.create_paste(serde_json::from_value(serde_json::json!({ "data": "old", "encryption_version": 0 })).unwrap()).unwrap();
+        let (expired, also_expired, trashed, kept) = (create(), create(), create(), create());
+        crate::db::tests::expire_paste(&db, &expired.id);
+        crate::db::tests::expire_paste(&db, &also_expired.id);
+        db.delete_paste_with_key(&trashed.id, crate::db::DeletePasteData { edit_key: trashed.edit_key.clone().unwrap() }).unwrap();
+        crate::db::tests::backdate_trashed_paste(&db, &trashed.id, 7200);
+
+        let maintenance = Arc::new(Maintenance::new(1));
+        let purge = || admin_purge_expired(State(db.clone()), Extension(maintenance.clone()));
+        let (status, body) = status_and_json(purge().await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["purged"], 3);
+        assert_eq!(body["removed"], serde_json::json!({ "expired": 2, "trashed": 1, "evicted": 0 }));
+        for id in [&expired.id, &also_expired.id, &trashed.id] {
+            assert!(db.get_paste(id).unwrap().is_none(), "{id} is gone");
+        }
+        assert!(db.get_paste(&kept.id).unwrap().is_some());
+
+        let (_, body) = status_and_json(purge().await).await;
+        assert_eq!(body["purged"], 0, "running it again is harmless");
+    }
+
+    #[tokio::test]
+    async fn reported_pastes_are_listed_until_dismissed() {
+        let config = Config { allow_plaintext_pastes: true, ..Config::default() };
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let paste = db.create_paste(serde_json::from_value(serde_json::json!({ "data": "spam", "encryption_version": 0 })).unwrap()).unwrap();
+        db.report_paste(&paste.id, "spam", "reporter", 0).unwrap();
+
+        let list = || admin_reported_pastes(State(db.clone()), Query(ReportsQuery { limit: 50 }));
+        let (status, body) = status_and_json(list().await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["pastes"][0]["paste_id"], paste.id);
+        assert_eq!(body["pastes"][0]["reports"], 1);
+        assert_eq!(body["pastes"][0]["reasons"], serde_json::json!(["spam"]));
+
+        let (status, body) = status_and_json(admin_dismiss_reports(State(db.clone()), Path(paste.id.clone())).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["dismissed"], 1);
+        let (_, body) = status_and_json(list().await).await;
+        assert_eq!(body["pastes"], serde_json::json!([]));
+    }
+}
@@ -1,3 +1,4 @@
 pub mod admin;
 pub mod paste;
+pub mod stats;
 pub mod workspace;
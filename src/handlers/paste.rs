@@ -1,83 +1,1371 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
-    Json,
+    body::{Body, Bytes},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
-use std::sync::Arc;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
 
-use crate::db::{Database, DbError, CreatePasteData, UpdatePasteData, DeletePasteData};
-use crate::error::json_error;
+use crate::config::Config;
+use crate::db::{Database, DbError, AppendPasteData, CreatePasteData, Encoding, IdempotentCreate, RawData, UpdatePasteData, DeletePasteData, Visibility, ENCRYPTION_VERSION_NONE};
+use crate::error::{access_denied, json_error};
+use crate::extract::{BodyBytes, JsonBody};
+use crate::language;
+use crate::multipart::{self, MultipartError};
+use crate::net::{self, mask_ip};
+use crate::models::batch::{BatchItemResult, BatchResults};
+use crate::models::paste::{BatchGetQuery, PasteDiffResponse, QrQuery, ReadTokenRequest, ReadTokenResponse, ReportPasteRequest, TaggedPasteItem, TaggedPasteListResponse, ValidatePasteRequest};
+use crate::qr::QrCode;
+use crate::read_token::{self, ReadTokenError};
+
+/// Maximum number of pastes accepted by a single batch create or get request.
+pub const MAX_BATCH_PASTES: usize = 20;
 
 // Handler for creating a new paste
 pub async fn create_paste(
     State(db): State<Arc<Database>>,
-    Json(payload): Json<CreatePasteData>,
+    Extension(config): Extension<Arc<Config>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    JsonBody(mut payload): JsonBody<CreatePasteData>,
 ) -> impl IntoResponse {
-    // Validate request
-    if payload.data.is_empty() {
-        return (StatusCode::BAD_REQUEST, Json(json_error("Data is required"))).into_response();
+    payload.creator_hash = creator_hash(&config, client);
+
+    let idempotency_key = match idempotency_key(&config, &headers) {
+        Ok(key) => key,
+        Err(message) => {
+            let body = serde_json::json!({ "error": message, "code": "invalid_idempotency_key" });
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
+    };
+
+    // A replay doesn't create anything, so it isn't charged again
+    let scope = client.ip().to_canonical().to_string();
+    let replaying = match idempotency_key {
+        Some(key) => db.idempotency_key_exists(&scope, key).unwrap_or_else(|e| {
+            tracing::error!("Database error checking idempotency key: {}", e);
+            false
+        }),
+        None => false,
+    };
+    if !replaying && let Some(response) = charge_daily_quota(&db, &config, client, 1) {
+        return response;
+    }
+
+    // Create the paste, or replay the original create for a repeated key
+    let result = match idempotency_key {
+        Some(key) => db
+            .create_paste_idempotent(payload, &scope, key)
+            .map(|outcome| match outcome {
+                IdempotentCreate::Created(paste) => (paste, false),
+                IdempotentCreate::Replayed(paste) => (paste, true),
+            }),
+        None => db.create_paste(payload).map(|paste| (paste, false)),
+    };
+
+    match result {
+        Ok((mut paste, replayed)) => {
+            paste.url = paste_url(&config, &headers, &paste.id);
+            let mut response = (StatusCode::CREATED, Json(paste)).into_response();
+            if replayed {
+                response.headers_mut().insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+            }
+            response
+        }
+        Err(err) => {
+            let (status, body) = create_error_body(&err);
+            (status, Json(body)).into_response()
+        }
+    }
+}
+
+// Count `creates` against the client's DAILY_CREATE_QUOTA, returning the response
+// to send instead when that would exceed it. Clients are keyed like the rate limiter, so an IPv6
+// subnet shares one quota.
+fn charge_daily_quota(db: &Database, config: &Config, client: SocketAddr, creates: u64) -> Option<Response> {
+    if config.daily_create_quota == 0 {
+        return None;
+    }
+
+    let key = mask_ip(client.ip(), config.rate_limit_ipv4_prefix, config.rate_limit_ipv6_prefix).to_string();
+    match db.increment_daily_quota(&key, creates, config.daily_create_quota) {
+        Ok(Some(_)) => None,
+        Ok(None) => {
+            let message = format!(
+                "Daily quota of {} pastes exceeded; it resets at midnight UTC",
+                config.daily_create_quota
+            );
+            let body = serde_json::json!({ "error": message, "code": "quota_exceeded" });
+            let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(secs_until_utc_midnight()));
+            Some(response)
+        }
+        Err(e) => {
+            tracing::error!("Database error charging daily quota: {}", e);
+            Some((StatusCode::INTERNAL_SERVER_ERROR, Json(json_error("Failed to create paste"))).into_response())
+        }
+    }
+}
+
+// Keyed hash of the client address to store with a new paste, when CREATOR_HASH_SECRET is set
+fn creator_hash(config: &Config, client: SocketAddr) -> Option<String> {
+    config
+        .creator_hash_secret
+        .as_deref()
+        .map(|secret| net::creator_hash(secret, client.ip()))
+}
+
+// Key for reporter hashes when CREATOR_HASH_SECRET isn't set. It changes on restart,
+// which only forgets who already reported what.
+static REPORTER_KEY: OnceLock<String> = OnceLock::new();
+
+// Keyed hash of the reporter's subnet, grouped like the rate limits so a rotating
+// IPv6 address still counts as one reporter
+fn reporter_hash(config: &Config, client: SocketAddr) -> String {
+    let key = config.creator_hash_secret.as_deref().unwrap_or_else(|| {
+        REPORTER_KEY.get_or_init(|| {
+            let bytes: [u8; 32] = rand::random();
+            URL_SAFE_NO_PAD.encode(bytes)
+        })
+    });
+    let subnet = mask_ip(client.ip(), config.rate_limit_ipv4_prefix, config.rate_limit_ipv6_prefix);
+    net::creator_hash(key, subnet)
+}
+
+// Record a content read in the access log, when ACCESS_LOG_ENABLED is set
+fn record_read(db: &Database, config: &Config, id: &str, client: SocketAddr, headers: &HeaderMap) {
+    if !config.access_log_enabled {
+        return;
+    }
+    let user_agent = headers.get(header::USER_AGENT).and_then(|value| value.to_str().ok());
+    db.record_access(id, creator_hash(config, client).as_deref(), user_agent);
+}
+
+fn secs_until_utc_midnight() -> i64 {
+    let now = chrono::Utc::now();
+    let midnight = (now.date_naive() + chrono::Days::new(1)).and_time(chrono::NaiveTime::MIN).and_utc();
+    (midnight - now).num_seconds().max(1)
+}
+
+// Set on a create response that replays an earlier request with the same key
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+// Longest accepted `Idempotency-Key`, enough for a UUID or a client-built key
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+// The request's `Idempotency-Key`, or `None` when it has none or the feature is
+// disabled. Keys must be 1-255 visible ASCII characters.
+fn idempotency_key<'a>(config: &Config, headers: &'a HeaderMap) -> Result<Option<&'a str>, String> {
+    if config.idempotency_ttl_secs == 0 {
+        return Ok(None);
+    }
+    let Some(value) = headers.get("idempotency-key") else {
+        return Ok(None);
+    };
+
+    let key = value.to_str().map(str::trim).unwrap_or_default();
+    if key.is_empty()
+        || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH
+        || !key.bytes().all(|b| b.is_ascii_graphic())
+    {
+        return Err(format!(
+            "Idempotency-Key must be 1-{} visible ASCII characters",
+            MAX_IDEMPOTENCY_KEY_LENGTH
+        ));
+    }
+    Ok(Some(key))
+}
+
+// Handler for creating a plaintext paste from a multipart file upload
+pub async fn upload_paste(
+    State(db): State<Arc<Database>>,
+    Extension(config): Extension<Arc<Config>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    BodyBytes(body): BodyBytes,
+) -> impl IntoResponse {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let parts = match multipart::boundary(content_type).and_then(|boundary| multipart::parse(&body, &boundary)) {
+        Ok(parts) => parts,
+        Err(err @ MultipartError::MissingBoundary) => {
+            return (StatusCode::UNSUPPORTED_MEDIA_TYPE, Json(json_error(&err.to_string()))).into_response();
+        }
+        Err(err) => return (StatusCode::BAD_REQUEST, Json(json_error(&err.to_string()))).into_response(),
+    };
+
+    let Some(file) = parts.iter().find(|part| part.name == "file") else {
+        return (StatusCode::BAD_REQUEST, Json(json_error("A file part is required"))).into_response();
+    };
+
+    let data = match std::str::from_utf8(&file.data) {
+        Ok(data) => data.to_string(),
+        Err(_) => {
+            return (StatusCode::BAD_REQUEST, Json(json_error("File must be valid UTF-8 text"))).into_response();
+        }
+    };
+
+    // An explicit language wins, then the file's extension, then the configured default
+    let language = parts
+        .iter()
+        .find(|part| part.name == "language")
+        .and_then(|part| std::str::from_utf8(&part.data).ok())
+        .map(str::trim)
+        .filter(|language| !language.is_empty())
+        .map(str::to_string)
+        .or_else(|| file.filename.as_deref().and_then(language::from_filename).map(str::to_string));
+
+    let visibility = match parts.iter().find(|part| part.name == "visibility") {
+        Some(part) => match std::str::from_utf8(&part.data).map(|value| value.trim().parse::<Visibility>()) {
+            Ok(Ok(visibility)) => visibility,
+            Ok(Err(message)) => return (StatusCode::BAD_REQUEST, Json(json_error(&message))).into_response(),
+            Err(_) => {
+                return (StatusCode::BAD_REQUEST, Json(json_error("Visibility must be valid UTF-8 text"))).into_response();
+            }
+        },
+        None => Visibility::default(),
+    };
+
+    let payload = CreatePasteData {
+        data,
+        language,
+        burn_after_read: false,
+        expires_in_minutes: None,
+        encryption_version: ENCRYPTION_VERSION_NONE,
+        tags: Vec::new(),
+        max_views: None,
+        content_sha256: None,
+        visibility,
+        encoding: Encoding::default(),
+        validate_syntax: false,
+        creator_hash: creator_hash(&config, client),
+    };
+
+    if let Some(response) = charge_daily_quota(&db, &config, client, 1) {
+        return response;
     }
 
-    // Create the paste
     match db.create_paste(payload) {
-        Ok(paste) => (StatusCode::CREATED, Json(paste)).into_response(),
+        Ok(mut paste) => {
+            paste.url = paste_url(&config, &headers, &paste.id);
+            (StatusCode::CREATED, Json(paste)).into_response()
+        }
         Err(err) => {
-            let (status, message) = match &err {
-                DbError::CharacterLimitExceeded(actual, max) => {
-                    (StatusCode::BAD_REQUEST, format!("Content too large: {} bytes (maximum: {} bytes)", actual, max))
-                }
-                DbError::ClientEncryptionRequired => {
-                    (StatusCode::BAD_REQUEST, "Data is required".to_string())
-                }
-                DbError::IdGenerationFailed => {
-                    tracing::error!("Failed to generate unique ID after maximum retries");
-                    (StatusCode::INTERNAL_SERVER_ERROR, "Server error: please try again".to_string())
-                }
-                _ => {
-                    tracing::error!("Database error: {}", err);
-                    (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create paste".to_string())
-                }
-            };
-            (status, Json(json_error(&message))).into_response()
+            let (status, body) = create_error_body(&err);
+            (status, Json(body)).into_response()
+        }
+    }
+}
+
+// Handler for checking a create request without storing it
+pub async fn validate_paste(
+    State(db): State<Arc<Database>>,
+    JsonBody(payload): JsonBody<ValidatePasteRequest>,
+) -> impl IntoResponse {
+    let ValidatePasteRequest { id, paste } = payload;
+
+    if let Err((status, message)) = check_create_request(&db, id.as_deref()) {
+        return (status, Json(json_error(&message))).into_response();
+    }
+
+    match db.validate_paste(paste, id.as_deref()) {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "valid": true }))).into_response(),
+        Err(err) => {
+            let (status, body) = create_error_body(&err);
+            (status, Json(body)).into_response()
+        }
+    }
+}
+
+// Handler for creating several pastes in one all-or-nothing request
+pub async fn create_pastes_batch(
+    State(db): State<Arc<Database>>,
+    Extension(config): Extension<Arc<Config>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    JsonBody(mut payload): JsonBody<Vec<CreatePasteData>>,
+) -> impl IntoResponse {
+    if payload.is_empty() || payload.len() > MAX_BATCH_PASTES {
+        let message = format!("Request must include between 1 and {} pastes", MAX_BATCH_PASTES);
+        return (StatusCode::BAD_REQUEST, Json(json_error(&message))).into_response();
+    }
+
+    if let Some(index) = payload.iter().position(|item| item.data.is_empty()) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "Data is required", "index": index })),
+        ).into_response();
+    }
+
+    if let Some(response) = charge_daily_quota(&db, &config, client, payload.len() as u64) {
+        return response;
+    }
+
+    let creator_hash = creator_hash(&config, client);
+    for item in &mut payload {
+        item.creator_hash = creator_hash.clone();
+    }
+
+    match db.create_pastes_batch(payload) {
+        Ok(mut pastes) => {
+            for paste in &mut pastes {
+                paste.url = paste_url(&config, &headers, &paste.id);
+            }
+            (StatusCode::CREATED, Json(pastes)).into_response()
+        }
+        Err(DbError::BatchItemFailed(index, err)) => {
+            let (status, mut body) = create_error_body(&err);
+            body["index"] = index.into();
+            (status, Json(body)).into_response()
+        }
+        Err(err) => {
+            let (status, body) = create_error_body(&err);
+            (status, Json(body)).into_response()
+        }
+    }
+}
+
+// Status and JSON error body for a paste creation error. Policy rejections carry a
+// stable code so clients can tell them apart from validation failures, and failed
+// field checks are listed under `errors`, each with its field and code. A single
+// failure keeps its own code at the top level; several get `validation_failed`.
+fn create_error_body(err: &DbError) -> (StatusCode, serde_json::Value) {
+    if let DbError::ValidationFailed(failures) = err {
+        let errors: Vec<serde_json::Value> = failures
+            .iter()
+            .map(|failure| {
+                let (_, mut item) = create_error_body(&failure.error);
+                item["field"] = failure.field.into();
+                item
+            })
+            .collect();
+        let mut body = match errors.as_slice() {
+            [only] => only.clone(),
+            _ => {
+                let mut body = json_error(&err.to_string());
+                body["code"] = "validation_failed".into();
+                body
+            }
+        };
+        body["errors"] = errors.into();
+        return (StatusCode::BAD_REQUEST, body);
+    }
+
+    let (status, message) = create_error(err);
+    let mut body = json_error(&message);
+    let code = match err {
+        DbError::CharacterLimitExceeded(..) => Some("too_large"),
+        DbError::ClientEncryptionRequired => Some("required"),
+        DbError::PlaintextNotAllowed => Some("plaintext_not_allowed"),
+        DbError::UnsupportedEncryptionVersion(_) => Some("unsupported_encryption_version"),
+        DbError::InvalidTag(_) => Some("invalid_tag"),
+        DbError::TooManyTags(..) => Some("too_many_tags"),
+        DbError::InvalidMaxViews => Some("invalid_max_views"),
+        DbError::ContentRejected => Some("content_rejected"),
+        DbError::InvalidContentHash => Some("invalid_content_hash"),
+        DbError::ContentHashMismatch => Some("content_hash_mismatch"),
+        DbError::IdempotencyKeyReused => Some("idempotency_key_reused"),
+        DbError::TooManyLines(..) => Some("too_many_lines"),
+        DbError::SyntaxInvalid(..) => Some("syntax_invalid"),
+        DbError::TtlTooLong(..) => Some("ttl_too_long"),
+        DbError::InvalidBase64(..) => Some("invalid_base64"),
+        DbError::EncodingOfEncrypted => Some("encoding_of_encrypted"),
+        _ => None,
+    };
+    if let Some(code) = code {
+        body["code"] = code.into();
+    }
+    (status, body)
+}
+
+// Map a paste creation error to a status and client-facing message
+fn create_error(err: &DbError) -> (StatusCode, String) {
+    match err {
+        DbError::CharacterLimitExceeded(actual, max) => {
+            (StatusCode::BAD_REQUEST, format!("Content too large: {} bytes (maximum: {} bytes)", actual, max))
+        }
+        DbError::ClientEncryptionRequired => {
+            (StatusCode::BAD_REQUEST, "Data is required".to_string())
+        }
+        DbError::PlaintextNotAllowed => {
+            (StatusCode::BAD_REQUEST, "Plaintext pastes are disabled on this server".to_string())
+        }
+        DbError::UnsupportedEncryptionVersion(version) => {
+            (StatusCode::BAD_REQUEST, format!("Unsupported encryption version: {}", version))
+        }
+        DbError::InvalidTag(tag) => {
+            (StatusCode::BAD_REQUEST, format!("Invalid tag: {:?} (use 1-32 letters, digits, '-' or '_')", tag))
+        }
+        DbError::TooManyTags(actual, max) => {
+            (StatusCode::BAD_REQUEST, format!("Too many tags: {} (maximum: {})", actual, max))
+        }
+        DbError::TooManyLines(actual, max) => {
+            (StatusCode::BAD_REQUEST, format!("Too many lines: {} (maximum: {})", actual, max))
+        }
+        DbError::SyntaxInvalid(..) => (StatusCode::BAD_REQUEST, err.to_string()),
+        DbError::TtlTooLong(..)
+        | DbError::InvalidBase64(..)
+        | DbError::EncodingOfEncrypted
+        | DbError::ValidationFailed(..) => (StatusCode::BAD_REQUEST, err.to_string()),
+        DbError::InvalidMaxViews => {
+            (StatusCode::BAD_REQUEST, "max_views must be at least 1".to_string())
+        }
+        DbError::InvalidContentHash => {
+            (StatusCode::BAD_REQUEST, "content_sha256 must be 64 hex characters".to_string())
+        }
+        DbError::ContentHashMismatch => {
+            (StatusCode::BAD_REQUEST, "content_sha256 does not match the received data".to_string())
+        }
+        DbError::PasteAlreadyExists | DbError::IdConflict => {
+            (StatusCode::CONFLICT, "A paste with this ID already exists".to_string())
+        }
+        DbError::ContentRejected => {
+            (StatusCode::FORBIDDEN, "Paste content is not allowed on this server".to_string())
+        }
+        DbError::IdempotencyKeyReused => {
+            (StatusCode::UNPROCESSABLE_ENTITY, "Idempotency-Key was already used with a different request".to_string())
+        }
+        DbError::IdGenerationFailed => {
+            tracing::error!("Failed to generate unique ID after maximum retries");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Server error: please try again".to_string())
+        }
+        _ => {
+            tracing::error!("Database error: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create paste".to_string())
+        }
+    }
+}
+
+// Request-level checks of a dry run, ahead of the database checks
+fn check_create_request(db: &Database, custom_id: Option<&str>) -> Result<(), (StatusCode, String)> {
+    if let Some(id) = custom_id
+        && !db.is_valid_id(id)
+    {
+        return Err((StatusCode::BAD_REQUEST, "Invalid paste ID format".to_string()));
+    }
+
+    Ok(())
+}
+
+// Absolute link to a paste's page
+fn paste_url(config: &Config, headers: &HeaderMap, id: &str) -> Option<String> {
+    public_base_url(config, headers).map(|base| format!("{}/{}", base, id))
+}
+
+// PUBLIC_BASE_URL when set, otherwise built from Host and X-Forwarded-Proto
+// (default http). None when there's no usable Host.
+fn public_base_url(config: &Config, headers: &HeaderMap) -> Option<String> {
+    let base = match &config.public_base_url {
+        Some(base) => base.trim_end_matches('/').to_string(),
+        None => {
+            let host = headers
+                .get(header::HOST)
+                .and_then(|value| value.to_str().ok())
+                .filter(|host| !host.is_empty() && !host.contains(['/', '\\', '@']))?;
+            // Proxies may append to the header; the first value is the client-facing scheme
+            let proto = headers
+                .get("x-forwarded-proto")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .map(str::trim)
+                .filter(|proto| proto.eq_ignore_ascii_case("https") || proto.eq_ignore_ascii_case("http"))
+                .map(str::to_ascii_lowercase)
+                .unwrap_or_else(|| "http".to_string());
+            format!("{}://{}", proto, host)
+        }
+    };
+
+    Some(base)
+}
+
+// Strong ETag for stored paste content
+fn paste_etag(data: &str) -> String {
+    format!("\"{}\"", URL_SAFE_NO_PAD.encode(Sha256::digest(data.as_bytes())))
+}
+
+// Whether an If-None-Match header matches the ETag (weak comparison, as for GET)
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|candidate| candidate.trim())
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+// Quality the Accept header gives a media type (exact, `type/*` or `*/*`)
+fn accept_quality(accept: &str, media_type: &str) -> f32 {
+    let main_type = media_type.split('/').next().unwrap_or_default();
+    accept
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let range = params.next()?.trim();
+            let matches = range.eq_ignore_ascii_case(media_type)
+                || range == "*/*"
+                || range
+                    .strip_suffix("/*")
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(main_type));
+            if !matches {
+                return None;
+            }
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some(quality)
+        })
+        .fold(0.0, f32::max)
+}
+
+// Whether the client prefers the bare paste content over the JSON document.
+// JSON wins ties, so a missing Accept or `*/*` gets JSON.
+fn wants_plain_text(headers: &HeaderMap) -> bool {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if accept.trim().is_empty() {
+        return false;
+    }
+    accept_quality(accept, "text/plain") > accept_quality(accept, "application/json")
+}
+
+// Code and message for a paste that can't be read. Expired and trashed pastes are
+// told apart unless `hide_expiry_distinction` reports every case as not found.
+fn not_found_reason(hide_expiry_distinction: bool, err: Option<&DbError>) -> (&'static str, &'static str) {
+    match err {
+        Some(DbError::PasteExpired) if !hide_expiry_distinction => ("paste_expired", "Paste has expired"),
+        Some(DbError::PasteTrashed) if !hide_expiry_distinction => ("paste_deleted", "Paste has been deleted"),
+        _ => ("paste_not_found", "Paste not found"),
+    }
+}
+
+fn not_found_body(hide_expiry_distinction: bool, err: Option<&DbError>) -> serde_json::Value {
+    let (code, message) = not_found_reason(hide_expiry_distinction, err);
+    let mut body = json_error(message);
+    body["code"] = code.into();
+    body
+}
+
+// Whether reading the paste is refused because it's private and the request lacks
+// its edit key in `X-Edit-Key`. Refusals are a 403, or a plain 404 under
+// `hide_forbidden`.
+fn read_access(db: &Database, config: &Config, id: &str, headers: &HeaderMap) -> Result<(), (StatusCode, serde_json::Value)> {
+    let edit_key = headers.get("x-edit-key").and_then(|value| value.to_str().ok());
+    match db.check_read_access(id, edit_key) {
+        Ok(()) => Ok(()),
+        Err(DbError::PastePrivate) if config.hide_forbidden => {
+            Err((StatusCode::NOT_FOUND, not_found_body(config.hide_expiry_distinction, None)))
+        }
+        Err(err @ DbError::PastePrivate) => {
+            let mut body = json_error(&err.to_string());
+            body["code"] = "paste_private".into();
+            Err((StatusCode::FORBIDDEN, body))
+        }
+        Err(err) => {
+            tracing::error!("Database error checking read access: {}", err);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, json_error("Failed to get paste")))
         }
     }
 }
 
 // Handler for getting a paste by ID
-pub async fn get_paste(State(db): State<Arc<Database>>, Path(id): Path<String>) -> impl IntoResponse {
-    // Validate ID format (alphanumeric, 6-16 chars)
-    if id.len() < 6 || id.len() > 16 || !id.chars().all(|c| c.is_alphanumeric()) {
+pub async fn get_paste(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !db.is_valid_id(&id) {
         return (
             StatusCode::BAD_REQUEST,
             Json(json_error("Invalid paste ID format")),
         ).into_response();
     }
+    if let Err((status, body)) = read_access(&db, &config, &id, &headers) {
+        return (status, Json(body)).into_response();
+    }
 
-    match db.get_paste(&id) {
-        Some(paste) => (StatusCode::OK, Json(paste)).into_response(),
-        None => (
+    paste_response(&db, &config, &id, client, &headers, false)
+}
+
+// Handler for reading a paste through a signed read token instead of its ID
+pub async fn get_paste_by_token(
+    State(db): State<Arc<Database>>,
+    Path(token): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(secret) = &config.read_token_secret else {
+        return (StatusCode::NOT_FOUND, Json(json_error("Read tokens are not enabled"))).into_response();
+    };
+
+    match read_token::verify_read_token(secret, &token) {
+        Ok(id) => paste_response(&db, &config, &id, client, &headers, true),
+        Err(err) => {
+            let (status, code) = match err {
+                ReadTokenError::Invalid => (StatusCode::FORBIDDEN, "invalid_read_token"),
+                ReadTokenError::Expired => (StatusCode::GONE, "read_token_expired"),
+            };
+            let mut body = json_error(&err.to_string());
+            body["code"] = code.into();
+            (status, Json(body)).into_response()
+        }
+    }
+}
+
+// The response to reading a paste, as JSON or text/plain with a strong ETag.
+// `via_token` marks a read through a read token, which caches must not keep.
+fn paste_response(db: &Database, config: &Config, id: &str, client: SocketAddr, headers: &HeaderMap, via_token: bool) -> Response {
+    // A revalidation that still matches doesn't count as a view
+    match db.get_paste_unless(id, |data| if_none_match(headers, &paste_etag(data))) {
+        Ok(Some(paste)) => {
+            let etag = paste_etag(&paste.data);
+            let vary = (header::VARY, "Accept".to_string());
+            let credentialed = via_token || paste.visibility == Visibility::Private || headers.contains_key("x-edit-key");
+            let limited_reads = paste.burn_after_read || paste.max_views.is_some();
+            let cache_control = (header::CACHE_CONTROL, paste_cache_control(config, limited_reads, credentialed, paste.expires_at));
+            if if_none_match(headers, &etag) {
+                return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag), vary, cache_control]).into_response();
+            }
+            record_read(db, config, id, client, headers);
+            if wants_plain_text(headers) {
+                let content_type = (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string());
+                return (StatusCode::OK, [(header::ETAG, etag), vary, cache_control, content_type], paste.data).into_response();
+            }
+            (StatusCode::OK, [(header::ETAG, etag), vary, cache_control], Json(paste)).into_response()
+        }
+        Ok(None) => (
             StatusCode::NOT_FOUND,
-            Json(json_error("Paste not found")),
+            Json(not_found_body(config.hide_expiry_distinction, None)),
         ).into_response(),
+        Err(err @ (DbError::PasteExpired | DbError::PasteTrashed)) => (
+            StatusCode::NOT_FOUND,
+            Json(not_found_body(config.hide_expiry_distinction, Some(&err))),
+        ).into_response(),
+        Err(err) => {
+            tracing::error!("Database error during get: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to get paste")),
+            ).into_response()
+        }
+    }
+}
+
+// Cache-Control for a paste read. Reads that use the paste up (burn after reading,
+// a view limit) or carry credentials (an edit key, a read token) must not be
+// stored anywhere. Others are `immutable` for PASTE_CACHE_MAX_AGE_SECS (a year by
+// default), but never past the paste's expiry. At 0 caches must revalidate every time.
+fn paste_cache_control(config: &Config, limited_reads: bool, credentialed: bool, expires_at: Option<DateTime<Utc>>) -> String {
+    if limited_reads || credentialed {
+        return "private, no-store".to_string();
+    }
+
+    let until_expiry = expires_at.map(|expires_at| (expires_at - Utc::now()).num_seconds().max(0) as u64);
+    match (config.paste_cache_max_age_secs, until_expiry) {
+        (0, _) => "no-cache".to_string(),
+        (_, Some(0)) => "private, no-store".to_string(),
+        (max_age, until_expiry) => format!("public, max-age={}, immutable", max_age.min(until_expiry.unwrap_or(u64::MAX))),
+    }
+}
+
+// Handler for a paste's stored data as `text/plain`, without the JSON envelope
+pub async fn get_paste_raw(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    serve_raw(db, id, &config, client, &headers, false)
+}
+
+// Handler for downloading a paste's stored data as a file named after its ID and language
+pub async fn download_paste(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    serve_raw(db, id, &config, client, &headers, true)
+}
+
+// Send a paste's stored data, inline as text or as an `attachment` download. Both
+// count the view the same way.
+fn serve_raw(db: Arc<Database>, id: String, config: &Config, client: SocketAddr, headers: &HeaderMap, download: bool) -> Response {
+    if !db.is_valid_id(&id) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Invalid paste ID format")),
+        ).into_response();
+    }
+    if let Err((status, body)) = read_access(&db, config, &id, headers) {
+        return (status, Json(body)).into_response();
+    }
+
+    let (data, info) = match db.get_raw_paste(&id) {
+        Ok(Some(opened)) => opened,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(not_found_body(config.hide_expiry_distinction, None)),
+            ).into_response();
+        }
+        Err(err @ (DbError::PasteExpired | DbError::PasteTrashed)) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(not_found_body(config.hide_expiry_distinction, Some(&err))),
+            ).into_response();
+        }
+        Err(err) => {
+            tracing::error!("Database error during raw get: {}", err);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to get paste")),
+            ).into_response();
+        }
+    };
+    record_read(&db, config, &id, client, headers);
+    let cache_control = paste_cache_control(config, info.limited_reads, headers.contains_key("x-edit-key"), info.expires_at);
+    let (language, encoding) = (info.language, info.encoding);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_str(&cache_control).unwrap());
+    if download {
+        // Paste IDs are alphanumeric, so the file name needs no quoting
+        let disposition = format!("attachment; filename=\"{}.{}\"", id, language::extension(&language));
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"));
+        headers.insert(header::CONTENT_DISPOSITION, HeaderValue::from_str(&disposition).unwrap());
+    } else if encoding == Encoding::Base64 {
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"));
+    } else {
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8"));
+    }
+
+    let data = match data {
+        RawData::Whole(data) => data,
+        RawData::Chunks(mut chunks) => {
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(chunks.size()));
+            // A failed chunk ends the body with an error, so the client sees a
+            // truncated response rather than a short one that looks complete
+            let chunks = std::iter::from_fn(move || chunks.next_chunk().transpose());
+            return (StatusCode::OK, headers, Body::from_stream(futures_util::stream::iter(chunks))).into_response();
+        }
+    };
+
+    // Base64 pastes are served as the bytes they encode. Pastes are capped at
+    // MAX_PASTE_BYTES, so decoding one whole is fine.
+    if encoding == Encoding::Base64 {
+        return match encoding.decode(&data) {
+            Ok(bytes) => (StatusCode::OK, headers, bytes).into_response(),
+            Err(e) => {
+                tracing::error!("Stored base64 paste {} does not decode: {}", id, e);
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error("Failed to get paste"))).into_response()
+            }
+        };
+    }
+
+    (StatusCode::OK, headers, data).into_response()
+}
+
+// Handler for HEAD: whether a paste exists, without reading it or counting a view
+pub async fn head_paste(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+) -> StatusCode {
+    if !db.is_valid_id(&id) {
+        return StatusCode::BAD_REQUEST;
+    }
+    if let Err((status, _)) = read_access(&db, &config, &id, &headers) {
+        return status;
+    }
+
+    match db.paste_is_live(&id) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(err) => {
+            tracing::error!("Database error during existence check: {}", err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+// Handler for getting a paste's metadata without its content
+pub async fn get_paste_meta(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !db.is_valid_id(&id) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Invalid paste ID format")),
+        ).into_response();
+    }
+    if let Err((status, body)) = read_access(&db, &config, &id, &headers) {
+        return (status, Json(body)).into_response();
+    }
+
+    match db.get_paste_meta(&id) {
+        Ok(Some(meta)) => (StatusCode::OK, Json(meta)).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(not_found_body(config.hide_expiry_distinction, None)),
+        ).into_response(),
+        Err(err @ (DbError::PasteExpired | DbError::PasteTrashed)) => (
+            StatusCode::NOT_FOUND,
+            Json(not_found_body(config.hide_expiry_distinction, Some(&err))),
+        ).into_response(),
+        Err(err) => {
+            tracing::error!("Database error during metadata get: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to get paste")),
+            ).into_response()
+        }
+    }
+}
+
+// Default and bounds of the QR code image's side, in pixels
+const DEFAULT_QR_PIXELS: u32 = 256;
+const MIN_QR_PIXELS: u32 = 64;
+const MAX_QR_PIXELS: u32 = 2048;
+
+// Handler for a QR code of a paste's link, as SVG. It checks the paste exists, like
+// the metadata endpoint, so it doesn't count a view or consume the paste. Encrypted
+// pastes get their link without the key fragment, which only the client has.
+pub async fn paste_qr(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    Query(query): Query<QrQuery>,
+) -> impl IntoResponse {
+    if !db.is_valid_id(&id) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Invalid paste ID format")),
+        ).into_response();
+    }
+    if let Err((status, body)) = read_access(&db, &config, &id, &headers) {
+        return (status, Json(body)).into_response();
+    }
+
+    match db.get_paste_meta(&id) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (StatusCode::NOT_FOUND, Json(not_found_body(config.hide_expiry_distinction, None))).into_response();
+        }
+        Err(err @ (DbError::PasteExpired | DbError::PasteTrashed)) => {
+            return (StatusCode::NOT_FOUND, Json(not_found_body(config.hide_expiry_distinction, Some(&err)))).into_response();
+        }
+        Err(err) => {
+            tracing::error!("Database error during QR code get: {}", err);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error("Failed to get paste"))).into_response();
+        }
+    }
+
+    let Some(url) = paste_url(&config, &headers, &id) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Can't build the paste link without PUBLIC_BASE_URL or a Host header")),
+        ).into_response();
+    };
+    let Some(code) = QrCode::encode(url.as_bytes()) else {
+        tracing::error!("Paste link too long for a QR code: {} bytes", url.len());
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json_error("Paste link is too long for a QR code")),
+        ).into_response();
+    };
+
+    let pixels = query.size.unwrap_or(DEFAULT_QR_PIXELS).clamp(MIN_QR_PIXELS, MAX_QR_PIXELS);
+    (
+        [(header::CONTENT_TYPE, "image/svg+xml")],
+        code.svg(pixels),
+    ).into_response()
+}
+
+// Handler for forking a paste: a new paste with the source's content and language
+pub async fn fork_paste(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !db.is_valid_id(&id) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Invalid paste ID format")),
+        ).into_response();
+    }
+    if let Err((status, body)) = read_access(&db, &config, &id, &headers) {
+        return (status, Json(body)).into_response();
+    }
+
+    if let Some(response) = charge_daily_quota(&db, &config, client, 1) {
+        return response;
+    }
+
+    match db.fork_paste(&id, creator_hash(&config, client)) {
+        Ok(mut paste) => {
+            paste.url = paste_url(&config, &headers, &paste.id);
+            (StatusCode::CREATED, Json(paste)).into_response()
+        }
+        Err(DbError::PasteNotFound) => (
+            StatusCode::NOT_FOUND,
+            Json(not_found_body(config.hide_expiry_distinction, None)),
+        ).into_response(),
+        Err(err @ (DbError::PasteExpired | DbError::PasteTrashed)) => (
+            StatusCode::NOT_FOUND,
+            Json(not_found_body(config.hide_expiry_distinction, Some(&err))),
+        ).into_response(),
+        Err(err) => {
+            let (status, body) = create_error_body(&err);
+            (status, Json(body)).into_response()
+        }
+    }
+}
+
+// Handler for a unified diff between two plaintext pastes
+pub async fn diff_pastes(
+    State(db): State<Arc<Database>>,
+    Path((id, other_id)): Path<(String, String)>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    for id in [&id, &other_id] {
+        if !db.is_valid_id(id) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json_error("Invalid paste ID format")),
+            ).into_response();
+        }
+        if let Err((status, body)) = read_access(&db, &config, id, &headers) {
+            return (status, Json(body)).into_response();
+        }
+    }
+
+    match db.diff_pastes(&id, &other_id) {
+        Ok(Some(diff)) => Json(PasteDiffResponse {
+            from: id,
+            to: other_id,
+            added: diff.added,
+            removed: diff.removed,
+            diff: diff.unified,
+        }).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(not_found_body(config.hide_expiry_distinction, None)),
+        ).into_response(),
+        Err(err @ (DbError::PasteExpired | DbError::PasteTrashed)) => (
+            StatusCode::NOT_FOUND,
+            Json(not_found_body(config.hide_expiry_distinction, Some(&err))),
+        ).into_response(),
+        Err(err @ DbError::DiffOfEncrypted) => {
+            let mut body = json_error(&err.to_string());
+            body["code"] = "diff_of_encrypted".into();
+            (StatusCode::CONFLICT, Json(body)).into_response()
+        }
+        Err(err) => {
+            tracing::error!("Database error during diff: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to diff pastes")),
+            ).into_response()
+        }
+    }
+}
+
+// Longest report reason accepted, in characters
+const MAX_REPORT_REASON_CHARS: usize = 1000;
+
+// Handler for reporting a paste for abuse. Reporters see no more than readers do:
+// a private paste needs its edit key, and gone pastes are a 404.
+pub async fn report_paste(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    JsonBody(payload): JsonBody<ReportPasteRequest>,
+) -> impl IntoResponse {
+    if !db.is_valid_id(&id) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Invalid paste ID format")),
+        ).into_response();
+    }
+
+    let reason = payload.reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_REPORT_REASON_CHARS {
+        let message = format!("Reason must be 1-{} characters", MAX_REPORT_REASON_CHARS);
+        let mut body = json_error(&message);
+        body["code"] = "invalid_reason".into();
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    }
+
+    if let Err((status, body)) = read_access(&db, &config, &id, &headers) {
+        return (status, Json(body)).into_response();
+    }
+    match db.get_paste_meta(&id) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (StatusCode::NOT_FOUND, Json(not_found_body(config.hide_expiry_distinction, None))).into_response();
+        }
+        Err(err @ (DbError::PasteExpired | DbError::PasteTrashed)) => {
+            return (StatusCode::NOT_FOUND, Json(not_found_body(config.hide_expiry_distinction, Some(&err)))).into_response();
+        }
+        Err(err) => {
+            tracing::error!("Database error during report: {}", err);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error("Failed to report paste"))).into_response();
+        }
+    }
+
+    match db.report_paste(&id, reason, &reporter_hash(&config, client), config.report_rate_limit) {
+        Ok(()) => {
+            tracing::info!("Paste {} reported", id);
+            (StatusCode::ACCEPTED, Json(serde_json::json!({ "success": true }))).into_response()
+        }
+        Err(err @ DbError::AlreadyReported) => {
+            let mut body = json_error(&err.to_string());
+            body["code"] = "already_reported".into();
+            (StatusCode::CONFLICT, Json(body)).into_response()
+        }
+        Err(err @ DbError::ReportLimitExceeded(_)) => {
+            let mut body = json_error(&err.to_string());
+            body["code"] = "report_limit_exceeded".into();
+            (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response()
+        }
+        Err(err) => {
+            tracing::error!("Database error during report: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error("Failed to report paste"))).into_response()
+        }
+    }
+}
+
+// Handler for minting a signed read token for a paste, authorized by its edit key
+pub async fn create_read_token(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    Json(payload): Json<ReadTokenRequest>,
+) -> impl IntoResponse {
+    let Some(secret) = &config.read_token_secret else {
+        return (StatusCode::NOT_FOUND, Json(json_error("Read tokens are not enabled"))).into_response();
+    };
+
+    if !db.is_valid_id(&id) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Invalid paste ID format")),
+        ).into_response();
+    }
+
+    if payload.edit_key.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Edit key is required")),
+        ).into_response();
+    }
+
+    if let Err(err) = db.check_edit_key(&id, &payload.edit_key) {
+        let (status, message) = match &err {
+            DbError::PasteNotFound => (StatusCode::NOT_FOUND, "Paste not found".to_string()),
+            DbError::InvalidEditKey => {
+                access_denied(config.hide_forbidden, StatusCode::FORBIDDEN, "Invalid edit key", "Paste not found")
+            }
+            _ => {
+                tracing::error!("Database error checking edit key: {}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create read token".to_string())
+            }
+        };
+        return (status, Json(json_error(&message))).into_response();
+    }
+
+    let expires_at = payload
+        .expires_in_minutes
+        .map(|minutes| chrono::Utc::now() + chrono::Duration::minutes(minutes as i64));
+    match read_token::sign_read_token(secret, &id, expires_at) {
+        Ok(token) => {
+            let url = public_base_url(&config, &headers).map(|base| format!("{}/v1/pastes/token/{}", base, token));
+            (StatusCode::CREATED, Json(ReadTokenResponse { token, url, expires_at })).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to sign read token: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to create read token")),
+            ).into_response()
+        }
+    }
+}
+
+// Handler for getting several pastes at once, with per-item results
+pub async fn get_pastes_batch(
+    State(db): State<Arc<Database>>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    Query(query): Query<BatchGetQuery>,
+) -> impl IntoResponse {
+    let ids: Vec<String> = query
+        .ids
+        .split(',')
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+
+    if ids.is_empty() || ids.len() > MAX_BATCH_PASTES {
+        let message = format!("Request must include between 1 and {} paste IDs", MAX_BATCH_PASTES);
+        return (StatusCode::BAD_REQUEST, Json(json_error(&message))).into_response();
+    }
+
+    let results = ids
+        .into_iter()
+        .map(|id| {
+            if !db.is_valid_id(&id) {
+                return BatchItemResult::failed(id, StatusCode::BAD_REQUEST, "Invalid paste ID format");
+            }
+            if let Err((status, body)) = read_access(&db, &config, &id, &headers) {
+                return BatchItemResult::failed(id, status, body["error"].as_str().unwrap_or("Failed to get paste"));
+            }
+            match db.get_paste(&id) {
+                Ok(Some(paste)) => BatchItemResult::ok(id, Some(paste)),
+                Ok(None) => BatchItemResult::failed(id, StatusCode::NOT_FOUND, "Paste not found"),
+                Err(err @ (DbError::PasteExpired | DbError::PasteTrashed)) => {
+                    let (_, message) = not_found_reason(config.hide_expiry_distinction, Some(&err));
+                    BatchItemResult::failed(id, StatusCode::NOT_FOUND, message)
+                }
+                Err(err) => {
+                    tracing::error!("Database error during batch get of {}: {}", id, err);
+                    BatchItemResult::failed(id, StatusCode::INTERNAL_SERVER_ERROR, "Failed to get paste")
+                }
+            }
+        })
+        .collect();
+
+    BatchResults { strict: query.strict.unwrap_or(false), results }.into_response()
+}
+
+// Handler for listing public pastes carrying a tag
+pub async fn get_pastes_by_tag(State(db): State<Arc<Database>>, Path(tag): Path<String>) -> impl IntoResponse {
+    let tag = match Database::normalize_tag(&tag) {
+        Ok(tag) => tag,
+        Err(_) => {
+            return (StatusCode::BAD_REQUEST, Json(json_error("Invalid tag format"))).into_response();
+        }
+    };
+
+    match db.list_pastes_by_tag(&tag) {
+        Ok(pastes) => {
+            let pastes = pastes
+                .into_iter()
+                .map(|paste| TaggedPasteItem {
+                    id: paste.id,
+                    language: paste.language,
+                    created_at: paste.created_at,
+                    encryption_version: paste.encryption_version,
+                    expires_at: paste.expires_at,
+                    visibility: paste.visibility,
+                })
+                .collect();
+            (StatusCode::OK, Json(TaggedPasteListResponse { tag, pastes })).into_response()
+        }
+        Err(err) => {
+            tracing::error!("Database error during tag listing: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error("Failed to list pastes"))).into_response()
+        }
+    }
+}
+
+// Handler for PUT on a paste: a `text/plain` body creates a paste at that ID
+// (when enabled), a JSON body is an update
+pub async fn put_paste(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    BodyBytes(body): BodyBytes,
+) -> Response {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+
+    // `curl -T` sends no Content-Type, so a bare body counts as text when creation is enabled
+    if mime.eq_ignore_ascii_case("text/plain") || (mime.is_empty() && config.allow_put_create) {
+        if !config.allow_put_create {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json_error("Creating pastes by PUT is disabled on this server")),
+            ).into_response();
+        }
+        return create_paste_by_put(db, id, &config, client, &headers, body).into_response();
+    }
+
+    if !mime.eq_ignore_ascii_case("application/json") {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Json(json_error("Expected Content-Type application/json or text/plain")),
+        ).into_response();
+    }
+
+    match Json::<UpdatePasteData>::from_bytes(&body) {
+        Ok(payload) => update_paste(State(db), Path(id), Extension(config), payload).await.into_response(),
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+// Whether the request carries `If-None-Match: *`, i.e. should only create and never replace
+fn if_none_match_any(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.trim() == "*")
+}
+
+// Create a plaintext paste at a custom ID from a raw PUT body. With `If-None-Match: *`
+// a taken ID is a failed precondition (412) rather than a conflict (409).
+fn create_paste_by_put(
+    db: Arc<Database>,
+    id: String,
+    config: &Config,
+    client: SocketAddr,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    if !db.is_valid_id(&id) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Invalid paste ID format")),
+        ).into_response();
+    }
+
+    let data = match String::from_utf8(body.to_vec()) {
+        Ok(data) if !data.is_empty() => data,
+        Ok(_) => return (StatusCode::BAD_REQUEST, Json(json_error("Data is required"))).into_response(),
+        Err(_) => {
+            return (StatusCode::BAD_REQUEST, Json(json_error("Body must be valid UTF-8 text"))).into_response();
+        }
+    };
+
+    if let Some(response) = charge_daily_quota(&db, config, client, 1) {
+        return response.into_response();
+    }
+
+    match db.create_paste_with_id(&id, data, creator_hash(config, client)) {
+        Ok(mut paste) => {
+            paste.url = paste_url(config, headers, &paste.id);
+            (StatusCode::CREATED, Json(paste)).into_response()
+        }
+        Err(DbError::PasteAlreadyExists | DbError::IdConflict) if if_none_match_any(headers) => {
+            let body = serde_json::json!({
+                "error": "A paste with this ID already exists (If-None-Match: *)",
+                "code": "precondition_failed",
+            });
+            (StatusCode::PRECONDITION_FAILED, Json(body)).into_response()
+        }
+        Err(err) => {
+            let (status, body) = create_error_body(&err);
+            (status, Json(body)).into_response()
+        }
+    }
+}
+
+// Handler for updating a paste
+pub async fn update_paste(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    Json(payload): Json<UpdatePasteData>,
+) -> impl IntoResponse {
+    // Validate ID format
+    if !db.is_valid_id(&id) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Invalid paste ID format")),
+        ).into_response();
+    }
+
+    // Validate request
+    if payload.data.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(json_error("Data is required"))).into_response();
+    }
+
+    if payload.edit_key.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(json_error("Edit key is required"))).into_response();
+    }
+
+    match db.update_paste(&id, payload) {
+        Ok(paste) => (StatusCode::OK, Json(paste)).into_response(),
+        Err(err) => {
+            let (status, message) = match &err {
+                DbError::PasteNotFound => {
+                    (StatusCode::NOT_FOUND, "Paste not found".to_string())
+                }
+                DbError::InvalidEditKey => {
+                    access_denied(config.hide_forbidden, StatusCode::FORBIDDEN, "Invalid edit key", "Paste not found")
+                }
+                DbError::CharacterLimitExceeded(actual, max) => {
+                    (StatusCode::BAD_REQUEST, format!("Content too large: {} bytes (maximum: {} bytes)", actual, max))
+                }
+                DbError::ClientEncryptionRequired => {
+                    (StatusCode::BAD_REQUEST, "Data is required".to_string())
+                }
+                DbError::ContentRejected
+                | DbError::InvalidContentHash
+                | DbError::ContentHashMismatch
+                | DbError::TooManyLines(..)
+                | DbError::InvalidBase64(..) => {
+                    let (status, body) = create_error_body(&err);
+                    return (status, Json(body)).into_response();
+                }
+                _ => {
+                    tracing::error!("Database error during update: {}", err);
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update paste".to_string())
+                }
+            };
+            (status, Json(json_error(&message))).into_response()
+        }
     }
 }
 
-// Handler for updating a paste
-pub async fn update_paste(
+// Handler for appending to a plaintext paste
+pub async fn append_paste(
     State(db): State<Arc<Database>>,
     Path(id): Path<String>,
-    Json(payload): Json<UpdatePasteData>,
+    Extension(config): Extension<Arc<Config>>,
+    JsonBody(payload): JsonBody<AppendPasteData>,
 ) -> impl IntoResponse {
-    // Validate ID format
-    if id.len() < 6 || id.len() > 16 || !id.chars().all(|c| c.is_alphanumeric()) {
+    if !db.is_valid_id(&id) {
         return (
             StatusCode::BAD_REQUEST,
             Json(json_error("Invalid paste ID format")),
         ).into_response();
     }
 
-    // Validate request
     if payload.data.is_empty() {
         return (StatusCode::BAD_REQUEST, Json(json_error("Data is required"))).into_response();
     }
@@ -86,7 +1374,7 @@ pub async fn update_paste(
         return (StatusCode::BAD_REQUEST, Json(json_error("Edit key is required"))).into_response();
     }
 
-    match db.update_paste(&id, payload) {
+    match db.append_paste(&id, payload) {
         Ok(paste) => (StatusCode::OK, Json(paste)).into_response(),
         Err(err) => {
             let (status, message) = match &err {
@@ -94,17 +1382,23 @@ pub async fn update_paste(
                     (StatusCode::NOT_FOUND, "Paste not found".to_string())
                 }
                 DbError::InvalidEditKey => {
-                    (StatusCode::FORBIDDEN, "Invalid edit key".to_string())
+                    access_denied(config.hide_forbidden, StatusCode::FORBIDDEN, "Invalid edit key", "Paste not found")
                 }
-                DbError::CharacterLimitExceeded(actual, max) => {
-                    (StatusCode::BAD_REQUEST, format!("Content too large: {} bytes (maximum: {} bytes)", actual, max))
+                DbError::AppendToEncrypted => {
+                    let mut body = json_error("Only plaintext pastes can be appended to; re-encrypt and update the whole paste instead");
+                    body["code"] = "append_to_encrypted".into();
+                    return (StatusCode::BAD_REQUEST, Json(body)).into_response();
                 }
-                DbError::ClientEncryptionRequired => {
-                    (StatusCode::BAD_REQUEST, "Data is required".to_string())
+                DbError::CharacterLimitExceeded(..)
+                | DbError::ContentRejected
+                | DbError::TooManyLines(..)
+                | DbError::InvalidBase64(..) => {
+                    let (status, body) = create_error_body(&err);
+                    return (status, Json(body)).into_response();
                 }
                 _ => {
-                    tracing::error!("Database error during update: {}", err);
-                    (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update paste".to_string())
+                    tracing::error!("Database error during append: {}", err);
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Failed to append to paste".to_string())
                 }
             };
             (status, Json(json_error(&message))).into_response()
@@ -116,10 +1410,11 @@ pub async fn update_paste(
 pub async fn delete_paste(
     State(db): State<Arc<Database>>,
     Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
     Json(payload): Json<DeletePasteData>,
 ) -> impl IntoResponse {
     // Validate ID format
-    if id.len() < 6 || id.len() > 16 || !id.chars().all(|c| c.is_alphanumeric()) {
+    if !db.is_valid_id(&id) {
         return (
             StatusCode::BAD_REQUEST,
             Json(json_error("Invalid paste ID format")),
@@ -142,7 +1437,7 @@ pub async fn delete_paste(
                     (StatusCode::NOT_FOUND, "Paste not found".to_string())
                 }
                 DbError::InvalidEditKey => {
-                    (StatusCode::FORBIDDEN, "Invalid edit key".to_string())
+                    access_denied(config.hide_forbidden, StatusCode::FORBIDDEN, "Invalid edit key", "Paste not found")
                 }
                 _ => {
                     tracing::error!("Database error during delete: {}", err);
@@ -153,3 +1448,1226 @@ pub async fn delete_paste(
         }
     }
 }
+
+// Handler for restoring a paste from the trash
+pub async fn restore_paste(
+    State(db): State<Arc<Database>>,
+    Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
+    Json(payload): Json<DeletePasteData>,
+) -> impl IntoResponse {
+    if !db.is_valid_id(&id) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Invalid paste ID format")),
+        ).into_response();
+    }
+
+    if payload.edit_key.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json_error("Edit key is required")),
+        ).into_response();
+    }
+
+    match db.restore_paste_with_key(&id, payload) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            let (status, message) = match &err {
+                DbError::PasteNotFound => {
+                    (StatusCode::NOT_FOUND, "Paste not found in trash".to_string())
+                }
+                DbError::InvalidEditKey => {
+                    access_denied(config.hide_forbidden, StatusCode::FORBIDDEN, "Invalid edit key", "Paste not found in trash")
+                }
+                _ => {
+                    tracing::error!("Database error during restore: {}", err);
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Failed to restore paste".to_string())
+                }
+            };
+            (status, Json(json_error(&message))).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn private_paste(db: &Database) -> (String, String) {
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({
+                "data": "secret",
+                "encryption_version": 0,
+                "visibility": "private",
+            })).unwrap())
+            .unwrap();
+        (paste.id, paste.edit_key.unwrap())
+    }
+
+    fn test_setup() -> (Database, Config) {
+        let config = Config { allow_plaintext_pastes: true, ..Config::default() };
+        let db = Database::in_memory(config.paste_options()).unwrap();
+        (db, config)
+    }
+
+    async fn status_and_json(response: impl IntoResponse) -> (StatusCode, serde_json::Value) {
+        let response = response.into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn large_raw_paste_reads_back_whole() {
+        let config = Config { allow_plaintext_pastes: true, max_paste_bytes: 1024 * 1024, ..Config::default() };
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let data: String = (0..20_000).map(|line| format!("line {}\n", line)).collect();
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": data, "encryption_version": 0 })).unwrap())
+            .unwrap();
+
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let response = serve_raw(db.clone(), paste.id.clone(), &config, client, &HeaderMap::new(), false);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain; charset=utf-8");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, data.as_bytes());
+        assert_eq!(db.get_paste_meta(&paste.id).unwrap().unwrap().views, 1);
+    }
+
+    #[tokio::test]
+    async fn large_raw_paste_streams_one_version_in_chunks() {
+        use futures_util::StreamExt;
+
+        let config = Config { allow_plaintext_pastes: true, max_paste_bytes: 1024 * 1024, ..Config::default() };
+        let dir = std::env::temp_dir().join(format!("rustybin-test-{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new_with_path(&dir, config.paste_options()).unwrap());
+        let data: String = (0..20_000).map(|line| format!("line {}\n", line)).collect();
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": data, "encryption_version": 0 })).unwrap())
+            .unwrap();
+
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let response = serve_raw(db.clone(), paste.id.clone(), &config, client, &HeaderMap::new(), false);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], data.len().to_string());
+        let mut chunks = response.into_body().into_data_stream();
+
+        let mut body = chunks.next().await.unwrap().unwrap().to_vec();
+        assert_eq!(body.len() as u64, crate::db::RAW_CHUNK_BYTES);
+
+        // An update landing mid-stream doesn't reach the chunks still to come
+        let update = UpdatePasteData {
+            data: "replaced".to_string(),
+            language: "plaintext".to_string(),
+            edit_key: paste.edit_key.clone().unwrap(),
+            content_sha256: None,
+        };
+        db.update_paste(&paste.id, update).unwrap();
+
+        let mut count = 1;
+        while let Some(chunk) = chunks.next().await {
+            body.extend_from_slice(&chunk.unwrap());
+            count += 1;
+        }
+        assert_eq!(count, data.len().div_ceil(crate::db::RAW_CHUNK_BYTES as usize));
+        assert_eq!(body, data.as_bytes());
+        assert_eq!(db.get_paste_meta(&paste.id).unwrap().unwrap().views, 1);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn database_failure_on_create_is_a_500() {
+        let (db, config) = test_setup();
+        let db = Arc::new(db);
+        crate::db::tests::break_pastes_table(&db);
+
+        let payload = serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap();
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let response = create_paste(State(db), Extension(Arc::new(config)), ConnectInfo(client), HeaderMap::new(), JsonBody(payload))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn creator_hash_groups_pastes_by_client_without_storing_the_ip() {
+        let config = Arc::new(Config {
+            allow_plaintext_pastes: true,
+            creator_hash_secret: Some("secret".to_string()),
+            ..Config::default()
+        });
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let create = |client: [u8; 4]| {
+            let payload = serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap();
+            let client = SocketAddr::from((client, 4000));
+            create_paste(State(db.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(payload))
+        };
+        for client in [[203, 0, 113, 9], [203, 0, 113, 9], [198, 51, 100, 4]] {
+            assert_eq!(create(client).await.into_response().status(), StatusCode::CREATED);
+        }
+
+        let same = net::creator_hash("secret", "203.0.113.9".parse().unwrap());
+        let other = net::creator_hash("secret", "198.51.100.4".parse().unwrap());
+        let stored = crate::db::tests::stored_values(&db);
+        let count = |hash: &str| stored.iter().filter(|value| **value == sqlite::Value::String(hash.to_string())).count();
+        assert_eq!((count(&same), count(&other)), (2, 1));
+
+        for value in &stored {
+            if let sqlite::Value::String(text) = value {
+                assert!(!text.contains("203.0.113.9") && !text.contains("198.51.100.4"), "raw IP stored: {}", text);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn read_token_statuses() {
+        let config = Arc::new(Config {
+            allow_plaintext_pastes: true,
+            read_token_secret: Some("secret".to_string()),
+            ..Config::default()
+        });
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": "shared", "encryption_version": 0 })).unwrap())
+            .unwrap();
+        let read = |token: String| {
+            let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+            get_paste_by_token(State(db.clone()), Path(token), Extension(config.clone()), ConnectInfo(client), HeaderMap::new())
+        };
+
+        let valid = read_token::sign_read_token("secret", &paste.id, None).unwrap();
+        assert_eq!(read(valid.clone()).await.into_response().status(), StatusCode::OK);
+
+        let tampered = read_token::sign_read_token("other-secret", &paste.id, None).unwrap();
+        assert_eq!(read(tampered).await.into_response().status(), StatusCode::FORBIDDEN);
+
+        let expired = read_token::sign_read_token("secret", &paste.id, Some(Utc::now() - chrono::Duration::minutes(1))).unwrap();
+        assert_eq!(read(expired).await.into_response().status(), StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn generated_ids_round_trip_for_any_length_and_charset() {
+        let configs = [
+            Config { allow_plaintext_pastes: true, paste_id_length: 32, ..Config::default() },
+            Config { allow_plaintext_pastes: true, paste_id_charset: crate::db::IdCharset::UrlSafe, ..Config::default() },
+        ];
+        for config in configs {
+            let config = Arc::new(config);
+            let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+            let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+
+            for _ in 0..20 {
+                let payload = serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap();
+                let response = create_paste(State(db.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(payload))
+                    .await
+                    .into_response();
+                assert_eq!(response.status(), StatusCode::CREATED);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                let id = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["id"].as_str().unwrap().to_string();
+                assert_eq!(id.len(), config.paste_id_length);
+
+                let response = get_paste(State(db.clone()), Path(id.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new())
+                    .await
+                    .into_response();
+                assert_eq!(response.status(), StatusCode::OK, "{}", id);
+            }
+
+            let workspace = db.create_workspace("hello".to_string(), false, None).unwrap();
+            let response = crate::handlers::workspace::get_workspace(State(db.clone()), Path(workspace.id.clone()))
+                .await
+                .into_response();
+            assert_eq!(response.status(), StatusCode::OK, "{}", workspace.id);
+
+            // An ID with `-` and `_` is looked up under url_safe and refused otherwise
+            let expected = match config.paste_id_charset {
+                crate::db::IdCharset::UrlSafe => StatusCode::NOT_FOUND,
+                crate::db::IdCharset::Alphanumeric => StatusCode::BAD_REQUEST,
+            };
+            let response = get_paste(State(db.clone()), Path("ab-_cd".to_string()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new())
+                .await
+                .into_response();
+            assert_eq!(response.status(), expected);
+        }
+    }
+
+    #[test]
+    fn lasting_pastes_are_cached_as_immutable() {
+        let config = Config::default();
+        assert_eq!(paste_cache_control(&config, false, false, None), "public, max-age=31536000, immutable");
+
+        // Never cached past the paste's expiry
+        let expires_at = Utc::now() + chrono::Duration::seconds(600);
+        let cache_control = paste_cache_control(&config, false, false, Some(expires_at));
+        let max_age: u64 = cache_control
+            .strip_prefix("public, max-age=")
+            .and_then(|rest| rest.strip_suffix(", immutable"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((595..=600).contains(&max_age), "{}", cache_control);
+
+        let config = Config { paste_cache_max_age_secs: 0, ..Config::default() };
+        assert_eq!(paste_cache_control(&config, false, false, None), "no-cache");
+    }
+
+    #[test]
+    fn limited_and_credentialed_reads_are_never_stored() {
+        let config = Config::default();
+        assert_eq!(paste_cache_control(&config, true, false, None), "private, no-store", "burn after reading or max_views");
+        assert_eq!(paste_cache_control(&config, false, true, None), "private, no-store", "edit key or read token");
+        assert_eq!(paste_cache_control(&config, false, false, Some(Utc::now())), "private, no-store", "expiring now");
+    }
+
+    #[tokio::test]
+    async fn paste_reads_send_cache_control_by_paste_type() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let create = |extra: serde_json::Value| {
+            let mut body = serde_json::json!({ "data": "hello", "encryption_version": 0 });
+            body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            db.create_paste(serde_json::from_value(body).unwrap()).unwrap()
+        };
+        let cases = [
+            (create(serde_json::json!({})), "public, max-age=31536000, immutable"),
+            (create(serde_json::json!({ "burn_after_read": true })), "private, no-store"),
+            (create(serde_json::json!({ "max_views": 5 })), "private, no-store"),
+        ];
+
+        for (paste, expected) in cases {
+            let response = get_paste(State(db.clone()), Path(paste.id.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new())
+                .await
+                .into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CACHE_CONTROL], expected, "{:?}", paste.id);
+        }
+
+        let paste = create(serde_json::json!({}));
+        let response = serve_raw(db.clone(), paste.id, &config, client, &HeaderMap::new(), false);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "public, max-age=31536000, immutable");
+
+        let (id, edit_key) = private_paste(&db);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-edit-key", HeaderValue::from_str(&edit_key).unwrap());
+        let response = serve_raw(db.clone(), id, &config, client, &headers, false);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "private, no-store");
+    }
+
+    #[test]
+    fn private_paste_without_key_is_forbidden() {
+        let (db, config) = test_setup();
+        let (id, edit_key) = private_paste(&db);
+
+        let (status, body) = read_access(&db, &config, &id, &HeaderMap::new()).unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "paste_private");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-edit-key", HeaderValue::from_str(&edit_key).unwrap());
+        assert!(read_access(&db, &config, &id, &headers).is_ok());
+    }
+
+    #[test]
+    fn hide_forbidden_reports_private_paste_as_missing() {
+        let (db, mut config) = test_setup();
+        config.hide_forbidden = true;
+        let (id, _) = private_paste(&db);
+
+        let (status, body) = read_access(&db, &config, &id, &HeaderMap::new()).unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "paste_not_found");
+    }
+
+    #[tokio::test]
+    async fn hide_forbidden_folds_every_denial_into_a_real_not_found() {
+        for hide_forbidden in [false, true] {
+            let (db, config) = test_setup();
+            let (db, config) = (Arc::new(db), Arc::new(Config { hide_forbidden, ..config }));
+            let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+            let (private, _) = private_paste(&db);
+            let missing = "missing1".to_string();
+
+            let get = |id: &String| get_paste(State(db.clone()), Path(id.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new());
+            let raw = |id: &String| serve_raw(db.clone(), id.clone(), &config, client, &HeaderMap::new(), false);
+            let update = |id: &String| {
+                let payload = UpdatePasteData {
+                    data: "changed".to_string(),
+                    language: "plaintext".to_string(),
+                    edit_key: "wrong-key".to_string(),
+                    content_sha256: None,
+                };
+                update_paste(State(db.clone()), Path(id.clone()), Extension(config.clone()), Json(payload))
+            };
+            let delete = |id: &String| {
+                let payload = DeletePasteData { edit_key: "wrong-key".to_string() };
+                delete_paste(State(db.clone()), Path(id.clone()), Extension(config.clone()), Json(payload))
+            };
+
+            let denied = [
+                status_and_json(get(&private).await).await,
+                status_and_json(raw(&private)).await,
+                status_and_json(update(&private).await).await,
+                status_and_json(delete(&private).await).await,
+            ];
+            let not_found = [
+                status_and_json(get(&missing).await).await,
+                status_and_json(raw(&missing)).await,
+                status_and_json(update(&missing).await).await,
+                status_and_json(delete(&missing).await).await,
+            ];
+            for ((denied, not_found), operation) in denied.into_iter().zip(not_found).zip(["get", "raw", "update", "delete"]) {
+                assert_eq!(not_found.0, StatusCode::NOT_FOUND, "{}", operation);
+                if hide_forbidden {
+                    assert_eq!(denied, not_found, "{}", operation);
+                } else {
+                    assert_eq!(denied.0, StatusCode::FORBIDDEN, "{}", operation);
+                }
+            }
+
+            let query = BatchGetQuery { ids: format!("{},{}", private, missing), strict: Some(true) };
+            let (_, batch) = status_and_json(get_pastes_batch(State(db.clone()), Extension(config.clone()), HeaderMap::new(), Query(query)).await).await;
+            let (denied, not_found) = (&batch["results"][0], &batch["results"][1]);
+            assert_eq!(not_found["status"], 404);
+            if hide_forbidden {
+                assert_eq!((&denied["status"], &denied["error"]), (&not_found["status"], &not_found["error"]));
+            } else {
+                assert_eq!(denied["status"], 403);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn by_tag_listing_normalizes_the_tag_and_refuses_bad_ones() {
+        let (db, _) = test_setup();
+        let db = Arc::new(db);
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({
+                "data": "hello",
+                "encryption_version": 0,
+                "visibility": "public",
+                "tags": ["rust"],
+            })).unwrap())
+            .unwrap();
+
+        let (status, body) = status_and_json(get_pastes_by_tag(State(db.clone()), Path("RUST".to_string())).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["tag"], "rust");
+        assert_eq!(body["pastes"][0]["id"], paste.id.as_str());
+        assert!(body["pastes"][0].get("data").is_none(), "listings carry no content");
+
+        let (status, _) = status_and_json(get_pastes_by_tag(State(db.clone()), Path("not a tag".to_string())).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn batch_get_reports_mixed_results_in_both_modes() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap())
+            .unwrap();
+        let ids = format!("{},missing1,bad!", paste.id);
+        let batch = |ids: String, strict| {
+            get_pastes_batch(State(db.clone()), Extension(config.clone()), HeaderMap::new(), Query(BatchGetQuery { ids, strict }))
+        };
+
+        // Strict: 207 with a list in request order
+        let (status, body) = status_and_json(batch(ids.clone(), Some(true)).await).await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        let results = body["results"].as_array().unwrap();
+        let summary: Vec<_> = results.iter().map(|item| (item["id"].clone(), item["status"].clone())).collect();
+        assert_eq!(summary, [
+            (paste.id.as_str().into(), 200.into()),
+            ("missing1".into(), 404.into()),
+            ("bad!".into(), 400.into()),
+        ]);
+        assert_eq!(results[0]["paste"]["data"], "hello");
+        assert!(results[0].get("error").is_none());
+        assert_eq!(results[1]["error"], "Paste not found");
+        assert!(results[1].get("paste").is_none());
+
+        // Otherwise: 200 with the same items keyed by ID
+        for strict in [Some(false), None] {
+            let (status, body) = status_and_json(batch(ids.clone(), strict).await).await;
+            assert_eq!(status, StatusCode::OK);
+            let results = body["results"].as_object().unwrap();
+            assert_eq!(results.len(), 3);
+            assert_eq!(results[paste.id.as_str()]["status"], 200);
+            assert_eq!(results["missing1"]["status"], 404);
+            assert_eq!(results["bad!"]["error"], "Invalid paste ID format");
+        }
+
+        let too_many = vec!["abcdef"; MAX_BATCH_PASTES + 1].join(",");
+        for ids in [" , ".to_string(), too_many] {
+            let (status, _) = status_and_json(batch(ids, Some(true)).await).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn put_creates_a_paste_at_a_free_id_only() {
+        let config = Arc::new(Config { allow_plaintext_pastes: true, allow_put_create: true, max_paste_bytes: 64, ..Config::default() });
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let put = |db: &Arc<Database>, config: &Arc<Config>, id: &str, content_type: Option<&str>, if_none_match: bool, body: &[u8]| {
+            let mut headers = HeaderMap::new();
+            if let Some(content_type) = content_type {
+                headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap());
+            }
+            if if_none_match {
+                headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("*"));
+            }
+            let body = BodyBytes(Bytes::copy_from_slice(body));
+            put_paste(State(db.clone()), Path(id.to_string()), Extension(config.clone()), ConnectInfo(client), headers, body)
+        };
+
+        // `curl -T` sends no Content-Type
+        let (status, body) = status_and_json(put(&db, &config, "mynotes", None, false, b"remember this").await).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["id"], "mynotes");
+        assert!(body["edit_key"].is_string());
+        assert_eq!(db.get_paste("mynotes").unwrap().unwrap().data, "remember this");
+
+        let (status, _) = status_and_json(put(&db, &config, "mynotes", Some("text/plain"), false, b"again").await).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, body) = status_and_json(put(&db, &config, "mynotes", Some("text/plain; charset=utf-8"), true, b"again").await).await;
+        assert_eq!((status, &body["code"]), (StatusCode::PRECONDITION_FAILED, &"precondition_failed".into()));
+        assert_eq!(db.get_paste("mynotes").unwrap().unwrap().data, "remember this", "a taken ID is left alone");
+
+        let refusals: [(&str, &[u8], &str); 4] = [
+            ("no", b"short id", "Invalid paste ID format"),
+            ("emptybody", b"", "Data is required"),
+            ("notutf8", &[0xff, 0xfe], "Body must be valid UTF-8 text"),
+            ("toolarge", &[b'a'; 65], "Content too large: 65 bytes (maximum: 64 bytes)"),
+        ];
+        for (id, body, error) in refusals {
+            let (status, response) = status_and_json(put(&db, &config, id, Some("text/plain"), false, body).await).await;
+            assert_eq!((status, response["error"].as_str().unwrap()), (StatusCode::BAD_REQUEST, error), "{}", id);
+        }
+
+        // Turned off, a text body is refused and a bare one isn't taken as text
+        let disabled = Arc::new(Config { allow_put_create: false, ..(*config).clone() });
+        let (status, _) = status_and_json(put(&db, &disabled, "freeid", Some("text/plain"), false, b"hello").await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = status_and_json(put(&db, &disabled, "freeid", None, false, b"hello").await).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert!(db.get_paste("freeid").unwrap().is_none());
+
+        // Plaintext pastes must be allowed too
+        let encrypted_only = Arc::new(Config { allow_plaintext_pastes: false, ..(*config).clone() });
+        let db = Arc::new(Database::in_memory(encrypted_only.paste_options()).unwrap());
+        let (status, body) = status_and_json(put(&db, &encrypted_only, "freeid", None, false, b"hello").await).await;
+        assert_eq!((status, &body["code"]), (StatusCode::BAD_REQUEST, &"plaintext_not_allowed".into()));
+    }
+
+    #[tokio::test]
+    async fn qr_code_is_an_svg_for_existing_pastes_only() {
+        let (db, config) = test_setup();
+        let config = Arc::new(Config { public_base_url: Some("https://paste.example.com".to_string()), ..config });
+        let db = Arc::new(db);
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap())
+            .unwrap();
+        let qr = |id: &str, size| {
+            paste_qr(State(db.clone()), Path(id.to_string()), Extension(config.clone()), HeaderMap::new(), Query(QrQuery { size }))
+        };
+
+        let response = qr(&paste.id, Some(1)).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let svg = String::from_utf8(body.to_vec()).unwrap();
+        assert!(svg.contains(&format!("width=\"{MIN_QR_PIXELS}\"")), "size is clamped to the minimum");
+
+        assert_eq!(qr("missing1", None).await.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn batch_create_refuses_oversized_batches_and_names_the_bad_item() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let batch = |datas: Vec<&str>| {
+            let items = datas
+                .into_iter()
+                .map(|data| serde_json::from_value(serde_json::json!({ "data": data, "encryption_version": 0 })).unwrap())
+                .collect();
+            create_pastes_batch(State(db.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(items))
+        };
+
+        let (status, body) = status_and_json(batch(vec!["one", "two"]).await).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body.as_array().map(Vec::len), Some(2));
+
+        for size in [0, MAX_BATCH_PASTES + 1] {
+            let (status, _) = status_and_json(batch(vec!["paste"; size]).await).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{} pastes", size);
+        }
+
+        let too_large = "x".repeat(config.max_paste_bytes + 1);
+        let (status, body) = status_and_json(batch(vec!["fine", &too_large]).await).await;
+        assert_eq!((status, &body["code"], &body["index"]), (StatusCode::BAD_REQUEST, &"too_large".into(), &1.into()));
+        let (status, body) = status_and_json(batch(vec!["fine", "fine", ""]).await).await;
+        assert_eq!((status, &body["index"]), (StatusCode::BAD_REQUEST, &2.into()));
+    }
+
+    #[tokio::test]
+    async fn conditional_get_is_not_modified_and_counts_no_view() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap())
+            .unwrap();
+        let get = |headers: HeaderMap| {
+            get_paste(State(db.clone()), Path(paste.id.clone()), Extension(config.clone()), ConnectInfo(client), headers)
+        };
+        let views = || db.get_paste_meta(&paste.id).unwrap().unwrap().views;
+
+        let response = get(HeaderMap::new()).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].clone();
+        assert_eq!(etag, paste_etag("hello"));
+        assert_eq!(views(), 1);
+
+        for matching in [etag.to_str().unwrap().to_string(), format!("\"other\", W/{}", etag.to_str().unwrap())] {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(&matching).unwrap());
+            let response = get(headers).await.into_response();
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{}", matching);
+            assert_eq!(response.headers()[header::ETAG], etag);
+            assert!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().is_empty());
+        }
+        assert_eq!(views(), 1, "revalidations aren't views");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"stale\""));
+        assert_eq!(get(headers).await.into_response().status(), StatusCode::OK);
+        assert_eq!(views(), 2);
+
+        let update: UpdatePasteData =
+            serde_json::from_value(serde_json::json!({ "data": "changed", "language": "plaintext", "edit_key": paste.edit_key })).unwrap();
+        db.update_paste(&paste.id, update).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag);
+        let response = get(headers).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK, "an edit changes the ETag");
+        assert_eq!(response.headers()[header::ETAG], paste_etag("changed"));
+    }
+
+    #[test]
+    fn accept_header_picks_plain_text_only_when_preferred() {
+        let accepting = |accept: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_str(accept).unwrap());
+            wants_plain_text(&headers)
+        };
+        assert!(!wants_plain_text(&HeaderMap::new()), "no Accept gets JSON");
+        for json in ["", "*/*", "application/json", "text/plain, application/json", "text/plain;q=0.5, application/json", "text/*;q=0.9, */*"] {
+            assert!(!accepting(json), "{:?}", json);
+        }
+        for text in ["text/plain", "TEXT/PLAIN", "text/*", "application/json;q=0.1, text/plain", "text/plain, */*;q=0.8"] {
+            assert!(accepting(text), "{:?}", text);
+        }
+    }
+
+    #[tokio::test]
+    async fn get_paste_answers_in_the_accepted_format() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap())
+            .unwrap();
+        let get = |accept: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_static(accept));
+            let client = ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000)));
+            get_paste(State(db.clone()), Path(paste.id.clone()), Extension(config.clone()), client, headers)
+        };
+
+        let response = get("text/plain").await.into_response();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain; charset=utf-8");
+        assert_eq!(response.headers()[header::VARY], "Accept");
+        assert_eq!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(), "hello");
+
+        for accept in ["application/json", "*/*"] {
+            let response = get(accept).await.into_response();
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json", "{}", accept);
+            let (_, body) = status_and_json(response).await;
+            assert_eq!((&body["id"], &body["data"]), (&paste.id.clone().into(), &"hello".into()));
+        }
+    }
+
+    #[tokio::test]
+    async fn validate_reports_each_failure_and_stores_nothing() {
+        let config = Config { allow_plaintext_pastes: true, max_paste_bytes: 16, ..Config::default() };
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let taken = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": "taken", "encryption_version": 0 })).unwrap())
+            .unwrap();
+        let validate = |body: serde_json::Value| {
+            let db = db.clone();
+            async move { status_and_json(validate_paste(State(db), JsonBody(serde_json::from_value(body).unwrap())).await).await }
+        };
+
+        let valid = [
+            serde_json::json!({ "data": "hello", "encryption_version": 0 }),
+            serde_json::json!({ "data": "hello", "encryption_version": 0, "language": "  Rust " }),
+            serde_json::json!({ "data": "hello", "encryption_version": 0, "id": "freeid" }),
+        ];
+        for body in valid {
+            assert_eq!(validate(body.clone()).await, (StatusCode::OK, serde_json::json!({ "valid": true })), "{}", body);
+        }
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 1, "validation stores nothing");
+
+        let (status, body) = validate(serde_json::json!({ "data": "", "encryption_version": 0 })).await;
+        assert_eq!((status, &body["code"]), (StatusCode::BAD_REQUEST, &"required".into()));
+        let (status, body) = validate(serde_json::json!({ "data": "x".repeat(17), "encryption_version": 0 })).await;
+        assert_eq!((status, &body["code"]), (StatusCode::BAD_REQUEST, &"too_large".into()));
+        let (status, _) = validate(serde_json::json!({ "data": "hello", "encryption_version": 0, "id": "no!" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = validate(serde_json::json!({ "data": "hello", "encryption_version": 0, "id": taken.id })).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn metadata_leaves_out_the_content_and_counts_no_view() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0, "language": "rust" })).unwrap())
+            .unwrap();
+        let meta = |id: &str| get_paste_meta(State(db.clone()), Path(id.to_string()), Extension(config.clone()), HeaderMap::new());
+
+        let (status, body) = status_and_json(meta(&paste.id).await).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut fields: Vec<&str> = body.as_object().unwrap().keys().map(String::as_str).collect();
+        fields.sort_unstable();
+        assert_eq!(fields, ["created_at", "encryption_version", "id", "language", "size_bytes", "views"]);
+        assert_eq!((&body["language"], &body["size_bytes"], &body["views"]), (&"rust".into(), &5.into(), &0.into()));
+        let (_, body) = status_and_json(meta(&paste.id).await).await;
+        assert_eq!(body["views"], 0, "metadata reads aren't views");
+
+        assert_eq!(status_and_json(meta("missing1").await).await.0, StatusCode::NOT_FOUND);
+        crate::db::tests::expire_paste(&db, &paste.id);
+        let (status, body) = status_and_json(meta(&paste.id).await).await;
+        assert_eq!((status, &body["code"]), (StatusCode::NOT_FOUND, &"paste_expired".into()));
+    }
+
+    #[tokio::test]
+    async fn create_response_links_to_the_paste() {
+        let (db, config) = test_setup();
+        let db = Arc::new(db);
+        let create = |config: Config, headers: &[(&'static str, &'static str)]| {
+            let mut map = HeaderMap::new();
+            for (name, value) in headers {
+                map.insert(*name, HeaderValue::from_static(value));
+            }
+            let payload = serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap();
+            let client = ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000)));
+            let response = create_paste(State(db.clone()), Extension(Arc::new(config)), client, map, JsonBody(payload));
+            async move {
+                let (status, body) = status_and_json(response.await).await;
+                assert_eq!(status, StatusCode::CREATED);
+                (body["id"].as_str().unwrap().to_string(), body.get("url").cloned())
+            }
+        };
+
+        let (id, url) = create(config.clone(), &[("host", "paste.local:8080")]).await;
+        assert_eq!(url, Some(format!("http://paste.local:8080/{}", id).into()), "http without X-Forwarded-Proto");
+        let (id, url) = create(config.clone(), &[("host", "paste.example"), ("x-forwarded-proto", "https, http")]).await;
+        assert_eq!(url, Some(format!("https://paste.example/{}", id).into()));
+
+        let with_base = Config { public_base_url: Some("https://bin.example/p/".to_string()), ..config.clone() };
+        let (id, url) = create(with_base, &[("host", "internal:3000")]).await;
+        assert_eq!(url, Some(format!("https://bin.example/p/{}", id).into()), "PUBLIC_BASE_URL wins");
+
+        for headers in [&[][..], &[("host", "evil.example/phish")][..]] {
+            let (_, url) = create(config.clone(), headers).await;
+            assert_eq!(url, None, "no usable Host, no url field");
+        }
+    }
+
+    #[tokio::test]
+    async fn uploaded_file_becomes_a_paste_with_its_language() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let upload = |content_type: &'static str, body: String| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            let client = ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000)));
+            upload_paste(State(db.clone()), Extension(config.clone()), client, headers, BodyBytes(body.into()))
+        };
+        let form = |parts: &[(&str, Option<&str>, &str)]| {
+            let mut body = String::new();
+            for (name, filename, data) in parts {
+                let filename = filename.map(|filename| format!("; filename=\"{}\"", filename)).unwrap_or_default();
+                body.push_str(&format!("--b0und\r\nContent-Disposition: form-data; name=\"{}\"{}\r\n\r\n{}\r\n", name, filename, data));
+            }
+            body + "--b0und--\r\n"
+        };
+        const MULTIPART: &str = "multipart/form-data; boundary=b0und";
+
+        let (status, body) = status_and_json(upload(MULTIPART, form(&[("file", Some("main.rs"), "fn main() {}\n")])).await).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let stored = db.get_paste(body["id"].as_str().unwrap()).unwrap().unwrap();
+        assert_eq!((stored.data.as_str(), stored.language.as_str()), ("fn main() {}\n", "rust"));
+
+        let parts = form(&[("language", None, "python"), ("file", Some("main.rs"), "print(1)")]);
+        let (_, body) = status_and_json(upload(MULTIPART, parts).await).await;
+        assert_eq!(body["language"], "python", "an explicit language wins over the extension");
+
+        let (status, _) = status_and_json(upload(MULTIPART, form(&[("language", None, "rust")])).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "no file part");
+        let (status, _) = status_and_json(upload("application/json", form(&[("file", None, "x")])).await).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn head_checks_existence_without_counting_views() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let paste = db.create_paste(serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap()).unwrap();
+        let gone = db.create_paste(serde_json::from_value(serde_json::json!({ "data": "bye", "encryption_version": 0 })).unwrap()).unwrap();
+        crate::db::tests::expire_paste(&db, &gone.id);
+
+        let head = |id: &str| head_paste(State(db.clone()), Path(id.to_string()), Extension(config.clone()), HeaderMap::new());
+        for _ in 0..3 {
+            assert_eq!(head(&paste.id).await, StatusCode::OK);
+        }
+        assert_eq!(head(&gone.id).await, StatusCode::NOT_FOUND, "expired");
+        assert_eq!(head("nosuch").await, StatusCode::NOT_FOUND);
+
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let (status, body) = status_and_json(get_paste(State(db.clone()), Path(paste.id.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new()).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["views"], 1, "only the GET counts");
+    }
+
+    #[tokio::test]
+    async fn content_policy_rejects_banned_plaintext_as_a_403() {
+        let (db, config) = test_setup();
+        let policy = crate::content_policy::BannedContentPolicy::new(&["forbidden".to_string()], &[]).unwrap();
+        let db = Arc::new(db.with_content_policy(Arc::new(policy)));
+        let config = Arc::new(config);
+        let create = |data: &str, encryption_version: u8| {
+            let payload = serde_json::from_value(serde_json::json!({ "data": data, "encryption_version": encryption_version })).unwrap();
+            let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+            create_paste(State(db.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(payload))
+        };
+
+        let (status, body) = status_and_json(create("some forbidden text", 0).await).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "content_rejected");
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 0);
+
+        let (status, _) = status_and_json(create("some allowed text", 0).await).await;
+        assert_eq!(status, StatusCode::CREATED);
+        // The server can't read client-encrypted content, so it isn't checked
+        let (status, _) = status_and_json(create("forbidden", 1).await).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn missing_pastes_are_told_apart_unless_hidden() {
+        for hide in [false, true] {
+            let config = Arc::new(Config {
+                allow_plaintext_pastes: true,
+                hide_expiry_distinction: hide,
+                trash_retention_secs: 3600,
+                ..Config::default()
+            });
+            let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+            let create = |extra: serde_json::Value| {
+                let mut body = serde_json::json!({ "data": "hello", "encryption_version": 0 });
+                body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+                db.create_paste(serde_json::from_value(body).unwrap()).unwrap()
+            };
+            let expired = create(serde_json::json!({}));
+            crate::db::tests::expire_paste(&db, &expired.id);
+            let burned = create(serde_json::json!({ "burn_after_read": true }));
+            db.get_paste(&burned.id).unwrap();
+            let trashed = create(serde_json::json!({}));
+            db.delete_paste_with_key(&trashed.id, DeletePasteData { edit_key: trashed.edit_key.clone().unwrap() }).unwrap();
+
+            let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+            let read = |id: String| get_paste(State(db.clone()), Path(id), Extension(config.clone()), ConnectInfo(client), HeaderMap::new());
+            let cases = [
+                ("nosuch".to_string(), "paste_not_found"),
+                (expired.id.clone(), if hide { "paste_not_found" } else { "paste_expired" }),
+                (burned.id.clone(), "paste_not_found"),
+                (trashed.id.clone(), if hide { "paste_not_found" } else { "paste_deleted" }),
+            ];
+            let mut bodies = Vec::new();
+            for (id, code) in cases {
+                let (status, body) = status_and_json(read(id).await).await;
+                assert_eq!(status, StatusCode::NOT_FOUND);
+                assert_eq!(body["code"], code, "hide {}", hide);
+                bodies.push(body);
+            }
+            if hide {
+                assert!(bodies.iter().all(|body| *body == bodies[0]), "identical bodies: {:?}", bodies);
+                // The expired row is left for the purge, so reading it costs the same as a miss
+                assert_eq!(db.purge_expired().unwrap().expired, 1);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn content_hash_is_checked_stored_and_echoed() {
+        const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let create = |hash: &str| {
+            let payload = serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0, "content_sha256": hash })).unwrap();
+            create_paste(State(db.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(payload))
+        };
+
+        let (status, created) = status_and_json(create(&HELLO_SHA256.to_uppercase()).await).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created["content_sha256"], HELLO_SHA256, "normalized to lowercase");
+        assert_eq!(created["server_sha256"], HELLO_SHA256);
+        let id = created["id"].as_str().unwrap().to_string();
+        let (_, read) = status_and_json(get_paste(State(db.clone()), Path(id), Extension(config.clone()), ConnectInfo(client), HeaderMap::new()).await).await;
+        assert_eq!(read["content_sha256"], HELLO_SHA256);
+        assert_eq!(read["server_sha256"], HELLO_SHA256);
+
+        let (status, body) = status_and_json(create(&"0".repeat(64)).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "content_hash_mismatch");
+        let (status, body) = status_and_json(create("not-a-hash").await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_content_hash");
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 1, "rejected pastes aren't stored");
+    }
+
+    #[tokio::test]
+    async fn fork_copies_the_paste_under_a_new_id_linked_to_the_source() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let source = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": "fn main() {}", "language": "rust", "encryption_version": 0 })).unwrap())
+            .unwrap();
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let fork = |id: &str| fork_paste(State(db.clone()), Path(id.to_string()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new());
+
+        let (status, forked) = status_and_json(fork(&source.id).await).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_ne!(forked["id"], source.id.as_str());
+        assert_eq!(forked["forked_from"], source.id.as_str());
+        assert_ne!(forked["edit_key"], source.edit_key.as_deref().unwrap(), "the fork gets its own edit key");
+
+        let stored = db.get_paste(forked["id"].as_str().unwrap()).unwrap().unwrap();
+        assert_eq!((stored.data.as_str(), stored.language.as_str()), ("fn main() {}", "rust"));
+        assert_eq!(stored.forked_from.as_deref(), Some(source.id.as_str()));
+
+        let (status, body) = status_and_json(fork("nosuch").await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "paste_not_found");
+    }
+
+    #[tokio::test]
+    async fn creates_past_the_daily_quota_are_refused_per_client() {
+        let config = Arc::new(Config { allow_plaintext_pastes: true, daily_create_quota: 2, ..Config::default() });
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let create = |client: [u8; 4]| {
+            let payload = serde_json::from_value(serde_json::json!({ "data": "hello", "encryption_version": 0 })).unwrap();
+            let client = SocketAddr::from((client, 4000));
+            create_paste(State(db.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(payload))
+        };
+
+        for _ in 0..2 {
+            assert_eq!(create([192, 0, 2, 1]).await.into_response().status(), StatusCode::CREATED);
+        }
+        let refused = create([192, 0, 2, 1]).await.into_response();
+        assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = refused.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+        assert!(retry_after <= 24 * 60 * 60, "until midnight UTC: {}", retry_after);
+        let (_, body) = status_and_json(refused).await;
+        assert_eq!(body["code"], "quota_exceeded");
+
+        assert_eq!(create([198, 51, 100, 1]).await.into_response().status(), StatusCode::CREATED);
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 3);
+    }
+
+    #[tokio::test]
+    async fn line_limit_applies_to_plaintext_only() {
+        let config = Arc::new(Config { allow_plaintext_pastes: true, max_paste_lines: 3, ..Config::default() });
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let create = |data: &str, encryption_version: u8| {
+            let payload = serde_json::from_value(serde_json::json!({ "data": data, "encryption_version": encryption_version })).unwrap();
+            let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+            create_paste(State(db.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(payload))
+        };
+
+        assert_eq!(status_and_json(create("one\ntwo\nthree\n", 0).await).await.0, StatusCode::CREATED);
+        let (status, body) = status_and_json(create("one\ntwo\nthree\nfour", 0).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "too_many_lines");
+        // The server can't see lines in ciphertext, so only the byte limit applies
+        assert_eq!(status_and_json(create("a\nb\nc\nd\ne\n", 1).await).await.0, StatusCode::CREATED);
+
+        let (db, config) = test_setup();
+        assert_eq!(config.max_paste_lines, 0, "disabled by default");
+        let unlimited: String = "line\n".repeat(10_000);
+        assert!(db.create_paste(serde_json::from_value(serde_json::json!({ "data": unlimited, "encryption_version": 0 })).unwrap()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn append_extends_plaintext_within_the_limit_and_refuses_ciphertext() {
+        let config = Arc::new(Config { allow_plaintext_pastes: true, max_paste_bytes: 16, ..Config::default() });
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let create = |encryption_version: u8| {
+            db.create_paste(serde_json::from_value(serde_json::json!({ "data": "line 1\n", "encryption_version": encryption_version })).unwrap())
+                .unwrap()
+        };
+        let append = |paste: &crate::db::Paste, data: &str| {
+            let payload = AppendPasteData { data: data.to_string(), edit_key: paste.edit_key.clone().unwrap() };
+            append_paste(State(db.clone()), Path(paste.id.clone()), Extension(config.clone()), JsonBody(payload))
+        };
+
+        let log = create(0);
+        let (status, body) = status_and_json(append(&log, "line 2\n").await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["server_sha256"], format!("{:x}", Sha256::digest("line 1\nline 2\n")), "hash of the whole paste");
+        assert_eq!(db.get_paste(&log.id).unwrap().unwrap().data, "line 1\nline 2\n");
+
+        let (status, body) = status_and_json(append(&log, "line 3\n").await).await;
+        assert_eq!((status, &body["code"]), (StatusCode::BAD_REQUEST, &"too_large".into()), "past the total size");
+        assert_eq!(db.get_paste(&log.id).unwrap().unwrap().data, "line 1\nline 2\n");
+
+        let encrypted = create(1);
+        let (status, body) = status_and_json(append(&encrypted, "more").await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "append_to_encrypted");
+        assert_eq!(db.get_paste(&encrypted.id).unwrap().unwrap().data, "line 1\n");
+    }
+
+    #[tokio::test]
+    async fn download_names_the_file_after_the_id_and_language() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+
+        for (language, extension) in [("rust", "rs"), ("python", "py"), ("plaintext", "txt"), ("klingon", "txt")] {
+            let paste = db
+                .create_paste(serde_json::from_value(serde_json::json!({ "data": "body", "language": language, "encryption_version": 0 })).unwrap())
+                .unwrap();
+            let response = download_paste(State(db.clone()), Path(paste.id.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new())
+                .await
+                .into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/octet-stream");
+            let disposition = format!("attachment; filename=\"{}.{}\"", paste.id, extension);
+            assert_eq!(response.headers()[header::CONTENT_DISPOSITION], disposition.as_str(), "{}", language);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(body, "body");
+            assert_eq!(db.get_paste_meta(&paste.id).unwrap().unwrap().views, 1, "counted like a raw read");
+        }
+    }
+
+    #[tokio::test]
+    async fn syntax_validation_rejects_malformed_json_only() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let create = |data: &str, language: &str, validate_syntax: bool| {
+            let payload = serde_json::from_value(serde_json::json!({
+                "data": data,
+                "language": language,
+                "encryption_version": 0,
+                "validate_syntax": validate_syntax,
+            }))
+            .unwrap();
+            let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+            create_paste(State(db.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(payload))
+        };
+
+        assert_eq!(status_and_json(create(r#"{"ok": [1, 2]}"#, "json", true).await).await.0, StatusCode::CREATED);
+        let (status, body) = status_and_json(create(r#"{"ok": [1, 2}"#, "json", true).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "syntax_invalid");
+        assert!(body["error"].as_str().unwrap().contains("line 1 column"), "includes the parse error: {}", body);
+
+        // Unsupported languages and pastes that don't ask are stored unchecked
+        assert_eq!(status_and_json(create("key: [unclosed", "yaml", true).await).await.0, StatusCode::CREATED);
+        assert_eq!(status_and_json(create(r#"{"ok": [1, 2}"#, "json", false).await).await.0, StatusCode::CREATED);
+        let encrypted = serde_json::json!({ "data": "not json", "language": "json", "encryption_version": 1, "validate_syntax": true });
+        assert!(db.create_paste(serde_json::from_value(encrypted).unwrap()).is_ok(), "ciphertext can't be parsed");
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 4);
+    }
+
+    #[tokio::test]
+    async fn racing_conditional_puts_claim_a_slug_once() {
+        let config = Arc::new(Config { allow_plaintext_pastes: true, allow_put_create: true, ..Config::default() });
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let put = |if_none_match: &'static str, body: String| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+            headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static(if_none_match));
+            let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+            let (db, config) = (db.clone(), config.clone());
+            async move {
+                let response = put_paste(State(db), Path("myslug".to_string()), Extension(config), ConnectInfo(client), headers, BodyBytes(body.into()));
+                status_and_json(response.await).await
+            }
+        };
+
+        let racers: Vec<_> = (0..8).map(|n| tokio::spawn(put("*", format!("claim {n}")))).collect();
+        let mut winners = Vec::new();
+        for (n, racer) in racers.into_iter().enumerate() {
+            let (status, body) = racer.await.unwrap();
+            match status {
+                StatusCode::CREATED => winners.push(n),
+                StatusCode::PRECONDITION_FAILED => assert_eq!(body["code"], "precondition_failed"),
+                other => panic!("unexpected {}", other),
+            }
+        }
+        assert_eq!(winners.len(), 1, "exactly one claim wins");
+        assert_eq!(db.get_paste("myslug").unwrap().unwrap().data, format!("claim {}", winners[0]));
+
+        // Only `*` asks for create-only; any other value leaves the plain conflict
+        let (status, _) = put("\"abc\"", "late".to_string()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn reads_are_recorded_in_the_access_log_when_enabled() {
+        let config = Config {
+            allow_plaintext_pastes: true,
+            access_log_enabled: true,
+            creator_hash_secret: Some("secret".to_string()),
+            ..Config::default()
+        };
+        let db = Arc::new(Database::in_memory(config.paste_options()).unwrap());
+        let config = Arc::new(config);
+        let paste = db.create_paste(serde_json::from_value(serde_json::json!({ "data": "read me", "encryption_version": 0 })).unwrap()).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::USER_AGENT, HeaderValue::from_static("curl/8.0"));
+        let client = SocketAddr::from(([192, 0, 2, 7], 4000));
+
+        let response = get_paste(State(db.clone()), Path(paste.id.clone()), Extension(config.clone()), ConnectInfo(client), headers.clone())
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let accesses = db.recent_accesses(&paste.id, 10).unwrap();
+        assert_eq!(accesses.len(), 1);
+        assert_eq!(accesses[0].user_agent.as_deref(), Some("curl/8.0"));
+        let ip_hash = accesses[0].ip_hash.as_deref().unwrap();
+        assert!(!ip_hash.contains("192.0.2.7"), "no address is stored");
+
+        // Metadata reads don't count
+        let response = get_paste_meta(State(db.clone()), Path(paste.id.clone()), Extension(config.clone()), headers).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(db.recent_accesses(&paste.id, 10).unwrap().len(), 1);
+
+        // Off by default
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let paste = db.create_paste(serde_json::from_value(serde_json::json!({ "data": "read me", "encryption_version": 0 })).unwrap()).unwrap();
+        let response = get_paste(State(db.clone()), Path(paste.id.clone()), Extension(config), ConnectInfo(client), HeaderMap::new()).await;
+        assert_eq!(response.into_response().status(), StatusCode::OK);
+        assert!(db.recent_accesses(&paste.id, 10).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn base64_pastes_download_as_their_bytes_and_must_decode() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let create = |data: String| {
+            let payload = serde_json::from_value(serde_json::json!({ "data": data, "encoding": "base64", "encryption_version": 0 })).unwrap();
+            create_paste(State(db.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(payload))
+        };
+        let bytes: Vec<u8> = (0..=255).rev().collect();
+
+        let (status, body) = status_and_json(create(base64::engine::general_purpose::STANDARD.encode(&bytes)).await).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["encoding"], "base64");
+        let id = body["id"].as_str().unwrap().to_string();
+        let response = download_paste(State(db.clone()), Path(id), Extension(config.clone()), ConnectInfo(client), HeaderMap::new())
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/octet-stream");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, bytes);
+
+        let (status, body) = status_and_json(create("not base64!".to_string()).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_base64");
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 1);
+    }
+
+    #[tokio::test]
+    async fn every_failed_check_is_reported_together() {
+        let (db, config) = test_setup();
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let create = |body: serde_json::Value| {
+            let client = SocketAddr::from(([127, 0, 0, 1], 4000));
+            create_paste(State(db.clone()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(serde_json::from_value(body).unwrap()))
+        };
+
+        let (status, body) = status_and_json(
+            create(serde_json::json!({ "data": "", "encryption_version": 0, "tags": ["has space"], "max_views": 0 })).await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "validation_failed");
+        let reported: Vec<(&str, &str)> = body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| (error["field"].as_str().unwrap(), error["code"].as_str().unwrap()))
+            .collect();
+        assert_eq!(reported, [("data", "required"), ("tags", "invalid_tag"), ("max_views", "invalid_max_views")]);
+        assert_eq!(db.storage_stats().unwrap().total_pastes, 0);
+
+        // A single failure keeps its own code at the top level
+        let (status, body) = status_and_json(create(serde_json::json!({ "data": "", "encryption_version": 0 })).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "required");
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn reports_are_recorded_once_per_reporter_and_rate_limited() {
+        let (db, config) = test_setup();
+        let config = Config { report_rate_limit: 2, ..config };
+        let (db, config) = (Arc::new(db), Arc::new(config));
+        let report = |id: &str, client: [u8; 4]| {
+            let payload = serde_json::from_value(serde_json::json!({ "reason": "spam" })).unwrap();
+            let client = SocketAddr::from((client, 4000));
+            report_paste(State(db.clone()), Path(id.to_string()), Extension(config.clone()), ConnectInfo(client), HeaderMap::new(), JsonBody(payload))
+        };
+        let create = || db.create_paste(serde_json::from_value(serde_json::json!({ "data": "buy now", "encryption_version": 0 })).unwrap()).unwrap().id;
+        let (first, second, third) = (create(), create(), create());
+
+        let (status, _) = status_and_json(report(&first, [192, 0, 2, 1]).await).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let (status, body) = status_and_json(report(&first, [192, 0, 2, 1]).await).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "already_reported");
+
+        let (status, _) = status_and_json(report(&second, [192, 0, 2, 1]).await).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let (status, body) = status_and_json(report(&third, [192, 0, 2, 1]).await).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "past two reports an hour");
+        assert_eq!(body["code"], "report_limit_exceeded");
+
+        // Another reporter on a different network still counts
+        let (status, _) = status_and_json(report(&first, [198, 51, 100, 1]).await).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let (status, _) = status_and_json(report("missing1", [198, 51, 100, 1]).await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let reported = db.reported_pastes(10).unwrap();
+        let counts: Vec<(&str, u64)> = reported.iter().map(|paste| (paste.paste_id.as_str(), paste.reports)).collect();
+        assert_eq!(counts, [(first.as_str(), 2), (second.as_str(), 1)]);
+        assert_eq!(reported[0].reasons, ["spam", "spam"]);
+    }
+}
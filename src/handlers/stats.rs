@@ -0,0 +1,123 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::db::{Database, DbError};
+use crate::error::json_error;
+use crate::models::stats::LanguageCount;
+
+/// How long language counts are served from memory before the database is queried again.
+pub const LANGUAGE_STATS_TTL: Duration = Duration::from_secs(30);
+
+/// Cached result of the language count query, shared across requests.
+pub struct LanguageStatsCache {
+    entry: Mutex<Option<(Instant, Vec<LanguageCount>)>>,
+    ttl: Duration,
+}
+
+impl LanguageStatsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entry: Mutex::new(None),
+            ttl,
+        }
+    }
+
+    // Serve the cached counts while fresh, otherwise reload them. The lock is held
+    // across the reload so concurrent misses run a single query.
+    fn get_or_load(&self, db: &Database) -> Result<Vec<LanguageCount>, DbError> {
+        let mut entry = self.entry.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((loaded_at, counts)) = entry.as_ref()
+            && loaded_at.elapsed() < self.ttl
+        {
+            return Ok(counts.clone());
+        }
+
+        let counts: Vec<LanguageCount> = db
+            .language_counts()?
+            .into_iter()
+            .map(|(language, count)| LanguageCount { language, count })
+            .collect();
+        *entry = Some((Instant::now(), counts.clone()));
+
+        Ok(counts)
+    }
+}
+
+// Handler for per-language paste counts, most used first
+pub async fn get_language_stats(
+    State(db): State<Arc<Database>>,
+    Extension(cache): Extension<Arc<LanguageStatsCache>>,
+) -> impl IntoResponse {
+    match cache.get_or_load(&db) {
+        Ok(counts) => (StatusCode::OK, Json(counts)).into_response(),
+        Err(err) => {
+            tracing::error!("Database error during language stats: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to get language statistics")),
+            ).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn seeded_db(languages: &[&str]) -> Arc<Database> {
+        let config = Config { allow_plaintext_pastes: true, ..Config::default() };
+        let db = Database::in_memory(config.paste_options()).unwrap();
+        for language in languages {
+            create(&db, language);
+        }
+        Arc::new(db)
+    }
+
+    fn create(db: &Database, language: &str) -> String {
+        let data = serde_json::json!({ "data": "hello", "encryption_version": 0, "language": language });
+        db.create_paste(serde_json::from_value(data).unwrap()).unwrap().id
+    }
+
+    async fn counts(db: &Arc<Database>, cache: &Arc<LanguageStatsCache>) -> serde_json::Value {
+        let response = get_language_stats(State(db.clone()), Extension(cache.clone())).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn counts_are_ordered_by_use_then_name() {
+        let db = seeded_db(&["rust", "python", "rust", "go", "python", "rust"]);
+        let expired = create(&db, "go");
+        crate::db::tests::expire_paste(&db, &expired);
+
+        let cache = Arc::new(LanguageStatsCache::new(Duration::ZERO));
+        assert_eq!(
+            counts(&db, &cache).await,
+            serde_json::json!([
+                { "language": "rust", "count": 3 },
+                { "language": "python", "count": 2 },
+                { "language": "go", "count": 1 },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn counts_are_cached_until_the_ttl_runs_out() {
+        let db = seeded_db(&["rust"]);
+        let cached = Arc::new(LanguageStatsCache::new(LANGUAGE_STATS_TTL));
+        let uncached = Arc::new(LanguageStatsCache::new(Duration::ZERO));
+        assert_eq!(counts(&db, &cached).await[0]["count"], 1);
+
+        create(&db, "rust");
+        assert_eq!(counts(&db, &cached).await[0]["count"], 1, "served from memory");
+        assert_eq!(counts(&db, &uncached).await[0]["count"], 2);
+    }
+}
@@ -2,12 +2,13 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
 use std::sync::Arc;
 
+use crate::config::Config;
 use crate::db::{Database, DbError};
-use crate::error::json_error;
+use crate::error::{access_denied, json_error};
 use crate::models::workspace::{
     CreateWorkspaceRequest, CreateWorkspaceResponse,
     UpdateWorkspaceRequest, WorkspaceResponse,
@@ -43,6 +44,11 @@ pub async fn create_workspace(
                 DbError::ClientEncryptionRequired => {
                     (StatusCode::BAD_REQUEST, "Data is required".to_string())
                 }
+                DbError::TtlTooLong(..) => {
+                    let mut body = json_error(&err.to_string());
+                    body["code"] = "ttl_too_long".into();
+                    return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+                }
                 DbError::IdGenerationFailed => {
                     tracing::error!("Failed to generate unique ID after maximum retries");
                     (StatusCode::INTERNAL_SERVER_ERROR, "Server error: please try again".to_string())
@@ -62,8 +68,8 @@ pub async fn get_workspace(
     State(db): State<Arc<Database>>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    // Validate ID format (alphanumeric, 6-16 chars)
-    if id.len() < 6 || id.len() > 16 || !id.chars().all(|c| c.is_alphanumeric()) {
+    // Validate ID format
+    if !db.is_valid_id(&id) {
         return (
             StatusCode::BAD_REQUEST,
             Json(json_error("Invalid workspace ID format")),
@@ -71,7 +77,7 @@ pub async fn get_workspace(
     }
 
     match db.get_workspace(&id) {
-        Some(paste) => {
+        Ok(Some(paste)) => {
             let response = WorkspaceResponse {
                 id: paste.id,
                 data: paste.data,
@@ -82,10 +88,17 @@ pub async fn get_workspace(
             };
             (StatusCode::OK, Json(response)).into_response()
         }
-        None => (
+        Ok(None) => (
             StatusCode::NOT_FOUND,
             Json(json_error("Workspace not found")),
         ).into_response(),
+        Err(err) => {
+            tracing::error!("Database error during workspace get: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json_error("Failed to get workspace")),
+            ).into_response()
+        }
     }
 }
 
@@ -93,10 +106,11 @@ pub async fn get_workspace(
 pub async fn update_workspace(
     State(db): State<Arc<Database>>,
     Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
     Json(payload): Json<UpdateWorkspaceRequest>,
 ) -> impl IntoResponse {
     // Validate ID format
-    if id.len() < 6 || id.len() > 16 || !id.chars().all(|c| c.is_alphanumeric()) {
+    if !db.is_valid_id(&id) {
         return (
             StatusCode::BAD_REQUEST,
             Json(json_error("Invalid workspace ID format")),
@@ -130,7 +144,7 @@ pub async fn update_workspace(
                     (StatusCode::NOT_FOUND, "Workspace not found".to_string())
                 }
                 DbError::InvalidEditKey => {
-                    (StatusCode::FORBIDDEN, "Invalid edit key".to_string())
+                    access_denied(config.hide_forbidden, StatusCode::FORBIDDEN, "Invalid edit key", "Workspace not found")
                 }
                 DbError::CharacterLimitExceeded(actual, max) => {
                     (StatusCode::BAD_REQUEST, format!("Content too large: {} bytes (maximum: {} bytes)", actual, max))
@@ -152,10 +166,11 @@ pub async fn update_workspace(
 pub async fn delete_workspace(
     State(db): State<Arc<Database>>,
     Path(id): Path<String>,
+    Extension(config): Extension<Arc<Config>>,
     Json(payload): Json<DeleteWorkspaceRequest>,
 ) -> impl IntoResponse {
     // Validate ID format
-    if id.len() < 6 || id.len() > 16 || !id.chars().all(|c| c.is_alphanumeric()) {
+    if !db.is_valid_id(&id) {
         return (
             StatusCode::BAD_REQUEST,
             Json(json_error("Invalid workspace ID format")),
@@ -178,7 +193,7 @@ pub async fn delete_workspace(
                     (StatusCode::NOT_FOUND, "Workspace not found".to_string())
                 }
                 DbError::InvalidEditKey => {
-                    (StatusCode::FORBIDDEN, "Invalid edit key".to_string())
+                    access_denied(config.hide_forbidden, StatusCode::FORBIDDEN, "Invalid edit key", "Workspace not found")
                 }
                 _ => {
                     tracing::error!("Database error during delete: {}", err);
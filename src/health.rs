@@ -1,12 +1,12 @@
+use std::path::Path;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::{Mutex, PoisonError};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 use sysinfo::{Disks, System};
 
 const DB_ERROR_WINDOW_SECS: u64 = 60;
 const DB_ERROR_THRESHOLD: u64 = 10;
-const DISK_FREE_THRESHOLD_MB: u64 = 100;
 const CPU_USAGE_THRESHOLD: f32 = 95.0;
 
 #[derive(Clone)]
@@ -14,6 +14,10 @@ pub struct HealthChecker {
     db_errors: Arc<Mutex<Vec<Instant>>>,
     db_error_count: Arc<AtomicU64>,
     db_path: String,
+    // Free space below which the disk is unhealthy
+    min_free_disk_mb: u64,
+    // Rate limiter entries past which the limiter is degraded; 0 disables the check
+    max_tracked_clients: usize,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -27,6 +31,7 @@ pub struct HealthChecks {
     pub disk: CheckResult,
     pub cpu: CheckResult,
     pub database: CheckResult,
+    pub rate_limiter: CheckResult,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -73,12 +78,24 @@ impl HealthChecker {
             db_errors: Arc::new(Mutex::new(Vec::new())),
             db_error_count: Arc::new(AtomicU64::new(0)),
             db_path,
+            min_free_disk_mb: 100,
+            max_tracked_clients: 0,
         }
     }
 
+    pub fn with_min_free_disk_mb(mut self, min_free_disk_mb: u64) -> Self {
+        self.min_free_disk_mb = min_free_disk_mb;
+        self
+    }
+
+    pub fn with_max_tracked_clients(mut self, max_tracked_clients: usize) -> Self {
+        self.max_tracked_clients = max_tracked_clients;
+        self
+    }
+
     pub fn record_db_error(&self) {
         let now = Instant::now();
-        let mut errors = self.db_errors.lock().unwrap();
+        let mut errors = self.db_errors.lock().unwrap_or_else(PoisonError::into_inner);
         errors.push(now);
         self.db_error_count.fetch_add(1, Ordering::Relaxed);
     }
@@ -86,34 +103,23 @@ impl HealthChecker {
     fn recent_db_errors(&self) -> u64 {
         let now = Instant::now();
         let window = Duration::from_secs(DB_ERROR_WINDOW_SECS);
-        let mut errors = self.db_errors.lock().unwrap();
+        let mut errors = self.db_errors.lock().unwrap_or_else(PoisonError::into_inner);
         errors.retain(|t| now.duration_since(*t) < window);
         errors.len() as u64
     }
 
     fn check_disk(&self) -> CheckResult {
-        let disks = Disks::new_with_refreshed_list();
-
-        // Find the disk that contains our DB path
-        let target_disk = disks
-            .iter()
-            .find(|d| {
-                self.db_path
-                    .starts_with(&d.mount_point().to_string_lossy().to_string())
-            })
-            .or_else(|| disks.iter().next());
-
-        match target_disk {
-            Some(disk) => {
-                let free_mb = disk.available_space() / (1024 * 1024);
-                let total_mb = disk.total_space() / (1024 * 1024);
+        match disk_space(Path::new(&self.db_path)) {
+            Some((available, total)) => {
+                let free_mb = available / (1024 * 1024);
+                let total_mb = total / (1024 * 1024);
                 let free_pct = if total_mb > 0 {
                     (free_mb as f64 / total_mb as f64) * 100.0
                 } else {
                     0.0
                 };
 
-                if free_mb < DISK_FREE_THRESHOLD_MB {
+                if free_mb < self.min_free_disk_mb {
                     CheckResult::unhealthy(format!(
                         "{}MB free ({}% of {}MB)",
                         free_mb, free_pct as u64, total_mb
@@ -170,14 +176,30 @@ impl HealthChecker {
         }
     }
 
-    pub fn check(&self) -> HealthStatus {
+    // A limiter tracking far more clients than usual points at a leak or a flood of
+    // distinct addresses; it still works, so this is never more than degraded
+    fn check_rate_limiter(&self, tracked_clients: usize) -> CheckResult {
+        if self.max_tracked_clients > 0 && tracked_clients > self.max_tracked_clients {
+            CheckResult::degraded(format!(
+                "{} clients tracked (threshold {})",
+                tracked_clients, self.max_tracked_clients
+            ))
+        } else {
+            CheckResult::ok()
+        }
+    }
+
+    /// Run every check. `tracked_clients` is the rate limiter's current entry count.
+    pub fn check(&self, tracked_clients: usize) -> HealthStatus {
         let disk = self.check_disk();
         let cpu = self.check_cpu();
         let database = self.check_database();
+        let rate_limiter = self.check_rate_limiter(tracked_clients);
 
-        let status = if disk.is_unhealthy() || cpu.is_unhealthy() || database.is_unhealthy() {
+        let results = [&disk, &cpu, &database, &rate_limiter];
+        let status = if results.iter().any(|r| r.is_unhealthy()) {
             "unhealthy"
-        } else if !disk.is_ok() || !cpu.is_ok() || !database.is_ok() {
+        } else if !results.iter().all(|r| r.is_ok()) {
             "degraded"
         } else {
             "ok"
@@ -189,7 +211,56 @@ impl HealthChecker {
                 disk,
                 cpu,
                 database,
+                rate_limiter,
             },
         }
     }
 }
+
+/// Available and total bytes on the filesystem holding `path`: the disk with the
+/// longest mount point that prefixes the path's absolute form.
+pub fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    // The database file may not exist yet; its directory does
+    let absolute = path
+        .canonicalize()
+        .or_else(|_| path.parent().map_or(Err(std::io::ErrorKind::NotFound.into()), Path::canonicalize))
+        .or_else(|_| std::env::current_dir().map(|dir| dir.join(path)))
+        .ok()?;
+
+    let disks = Disks::new_with_refreshed_list();
+    disks
+        .iter()
+        .filter(|disk| absolute.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| (disk.available_space(), disk.total_space()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker() -> HealthChecker {
+        HealthChecker::new(std::env::temp_dir().join("pastes.db").to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn disk_below_the_free_space_floor_is_unhealthy() {
+        let low = checker().with_min_free_disk_mb(u64::MAX).check_disk();
+        assert_eq!(low.status, "unhealthy");
+        assert!(low.message.unwrap().contains("MB free"));
+        assert_ne!(checker().with_min_free_disk_mb(0).check_disk().status, "unhealthy");
+
+        let health = checker().with_min_free_disk_mb(u64::MAX).check(0);
+        assert_eq!((health.status, health.checks.disk.status), ("unhealthy", "unhealthy"));
+    }
+
+    #[test]
+    fn a_large_rate_limiter_is_degraded_but_never_unhealthy() {
+        let limited = checker().with_max_tracked_clients(100);
+        assert!(limited.check_rate_limiter(100).is_ok());
+        let large = limited.check_rate_limiter(101);
+        assert_eq!(large.status, "degraded");
+        assert_eq!(large.message.as_deref(), Some("101 clients tracked (threshold 100)"));
+        assert!(checker().check_rate_limiter(usize::MAX).is_ok(), "0 disables the check");
+    }
+}
@@ -0,0 +1,250 @@
+/// Language value that asks the server to pick the language itself.
+pub const AUTO: &str = "auto";
+
+/// Language stored when nothing better is known.
+pub const PLAINTEXT: &str = "plaintext";
+
+/// Language identifiers the frontend highlights, as advertised by `GET /v1/config`.
+pub const SUPPORTED: &[&str] = &[
+    PLAINTEXT, "rust", "typescript", "tsx", "javascript", "jsx", "python", "cpp", "c", "go", "java",
+    "markdown", "json", "yaml", "html", "css", "sql", "bash", "ruby", "php", "swift", "kotlin",
+    "csharp", "lua", "scala", "haskell", "elixir", "erlang", "perl", "powershell", "graphql",
+];
+
+// Lines inspected by the keyword rules; enough to see imports and the first definitions
+const MAX_SCANNED_LINES: usize = 200;
+
+/// Whether a requested language means "detect it" (`auto` or blank).
+pub fn is_auto(language: &str) -> bool {
+    let language = language.trim();
+    language.is_empty() || language.eq_ignore_ascii_case(AUTO)
+}
+
+/// Language identifier for a file name's extension, matching the frontend's detection.
+pub fn from_filename(filename: &str) -> Option<&'static str> {
+    let (_, extension) = filename.rsplit_once('.')?;
+    let language = match extension.to_ascii_lowercase().as_str() {
+        "rs" => "rust",
+        "ts" => "typescript",
+        "tsx" => "tsx",
+        "js" => "javascript",
+        "jsx" => "jsx",
+        "py" => "python",
+        "cpp" | "cc" | "cxx" | "hpp" => "cpp",
+        "c" | "h" => "c",
+        "go" => "go",
+        "java" => "java",
+        "md" => "markdown",
+        "json" => "json",
+        "yaml" | "yml" | "toml" | "ini" => "yaml",
+        "html" | "xml" => "html",
+        "css" => "css",
+        "sql" => "sql",
+        "sh" | "bash" => "bash",
+        "rb" => "ruby",
+        "php" => "php",
+        "swift" => "swift",
+        "kt" => "kotlin",
+        "cs" => "csharp",
+        "lua" => "lua",
+        "scala" => "scala",
+        "hs" => "haskell",
+        "ex" | "exs" => "elixir",
+        "erl" => "erlang",
+        "pl" => "perl",
+        "ps1" => "powershell",
+        "graphql" | "gql" => "graphql",
+        "txt" => PLAINTEXT,
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// File extension for a language identifier, for naming downloads; `txt` when
+/// the language has none of its own.
+pub fn extension(language: &str) -> &'static str {
+    match language.trim().to_ascii_lowercase().as_str() {
+        "rust" => "rs",
+        "typescript" => "ts",
+        "tsx" => "tsx",
+        "javascript" => "js",
+        "jsx" => "jsx",
+        "python" => "py",
+        "cpp" => "cpp",
+        "c" => "c",
+        "go" => "go",
+        "java" => "java",
+        "markdown" => "md",
+        "json" => "json",
+        "yaml" => "yaml",
+        "html" => "html",
+        "css" => "css",
+        "sql" => "sql",
+        "bash" => "sh",
+        "ruby" => "rb",
+        "php" => "php",
+        "swift" => "swift",
+        "kotlin" => "kt",
+        "csharp" => "cs",
+        "lua" => "lua",
+        "scala" => "scala",
+        "haskell" => "hs",
+        "elixir" => "ex",
+        "erlang" => "erl",
+        "perl" => "pl",
+        "powershell" => "ps1",
+        "graphql" => "graphql",
+        _ => "txt",
+    }
+}
+
+/// Guess the language of plaintext content from a shebang, its overall shape, or
+/// telltale keywords. `None` when no rule matches.
+pub fn detect(content: &str) -> Option<&'static str> {
+    let content = content.trim_start();
+    if content.is_empty() {
+        return None;
+    }
+
+    if let Some(shebang) = content.strip_prefix("#!") {
+        return from_shebang(shebang.lines().next().unwrap_or_default());
+    }
+
+    if content.starts_with("<?php") {
+        return Some("php");
+    }
+
+    if (content.starts_with('{') || content.starts_with('['))
+        && serde_json::from_str::<serde::de::IgnoredAny>(content).is_ok()
+    {
+        return Some("json");
+    }
+
+    if content.starts_with('<') && content.trim_end().ends_with('>') {
+        return Some("html");
+    }
+
+    from_keywords(content)
+}
+
+// Interpreter named by a shebang line such as `/usr/bin/env python3 -u`
+fn from_shebang(line: &str) -> Option<&'static str> {
+    let mut words = line.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+
+    // python3.12 -> python, node18 -> node
+    let interpreter = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = match interpreter {
+        "python" | "pypy" => "python",
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "ash" => "bash",
+        "node" | "nodejs" | "deno" | "bun" => "javascript",
+        "ts-node" => "typescript",
+        "ruby" => "ruby",
+        "perl" => "perl",
+        "php" => "php",
+        "lua" | "luajit" => "lua",
+        "pwsh" | "powershell" => "powershell",
+        "elixir" => "elixir",
+        "escript" => "erlang",
+        "swift" => "swift",
+        "scala" => "scala",
+        "runghc" | "runhaskell" => "haskell",
+        _ => return None,
+    };
+    Some(language)
+}
+
+// Line-start keywords that are distinctive enough to decide on their own
+fn from_keywords(content: &str) -> Option<&'static str> {
+    let lines: Vec<&str> = content.lines().take(MAX_SCANNED_LINES).map(str::trim_start).collect();
+    let any_line = |prefixes: &[&str]| lines.iter().any(|line| prefixes.iter().any(|prefix| line.starts_with(prefix)));
+
+    if any_line(&["package main", "func main()"]) {
+        return Some("go");
+    }
+    if any_line(&["fn main()", "use std::", "pub fn ", "impl ", "#[derive("]) {
+        return Some("rust");
+    }
+    if any_line(&["#include <iostream>", "using namespace std", "std::cout"]) {
+        return Some("cpp");
+    }
+    if any_line(&["#include <", "#include \""]) {
+        return Some("c");
+    }
+    if any_line(&["public class ", "public static void main(", "import java."]) {
+        return Some("java");
+    }
+    if lines.iter().any(|line| {
+        (line.starts_with("def ") || line.starts_with("class ")) && line.trim_end().ends_with(':')
+            || line.starts_with("from ") && line.contains(" import ")
+            || line.starts_with("if __name__ ==")
+    }) {
+        return Some("python");
+    }
+    if any_line(&["interface ", "export interface ", "export type "]) {
+        return Some("typescript");
+    }
+    if any_line(&["console.log(", "function ", "module.exports", "export default ", "const ", "let "]) {
+        return Some("javascript");
+    }
+
+    if lines.iter().any(|line| {
+        let line = line.to_ascii_uppercase();
+        line.starts_with("CREATE TABLE")
+            || line.starts_with("INSERT INTO")
+            || line.starts_with("SELECT ") && line.contains(" FROM ")
+    }) {
+        return Some("sql");
+    }
+
+    if any_line(&["# ", "## ", "```"]) {
+        return Some("markdown");
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shebangs_name_the_interpreter() {
+        assert_eq!(detect("#!/usr/bin/env python3\nprint('hi')"), Some("python"));
+        assert_eq!(detect("#!/usr/bin/python3.12 -u\n"), Some("python"));
+        assert_eq!(detect("#!/usr/bin/env -S node --trace-warnings\n"), Some("javascript"));
+        assert_eq!(detect("#!/bin/sh\necho hi"), Some("bash"));
+        assert_eq!(detect("#!/opt/unknown-shell\n"), None);
+    }
+
+    #[test]
+    fn json_and_markup_are_recognized_by_shape() {
+        assert_eq!(detect("  {\"name\": \"rustybin\", \"tags\": [1, 2]}\n"), Some("json"));
+        assert_eq!(detect("[1, 2, 3]"), Some("json"));
+        assert_eq!(detect("{ not json"), None);
+        assert_eq!(detect("<!doctype html>\n<html></html>"), Some("html"));
+    }
+
+    #[test]
+    fn keywords_decide_when_nothing_else_does() {
+        assert_eq!(detect("package main\n\nfunc main() {}"), Some("go"));
+        assert_eq!(detect("use std::io;\n\nfn main() {}"), Some("rust"));
+        assert_eq!(detect("def greet(name):\n    return name"), Some("python"));
+        assert_eq!(detect("select id from pastes"), Some("sql"));
+        assert_eq!(detect("just some notes"), None);
+        assert_eq!(detect("   \n"), None);
+    }
+
+    #[test]
+    fn filenames_and_extensions_map_both_ways() {
+        assert_eq!(from_filename("Main.RS"), Some("rust"));
+        assert_eq!(from_filename("archive.tar.gz"), None);
+        assert_eq!(from_filename("Makefile"), None);
+        assert_eq!(extension("rust"), "rs");
+        assert_eq!(extension("plaintext"), "txt");
+        assert!(is_auto(" AUTO ") && is_auto("") && !is_auto("rust"));
+    }
+}
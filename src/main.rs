@@ -1,26 +1,51 @@
+mod access_log;
 mod auth;
+mod compress;
+mod config;
+mod content_policy;
 mod db;
+mod diff;
 mod error;
+mod extract;
 mod handlers;
 mod health;
+mod language;
+mod log_format;
+mod maintenance;
+mod migrations;
 mod models;
+mod multipart;
+mod net;
+mod openapi;
+mod operations;
+mod preflight;
+mod pretty_json;
+mod qr;
+mod read_only;
+mod read_token;
+mod security_headers;
+mod server_timing;
 
 use axum::{
     Json, Router,
-    extract::State,
+    extract::{DefaultBodyLimit, MatchedPath, State},
     http::{HeaderMap, HeaderValue, Method, Request, StatusCode},
     middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::{delete, get, post, put},
+    Extension,
 };
+use config::{Config, RouteRateLimit};
 use db::Database;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::net::IpAddr;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::sync::{Mutex, PoisonError};
 use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
 use tower_http::cors::CorsLayer;
 use tower_http::services::ServeDir;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -28,112 +53,434 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use auth::require_admin_auth;
 use error::json_error;
 use handlers::admin::{
-    admin_bulk_delete, admin_delete_paste, admin_list_pastes, admin_login, admin_logout,
-    admin_stats,
+    MAX_IMPORT_BYTES, admin_bulk_delete, admin_checkpoint, admin_delete_many, admin_delete_paste,
+    admin_dismiss_reports, admin_export, admin_get_paste, admin_import, admin_list_pastes, admin_login, admin_logout, admin_pin_paste,
+    admin_paste_accesses, admin_purge_expired, admin_reported_pastes, admin_stats, admin_storage_stats, admin_unpin_paste, admin_vacuum,
 };
-use handlers::paste::{create_paste, delete_paste, get_paste, update_paste};
+use handlers::paste::{
+    MAX_BATCH_PASTES, append_paste, create_paste, create_pastes_batch, create_read_token, delete_paste, diff_pastes, download_paste,
+    fork_paste, get_paste, get_paste_by_token, get_paste_meta, get_paste_raw, get_pastes_batch, get_pastes_by_tag, head_paste, paste_qr,
+    put_paste, report_paste, restore_paste, upload_paste, validate_paste,
+};
+use handlers::stats::{LANGUAGE_STATS_TTL, LanguageStatsCache, get_language_stats};
 use handlers::workspace::{create_workspace, delete_workspace, get_workspace, update_workspace};
 use health::HealthChecker;
+use log_format::{JsonFormat, LogFormat};
+use maintenance::Maintenance;
+use net::{IpNetwork, mask_ip};
+
+// One client's usage of a bucket; the window starts at its first request
+struct Window {
+    count: u32,
+    started: Instant,
+    last_seen: Instant,
+}
+
+// Result of a rate limit check against the bucket the request was counted in
+struct RateLimitStatus {
+    limit: u32,
+    remaining: u32,
+    reset_after: u32,
+}
+
+// A per-minute budget and each client's window in it
+struct Bucket {
+    limit: u32,
+    windows: Mutex<HashMap<IpAddr, Window>>,
+}
+
+impl Bucket {
+    fn new(limit: u32) -> Self {
+        Self { limit, windows: Mutex::new(HashMap::new()) }
+    }
+}
 
 // Define a simple rate limiter for our application
 struct AppRateLimiter {
-    // Rate limiter for GET requests (most permissive)
-    read_limiter: Arc<Mutex<HashMap<IpAddr, u32>>>,
-    // Rate limiter for POST requests (more restrictive)
-    create_limiter: Arc<Mutex<HashMap<IpAddr, u32>>>,
-    // Rate limiter for DELETE requests (most restrictive)
-    delete_limiter: Arc<Mutex<HashMap<IpAddr, u32>>>,
-    // Rate limiter for PUT requests (same as create)
-    update_limiter: Arc<Mutex<HashMap<IpAddr, u32>>>,
-    // Limits
-    read_limit: u32,
-    create_limit: u32,
-    delete_limit: u32,
-    update_limit: u32,
-    // Last reset time
-    last_reset: Arc<Mutex<Instant>>,
-    // Reset interval (1 minute)
+    // Default buckets by method: GET and HEAD read, POST creates, PUT updates
+    read: Bucket,
+    create: Bucket,
+    update: Bucket,
+    delete: Bucket,
+    // Buckets of routes with their own limit, keyed "METHOD /route" or "/route"
+    // for every method, with routes as the router matched them
+    routes: HashMap<String, Bucket>,
+    // Last time stale windows and semaphores were swept
+    last_sweep: Arc<Mutex<Instant>>,
+    // Window length (1 minute)
     reset_interval: Duration,
+    // Per-IP semaphores capping simultaneous in-flight requests
+    in_flight: Arc<Mutex<HashMap<IpAddr, Arc<Semaphore>>>>,
+    // Maximum concurrent requests per IP (0 = unlimited)
+    max_concurrent: u32,
+    // Prefix lengths that client addresses are masked to before keying the maps
+    ipv4_prefix: u8,
+    ipv6_prefix: u8,
+    // Trusted networks that bypass every limit
+    allowlist: Vec<IpNetwork>,
+    // Request paths (e.g. probes) that bypass every limit, for any client
+    exempt_paths: HashSet<String>,
+    // When each IP last got a create through, and how long it must wait before the next
+    last_create: Arc<Mutex<HashMap<IpAddr, Instant>>>,
+    create_min_interval: Duration,
+    // Most clients each map may track before the least recently seen are evicted (0 = unlimited)
+    max_tracked: usize,
+    // Clients evicted to stay under max_tracked since startup
+    evicted: AtomicU64,
 }
 
 impl AppRateLimiter {
-    fn new(read_limit: u32, create_limit: u32, delete_limit: u32, update_limit: u32) -> Self {
+    fn new(
+        read_limit: u32,
+        create_limit: u32,
+        delete_limit: u32,
+        update_limit: u32,
+        max_concurrent: u32,
+    ) -> Self {
         Self {
-            read_limiter: Arc::new(Mutex::new(HashMap::new())),
-            create_limiter: Arc::new(Mutex::new(HashMap::new())),
-            delete_limiter: Arc::new(Mutex::new(HashMap::new())),
-            update_limiter: Arc::new(Mutex::new(HashMap::new())),
-            read_limit,
-            create_limit,
-            delete_limit,
-            update_limit,
-            last_reset: Arc::new(Mutex::new(Instant::now())),
+            read: Bucket::new(read_limit),
+            create: Bucket::new(create_limit),
+            update: Bucket::new(update_limit),
+            delete: Bucket::new(delete_limit),
+            routes: HashMap::new(),
+            last_sweep: Arc::new(Mutex::new(Instant::now())),
             reset_interval: Duration::from_secs(60),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            max_concurrent,
+            ipv4_prefix: 32,
+            ipv6_prefix: 128,
+            allowlist: Vec::new(),
+            exempt_paths: HashSet::new(),
+            last_create: Arc::new(Mutex::new(HashMap::new())),
+            create_min_interval: Duration::ZERO,
+            max_tracked: 0,
+            evicted: AtomicU64::new(0),
         }
     }
 
-    fn check_and_update(&self, ip: &IpAddr, method: &Method) -> Result<u32, u32> {
-        // Check if we need to reset counters
+    // Aggregate clients by subnet so one host can't dodge limits by rotating addresses
+    fn with_subnet_prefixes(mut self, ipv4_prefix: u8, ipv6_prefix: u8) -> Self {
+        self.ipv4_prefix = ipv4_prefix;
+        self.ipv6_prefix = ipv6_prefix;
+        self
+    }
+
+    fn with_allowlist(mut self, allowlist: Vec<IpNetwork>) -> Self {
+        self.allowlist = allowlist;
+        self
+    }
+
+    // Give routes their own budgets in place of their method's, for ones that cost
+    // more (or less) than their neighbours
+    fn with_route_limits(mut self, limits: Vec<RouteRateLimit>) -> Self {
+        self.routes = limits.into_iter().map(|limit| (limit.key(), Bucket::new(limit.limit))).collect();
+        self
+    }
+
+    fn with_exempt_paths(mut self, paths: &[String]) -> Self {
+        self.exempt_paths = paths.iter().cloned().collect();
+        self
+    }
+
+    // Require a gap between one IP's creates, on top of the per-minute count
+    fn with_create_min_interval(mut self, interval: Duration) -> Self {
+        self.create_min_interval = interval;
+        self
+    }
+
+    // Bound each per-client map, so a flood of distinct (e.g. spoofed IPv6) addresses
+    // can't grow them until the next sweep
+    fn with_max_tracked(mut self, max_tracked: usize) -> Self {
+        self.max_tracked = max_tracked;
+        self
+    }
+
+    // Make room for a new key in a full map by dropping its least recently seen tenth,
+    // so a flood pays for one scan per batch rather than one per request
+    fn make_room<V>(&self, map: &mut HashMap<IpAddr, V>, key: &IpAddr, last_seen: impl Fn(&V) -> Instant) {
+        if self.max_tracked == 0 || map.len() < self.max_tracked || map.contains_key(key) {
+            return;
+        }
+
+        let mut seen: Vec<Instant> = map.values().map(&last_seen).collect();
+        let count = (self.max_tracked / 10).max(1).min(seen.len());
+        let (_, cutoff, _) = seen.select_nth_unstable(count - 1);
+        let cutoff = *cutoff;
+        let before = map.len();
+        map.retain(|_, value| last_seen(value) > cutoff);
+        self.record_evictions(before - map.len());
+    }
+
+    fn record_evictions(&self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let total = self.evicted.fetch_add(count as u64, Ordering::Relaxed) + count as u64;
+        tracing::warn!(
+            "Rate limiter is tracking {} clients; evicted the {} least recently seen ({} since startup)",
+            self.max_tracked, count, total
+        );
+    }
+
+    fn is_allowlisted(&self, ip: &IpAddr) -> bool {
+        self.allowlist.iter().any(|network| network.contains(*ip))
+    }
+
+    fn is_exempt_path(&self, path: &str) -> bool {
+        self.exempt_paths.contains(path)
+    }
+
+    fn key(&self, ip: &IpAddr) -> IpAddr {
+        mask_ip(*ip, self.ipv4_prefix, self.ipv6_prefix)
+    }
+
+    // Reserve one of this IP's concurrent request slots; the slot is freed when the permit drops.
+    // Returns Ok(None) when concurrency limiting is disabled.
+    fn try_acquire_slot(&self, ip: &IpAddr) -> Result<Option<OwnedSemaphorePermit>, TryAcquireError> {
+        if self.max_concurrent == 0 {
+            return Ok(None);
+        }
+
+        let key = self.key(ip);
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(PoisonError::into_inner);
+        // Semaphores with requests in flight can't be dropped, so only idle ones are evicted
+        if self.max_tracked > 0 && in_flight.len() >= self.max_tracked && !in_flight.contains_key(&key) {
+            let before = in_flight.len();
+            in_flight.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+            self.record_evictions(before - in_flight.len());
+        }
+        let semaphore = in_flight
+            .entry(key)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_concurrent as usize)))
+            .clone();
+        drop(in_flight);
+
+        semaphore.try_acquire_owned().map(Some)
+    }
+
+    // Methods some route handles; anything else is answered with 405 by the router
+    // and isn't charged to a bucket
+    fn is_limited_method(method: &Method) -> bool {
+        matches!(
+            *method,
+            Method::GET | Method::HEAD | Method::POST | Method::PUT | Method::DELETE
+        )
+    }
+
+    fn buckets(&self) -> impl Iterator<Item = &Bucket> {
+        [&self.read, &self.create, &self.update, &self.delete].into_iter().chain(self.routes.values())
+    }
+
+    // The bucket a request is counted in: its route's own, for the method first and
+    // then for any method, or else its method's. HEAD shares GET's, as it does by default.
+    fn bucket(&self, method: &Method, route: Option<&str>) -> &Bucket {
+        let method = if *method == Method::HEAD { &Method::GET } else { method };
+        let route_bucket = route.filter(|_| !self.routes.is_empty()).and_then(|route| {
+            self.routes.get(&format!("{} {}", method, route)).or_else(|| self.routes.get(route))
+        });
+        if let Some(bucket) = route_bucket {
+            return bucket;
+        }
+        match *method {
+            Method::POST => &self.create,
+            Method::DELETE => &self.delete,
+            Method::PUT => &self.update,
+            _ => &self.read, // GET, and the read bucket for anything else
+        }
+    }
+
+    // Count a request against its bucket (see `bucket`). Both outcomes report that
+    // bucket's limit and when this client's window in it resets.
+    fn check_and_update(&self, ip: &IpAddr, method: &Method, route: Option<&str>) -> Result<RateLimitStatus, RateLimitStatus> {
         let now = Instant::now();
-        let mut last_reset = self.last_reset.lock().unwrap();
-        if now.duration_since(*last_reset) >= self.reset_interval {
-            // Reset all counters
-            self.read_limiter.lock().unwrap().clear();
-            self.create_limiter.lock().unwrap().clear();
-            self.delete_limiter.lock().unwrap().clear();
-            self.update_limiter.lock().unwrap().clear();
-            *last_reset = now;
-        }
-
-        // Choose the appropriate limiter based on the HTTP method
-        let (limiter, limit) = match method {
-            &Method::GET => (&self.read_limiter, self.read_limit),
-            &Method::POST => (&self.create_limiter, self.create_limit),
-            &Method::DELETE => (&self.delete_limiter, self.delete_limit),
-            &Method::PUT => (&self.update_limiter, self.update_limit),
-            _ => (&self.read_limiter, self.read_limit), // Default to read limiter for other methods
-        };
+        let mut last_sweep = self.last_sweep.lock().unwrap_or_else(PoisonError::into_inner);
+        if now.duration_since(*last_sweep) >= self.reset_interval {
+            // Drop windows that have run out
+            for bucket in self.buckets() {
+                bucket
+                    .windows
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .retain(|_, window| now.duration_since(window.started) < self.reset_interval);
+            }
+            self.last_create
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .retain(|_, last| now.duration_since(*last) < self.create_min_interval);
+            // Drop semaphores for IPs with nothing in flight (the map holds the only reference)
+            self.in_flight
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+            *last_sweep = now;
+        }
+        drop(last_sweep);
 
-        // Get the current count for this IP
-        let mut map = limiter.lock().unwrap();
-        let count = map.entry(*ip).or_insert(0);
+        let bucket = self.bucket(method, route);
+        let limit = bucket.limit;
 
-        // Check if we're over the limit
-        if *count >= limit {
-            // Calculate remaining time until reset
-            let elapsed = now.duration_since(*last_reset);
-            let remaining = self.reset_interval.saturating_sub(elapsed).as_secs();
+        // Get this IP's window, starting a new one if the last has run out
+        let mut map = bucket.windows.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = self.key(ip);
+        self.make_room(&mut map, &key, |window| window.last_seen);
+        let window = map.entry(key).or_insert(Window { count: 0, started: now, last_seen: now });
+        if now.duration_since(window.started) >= self.reset_interval {
+            *window = Window { count: 0, started: now, last_seen: now };
+        }
+        window.last_seen = now;
 
-            // Return error with remaining time
-            Err(remaining as u32)
+        let reset_after = self.secs_until_reset(now, window.started);
+
+        // Check if we're over the limit
+        if window.count >= limit {
+            Err(RateLimitStatus { limit, remaining: 0, reset_after })
         } else {
-            // Increment the counter
-            *count += 1;
+            window.count += 1;
+            Ok(RateLimitStatus { limit, remaining: limit - window.count, reset_after })
+        }
+    }
+
+    // Whole seconds (rounded up) this IP must still wait before its next create,
+    // or None when it may create now
+    fn create_cooldown(&self, ip: &IpAddr) -> Option<u32> {
+        if self.create_min_interval.is_zero() {
+            return None;
+        }
+
+        let last_create = self.last_create.lock().unwrap_or_else(PoisonError::into_inner);
+        self.cooldown_left(&last_create, &self.key(ip), Instant::now())
+    }
+
+    // Start this IP's create cooldown once a create has been let through. Fails like
+    // `create_cooldown` when a concurrent create from the same IP started it first.
+    fn start_create_cooldown(&self, ip: &IpAddr) -> Result<(), u32> {
+        if self.create_min_interval.is_zero() {
+            return Ok(());
+        }
 
-            // Return remaining requests
-            Ok(limit - *count)
+        let now = Instant::now();
+        let mut last_create = self.last_create.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = self.key(ip);
+        if let Some(wait) = self.cooldown_left(&last_create, &key, now) {
+            return Err(wait);
         }
+        self.make_room(&mut last_create, &key, |last| *last);
+        last_create.insert(key, now);
+        Ok(())
+    }
+
+    fn cooldown_left(&self, last_create: &HashMap<IpAddr, Instant>, key: &IpAddr, now: Instant) -> Option<u32> {
+        let last = last_create.get(key)?;
+        let wait = self.create_min_interval.saturating_sub(now.duration_since(*last));
+        (!wait.is_zero()).then(|| wait.as_secs_f64().ceil() as u32)
+    }
+
+    // Each method bucket's budget for this IP as it stands, without counting anything
+    fn peek(&self, ip: &IpAddr) -> [(&'static str, RateLimitStatus); 4] {
+        let now = Instant::now();
+        let key = self.key(ip);
+        [("read", &self.read), ("create", &self.create), ("update", &self.update), ("delete", &self.delete)]
+            .map(|(name, bucket)| (name, self.peek_bucket(bucket, &key, now)))
     }
 
-    fn get_reset_time(&self) -> u32 {
+    // The same for each route with its own limit, by its key, in key order
+    fn peek_routes(&self, ip: &IpAddr) -> Vec<(&str, RateLimitStatus)> {
         let now = Instant::now();
-        let last_reset = *self.last_reset.lock().unwrap();
-        let elapsed = now.duration_since(last_reset);
-        self.reset_interval.saturating_sub(elapsed).as_secs() as u32
+        let key = self.key(ip);
+        let mut routes: Vec<_> = self
+            .routes
+            .iter()
+            .map(|(route, bucket)| (route.as_str(), self.peek_bucket(bucket, &key, now)))
+            .collect();
+        routes.sort_by_key(|(route, _)| *route);
+        routes
+    }
+
+    // A client with no live window has the full limit and a full window ahead
+    fn peek_bucket(&self, bucket: &Bucket, key: &IpAddr, now: Instant) -> RateLimitStatus {
+        let limit = bucket.limit;
+        match bucket.windows.lock().unwrap_or_else(PoisonError::into_inner).get(key) {
+            Some(window) if now.duration_since(window.started) < self.reset_interval => RateLimitStatus {
+                limit,
+                remaining: limit.saturating_sub(window.count),
+                reset_after: self.secs_until_reset(now, window.started),
+            },
+            _ => RateLimitStatus { limit, remaining: limit, reset_after: self.reset_interval.as_secs() as u32 },
+        }
+    }
+
+    // Entries in the largest per-client map, for spotting runaway growth
+    fn tracked_clients(&self) -> usize {
+        self.buckets()
+            .map(|bucket| bucket.windows.lock().unwrap_or_else(PoisonError::into_inner).len())
+            .chain([self.in_flight.lock().unwrap_or_else(PoisonError::into_inner).len(), self.last_create.lock().unwrap_or_else(PoisonError::into_inner).len()])
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Round up so a blocked client is never told to retry in 0 seconds
+    fn secs_until_reset(&self, now: Instant, started: Instant) -> u32 {
+        self.reset_interval
+            .saturating_sub(now.duration_since(started))
+            .as_secs_f64()
+            .ceil() as u32
     }
 }
 
-fn add_rate_limit_headers(headers: &mut HeaderMap, remaining: u32, reset_after_secs: u32) {
+fn add_rate_limit_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    headers.insert(
+        "x-ratelimit-limit",
+        HeaderValue::from_str(&status.limit.to_string()).unwrap(),
+    );
     headers.insert(
         "x-ratelimit-remaining",
-        HeaderValue::from_str(&remaining.to_string()).unwrap(),
+        HeaderValue::from_str(&status.remaining.to_string()).unwrap(),
     );
     headers.insert(
         "x-ratelimit-reset",
-        HeaderValue::from_str(&reset_after_secs.to_string()).unwrap(),
+        HeaderValue::from_str(&status.reset_after.to_string()).unwrap(),
     );
 }
 
+// Standard `Retry-After` for a throttled request, matching `x-ratelimit-reset`
+fn add_retry_after_header(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    headers.insert(
+        axum::http::header::RETRY_AFTER,
+        HeaderValue::from_str(&status.reset_after.to_string()).unwrap(),
+    );
+}
+
+// Headers for allowlisted clients, which have no budget to report
+fn add_unlimited_headers(headers: &mut HeaderMap) {
+    headers.insert("x-ratelimit-limit", HeaderValue::from_static("unlimited"));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from_static("unlimited"));
+}
+
+// Routes that store a new paste or workspace when POSTed to. Only creates wait out
+// CREATE_MIN_INTERVAL_MS; validation, appends, reports and the like don't.
+const CREATE_ROUTES: [&str; 5] = ["/v1/pastes", "/v1/pastes/batch", "/v1/pastes/upload", "/v1/pastes/{id}/fork", "/v1/workspaces"];
+
+// Whether a request creates a paste or workspace, by the route it matched. A text
+// PUT to a paste ID creates the paste at that ID.
+fn is_create_request(req: &Request<axum::body::Body>, route: Option<&str>) -> bool {
+    match (req.method(), route) {
+        (&Method::POST, Some(route)) => CREATE_ROUTES.contains(&route),
+        (&Method::PUT, Some("/v1/pastes/{id}")) => operations::is_text_body(req),
+        _ => false,
+    }
+}
+
+fn cooldown_response(retry_after: u32) -> Response {
+    let mut body = json_error(&format!("Creating too quickly. Try again in {} seconds", retry_after));
+    body["code"] = "cooldown".into();
+    let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+    response.headers_mut().insert(
+        axum::http::header::RETRY_AFTER,
+        HeaderValue::from_str(&retry_after.to_string()).unwrap(),
+    );
+    response
+}
+
 // Custom rate limiting middleware
 async fn rate_limit(req: Request<axum::body::Body>, next: Next) -> Result<Response, StatusCode> {
     // Get the client's IP address
@@ -153,9 +500,53 @@ async fn rate_limit(req: Request<axum::body::Body>, next: Next) -> Result<Respon
     // Get the method
     let method = req.method().clone();
 
-    // Check if the request is allowed for this IP
-    match rate_limiter.check_and_update(&ip, &method) {
-        Ok(remaining) => {
+    // OPTIONS never reaches a handler; the router only reports the route's methods
+    if method == Method::OPTIONS {
+        return Ok(next.run(req).await);
+    }
+
+    // Probes on RATE_LIMIT_EXEMPT_PATHS are neither counted nor capped
+    if rate_limiter.is_exempt_path(req.uri().path()) {
+        return Ok(next.run(req).await);
+    }
+
+    // Trusted clients skip both the concurrency cap and the per-minute buckets
+    if rate_limiter.is_allowlisted(&ip) {
+        let mut response = next.run(req).await;
+        add_unlimited_headers(response.headers_mut());
+        return Ok(response);
+    }
+
+    // Cap simultaneous in-flight requests before counting against the per-minute budget
+    let _slot = match rate_limiter.try_acquire_slot(&ip) {
+        Ok(slot) => slot,
+        Err(_) => {
+            return Ok((
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(json_error("Too many concurrent requests")),
+            )
+                .into_response());
+        }
+    };
+
+    if !AppRateLimiter::is_limited_method(&method) {
+        return Ok(next.run(req).await);
+    }
+
+    // Check if the request is allowed for this IP, in its route's bucket when it has one.
+    // A create too soon after the last one is refused before it's counted.
+    let route = req.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    let is_create = is_create_request(&req, route.as_deref());
+    if is_create && let Some(retry_after) = rate_limiter.create_cooldown(&ip) {
+        return Ok(cooldown_response(retry_after));
+    }
+    match rate_limiter.check_and_update(&ip, &method, route.as_deref()) {
+        Ok(status) => {
+            // Only creates the count let through start the cooldown
+            if is_create && let Err(retry_after) = rate_limiter.start_create_cooldown(&ip) {
+                return Ok(cooldown_response(retry_after));
+            }
+
             // Track server errors for health monitoring
             let health_checker = req.extensions().get::<Arc<HealthChecker>>().cloned();
 
@@ -163,24 +554,21 @@ async fn rate_limit(req: Request<axum::body::Body>, next: Next) -> Result<Respon
             let mut response = next.run(req).await;
 
             // Record DB/server errors
-            if response.status().is_server_error() {
-                if let Some(hc) = health_checker {
-                    hc.record_db_error();
-                }
+            if response.status().is_server_error()
+                && let Some(hc) = health_checker
+            {
+                hc.record_db_error();
             }
 
-            // Get time until reset
-            let reset_after = rate_limiter.get_reset_time();
-
             // Add rate limit headers to the response
-            add_rate_limit_headers(response.headers_mut(), remaining, reset_after);
+            add_rate_limit_headers(response.headers_mut(), &status);
 
             Ok(response)
         }
-        Err(reset_after) => {
+        Err(status) => {
             // Request is not allowed, return a 429 Too Many Requests response
             let error_message =
-                format!("Rate limit exceeded. Try again in {} seconds", reset_after);
+                format!("Rate limit exceeded. Try again in {} seconds", status.reset_after);
 
             // Create response with rate limit headers
             let mut response = (
@@ -190,132 +578,261 @@ async fn rate_limit(req: Request<axum::body::Body>, next: Next) -> Result<Respon
                 .into_response();
 
             // Add rate limit headers
-            add_rate_limit_headers(response.headers_mut(), 0, reset_after);
+            add_rate_limit_headers(response.headers_mut(), &status);
+            add_retry_after_header(response.headers_mut(), &status);
 
             Ok(response)
         }
     }
 }
 
-#[tokio::main]
-async fn main() {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+// Swap the socket peer in `ConnectInfo` for the client named by a trusted proxy's
+// forwarding headers, so the rate limiter, handlers and access log all see it
+async fn forwarded_client(
+    State(trusted): State<Arc<Vec<IpNetwork>>>,
+    mut req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    if let Some(axum::extract::ConnectInfo(peer)) = req.extensions().get::<axum::extract::ConnectInfo<SocketAddr>>().copied() {
+        let client = net::client_ip(peer.ip(), req.headers(), &trusted);
+        if client != peer.ip() {
+            req.extensions_mut().insert(axum::extract::ConnectInfo(SocketAddr::new(client, peer.port())));
+        }
+    }
+    next.run(req).await
+}
 
-    // Load environment variables
-    dotenv::dotenv().ok();
+// 405 for a known route hit with a method it doesn't support. The router adds the
+// `Allow` header listing the supported methods.
+async fn method_not_allowed() -> impl IntoResponse {
+    (
+        StatusCode::METHOD_NOT_ALLOWED,
+        Json(json_error("Method not allowed")),
+    )
+}
 
-    // Create database instance
-    let db = Arc::new(Database::new());
+// Fail requests that take longer than the configured timeout with 408. The timer
+// only fires while the handler is awaiting; it can't interrupt blocking work.
+async fn request_timeout(
+    State(timeout): State<Duration>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    match tokio::time::timeout(timeout, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => (
+            StatusCode::REQUEST_TIMEOUT,
+            Json(json_error("Request timed out")),
+        )
+            .into_response(),
+    }
+}
 
-    // Create health checker
-    let health_checker = Arc::new(HealthChecker::new("data/pastes.db".to_string()));
-
-    // Get port from environment or use default
-    let port = env::var("PORT")
-        .unwrap_or_else(|_| "3000".to_string())
-        .parse::<u16>()
-        .expect("PORT must be a number");
-
-    // Get allowed origins from environment variable or use defaults
-    let allowed_origins_str = env::var("CORS_ALLOWED_ORIGINS")
-        .unwrap_or_else(|_| "https://rustybin.net,https://rustyb.in,http://localhost:8080,http://localhost:5173,https://api.rustybin.net,https://api.rustyb.in".to_string());
-
-    let allowed_origins: Vec<axum::http::HeaderValue> = allowed_origins_str
-        .split(',')
-        .map(|origin| {
-            origin
-                .trim()
-                .parse()
-                .expect(&format!("Invalid origin URL: {}", origin))
-        })
-        .collect();
+// Build the CORS layer from the configured origins, skipping any that aren't
+// valid header values. Returns the layer and the origins actually allowed.
+fn build_cors_layer(origins: &[String], max_age_secs: u64) -> (CorsLayer, Vec<String>) {
+    let mut allowed_origins: Vec<HeaderValue> = Vec::new();
+    let mut effective_origins: Vec<String> = Vec::new();
+
+    for origin in origins {
+        match origin.parse::<HeaderValue>() {
+            Ok(value) => {
+                allowed_origins.push(value);
+                effective_origins.push(origin.clone());
+            }
+            Err(_) => tracing::warn!("Ignoring invalid CORS origin: {:?}", origin),
+        }
+    }
+
+    if allowed_origins.is_empty() {
+        tracing::warn!("No valid CORS origins configured; cross-origin requests will be rejected");
+    }
 
-    // Configure CORS
     let cors = CorsLayer::new()
         .allow_origin(allowed_origins)
         .allow_methods([
-            axum::http::Method::GET,
-            axum::http::Method::POST,
-            axum::http::Method::PUT,
-            axum::http::Method::DELETE,
-            axum::http::Method::OPTIONS,
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::OPTIONS,
         ])
-        .allow_headers([
-            axum::http::header::CONTENT_TYPE,
-            axum::http::header::AUTHORIZATION,
-            axum::http::header::ACCEPT,
-            axum::http::header::ORIGIN,
+        .allow_headers(preflight::ALLOWED_HEADERS)
+        .expose_headers([
+            axum::http::header::ETAG,
+            axum::http::header::CONTENT_DISPOSITION,
+            axum::http::HeaderName::from_static("idempotent-replayed"),
         ])
         .allow_credentials(true)
-        .max_age(std::time::Duration::from_secs(3600));
+        .max_age(Duration::from_secs(max_age_secs));
 
-    // Get rate limit configuration from environment variables or use defaults
-    let read_limit = env::var("READ_RATE_LIMIT")
-        .unwrap_or_else(|_| "45".to_string())
-        .parse::<u32>()
-        .unwrap_or(45);
+    (cors, effective_origins)
+}
 
-    let create_limit = env::var("CREATE_RATE_LIMIT")
-        .unwrap_or_else(|_| "15".to_string())
-        .parse::<u32>()
-        .unwrap_or(15);
+#[tokio::main]
+async fn main() {
+    // Load environment variables first, so a .env file can set RUST_LOG and LOG_FORMAT
+    dotenv::dotenv().ok();
 
-    let delete_limit = env::var("DELETE_RATE_LIMIT")
-        .unwrap_or_else(|_| "15".to_string())
-        .parse::<u32>()
-        .unwrap_or(15);
+    // Initialize tracing before anything logs
+    let default_format = LogFormat::default_for(&std::env::var("RUST_ENV").unwrap_or_default());
+    let (log_format, log_format_error) = match std::env::var("LOG_FORMAT") {
+        Ok(value) => match value.parse() {
+            Ok(format) => (format, None),
+            Err(e) => (default_format, Some(e)),
+        },
+        Err(_) => (default_format, None),
+    };
+    let registry = tracing_subscriber::registry().with(tracing_subscriber::EnvFilter::new(
+        std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
+    ));
+    match log_format {
+        LogFormat::Pretty => registry.with(tracing_subscriber::fmt::layer()).init(),
+        LogFormat::Compact => registry.with(tracing_subscriber::fmt::layer().compact()).init(),
+        LogFormat::Json => registry.with(tracing_subscriber::fmt::layer().event_format(JsonFormat)).init(),
+    }
+    // Reported once logging works, and fatal like any other invalid setting
+    if let Some(e) = log_format_error {
+        tracing::error!("Invalid LOG_FORMAT: {}", e);
+        std::process::exit(1);
+    }
 
-    let update_limit = env::var("UPDATE_RATE_LIMIT")
-        .unwrap_or_else(|_| "15".to_string())
-        .parse::<u32>()
-        .unwrap_or(15);
+    // Load configuration (config file, then environment overrides)
+    let config = match Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    match serde_json::to_string(&*config) {
+        Ok(effective) => tracing::info!(config = %effective, "Effective configuration"),
+        Err(e) => tracing::warn!("Failed to serialize configuration for logging: {}", e),
+    }
+
+    // A read-only data volume would otherwise only fail at the first write, deep in
+    // a request; fail fast unless read-only mode was asked for
+    let writable = Database::check_writable(&config.data_dir);
+    if let Err(e) = &writable
+        && !config.read_only_mode
+    {
+        tracing::error!(
+            "Data directory {} is not writable: {}. Fix its permissions or mount, or set READ_ONLY_MODE=true to serve existing pastes read-only",
+            config.data_dir.display(),
+            e
+        );
+        std::process::exit(1);
+    }
+
+    // Create database instance
+    let opened = if config.read_only_mode {
+        tracing::warn!("READ_ONLY_MODE is set: pastes are served read-only and writes return 503");
+        Database::open_read_only(&config.data_dir, config.paste_options(), writable.is_err())
+    } else {
+        Database::open_with_retry(&config.data_dir, config.paste_options(), config.db_open_retry())
+    };
+    let db = match opened {
+        Ok(db) => match config.content_policy() {
+            Some(policy) => Arc::new(db.with_content_policy(Arc::new(policy))),
+            None => Arc::new(db),
+        },
+        Err(e) => {
+            tracing::error!("Failed to open database in {}: {}", config.data_dir.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    // Serialize maintenance tasks and start the expired-paste sweeper
+    let maintenance = Arc::new(Maintenance::new(config.max_concurrent_maintenance));
+    if config.purge_interval_secs > 0 && !config.read_only_mode {
+        maintenance::spawn_purge_sweeper(
+            maintenance.clone(),
+            db.clone(),
+            Duration::from_secs(config.purge_interval_secs),
+        );
+    }
+    if config.checkpoint_interval_secs > 0 && !config.read_only_mode {
+        maintenance::spawn_checkpoint_scheduler(
+            maintenance.clone(),
+            db.clone(),
+            Duration::from_secs(config.checkpoint_interval_secs),
+        );
+    }
+    if config.view_flush_interval_secs > 0 && !config.read_only_mode {
+        maintenance::spawn_view_flusher(db.clone(), Duration::from_secs(config.view_flush_interval_secs));
+    }
+
+    // Create health checker
+    let health_checker = Arc::new(
+        HealthChecker::new(Database::database_path(&config.data_dir).to_string_lossy().to_string())
+            .with_min_free_disk_mb(config.health_min_free_disk_mb)
+            .with_max_tracked_clients(config.health_max_tracked_clients),
+    );
+
+    // Configure CORS
+    let (cors, effective_origins) = build_cors_layer(&config.cors_allowed_origins, config.cors_max_age_secs);
 
     // Create rate limiter
     let rate_limiter = Arc::new(AppRateLimiter::new(
-        read_limit,
-        create_limit,
-        delete_limit,
-        update_limit,
-    ));
+        config.read_rate_limit,
+        config.create_rate_limit,
+        config.delete_rate_limit,
+        config.update_rate_limit,
+        config.max_concurrent_per_ip,
+    )
+    .with_subnet_prefixes(config.rate_limit_ipv4_prefix, config.rate_limit_ipv6_prefix)
+    .with_allowlist(config.rate_limit_allowlist())
+    .with_exempt_paths(&config.rate_limit_exempt_paths)
+    .with_route_limits(config.route_rate_limits())
+    .with_create_min_interval(Duration::from_millis(config.create_min_interval_ms))
+    .with_max_tracked(config.rate_limit_max_tracked_ips));
 
     // Build our application with routes
-    let config_state = Arc::new(ConfigInfo {
-        read_limit,
-        create_limit,
-        update_limit,
-        delete_limit,
-        reset_interval_secs: 60,
-    });
+    let config_state = Arc::new(ConfigInfo::from_config(&config));
 
     let app = Router::new()
         .route(
             "/v1/health",
             get({
                 let hc = health_checker.clone();
-                move || health_check(hc)
+                let limiter = rate_limiter.clone();
+                move |query: axum::extract::Query<HealthQuery>| health_check(hc, limiter.clone(), query.0)
             }),
         )
+        .route("/v1/pastes", post(create_paste).get(get_pastes_batch))
         .route(
-            "/v1/config",
-            get({
-                let config = config_state.clone();
-                move || get_config(config)
-            }),
+            "/v1/pastes/batch",
+            // Room for every item at the size limit, plus JSON overhead
+            post(create_pastes_batch).layer(DefaultBodyLimit::max(
+                MAX_BATCH_PASTES * config.max_paste_bytes + 64 * 1024,
+            )),
+        )
+        .route("/v1/pastes/validate", post(validate_paste))
+        .route(
+            "/v1/pastes/upload",
+            post(upload_paste).layer(DefaultBodyLimit::max(config.upload_body_limit())),
         )
-        .route("/v1/pastes", post(create_paste))
-        .route("/v1/pastes/{id}", get(get_paste))
-        .route("/v1/pastes/{id}", put(update_paste))
+        .route("/v1/pastes/{id}", get(get_paste).head(head_paste))
+        .route("/v1/pastes/{id}", put(put_paste))
         .route("/v1/pastes/{id}", delete(delete_paste))
+        .route("/v1/pastes/{id}/meta", get(get_paste_meta))
+        .route("/v1/pastes/{id}/raw", get(get_paste_raw))
+        .route("/v1/pastes/{id}/download", get(download_paste))
+        .route("/v1/pastes/{id}/qr", get(paste_qr))
+        .route("/v1/pastes/{id}/append", post(append_paste))
+        .route("/v1/pastes/{id}/restore", post(restore_paste))
+        .route("/v1/pastes/{id}/fork", post(fork_paste))
+        .route("/v1/pastes/{id}/diff/{other_id}", get(diff_pastes))
+        .route("/v1/pastes/{id}/token", post(create_read_token))
+        .route("/v1/pastes/{id}/report", post(report_paste))
+        .route("/v1/pastes/token/{token}", get(get_paste_by_token))
+        .route("/v1/pastes/by-tag/{tag}", get(get_pastes_by_tag))
+        .route("/v1/stats/languages", get(get_language_stats))
         .route("/v1/workspaces", post(create_workspace))
         .route("/v1/workspaces/{id}", get(get_workspace))
         .route("/v1/workspaces/{id}", put(update_workspace))
         .route("/v1/workspaces/{id}", delete(delete_workspace))
+        .method_not_allowed_fallback(method_not_allowed)
         .with_state(db.clone())
         .layer(middleware::from_fn_with_state(
             (rate_limiter.clone(), health_checker.clone()),
@@ -329,24 +846,17 @@ async fn main() {
         ));
 
     // Admin rate limiter (separate from public API per FR-015)
-    let admin_login_limit = env::var("ADMIN_LOGIN_RATE_LIMIT")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(5u32);
-    let admin_read_limit = env::var("ADMIN_READ_RATE_LIMIT")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(60u32);
-    let admin_delete_limit = env::var("ADMIN_DELETE_RATE_LIMIT")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(20u32);
+    let admin_login_limit = config.admin_login_rate_limit;
+    let admin_read_limit = config.admin_read_rate_limit;
+    let admin_delete_limit = config.admin_delete_rate_limit;
     let admin_rate_limiter = Arc::new(AppRateLimiter::new(
         admin_read_limit,
         admin_login_limit,
         admin_delete_limit,
         admin_read_limit,
-    ));
+        0,
+    ).with_subnet_prefixes(config.rate_limit_ipv4_prefix, config.rate_limit_ipv6_prefix)
+    .with_max_tracked(config.rate_limit_max_tracked_ips));
 
     // Conditionally register admin routes when ADMIN_SECRET is set
     let app = if env::var("ADMIN_SECRET").is_ok() {
@@ -359,12 +869,30 @@ async fn main() {
         // Protected admin routes (require auth)
         let admin_protected = Router::new()
             .route("/v1/admin/stats", get(admin_stats))
+            .route("/v1/admin/storage", get(admin_storage_stats))
             .route(
                 "/v1/admin/pastes",
                 get(admin_list_pastes).delete(admin_bulk_delete),
             )
-            .route("/v1/admin/pastes/{id}", delete(admin_delete_paste))
+            .route("/v1/admin/pastes/delete", post(admin_delete_many))
+            .route("/v1/admin/pastes/{id}", get(admin_get_paste).delete(admin_delete_paste))
+            .route("/v1/admin/pastes/{id}/accesses", get(admin_paste_accesses))
+            .route("/v1/admin/pastes/{id}/reports", delete(admin_dismiss_reports))
+            .route("/v1/admin/reports", get(admin_reported_pastes))
+            .route(
+                "/v1/admin/pastes/{id}/pin",
+                put(admin_pin_paste).delete(admin_unpin_paste),
+            )
             .route("/v1/admin/logout", post(admin_logout))
+            .route("/v1/admin/maintenance/purge", post(admin_purge_expired))
+            .route("/v1/admin/maintenance/vacuum", post(admin_vacuum))
+            .route("/v1/admin/checkpoint", post(admin_checkpoint))
+            .route("/v1/admin/export", get(admin_export))
+            .route(
+                "/v1/admin/import",
+                post(admin_import).layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES)),
+            )
+            .method_not_allowed_fallback(method_not_allowed)
             .layer(middleware::from_fn(require_admin_auth))
             .with_state(db.clone());
 
@@ -381,23 +909,23 @@ async fn main() {
                         .map(|ci| ci.0.ip())
                         .unwrap_or_else(|| "0.0.0.0".parse().unwrap());
 
-                    match limiter.check_and_update(&ip, &Method::POST) {
-                        Ok(remaining) => {
+                    match limiter.check_and_update(&ip, &Method::POST, None) {
+                        Ok(status) => {
                             let mut response = next.run(req).await;
-                            let reset = limiter.get_reset_time();
-                            add_rate_limit_headers(response.headers_mut(), remaining, reset);
+                            add_rate_limit_headers(response.headers_mut(), &status);
                             Ok::<Response, StatusCode>(response)
                         }
-                        Err(reset_after) => {
+                        Err(status) => {
                             let msg = format!(
                                 "Too many login attempts. Try again in {} seconds",
-                                reset_after
+                                status.reset_after
                             );
                             let mut response = (
                                 StatusCode::TOO_MANY_REQUESTS,
                                 Json(json_error(&msg)),
                             ).into_response();
-                            add_rate_limit_headers(response.headers_mut(), 0, reset_after);
+                            add_rate_limit_headers(response.headers_mut(), &status);
+                            add_retry_after_header(response.headers_mut(), &status);
                             Ok(response)
                         }
                     }
@@ -410,25 +938,125 @@ async fn main() {
         app
     };
 
-    // Apply CORS after merging all routes so it covers admin endpoints too
-    let app = app.layer(cors);
+    // API description, build info, public limits and the caller's rate limit budget,
+    // outside the rate-limited routes
+    let app = app
+        .route("/v1/openapi.json", get(openapi::openapi_spec))
+        .route("/v1/version", get(version_info))
+        .route(
+            "/v1/config",
+            get({
+                let config = config_state.clone();
+                move || get_config(config)
+            }),
+        )
+        .route(
+            "/v1/ratelimit",
+            get({
+                let limiter = rate_limiter.clone();
+                move |axum::extract::ConnectInfo(client): axum::extract::ConnectInfo<SocketAddr>| rate_limit_budget(limiter.clone(), client)
+            }),
+        );
 
-    // Add static file serving for production
-    let app = if env::var("RUST_ENV").unwrap_or_default() == "production" {
-        app.fallback_service(ServeDir::new("dist").fallback(axum::routing::get(serve_spa)))
+    // Cap request bodies (routes with their own limit override it), share the
+    // configuration and maintenance coordinator with handlers, then apply
+    // `?pretty=true` formatting and CORS after merging all routes so they cover
+    // admin endpoints too
+    let app = app
+        .layer(DefaultBodyLimit::max(config.request_body_limit()))
+        .layer(Extension(config.clone()))
+        .layer(Extension(maintenance))
+        .layer(Extension(Arc::new(LanguageStatsCache::new(LANGUAGE_STATS_TTL))))
+        .layer(middleware::from_fn(pretty_json::pretty_json));
+
+    // Turn writes away before they reach a handler, inside CORS so browsers can
+    // read the 503
+    let app = if config.read_only_mode {
+        app.layer(middleware::from_fn(read_only::reject_writes))
     } else {
         app
     };
+    // Refuse disabled operations the same way, with 403 since it won't change
+    // until the operator reconfigures
+    let enabled_operations = config.enabled_operations();
+    let app = if enabled_operations.all_enabled() {
+        app
+    } else {
+        app.layer(middleware::from_fn_with_state(Arc::new(enabled_operations), operations::reject_disabled))
+    };
+    // Answer OPTIONS on API routes with each route's own methods, from outside
+    // CORS so it doesn't answer them with the global list first
+    let preflight = preflight::Preflight::new(app.clone(), effective_origins.clone(), config.cors_max_age_secs);
+    let app = app
+        .layer(cors)
+        .layer(middleware::from_fn_with_state(preflight, preflight::preflight));
+
+    // Add static file serving for production, with browser security headers on
+    // the static responses only
+    let app = if config.is_production() {
+        SPA_INDEX.get_or_init(load_spa_index);
+        let spa = Router::new()
+            .fallback_service(ServeDir::new("dist").fallback(axum::routing::get(serve_spa)))
+            .layer(middleware::from_fn_with_state(
+                config.content_security_policy(),
+                security_headers::security_headers,
+            ));
+        app.fallback_service(spa)
+    } else {
+        app
+    };
+
+    // Time the whole request, static files included, when asked to
+    let app = if config.server_timing {
+        app.layer(middleware::from_fn(server_timing::server_timing))
+    } else {
+        app
+    };
+
+    // Bound request time outside the rate limiter, which has already counted the
+    // request by the time a handler can stall
+    let app = if config.request_timeout_secs > 0 {
+        app.layer(middleware::from_fn_with_state(
+            Duration::from_secs(config.request_timeout_secs),
+            request_timeout,
+        ))
+    } else {
+        app
+    };
+
+    // Log every request last, so it sees the final response from every layer
+    let quiet_paths: HashSet<String> = config.quiet_paths.iter().cloned().collect();
+    let app = app.layer(middleware::from_fn_with_state(Arc::new(quiet_paths), access_log::access_log));
+
+    // Resolve the client behind trusted proxies before anything reads it
+    let trusted_proxies = config.trusted_proxies();
+    let app = if trusted_proxies.is_empty() {
+        app
+    } else {
+        app.layer(middleware::from_fn_with_state(Arc::new(trusted_proxies), forwarded_client))
+    };
 
     // Define the address to listen on
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     tracing::info!("Listening on {}", addr);
-    tracing::info!("CORS allowed origins: {}", allowed_origins_str);
+    tracing::info!("CORS allowed origins: {}", effective_origins.join(","));
     tracing::info!("Rate limiting enabled per IP:");
-    tracing::info!("  - Read operations: {} per minute", read_limit);
-    tracing::info!("  - Create operations: {} per minute", create_limit);
-    tracing::info!("  - Update operations: {} per minute", update_limit);
-    tracing::info!("  - Delete operations: {} per minute", delete_limit);
+    tracing::info!("  - Read operations: {} per minute", config.read_rate_limit);
+    tracing::info!("  - Create operations: {} per minute", config.create_rate_limit);
+    tracing::info!("  - Update operations: {} per minute", config.update_rate_limit);
+    tracing::info!("  - Delete operations: {} per minute", config.delete_rate_limit);
+    if config.max_concurrent_per_ip > 0 {
+        tracing::info!("  - Concurrent requests: {} in flight", config.max_concurrent_per_ip);
+    }
+    if !config.rate_limit_allowlist.is_empty() {
+        tracing::info!("  - Exempt: {}", config.rate_limit_allowlist.join(", "));
+    }
+    for route in config.route_rate_limits() {
+        tracing::info!("  - {}: {} per minute", route.key(), route.limit);
+    }
+    if !config.rate_limit_exempt_paths.is_empty() {
+        tracing::info!("  - Exempt paths: {}", config.rate_limit_exempt_paths.join(", "));
+    }
 
     // Start the server
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
@@ -437,8 +1065,46 @@ async fn main() {
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
     )
+    .with_graceful_shutdown(shutdown_signal())
     .await
     .unwrap();
+
+    // Persist buffered view counts before exiting
+    match db.flush_views() {
+        Ok(flushed) => tracing::info!("Shut down; flushed view counts for {} paste(s)", flushed),
+        Err(e) => tracing::error!("Failed to flush view counts on shutdown: {}", e),
+    }
+}
+
+// Resolves on Ctrl+C or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+    tracing::info!("Shutdown signal received, draining connections");
 }
 
 // Config info shared with frontend
@@ -448,12 +1114,50 @@ struct ConfigInfo {
     update_limit: u32,
     delete_limit: u32,
     reset_interval_secs: u32,
+    max_paste_bytes: usize,
+    max_paste_lines: usize,
+    max_tags_per_paste: usize,
+    paste_id_length: usize,
+    plaintext_allowed: bool,
+    read_only: bool,
+    operations: operations::EnabledOperations,
+    max_ttl_seconds: u64,
+    default_ttl_seconds: u64,
+    clamp_ttl: bool,
+}
+
+impl ConfigInfo {
+    fn from_config(config: &Config) -> Self {
+        ConfigInfo {
+            read_limit: config.read_rate_limit,
+            create_limit: config.create_rate_limit,
+            update_limit: config.update_rate_limit,
+            delete_limit: config.delete_rate_limit,
+            reset_interval_secs: 60,
+            max_paste_bytes: config.max_paste_bytes,
+            max_paste_lines: config.max_paste_lines,
+            max_tags_per_paste: config.max_tags_per_paste,
+            paste_id_length: config.paste_id_length,
+            plaintext_allowed: config.allow_plaintext_pastes,
+            read_only: config.read_only_mode,
+            operations: config.enabled_operations(),
+            max_ttl_seconds: config.max_ttl_seconds,
+            default_ttl_seconds: config.default_ttl_seconds,
+            clamp_ttl: config.clamp_ttl,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct HealthQuery {
+    #[serde(default)]
+    verbose: bool,
 }
 
 // Health check endpoint
-// only expose status, log details server-side
-async fn health_check(health_checker: Arc<HealthChecker>) -> impl IntoResponse {
-    let health = health_checker.check();
+// only expose statuses (per check with `?verbose=true`), log details server-side
+async fn health_check(health_checker: Arc<HealthChecker>, limiter: Arc<AppRateLimiter>, query: HealthQuery) -> impl IntoResponse {
+    let health = health_checker.check(limiter.tracked_clients());
     let status_code = match health.status {
         "ok" => StatusCode::OK,
         "degraded" => StatusCode::OK,
@@ -461,7 +1165,7 @@ async fn health_check(health_checker: Arc<HealthChecker>) -> impl IntoResponse {
     };
     if health.status != "ok" {
         tracing::warn!(
-            "Health check: {} (disk: {} {}, cpu: {} {}, db: {} {})",
+            "Health check: {} (disk: {} {}, cpu: {} {}, db: {} {}, rate limiter: {} {})",
             health.status,
             health.checks.disk.status,
             health.checks.disk.message.as_deref().unwrap_or(""),
@@ -469,35 +1173,627 @@ async fn health_check(health_checker: Arc<HealthChecker>) -> impl IntoResponse {
             health.checks.cpu.message.as_deref().unwrap_or(""),
             health.checks.database.status,
             health.checks.database.message.as_deref().unwrap_or(""),
+            health.checks.rate_limiter.status,
+            health.checks.rate_limiter.message.as_deref().unwrap_or(""),
         );
     }
-    (
-        status_code,
-        Json(serde_json::json!({ "status": health.status })),
-    )
+
+    let mut body = serde_json::json!({ "status": health.status });
+    if query.verbose {
+        body["checks"] = serde_json::json!({
+            "disk": health.checks.disk.status,
+            "cpu": health.checks.cpu.status,
+            "database": health.checks.database.status,
+            "rate_limiter": health.checks.rate_limiter.status,
+        });
+    }
+    (status_code, Json(body))
 }
 
-// Config endpoint - exposes rate limits
-async fn get_config(config: Arc<ConfigInfo>) -> impl IntoResponse {
+// Version endpoint - crate version plus the git commit and build time baked in by build.rs
+async fn version_info() -> impl IntoResponse {
+    let built_at = env!("RUSTYBIN_BUILT_AT")
+        .parse()
+        .ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .map(|built_at| built_at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+
     Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_sha": env!("RUSTYBIN_GIT_SHA"),
+        "built_at": built_at,
+    }))
+}
+
+// Rate limit endpoint - the caller's remaining budget in each bucket. It sits outside
+// the rate-limited routes, so asking never spends any of it.
+async fn rate_limit_budget(limiter: Arc<AppRateLimiter>, client: SocketAddr) -> impl IntoResponse {
+    let ip = client.ip();
+    if limiter.is_allowlisted(&ip) {
+        return Json(serde_json::json!({ "unlimited": true }));
+    }
+
+    let mut body = serde_json::json!({ "unlimited": false });
+    for (name, status) in limiter.peek(&ip) {
+        body[name] = serde_json::json!({
+            "limit": status.limit,
+            "remaining": status.remaining,
+            "reset_after": status.reset_after,
+        });
+    }
+    let routes = limiter.peek_routes(&ip);
+    if !routes.is_empty() {
+        body["routes"] = routes
+            .into_iter()
+            .map(|(route, status)| {
+                let budget = serde_json::json!({
+                    "limit": status.limit,
+                    "remaining": status.remaining,
+                    "reset_after": status.reset_after,
+                });
+                (route.to_string(), budget)
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+    }
+    Json(body)
+}
+
+// Public limits and features for frontends to adapt to. It only changes on restart,
+// so clients may cache it for a few minutes.
+async fn get_config(config: Arc<ConfigInfo>) -> impl IntoResponse {
+    let body = Json(serde_json::json!({
         "rate_limits": {
             "reset_interval_secs": config.reset_interval_secs,
             "read": config.read_limit,
             "create": config.create_limit,
             "update": config.update_limit,
             "delete": config.delete_limit,
-        }
-    }))
+        },
+        "max_paste_bytes": config.max_paste_bytes,
+        "max_paste_lines": config.max_paste_lines,
+        "max_tags_per_paste": config.max_tags_per_paste,
+        "paste_id_length": config.paste_id_length,
+        "supported_languages": language::SUPPORTED,
+        "ttl_enabled": true,
+        "max_ttl_seconds": config.max_ttl_seconds,
+        "default_ttl_seconds": config.default_ttl_seconds,
+        "ttl_clamped": config.clamp_ttl,
+        "burn_after_read_supported": true,
+        "plaintext_allowed": config.plaintext_allowed,
+        "read_only": config.read_only,
+        "operations": {
+            "create": config.operations.create && !config.read_only,
+            "delete": config.operations.delete && !config.read_only,
+            "read": config.operations.read,
+        },
+    }));
+    ([(axum::http::header::CACHE_CONTROL, "public, max-age=300")], body)
 }
 
-// Fallback handler for SPA in production
-async fn serve_spa() -> impl IntoResponse {
-    (
-        StatusCode::OK,
-        axum::response::Html(
-            std::fs::read_to_string("dist/index.html").unwrap_or_else(|_| {
-                "<html><body><h1>Error loading SPA</h1></body></html>".to_string()
-            }),
-        ),
-    )
+// The SPA's index.html, read once when production mode starts serving it
+static SPA_INDEX: OnceLock<String> = OnceLock::new();
+
+fn load_spa_index() -> String {
+    std::fs::read_to_string("dist/index.html").unwrap_or_else(|e| {
+        tracing::error!("Failed to read dist/index.html: {}", e);
+        "<html><body><h1>Error loading SPA</h1></body></html>".to_string()
+    })
+}
+
+// Fallback handler for SPA in production: client-side routes get index.html,
+// while unknown API paths and missing files (anything with an extension) are 404s
+async fn serve_spa(uri: axum::http::Uri) -> Response {
+    let path = uri.path();
+    if path == "/v1" || path.starts_with("/v1/") {
+        return (StatusCode::NOT_FOUND, Json(json_error("Not found"))).into_response();
+    }
+    if path.rsplit('/').next().is_some_and(|segment| segment.contains('.')) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    axum::response::Html(SPA_INDEX.get_or_init(load_spa_index).as_str()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED);
+
+    // A router with one create route and one other POST route behind `rate_limit`
+    fn limited_app(limiter: Arc<AppRateLimiter>) -> Router {
+        let routes = Router::new()
+            .route("/v1/pastes", post(|| async { StatusCode::CREATED }))
+            .route("/v1/pastes/{id}/report", post(|| async { StatusCode::ACCEPTED }));
+        behind_rate_limit(routes, limiter)
+    }
+
+    fn behind_rate_limit(routes: Router, limiter: Arc<AppRateLimiter>) -> Router {
+        routes.layer(middleware::from_fn(move |mut req: Request<Body>, next: Next| {
+            let limiter = limiter.clone();
+            async move {
+                req.extensions_mut().insert(limiter);
+                rate_limit(req, next).await
+            }
+        }))
+    }
+
+    async fn post_to(app: &Router, path: &str) -> Response {
+        app.clone().oneshot(Request::post(path).body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    async fn get_from(app: &Router, client: &str) -> Response {
+        let mut request = Request::get("/v1/pastes/abc").body(Body::empty()).unwrap();
+        let client = SocketAddr::new(client.parse().unwrap(), 4000);
+        request.extensions_mut().insert(axum::extract::ConnectInfo(client));
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    fn cooldown_limiter(create_limit: u32, interval: Duration) -> Arc<AppRateLimiter> {
+        Arc::new(AppRateLimiter::new(100, create_limit, 100, 100, 0).with_create_min_interval(interval))
+    }
+
+    #[test]
+    fn sweep_survives_poisoned_locks() {
+        let mut limiter = AppRateLimiter::new(100, 100, 100, 100, 1);
+        // Every check sweeps
+        limiter.reset_interval = Duration::ZERO;
+        assert!(limiter.check_and_update(&CLIENT, &Method::GET, None).is_ok());
+
+        fn poison<T>(lock: &Mutex<T>) {
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let _guard = lock.lock().unwrap();
+                panic!("poison the lock");
+            }));
+            assert!(lock.is_poisoned());
+        }
+        poison(&limiter.read.windows);
+        poison(&limiter.last_create);
+        poison(&limiter.in_flight);
+
+        assert!(limiter.check_and_update(&CLIENT, &Method::GET, None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn back_to_back_creates_are_throttled() {
+        let app = limited_app(cooldown_limiter(100, Duration::from_secs(60)));
+
+        assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::CREATED);
+        let response = post_to(&app, "/v1/pastes").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[axum::http::header::RETRY_AFTER], "60");
+    }
+
+    #[tokio::test]
+    async fn spaced_out_creates_pass() {
+        let app = limited_app(cooldown_limiter(100, Duration::from_millis(50)));
+
+        assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::CREATED);
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn other_posts_skip_the_cooldown() {
+        let app = limited_app(cooldown_limiter(100, Duration::from_secs(60)));
+
+        assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::CREATED);
+        for _ in 0..2 {
+            assert_eq!(post_to(&app, "/v1/pastes/abc/report").await.status(), StatusCode::ACCEPTED);
+        }
+    }
+
+    #[tokio::test]
+    async fn create_refused_by_the_count_starts_no_cooldown() {
+        let limiter = cooldown_limiter(0, Duration::from_secs(60));
+        let app = limited_app(limiter.clone());
+
+        assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limiter.create_cooldown(&CLIENT), None);
+    }
+
+    #[tokio::test]
+    async fn requests_past_the_concurrency_cap_are_rejected() {
+        const MAX_CONCURRENT: u32 = 2;
+        // Handlers announce themselves on `entered` and wait for a `release` permit
+        let entered = Arc::new(Semaphore::new(0));
+        let release = Arc::new(Semaphore::new(0));
+        let slow = {
+            let (entered, release) = (entered.clone(), release.clone());
+            get(move || async move {
+                entered.add_permits(1);
+                release.acquire().await.unwrap().forget();
+                StatusCode::OK
+            })
+        };
+        let limiter = Arc::new(AppRateLimiter::new(100, 100, 100, 100, MAX_CONCURRENT));
+        let app = behind_rate_limit(Router::new().route("/v1/slow", slow), limiter);
+        let slow_request = || Request::get("/v1/slow").body(Body::empty()).unwrap();
+
+        let in_flight: Vec<_> = (0..MAX_CONCURRENT).map(|_| tokio::spawn(app.clone().oneshot(slow_request()))).collect();
+        entered.acquire_many(MAX_CONCURRENT).await.unwrap().forget();
+
+        let rejected = app.clone().oneshot(slow_request()).await.unwrap();
+        assert_eq!(rejected.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        release.add_permits(MAX_CONCURRENT as usize);
+        for request in in_flight {
+            assert_eq!(request.await.unwrap().unwrap().status(), StatusCode::OK);
+        }
+
+        // The finished requests gave their slots back
+        release.add_permits(1);
+        assert_eq!(app.oneshot(slow_request()).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn one_ipv6_subnet_shares_a_bucket() {
+        let limiter = AppRateLimiter::new(1, 100, 100, 100, 0).with_subnet_prefixes(32, 64);
+        let (first, same_subnet, other_subnet) =
+            ("2001:db8::1".parse().unwrap(), "2001:db8::ffff".parse().unwrap(), "2001:db8:0:1::1".parse().unwrap());
+
+        assert!(limiter.check_and_update(&first, &Method::GET, None).is_ok());
+        assert!(limiter.check_and_update(&same_subnet, &Method::GET, None).is_err());
+        assert!(limiter.check_and_update(&other_subnet, &Method::GET, None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn allowlisted_clients_are_never_limited() {
+        let allowlist = vec!["10.0.0.0/8".parse().unwrap()];
+        let limiter = Arc::new(AppRateLimiter::new(1, 100, 100, 100, 0).with_allowlist(allowlist));
+        let app = behind_rate_limit(Router::new().route("/v1/pastes/{id}", get(|| async { StatusCode::OK })), limiter);
+
+        for _ in 0..3 {
+            let response = get_from(&app, "10.1.2.3").await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["x-ratelimit-remaining"], "unlimited");
+        }
+
+        assert_eq!(get_from(&app, "192.0.2.1").await.status(), StatusCode::OK);
+        assert_eq!(get_from(&app, "192.0.2.1").await.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn cors_allows_only_the_valid_configured_origins() {
+        let sample = "https://paste.example, http://localhost:3000,https://bad\norigin";
+        let origins: Vec<String> = sample.split(',').map(|origin| origin.trim().to_string()).collect();
+        let (cors, effective) = build_cors_layer(&origins, 600);
+        assert_eq!(effective, ["https://paste.example", "http://localhost:3000"], "the invalid origin is skipped");
+
+        let app = Router::new().route("/v1/pastes/{id}", get(|| async { StatusCode::OK })).layer(cors);
+        let from = |origin: &str| Request::get("/v1/pastes/abc").header("origin", origin).body(Body::empty()).unwrap();
+
+        for origin in ["https://paste.example", "http://localhost:3000"] {
+            let response = app.clone().oneshot(from(origin)).await.unwrap();
+            assert_eq!(response.headers()["access-control-allow-origin"], origin);
+        }
+        let response = app.oneshot(from("https://rustybin.net")).await.unwrap();
+        assert!(response.headers().get("access-control-allow-origin").is_none(), "the old default is gone");
+    }
+
+    #[tokio::test]
+    async fn each_method_reports_its_own_bucket() {
+        let limiter = Arc::new(AppRateLimiter::new(5, 3, 2, 4, 0));
+        let routes = Router::new().route(
+            "/v1/pastes/{id}",
+            get(|| async { StatusCode::OK }).post(|| async { StatusCode::OK }).delete(|| async { StatusCode::OK }).put(|| async { StatusCode::OK }),
+        );
+        let app = behind_rate_limit(routes, limiter.clone());
+        let send = |method: Method| {
+            let request = Request::builder().method(method).uri("/v1/pastes/abc").body(Body::empty()).unwrap();
+            app.clone().oneshot(request)
+        };
+        let headers = |response: &Response| {
+            ["x-ratelimit-limit", "x-ratelimit-remaining", "x-ratelimit-reset"]
+                .map(|name| response.headers()[name].to_str().unwrap().parse::<u32>().unwrap())
+        };
+
+        // The read window started half a minute before the others
+        send(Method::GET).await.unwrap();
+        for window in limiter.read.windows.lock().unwrap().values_mut() {
+            window.started -= Duration::from_secs(30);
+        }
+
+        assert_eq!(headers(&send(Method::GET).await.unwrap()), [5, 3, 30]);
+        assert_eq!(headers(&send(Method::POST).await.unwrap()), [3, 2, 60]);
+        assert_eq!(headers(&send(Method::DELETE).await.unwrap()), [2, 1, 60]);
+        assert_eq!(headers(&send(Method::PUT).await.unwrap()), [4, 3, 60]);
+
+        send(Method::DELETE).await.unwrap();
+        let refused = send(Method::DELETE).await.unwrap();
+        assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(headers(&refused), [2, 0, 60]);
+        assert_eq!(headers(&send(Method::GET).await.unwrap()), [5, 2, 30], "other buckets are untouched");
+    }
+
+    #[tokio::test]
+    async fn stalled_requests_time_out_after_being_counted() {
+        let limiter = Arc::new(AppRateLimiter::new(5, 100, 100, 100, 0));
+        let routes = Router::new()
+            .route("/v1/slow", get(|| async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                StatusCode::OK
+            }))
+            .route("/v1/fast", get(|| async { StatusCode::OK }));
+        let app = behind_rate_limit(routes, limiter.clone())
+            .layer(middleware::from_fn_with_state(Duration::from_millis(50), request_timeout));
+        let send = |path: &str| app.clone().oneshot(Request::get(path).body(Body::empty()).unwrap());
+
+        let response = send("/v1/slow").await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["error"], "Request timed out");
+
+        assert_eq!(send("/v1/fast").await.unwrap().status(), StatusCode::OK);
+        let [(_, read), ..] = limiter.peek(&CLIENT);
+        assert_eq!(read.remaining, 3, "the timed out request still counted");
+    }
+
+    #[tokio::test]
+    async fn unsupported_methods_get_a_405_with_allow_and_arent_counted() {
+        let limiter = Arc::new(AppRateLimiter::new(5, 5, 5, 5, 0));
+        let ok = || async { StatusCode::OK };
+        let routes = Router::new()
+            .route("/v1/pastes", post(ok).get(ok))
+            .route("/v1/pastes/{id}", get(ok).head(ok))
+            .route("/v1/pastes/{id}", put(ok))
+            .route("/v1/pastes/{id}", delete(ok))
+            .method_not_allowed_fallback(method_not_allowed);
+        let app = behind_rate_limit(routes, limiter.clone());
+        let patch = |path: &str| app.clone().oneshot(Request::patch(path).body(Body::empty()).unwrap());
+
+        for (path, allow) in [("/v1/pastes/abc", ["DELETE", "GET", "HEAD", "PUT"].as_slice()), ("/v1/pastes", &["GET", "HEAD", "POST"])] {
+            let response = patch(path).await.unwrap();
+            assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED, "{}", path);
+            let mut allowed: Vec<&str> = response.headers()[axum::http::header::ALLOW].to_str().unwrap().split(',').collect();
+            allowed.sort_unstable();
+            assert_eq!(allowed, allow);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["error"], "Method not allowed");
+        }
+
+        for (_, status) in limiter.peek(&CLIENT) {
+            assert_eq!(status.remaining, 5, "PATCH uses up no bucket");
+        }
+    }
+
+    #[tokio::test]
+    async fn version_reports_the_crate_version_and_build() {
+        let response = version_info().await.into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        let git_sha = body["git_sha"].as_str().unwrap();
+        assert!(git_sha == "unknown" || (git_sha.len() >= 7 && git_sha.chars().all(|c| c.is_ascii_hexdigit())), "{}", git_sha);
+        let built_at = body["built_at"].as_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(built_at).is_ok(), "{}", built_at);
+    }
+
+    #[tokio::test]
+    async fn throttled_requests_say_when_to_retry() {
+        let limiter = Arc::new(AppRateLimiter::new(100, 1, 100, 100, 0));
+        let app = limited_app(limiter);
+
+        assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::CREATED);
+        let response = post_to(&app, "/v1/pastes").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let headers = response.headers();
+        assert_eq!(headers["x-ratelimit-remaining"], "0");
+        let retry_after: u64 = headers[axum::http::header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+        assert_eq!(headers["x-ratelimit-reset"], retry_after.to_string().as_str());
+        assert!((1..=60).contains(&retry_after), "within the minute window: {}", retry_after);
+    }
+
+    #[tokio::test]
+    async fn budget_reflects_spent_creates_without_spending_any() {
+        let limiter = Arc::new(AppRateLimiter::new(10, 5, 10, 10, 0));
+        let app = limited_app(limiter.clone());
+        for _ in 0..3 {
+            assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::CREATED);
+        }
+
+        let budget = || async {
+            let response = rate_limit_budget(limiter.clone(), SocketAddr::new(CLIENT, 4000)).await.into_response();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+        let first = budget().await;
+        assert_eq!(first["unlimited"], false);
+        assert_eq!((first["create"]["limit"].as_u64(), first["create"]["remaining"].as_u64()), (Some(5), Some(2)));
+        assert_eq!(first["read"]["remaining"], 10, "untouched buckets are full");
+        assert_eq!(budget().await["create"]["remaining"], 2, "asking spends nothing");
+        assert_eq!(limiter.peek(&CLIENT)[0].1.remaining, 10);
+    }
+
+    #[tokio::test]
+    async fn spa_routes_get_the_cached_index_and_unknown_paths_a_404() {
+        const INDEX: &str = "<!doctype html><div id=\"root\">cached</div>";
+        let _ = SPA_INDEX.set(INDEX.to_string());
+        let app = Router::new().fallback(serve_spa);
+        let fetch = |path: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+                let status = response.status();
+                let content_type = response.headers().get(axum::http::header::CONTENT_TYPE).cloned();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, content_type, String::from_utf8(body.to_vec()).unwrap())
+            }
+        };
+
+        for path in ["/", "/abc123", "/admin/reports"] {
+            let (status, content_type, body) = fetch(path).await;
+            assert_eq!(status, StatusCode::OK, "{}", path);
+            assert!(content_type.unwrap().to_str().unwrap().starts_with("text/html"));
+            assert_eq!(body, SPA_INDEX.get().unwrap().as_str(), "served from memory");
+        }
+
+        let (status, content_type, body) = fetch("/v1/bogus").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type.unwrap(), "application/json");
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap()["error"], "Not found");
+        assert_eq!(fetch("/assets/missing.js").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn health_reports_each_check_and_503s_when_unhealthy() {
+        let limiter = Arc::new(AppRateLimiter::new(100, 100, 100, 100, 0));
+        for client in ["192.0.2.1", "192.0.2.2"] {
+            assert!(limiter.check_and_update(&client.parse().unwrap(), &Method::GET, None).is_ok());
+        }
+        let data_path = std::env::temp_dir().join("pastes.db").to_string_lossy().into_owned();
+        let checker = Arc::new(HealthChecker::new(data_path).with_min_free_disk_mb(u64::MAX).with_max_tracked_clients(1));
+
+        let response = health_check(checker.clone(), limiter.clone(), HealthQuery { verbose: true }).await.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["status"], "unhealthy");
+        assert_eq!((&body["checks"]["disk"], &body["checks"]["rate_limiter"]), (&"unhealthy".into(), &"degraded".into()));
+
+        let response = health_check(checker, limiter, HealthQuery { verbose: false }).await.into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), serde_json::json!({ "status": "unhealthy" }));
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_a_413_before_the_handler_runs() {
+        let config = Config { max_request_bytes: Some(128), max_upload_bytes: Some(1024), ..Config::default() };
+        let handled = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let count = |handled: &Arc<std::sync::atomic::AtomicUsize>| {
+            let handled = handled.clone();
+            move |crate::extract::BodyBytes(_): crate::extract::BodyBytes| async move {
+                handled.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                StatusCode::CREATED
+            }
+        };
+        let app = Router::new()
+            .route("/v1/pastes", post(count(&handled)))
+            .route("/v1/pastes/upload", post(count(&handled)).layer(DefaultBodyLimit::max(config.upload_body_limit())))
+            .layer(DefaultBodyLimit::max(config.request_body_limit()));
+        let send = |uri: &'static str, bytes: usize| {
+            let app = app.clone();
+            async move {
+                let body = serde_json::json!({ "data": "x".repeat(bytes) }).to_string();
+                app.oneshot(Request::post(uri).body(Body::from(body)).unwrap()).await.unwrap()
+            }
+        };
+
+        let refused = send("/v1/pastes", 200).await;
+        assert_eq!(refused.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = axum::body::to_bytes(refused.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["code"], "body_too_large");
+        assert_eq!(handled.load(std::sync::atomic::Ordering::SeqCst), 0, "the handler never ran");
+
+        // Uploads have their own, larger limit
+        assert_eq!(send("/v1/pastes/upload", 200).await.status(), StatusCode::CREATED);
+        assert_eq!(send("/v1/pastes/upload", 2000).await.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(send("/v1/pastes", 64).await.status(), StatusCode::CREATED);
+        assert_eq!(handled.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        let defaults = Config { max_paste_bytes: 1000, ..Config::default() };
+        assert_eq!(defaults.request_body_limit(), 2000 + 64 * 1024, "room for JSON escaping");
+        assert_eq!(defaults.upload_body_limit(), 1000 + 64 * 1024);
+    }
+
+    #[test]
+    fn tracked_clients_stay_under_the_cap() {
+        const MAX_TRACKED: usize = 10;
+        let limiter = AppRateLimiter::new(1000, 100, 100, 100, 0).with_max_tracked(MAX_TRACKED);
+        let regular: IpAddr = "192.0.2.1".parse().unwrap();
+
+        for n in 0..100u8 {
+            let flood = IpAddr::from([10, 0, 0, n]);
+            assert!(limiter.check_and_update(&flood, &Method::GET, None).is_ok());
+            // A client seen between the flood's requests is never the least recent
+            assert!(limiter.check_and_update(&regular, &Method::GET, None).is_ok());
+            assert!(limiter.read.windows.lock().unwrap().len() <= MAX_TRACKED);
+        }
+
+        assert!(limiter.evicted.load(Ordering::Relaxed) >= 90, "{} evicted", limiter.evicted.load(Ordering::Relaxed));
+        assert_eq!(limiter.peek(&regular)[0].1.remaining, 1000 - 100, "its count survived every eviction");
+        assert_eq!(limiter.tracked_clients(), limiter.read.windows.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn config_endpoint_reports_the_configured_limits() {
+        let config = Config { max_paste_bytes: 12345, paste_id_length: 10, ..Config::default() };
+        let response = get_config(Arc::new(ConfigInfo::from_config(&config))).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["cache-control"], "public, max-age=300");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["max_paste_bytes"], 12345);
+        assert_eq!(body["paste_id_length"], 10);
+        assert_eq!(body["rate_limits"]["create"], config.create_rate_limit);
+        assert_eq!(body["supported_languages"].as_array().unwrap().len(), language::SUPPORTED.len());
+    }
+
+    #[tokio::test]
+    async fn config_endpoint_reports_disabled_operations() {
+        let config = Config { enable_create: false, ..Config::default() };
+        let response = get_config(Arc::new(ConfigInfo::from_config(&config))).await.into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["operations"], serde_json::json!({ "create": false, "delete": true, "read": true }));
+    }
+
+    #[tokio::test]
+    async fn preflights_are_not_counted_against_the_limits() {
+        let app = limited_app(Arc::new(AppRateLimiter::new(100, 1, 100, 100, 0)));
+        for _ in 0..5 {
+            let request = Request::builder().method(Method::OPTIONS).uri("/v1/pastes").body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        }
+        assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::CREATED, "the one create is still there");
+        assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn exempt_paths_are_never_throttled_and_others_still_are() {
+        let limiter = AppRateLimiter::new(100, 1, 100, 100, 1).with_exempt_paths(&["/v1/pastes/abc/report".to_string()]);
+        let app = limited_app(Arc::new(limiter));
+
+        for _ in 0..5 {
+            let response = post_to(&app, "/v1/pastes/abc/report").await;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+        }
+        assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::CREATED, "exempt requests took nothing");
+        assert_eq!(post_to(&app, "/v1/pastes").await.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(post_to(&app, "/v1/pastes/abc/report").await.status(), StatusCode::ACCEPTED);
+        assert_eq!(post_to(&app, "/v1/pastes/xyz/report").await.status(), StatusCode::TOO_MANY_REQUESTS, "paths match exactly");
+    }
+
+    #[tokio::test]
+    async fn a_costly_route_can_be_limited_tighter_than_a_cheap_one() {
+        let raw = RouteRateLimit { method: Some("GET".to_string()), route: "/v1/pastes/{id}/raw".to_string(), limit: 2 };
+        let limiter = AppRateLimiter::new(5, 100, 100, 100, 0).with_route_limits(vec![raw]);
+        let routes = Router::new()
+            .route("/v1/pastes/{id}", get(|| async { "paste" }))
+            .route("/v1/pastes/{id}/raw", get(|| async { "raw" }));
+        let app = behind_rate_limit(routes, Arc::new(limiter));
+        let get_path = |path: &'static str| {
+            let app = app.clone();
+            async move { app.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap() }
+        };
+
+        for _ in 0..2 {
+            assert_eq!(get_path("/v1/pastes/abc/raw").await.status(), StatusCode::OK);
+        }
+        assert_eq!(get_path("/v1/pastes/abc/raw").await.status(), StatusCode::TOO_MANY_REQUESTS);
+        // Other ids share the route's bucket
+        assert_eq!(get_path("/v1/pastes/xyz/raw").await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Item GETs keep the read limit, untouched by the raw ones
+        for _ in 0..5 {
+            assert_eq!(get_path("/v1/pastes/abc").await.status(), StatusCode::OK);
+        }
+        assert_eq!(get_path("/v1/pastes/abc").await.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
 }
@@ -0,0 +1,195 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::db::{CheckpointMode, CheckpointResult, Database, DbError, PurgeCounts};
+
+/// Coordinates database maintenance tasks (expired-paste purges, vacuums, ...)
+/// so they don't pile up on the connection's write lock.
+///
+/// At most `max_concurrent` tasks run at once; a task that can't get a slot is
+/// skipped rather than queued.
+#[derive(Debug)]
+pub struct Maintenance {
+    slots: Arc<Semaphore>,
+}
+
+impl Maintenance {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// Claim a maintenance slot for `task`, or `None` (logged) if all slots are busy.
+    /// The slot is released when the returned permit is dropped.
+    pub fn try_begin(&self, task: &str) -> Option<OwnedSemaphorePermit> {
+        match self.slots.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                tracing::info!("Skipping {}: another maintenance task is running", task);
+                None
+            }
+        }
+    }
+}
+
+// Run a blocking maintenance task in a slot, if one is free
+async fn run_task<F, T>(maintenance: &Maintenance, task: &'static str, work: F) -> Option<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let _slot = maintenance.try_begin(task)?;
+    match tokio::task::spawn_blocking(work).await {
+        Ok(result) => Some(result),
+        Err(e) => {
+            tracing::error!("Maintenance task {} panicked: {}", task, e);
+            None
+        }
+    }
+}
+
+/// Purge expired pastes now. Returns `None` if maintenance is already running.
+pub async fn purge_expired(maintenance: &Maintenance, db: Arc<Database>) -> Option<Result<PurgeCounts, DbError>> {
+    run_task(maintenance, "expired paste purge", move || db.purge_expired()).await
+}
+
+/// Vacuum the database now. Returns `None` if maintenance is already running.
+pub async fn vacuum(maintenance: &Maintenance, db: Arc<Database>) -> Option<Result<(), DbError>> {
+    run_task(maintenance, "vacuum", move || db.vacuum()).await
+}
+
+/// Checkpoint the WAL now. Returns `None` if maintenance is already running.
+pub async fn checkpoint(
+    maintenance: &Maintenance,
+    db: Arc<Database>,
+    mode: CheckpointMode,
+) -> Option<Result<CheckpointResult, DbError>> {
+    run_task(maintenance, "WAL checkpoint", move || db.checkpoint(mode)).await
+}
+
+/// Spawn the background sweeper that purges expired pastes every `interval`.
+pub fn spawn_purge_sweeper(maintenance: Arc<Maintenance>, db: Arc<Database>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            ticker.tick().await;
+            match purge_expired(&maintenance, db.clone()).await {
+                Some(Ok(removed)) if removed.total() > 0 => tracing::info!(
+                    "Purged {} pastes ({} expired, {} trashed, {} evicted)",
+                    removed.total(),
+                    removed.expired,
+                    removed.trashed,
+                    removed.evicted
+                ),
+                Some(Ok(_)) | None => {}
+                Some(Err(e)) => tracing::error!("Expired paste purge failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Spawn the background task that runs a passive WAL checkpoint every `interval`.
+pub fn spawn_checkpoint_scheduler(maintenance: Arc<Maintenance>, db: Arc<Database>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            ticker.tick().await;
+            match checkpoint(&maintenance, db.clone(), CheckpointMode::Passive).await {
+                Some(Ok(result)) => tracing::debug!(
+                    "WAL checkpoint: {} of {} frames checkpointed{}",
+                    result.checkpointed_frames,
+                    result.log_frames,
+                    if result.busy { " (busy)" } else { "" }
+                ),
+                None => {}
+                Some(Err(e)) => tracing::error!("WAL checkpoint failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Periodically write buffered view counts to the database. Not a maintenance
+/// task: it's a short transaction and skipping it would only delay the counts.
+pub fn spawn_view_flusher(db: Arc<Database>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            ticker.tick().await;
+            let db = db.clone();
+            match tokio::task::spawn_blocking(move || db.flush_views()).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(flushed)) => tracing::debug!("Flushed view counts for {} paste(s)", flushed),
+                Ok(Err(e)) => tracing::error!("Failed to flush view counts: {}", e),
+                Err(e) => tracing::error!("View flush task panicked: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn test_db() -> Arc<Database> {
+        Arc::new(Database::in_memory(Config::default().paste_options()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn overlapping_tasks_dont_both_run() {
+        let maintenance = Arc::new(Maintenance::new(1));
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (finish_tx, finish_rx) = std::sync::mpsc::channel::<()>();
+
+        let running = tokio::spawn({
+            let maintenance = maintenance.clone();
+            async move {
+                run_task(&maintenance, "slow task", move || {
+                    started_tx.send(()).unwrap();
+                    finish_rx.recv().unwrap();
+                })
+                .await
+            }
+        });
+        tokio::task::spawn_blocking(move || started_rx.recv().unwrap()).await.unwrap();
+
+        // Every trigger is skipped while the first task holds the only slot
+        assert!(vacuum(&maintenance, test_db()).await.is_none());
+        assert!(purge_expired(&maintenance, test_db()).await.is_none());
+        assert!(checkpoint(&maintenance, test_db(), CheckpointMode::Passive).await.is_none());
+
+        finish_tx.send(()).unwrap();
+        assert!(running.await.unwrap().is_some());
+        assert!(matches!(purge_expired(&maintenance, test_db()).await, Some(Ok(_))));
+    }
+
+    #[test]
+    fn slots_follow_the_configured_limit() {
+        let maintenance = Maintenance::new(2);
+        let first = maintenance.try_begin("first").unwrap();
+        let _second = maintenance.try_begin("second").unwrap();
+        assert!(maintenance.try_begin("third").is_none());
+        drop(first);
+        assert!(maintenance.try_begin("third").is_some());
+
+        // A limit of 0 still lets one task run
+        let maintenance = Maintenance::new(0);
+        let _only = maintenance.try_begin("only").unwrap();
+        assert!(maintenance.try_begin("second").is_none());
+    }
+
+    #[tokio::test]
+    async fn a_panicking_task_releases_its_slot() {
+        let maintenance = Maintenance::new(1);
+        assert!(run_task(&maintenance, "panicking task", || panic!("task failed")).await.is_none());
+        assert!(maintenance.try_begin("next").is_some());
+    }
+}
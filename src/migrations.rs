@@ -0,0 +1,331 @@
+use sqlite::{Connection, State};
+
+use crate::db::DbError;
+
+// A schema migration: a description and the statements that apply it
+type Migration = (&'static str, fn(&Connection) -> Result<(), DbError>);
+
+/// Ordered schema migrations. Migration `n` (1-based) brings the database to
+/// `PRAGMA user_version = n`; append new migrations, never reorder or edit them.
+const MIGRATIONS: &[Migration] = &[
+    ("create pastes table", create_pastes_table),
+    ("add paste type column", add_type_column),
+    ("add normalized column", add_normalized_column),
+    ("create paste_tags table", create_paste_tags_table),
+    ("add view count columns", add_view_columns),
+    ("add soft-delete column", add_deleted_at_column),
+    ("add pinned column", add_pinned_column),
+    ("add content hash column", add_content_sha256_column),
+    ("create idempotency_keys table", create_idempotency_keys_table),
+    ("add forked_from column", add_forked_from_column),
+    ("create daily_quotas table", create_daily_quotas_table),
+    ("add creator_hash column", add_creator_hash_column),
+    ("create type and language index", create_type_language_index),
+    ("add visibility column", add_visibility_column),
+    ("create access_log table", create_access_log_table),
+    ("add encoding column", add_encoding_column),
+    ("create paste_reports table", create_paste_reports_table),
+    ("create language index", create_language_index),
+    ("add compressed column", add_compressed_column),
+    ("drop edit keys from idempotency_keys", drop_idempotency_edit_keys),
+];
+
+/// Latest schema version known to this build.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+/// Apply any migrations the database is behind on, each in its own transaction.
+/// Returns the resulting schema version.
+///
+/// Safe to run from several processes opening the same file at once, as in a
+/// rolling deploy: each migration re-reads the version once it holds the write
+/// lock and is skipped if another process has applied it meanwhile.
+pub fn run(conn: &Connection) -> Result<u32, DbError> {
+    let current = schema_version(conn)?;
+    if current > latest_version() {
+        tracing::warn!(
+            "Database schema version {} is newer than this build supports ({})",
+            current,
+            latest_version()
+        );
+        return Ok(current);
+    }
+
+    for (index, (description, migrate)) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = index as u32 + 1;
+
+        conn.execute("BEGIN IMMEDIATE;")?;
+        let applied = schema_version(conn).and_then(|now| {
+            if now >= version {
+                return Ok(false);
+            }
+            migrate(conn)?;
+            conn.execute(format!("PRAGMA user_version = {};", version))?;
+            Ok(true)
+        });
+        match applied {
+            Ok(applied) => {
+                conn.execute("COMMIT;")?;
+                if applied {
+                    tracing::info!("Applied schema migration {}: {}", version, description);
+                } else {
+                    tracing::debug!("Schema migration {} was already applied by another process", version);
+                }
+            }
+            Err(e) => {
+                let _ = conn.execute("ROLLBACK;");
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(latest_version())
+}
+
+fn schema_version(conn: &Connection) -> Result<u32, DbError> {
+    let mut stmt = conn.prepare("PRAGMA user_version;")?;
+    match stmt.next()? {
+        State::Row => Ok(stmt.read::<i64, _>(0)? as u32),
+        State::Done => Ok(0),
+    }
+}
+
+// Add a column unless it already exists (databases created before versioning
+// may have any subset of the later columns)
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<(), DbError> {
+    let mut stmt = conn.prepare(format!("PRAGMA table_info({});", table))?;
+    while let State::Row = stmt.next()? {
+        if stmt.read::<String, _>("name")? == column {
+            return Ok(());
+        }
+    }
+
+    conn.execute(format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, definition))?;
+    Ok(())
+}
+
+fn create_pastes_table(conn: &Connection) -> Result<(), DbError> {
+    conn.execute("
+        CREATE TABLE IF NOT EXISTS pastes (
+            id TEXT PRIMARY KEY,
+            data TEXT NOT NULL,
+            language TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            encryption_version INTEGER NOT NULL DEFAULT 0,
+            edit_key_hash TEXT,
+            burn_after_read INTEGER NOT NULL DEFAULT 0,
+            expires_at INTEGER
+        );
+    ")?;
+
+    add_column(conn, "pastes", "edit_key_hash", "TEXT")?;
+    add_column(conn, "pastes", "burn_after_read", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column(conn, "pastes", "expires_at", "INTEGER")?;
+
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_pastes_created_at ON pastes(created_at DESC);")?;
+    Ok(())
+}
+
+fn add_type_column(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "type", "TEXT NOT NULL DEFAULT 'paste'")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_type ON pastes(type);")?;
+    Ok(())
+}
+
+fn add_normalized_column(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "normalized", "INTEGER NOT NULL DEFAULT 0")
+}
+
+fn create_paste_tags_table(conn: &Connection) -> Result<(), DbError> {
+    conn.execute("
+        CREATE TABLE IF NOT EXISTS paste_tags (
+            paste_id TEXT NOT NULL REFERENCES pastes(id) ON DELETE CASCADE,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (paste_id, tag)
+        );
+    ")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_paste_tags_tag ON paste_tags(tag);")?;
+    Ok(())
+}
+
+fn add_view_columns(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "views", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column(conn, "pastes", "max_views", "INTEGER")
+}
+
+fn add_deleted_at_column(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "deleted_at", "INTEGER")
+}
+
+fn add_pinned_column(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "pinned", "INTEGER NOT NULL DEFAULT 0")
+}
+
+fn add_content_sha256_column(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "content_sha256", "TEXT")
+}
+
+fn create_idempotency_keys_table(conn: &Connection) -> Result<(), DbError> {
+    conn.execute("
+        CREATE TABLE IF NOT EXISTS idempotency_keys (
+            scope TEXT NOT NULL,
+            key TEXT NOT NULL,
+            fingerprint TEXT NOT NULL,
+            paste_id TEXT NOT NULL,
+            response TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (scope, key)
+        );
+    ")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created_at ON idempotency_keys(created_at);")?;
+    Ok(())
+}
+
+fn add_forked_from_column(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "forked_from", "TEXT")
+}
+
+fn create_daily_quotas_table(conn: &Connection) -> Result<(), DbError> {
+    conn.execute("
+        CREATE TABLE IF NOT EXISTS daily_quotas (
+            client TEXT NOT NULL,
+            day TEXT NOT NULL,
+            creates INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (client, day)
+        );
+    ")?;
+    Ok(())
+}
+
+fn add_creator_hash_column(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "creator_hash", "TEXT")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_pastes_creator_hash ON pastes(creator_hash);")?;
+    Ok(())
+}
+
+// Serves the per-language counts: `type = 'paste'` narrows the index and the rows
+// come out grouped by language, with no temporary b-tree for the GROUP BY. An index
+// on language alone loses to idx_type for that query.
+fn create_type_language_index(conn: &Connection) -> Result<(), DbError> {
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_pastes_type_language ON pastes(type, language);")?;
+    Ok(())
+}
+
+fn add_visibility_column(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "visibility", "TEXT NOT NULL DEFAULT 'unlisted'")
+}
+
+fn create_access_log_table(conn: &Connection) -> Result<(), DbError> {
+    conn.execute("
+        CREATE TABLE IF NOT EXISTS access_log (
+            paste_id TEXT NOT NULL,
+            accessed_at INTEGER NOT NULL,
+            ip_hash TEXT,
+            user_agent TEXT
+        );
+    ")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_access_log_paste_accessed ON access_log(paste_id, accessed_at);")?;
+    Ok(())
+}
+
+fn add_encoding_column(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "encoding", "TEXT NOT NULL DEFAULT 'utf8'")
+}
+
+fn create_paste_reports_table(conn: &Connection) -> Result<(), DbError> {
+    conn.execute("
+        CREATE TABLE IF NOT EXISTS paste_reports (
+            paste_id TEXT NOT NULL REFERENCES pastes(id) ON DELETE CASCADE,
+            reason TEXT NOT NULL,
+            reporter_hash TEXT NOT NULL,
+            reported_at INTEGER NOT NULL
+        );
+    ")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_paste_reports_paste ON paste_reports(paste_id);")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_paste_reports_reporter ON paste_reports(reporter_hash, reported_at);")?;
+    Ok(())
+}
+
+// Serves GROUP BY language over every row, as in the admin stats, which the
+// `(type, language)` index only covers once `type` is pinned
+fn create_language_index(conn: &Connection) -> Result<(), DbError> {
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_pastes_language ON pastes(language);")?;
+    Ok(())
+}
+
+// 0 when `data` is stored verbatim, otherwise the byte length of the text its
+// compressed block expands to
+fn add_compressed_column(conn: &Connection) -> Result<(), DbError> {
+    add_column(conn, "pastes", "compressed", "INTEGER NOT NULL DEFAULT 0")
+}
+
+// Replays used to carry the edit key, so stored responses held it in plain text
+fn drop_idempotency_edit_keys(conn: &Connection) -> Result<(), DbError> {
+    conn.execute("UPDATE idempotency_keys SET response = json_remove(response, '$.edit_key');")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query_plan(conn: &Connection, query: &str) -> String {
+        let mut stmt = conn.prepare(format!("EXPLAIN QUERY PLAN {}", query)).unwrap();
+        let mut plan = Vec::new();
+        while let State::Row = stmt.next().unwrap() {
+            plan.push(stmt.read::<String, _>("detail").unwrap());
+        }
+        plan.join("\n")
+    }
+
+    #[test]
+    fn migrates_a_fresh_database_to_the_latest_version() {
+        let conn = Connection::open(":memory:").unwrap();
+        assert_eq!(run(&conn).unwrap(), latest_version());
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn running_again_changes_nothing() {
+        let conn = Connection::open(":memory:").unwrap();
+        run(&conn).unwrap();
+        assert_eq!(run(&conn).unwrap(), latest_version());
+
+        // Every migration is safe to re-apply over its own result
+        for (_, migrate) in MIGRATIONS {
+            migrate(&conn).unwrap();
+        }
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn language_counts_use_the_language_index() {
+        let conn = Connection::open(":memory:").unwrap();
+        run(&conn).unwrap();
+
+        let plan = query_plan(&conn, "SELECT language, COUNT(*) FROM pastes GROUP BY language");
+        assert!(plan.contains("idx_pastes_language"), "{}", plan);
+
+        // The live counts narrow by type first, as `Database::language_counts` does
+        let plan = query_plan(&conn, "SELECT language, COUNT(*) FROM pastes WHERE type = 'paste' GROUP BY language");
+        assert!(plan.contains("idx_pastes_type_language"), "{}", plan);
+        assert!(!plan.contains("TEMP B-TREE"), "{}", plan);
+    }
+
+    #[test]
+    fn stored_idempotent_responses_lose_their_edit_keys() {
+        let conn = Connection::open(":memory:").unwrap();
+        run(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO idempotency_keys (scope, key, fingerprint, paste_id, response, created_at) \
+             VALUES ('client', 'key-1', 'abc', 'paste1', '{\"id\":\"paste1\",\"edit_key\":\"secret\"}', 0)",
+        )
+        .unwrap();
+
+        drop_idempotency_edit_keys(&conn).unwrap();
+        let mut stmt = conn.prepare("SELECT response FROM idempotency_keys").unwrap();
+        stmt.next().unwrap();
+        assert_eq!(stmt.read::<String, _>(0).unwrap(), r#"{"id":"paste1"}"#);
+    }
+}
@@ -1,5 +1,8 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::db::{AccessLogEntry, ImportConflict, ReportedPaste, Visibility};
+
 /// Request body for admin login.
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
@@ -38,6 +41,16 @@ pub struct StatsResponse {
     pub pastes_over_time: Vec<TimeSeriesPoint>,
 }
 
+/// Response body for storage statistics.
+#[derive(Debug, Serialize)]
+pub struct StorageStatsResponse {
+    pub total_pastes: i64,
+    pub total_bytes: i64,
+    pub oldest_created_at: Option<String>,
+    pub newest_created_at: Option<String>,
+    pub by_encryption_version: std::collections::HashMap<String, i64>,
+}
+
 /// A single point in the time-series chart data.
 #[derive(Debug, Serialize)]
 pub struct TimeSeriesPoint {
@@ -64,6 +77,8 @@ pub struct PasteFilterParams {
     pub search: Option<String>,
     pub start_date: Option<i64>,
     pub end_date: Option<i64>,
+    pub creator_hash: Option<String>,
+    pub visibility: Option<Visibility>,
 }
 
 fn default_page() -> i64 {
@@ -95,6 +110,8 @@ pub struct PasteListItem {
     pub has_expiration: bool,
     pub expires_at: Option<String>,
     pub encryption_version: u8,
+    pub pinned: bool,
+    pub visibility: Visibility,
 }
 
 /// Paginated response for the paste list endpoint.
@@ -113,6 +130,68 @@ pub struct BulkDeleteRequest {
     pub ids: Vec<String>,
 }
 
+/// Request body for `POST /v1/admin/pastes/delete`: either `ids`, or `created_before`
+/// with `confirm: true`.
+#[derive(Debug, Deserialize)]
+pub struct DeleteManyRequest {
+    #[serde(default)]
+    pub ids: Option<Vec<String>>,
+    #[serde(default)]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// Response body for `POST /v1/admin/pastes/delete`.
+#[derive(Debug, Serialize)]
+pub struct DeleteManyResponse {
+    pub success: bool,
+    pub deleted_count: usize,
+}
+
+/// Query parameters for a paste's access log.
+#[derive(Debug, Deserialize)]
+pub struct AccessLogQuery {
+    #[serde(default = "default_access_log_limit")]
+    pub limit: usize,
+}
+
+fn default_access_log_limit() -> usize {
+    50
+}
+
+/// Response body for a paste's access log, newest first.
+#[derive(Debug, Serialize)]
+pub struct AccessLogResponse {
+    pub paste_id: String,
+    pub enabled: bool,
+    pub accesses: Vec<AccessLogEntry>,
+}
+
+/// Query parameters for the abuse report list.
+#[derive(Debug, Deserialize)]
+pub struct ReportsQuery {
+    #[serde(default = "default_reports_limit")]
+    pub limit: usize,
+}
+
+fn default_reports_limit() -> usize {
+    50
+}
+
+/// Response body for the abuse report list, most reported first.
+#[derive(Debug, Serialize)]
+pub struct ReportedPastesResponse {
+    pub pastes: Vec<ReportedPaste>,
+}
+
+/// Response body for dismissing a paste's reports.
+#[derive(Debug, Serialize)]
+pub struct DismissReportsResponse {
+    pub success: bool,
+    pub dismissed: usize,
+}
+
 /// Response body for single paste deletion.
 #[derive(Debug, Serialize)]
 pub struct DeleteResponse {
@@ -127,3 +206,14 @@ pub struct BulkDeleteResponse {
     pub deleted_count: usize,
     pub not_found: Vec<String>,
 }
+
+/// Query parameters for the import endpoint.
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    #[serde(default = "default_on_conflict")]
+    pub on_conflict: ImportConflict,
+}
+
+fn default_on_conflict() -> ImportConflict {
+    ImportConflict::Skip
+}
@@ -0,0 +1,65 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::db::Paste;
+
+/// Query parameters selecting how per-item batch results are reported.
+#[derive(Debug, Deserialize)]
+pub struct BatchModeQuery {
+    pub strict: Option<bool>,
+}
+
+/// Outcome for a single item of a batch request.
+#[derive(Debug, Serialize)]
+pub struct BatchItemResult {
+    pub id: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paste: Option<Paste>,
+}
+
+impl BatchItemResult {
+    pub fn ok(id: String, paste: Option<Paste>) -> Self {
+        Self { id, status: StatusCode::OK.as_u16(), error: None, paste }
+    }
+
+    pub fn failed(id: String, status: StatusCode, error: &str) -> Self {
+        Self { id, status: status.as_u16(), error: Some(error.to_string()), paste: None }
+    }
+}
+
+/// Per-item batch results.
+///
+/// Strict mode responds `207 Multi-Status` with results as a list in request
+/// order; otherwise `200 OK` with results keyed by id.
+#[derive(Debug)]
+pub struct BatchResults {
+    pub strict: bool,
+    pub results: Vec<BatchItemResult>,
+}
+
+impl IntoResponse for BatchResults {
+    fn into_response(self) -> Response {
+        if self.strict {
+            (
+                StatusCode::MULTI_STATUS,
+                Json(serde_json::json!({ "results": self.results })),
+            )
+                .into_response()
+        } else {
+            let results: BTreeMap<String, BatchItemResult> = self
+                .results
+                .into_iter()
+                .map(|result| (result.id.clone(), result))
+                .collect();
+            (StatusCode::OK, Json(serde_json::json!({ "results": results }))).into_response()
+        }
+    }
+}
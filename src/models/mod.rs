@@ -1,8 +1,16 @@
 // Models are defined in db.rs since they're tightly coupled to the database layer.
 // This module provides re-exports for cleaner imports.
+#[allow(unused_imports)]
 pub use crate::db::{CreatePasteData, DeletePasteData, Paste, UpdatePasteData};
 
 pub mod workspace;
+#[allow(unused_imports)]
 pub use workspace::*;
 
 pub mod admin;
+
+pub mod paste;
+
+pub mod batch;
+
+pub mod stats;
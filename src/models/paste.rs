@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+use crate::db::{CreatePasteData, Visibility};
+
+#[derive(Debug, Serialize)]
+pub struct TaggedPasteItem {
+    pub id: String,
+    pub language: String,
+    pub created_at: DateTime<Utc>,
+    pub encryption_version: u8,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaggedPasteListResponse {
+    pub tag: String,
+    pub pastes: Vec<TaggedPasteItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchGetQuery {
+    pub ids: String, // Comma-separated paste IDs
+    pub strict: Option<bool>,
+}
+
+/// Query parameters for a paste's QR code: the image's width and height in pixels.
+#[derive(Debug, Deserialize)]
+pub struct QrQuery {
+    pub size: Option<u32>,
+}
+
+/// Body for the create dry run: a create request plus an optional custom ID to check.
+#[derive(Debug, Deserialize)]
+pub struct ValidatePasteRequest {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub paste: CreatePasteData,
+}
+
+/// Body for reporting a paste for abuse.
+#[derive(Debug, Deserialize)]
+pub struct ReportPasteRequest {
+    pub reason: String,
+}
+
+/// Body for minting a read token: the paste's edit key and an optional lifetime.
+#[derive(Debug, Deserialize)]
+pub struct ReadTokenRequest {
+    pub edit_key: String,
+    #[serde(default)]
+    pub expires_in_minutes: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadTokenResponse {
+    pub token: String,
+    pub url: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Unified diff between two plaintext pastes, from `from` to `to`.
+#[derive(Debug, Serialize)]
+pub struct PasteDiffResponse {
+    pub from: String,
+    pub to: String,
+    pub added: usize,
+    pub removed: usize,
+    pub diff: String,
+}
@@ -0,0 +1,7 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LanguageCount {
+    pub language: String,
+    pub count: u64,
+}
@@ -0,0 +1,158 @@
+use axum::body::Bytes;
+
+/// Errors from parsing a `multipart/form-data` body.
+#[derive(Debug, thiserror::Error)]
+pub enum MultipartError {
+    #[error("Content-Type must be multipart/form-data with a boundary")]
+    MissingBoundary,
+
+    #[error("Malformed multipart body: {0}")]
+    Malformed(&'static str),
+}
+
+/// One part of a `multipart/form-data` body. `data` shares the request buffer.
+#[derive(Debug)]
+pub struct Part {
+    pub name: String,
+    pub filename: Option<String>,
+    pub data: Bytes,
+}
+
+/// The boundary parameter of a `multipart/form-data` content type.
+pub fn boundary(content_type: &str) -> Result<String, MultipartError> {
+    let mut params = content_type.split(';');
+    let mime = params.next().unwrap_or_default().trim();
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return Err(MultipartError::MissingBoundary);
+    }
+
+    params
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|boundary| !boundary.is_empty() && boundary.len() <= 70)
+        .ok_or(MultipartError::MissingBoundary)
+}
+
+/// Split a buffered `multipart/form-data` body into its parts.
+pub fn parse(body: &Bytes, boundary: &str) -> Result<Vec<Part>, MultipartError> {
+    let opening = format!("--{}", boundary);
+    let delimiter = format!("\r\n--{}", boundary);
+
+    // Anything before the first boundary is preamble and ignored
+    let mut pos = find(body, opening.as_bytes(), 0).ok_or(MultipartError::Malformed("missing opening boundary"))?
+        + opening.len();
+
+    let mut parts = Vec::new();
+    loop {
+        if body[pos..].starts_with(b"--") {
+            return Ok(parts);
+        }
+        if !body[pos..].starts_with(b"\r\n") {
+            return Err(MultipartError::Malformed("expected line break after boundary"));
+        }
+        pos += 2;
+
+        let headers_end = find(body, b"\r\n\r\n", pos).ok_or(MultipartError::Malformed("unterminated part headers"))?;
+        let headers = std::str::from_utf8(&body[pos..headers_end])
+            .map_err(|_| MultipartError::Malformed("part headers must be UTF-8"))?;
+        let data_start = headers_end + 4;
+
+        let data_end = find(body, delimiter.as_bytes(), data_start).ok_or(MultipartError::Malformed("missing closing boundary"))?;
+
+        let (name, filename) = content_disposition(headers).ok_or(MultipartError::Malformed("part without a form-data name"))?;
+        parts.push(Part {
+            name,
+            filename,
+            data: body.slice(data_start..data_end),
+        });
+
+        pos = data_end + delimiter.len();
+    }
+}
+
+// Name and filename from a part's `Content-Disposition: form-data; name="..."; filename="..."`
+fn content_disposition(headers: &str) -> Option<(String, Option<String>)> {
+    let value = headers.split("\r\n").find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case("content-disposition").then_some(value)
+    })?;
+
+    let mut params = value.split(';');
+    if !params.next()?.trim().eq_ignore_ascii_case("form-data") {
+        return None;
+    }
+
+    let mut name = None;
+    let mut filename = None;
+    for param in params {
+        let Some((key, value)) = param.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_string();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "name" => name = Some(value),
+            "filename" => filename = Some(value),
+            _ => {}
+        }
+    }
+
+    Some((name?, filename))
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|offset| from + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = "preamble\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"main.rs\"\r\n\
+        Content-Type: text/plain\r\n\r\n\
+        fn main() {}\r\n\r\n--XyZ\r\n\
+        content-disposition: form-data; name=language\r\n\r\n\
+        rust\r\n--XyZ--\r\n";
+
+    #[test]
+    fn boundary_comes_from_the_content_type() {
+        assert_eq!(boundary("multipart/form-data; boundary=XyZ").unwrap(), "XyZ");
+        assert_eq!(boundary("Multipart/Form-Data; charset=utf-8; BOUNDARY=\"a b\"").unwrap(), "a b");
+        for content_type in ["application/json", "multipart/form-data", "multipart/form-data; boundary=", "multipart/mixed; boundary=XyZ"] {
+            assert!(matches!(boundary(content_type), Err(MultipartError::MissingBoundary)), "{}", content_type);
+        }
+        let too_long = format!("multipart/form-data; boundary={}", "b".repeat(71));
+        assert!(boundary(&too_long).is_err());
+    }
+
+    #[test]
+    fn parts_keep_their_names_filenames_and_bytes() {
+        let parts = parse(&Bytes::from_static(BODY.as_bytes()), "XyZ").unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!((parts[0].name.as_str(), parts[0].filename.as_deref()), ("file", Some("main.rs")));
+        assert_eq!(&parts[0].data[..], b"fn main() {}\r\n", "only the delimiter's line break is stripped");
+        assert_eq!((parts[1].name.as_str(), parts[1].filename.as_deref(), &parts[1].data[..]), ("language", None, &b"rust"[..]));
+    }
+
+    #[test]
+    fn malformed_bodies_are_refused() {
+        let cases = [
+            ("no boundary at all", "missing opening boundary"),
+            ("--XyZ\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\ndata", "missing closing boundary"),
+            ("--XyZ\r\nContent-Disposition: form-data; name=\"file\"", "unterminated part headers"),
+            ("--XyZ\r\nContent-Disposition: attachment\r\n\r\ndata\r\n--XyZ--", "part without a form-data name"),
+            ("--XyZjunk", "expected line break after boundary"),
+        ];
+        for (body, reason) in cases {
+            match parse(&Bytes::from(body), "XyZ") {
+                Err(MultipartError::Malformed(found)) => assert_eq!(found, reason, "{:?}", body),
+                other => panic!("{:?} parsed as {:?}", body, other),
+            }
+        }
+    }
+}
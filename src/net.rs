@@ -0,0 +1,232 @@
+use axum::http::{HeaderMap, header};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+/// Hex HMAC-SHA256 of `ip` under `secret`. Pastes from one address share a value
+/// that can't be turned back into the address without the secret.
+pub fn creator_hash(secret: &str, ip: IpAddr) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(ip.to_canonical().to_string().as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Zero the host bits of an address beyond the given prefix length. IPv4-mapped
+/// IPv6 addresses are treated as IPv4 so dual-stack listeners key them consistently.
+pub fn mask_ip(ip: IpAddr, ipv4_prefix: u8, ipv6_prefix: u8) -> IpAddr {
+    match ip.to_canonical() {
+        IpAddr::V4(addr) => {
+            let mask = u32::MAX.checked_shl(32 - ipv4_prefix.min(32) as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+        }
+        IpAddr::V6(addr) => {
+            let mask = u128::MAX.checked_shl(128 - ipv6_prefix.min(128) as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+        }
+    }
+}
+
+/// An IP network in CIDR notation (`10.0.0.0/8`, `fd00::/8`); a bare address is a
+/// single-host network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpNetwork {
+    /// Whether `ip` falls inside this network. IPv4-mapped IPv6 addresses match
+    /// IPv4 networks.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_ipv4() == self.network.is_ipv4() && mask_ip(ip, self.prefix, self.prefix) == self.network
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None),
+        };
+
+        let address = address
+            .parse::<IpAddr>()
+            .map_err(|_| format!("invalid address in {:?}", value))?
+            .to_canonical();
+        let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| format!("invalid prefix length in {:?}", value))?,
+            None => max_prefix,
+        };
+
+        // Store the network address itself so `contains` is a single masked compare
+        Ok(Self {
+            network: mask_ip(address, prefix, prefix),
+            prefix,
+        })
+    }
+}
+
+/// The client behind a request that arrived from `peer`. Only a trusted proxy's
+/// forwarding headers are believed: the chain in `Forwarded` (or, without one,
+/// `X-Forwarded-For`) is walked back from the nearest hop, past further trusted
+/// proxies, to the first address that isn't one. A malformed or unknown hop ends
+/// the walk early, and then the peer itself is used.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpNetwork]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted.iter().any(|network| network.contains(ip));
+    if !is_trusted(peer) {
+        return peer;
+    }
+
+    let chain = forwarded_chain(headers).or_else(|| x_forwarded_for_chain(headers));
+    let Some(chain) = chain else {
+        return peer;
+    };
+
+    // Everything right of the client was added by our own proxies; if all of them
+    // are trusted, the leftmost hop is as close to the client as we can get
+    let mut client = peer;
+    for hop in chain.iter().rev() {
+        match hop {
+            Some(ip) if is_trusted(*ip) => client = *ip,
+            Some(ip) => return *ip,
+            None => return peer,
+        }
+    }
+    client
+}
+
+// `for=` addresses from every `Forwarded` header, nearest client first; `None` when
+// the header is absent or names no one
+fn forwarded_chain(headers: &HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    let chain: Vec<Option<IpAddr>> = headers
+        .get_all(header::FORWARDED)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.trim().split_once('=')?;
+                key.trim().eq_ignore_ascii_case("for").then(|| parse_node(value.trim().trim_matches('"')))
+            })
+        })
+        .collect();
+    (!chain.is_empty()).then_some(chain)
+}
+
+fn x_forwarded_for_chain(headers: &HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    let chain: Vec<Option<IpAddr>> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+        .map(|hop| parse_node(hop.trim()))
+        .collect();
+    (!chain.is_empty()).then_some(chain)
+}
+
+// A forwarded node: `192.0.2.1`, `192.0.2.1:8080`, `[2001:db8::1]`, `[2001:db8::1]:443`
+// or a bare IPv6 address. `unknown` and obfuscated identifiers give `None`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Some(rest) = node.strip_prefix('[') {
+        let (address, _port) = rest.split_once(']')?;
+        return address.parse::<Ipv6Addr>().ok().map(|address| IpAddr::V6(address).to_canonical());
+    }
+
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .map(|ip| ip.to_canonical())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn addresses_in_one_ipv6_subnet_mask_alike() {
+        let masked = mask_ip(ip("2001:db8:1:2:aaaa::1"), 32, 64);
+        assert_eq!(masked, ip("2001:db8:1:2::"));
+        assert_eq!(mask_ip(ip("2001:db8:1:2:bbbb::2"), 32, 64), masked);
+        assert_ne!(mask_ip(ip("2001:db8:1:3::1"), 32, 64), masked);
+    }
+
+    #[test]
+    fn ipv4_masks_to_its_own_prefix() {
+        assert_eq!(mask_ip(ip("192.0.2.77"), 32, 64), ip("192.0.2.77"));
+        assert_eq!(mask_ip(ip("192.0.2.77"), 24, 64), ip("192.0.2.0"));
+        assert_eq!(mask_ip(ip("192.0.2.77"), 0, 64), ip("0.0.0.0"));
+        // Mapped addresses are keyed as the IPv4 address they carry
+        assert_eq!(mask_ip(ip("::ffff:192.0.2.77"), 24, 64), ip("192.0.2.0"));
+    }
+
+    #[test]
+    fn networks_parse_and_match() {
+        let network: IpNetwork = "10.1.2.3/8".parse().unwrap();
+        assert!(network.contains(ip("10.200.0.1")));
+        assert!(network.contains(ip("::ffff:10.0.0.1")));
+        assert!(!network.contains(ip("11.0.0.1")));
+
+        let host: IpNetwork = "2001:db8::1".parse().unwrap();
+        assert!(host.contains(ip("2001:db8::1")));
+        assert!(!host.contains(ip("2001:db8::2")));
+
+        for invalid in ["10.0.0.0/33", "fd00::/129", "not-an-ip", "10.0.0.0/x"] {
+            assert!(invalid.parse::<IpNetwork>().is_err(), "{}", invalid);
+        }
+    }
+
+    fn forwarded(name: &str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(axum::http::HeaderName::from_bytes(name.as_bytes()).unwrap(), value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn forwarded_header_names_the_client() {
+        let proxy = ip("10.0.0.1");
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+
+        let headers = forwarded("forwarded", "for=\"[2001:db8::1]:443\";proto=https");
+        assert_eq!(client_ip(proxy, &headers, &trusted), ip("2001:db8::1"));
+
+        let headers = forwarded("forwarded", "for=192.0.2.60;by=10.0.0.1");
+        assert_eq!(client_ip(proxy, &headers, &trusted), ip("192.0.2.60"));
+
+        let headers = forwarded("x-forwarded-for", "192.0.2.60, 10.0.0.2");
+        assert_eq!(client_ip(proxy, &headers, &trusted), ip("192.0.2.60"));
+    }
+
+    #[test]
+    fn malformed_or_untrusted_forwarding_falls_back_to_the_peer() {
+        let proxy = ip("10.0.0.1");
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+
+        let headers = forwarded("forwarded", "for=not-an-address");
+        assert_eq!(client_ip(proxy, &headers, &trusted), proxy);
+
+        // Only a trusted peer's forwarding headers are believed
+        let headers = forwarded("forwarded", "for=192.0.2.60");
+        assert_eq!(client_ip(ip("198.51.100.7"), &headers, &trusted), ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn creator_hash_is_per_address_and_secret() {
+        let hash = creator_hash("secret", ip("192.0.2.1"));
+        assert_eq!(hash.len(), 64);
+        assert_eq!(creator_hash("secret", ip("192.0.2.1")), hash);
+        assert_eq!(creator_hash("secret", ip("::ffff:192.0.2.1")), hash);
+        assert_ne!(creator_hash("secret", ip("192.0.2.2")), hash);
+        assert_ne!(creator_hash("other", ip("192.0.2.1")), hash);
+    }
+}
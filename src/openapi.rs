@@ -0,0 +1,64 @@
+use axum::{http::header, response::IntoResponse};
+
+// Hand-maintained OpenAPI 3 document; update it alongside route and DTO changes
+const OPENAPI_SPEC: &str = include_str!("openapi.json");
+
+/// GET /v1/openapi.json - machine-readable API description.
+pub async fn openapi_spec() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI_SPEC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every `$ref` in the document, for checking they all resolve
+    fn refs<'a>(value: &'a serde_json::Value, found: &mut Vec<&'a str>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(serde_json::Value::String(target)) = map.get("$ref") {
+                    found.push(target);
+                }
+                map.values().for_each(|value| refs(value, found));
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|value| refs(value, found)),
+            _ => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn served_spec_describes_paste_creation() {
+        let response = openapi_spec().await.into_response();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let spec: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let create = &spec["paths"]["/v1/pastes"]["post"];
+        assert_eq!(create["operationId"], "createPaste");
+        assert_eq!(create["requestBody"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/CreatePasteData");
+        assert_eq!(create["responses"]["429"]["$ref"], "#/components/responses/RateLimited");
+
+        let mut found = Vec::new();
+        refs(&spec, &mut found);
+        for target in found {
+            let pointer = target.strip_prefix('#').unwrap();
+            assert!(spec.pointer(pointer).is_some(), "{} doesn't resolve", target);
+        }
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let spec: serde_json::Value = serde_json::from_str(OPENAPI_SPEC).unwrap();
+        let main = include_str!("main.rs");
+        let production = &main[..main.find("#[cfg(test)]").unwrap()];
+        let routes: Vec<&str> = production
+            .split(".route(")
+            .skip(1)
+            .map(|route| route.trim_start().trim_start_matches('"').split('"').next().unwrap())
+            .collect();
+        assert!(routes.len() > 30, "found only {:?}", routes);
+        for path in routes {
+            assert!(spec["paths"].get(path).is_some(), "{} is missing from openapi.json", path);
+        }
+    }
+}
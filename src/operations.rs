@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    body::Body,
+    extract::State,
+    http::{Method, Request, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::error::json_error;
+
+/// The public paste and workspace operations this server serves, from
+/// `ENABLE_CREATE`, `ENABLE_DELETE` and `ENABLE_READ`. Admin routes are never
+/// turned off.
+#[derive(Debug, Clone, Copy)]
+pub struct EnabledOperations {
+    pub create: bool,
+    pub delete: bool,
+    pub read: bool,
+}
+
+impl EnabledOperations {
+    pub fn all_enabled(&self) -> bool {
+        self.create && self.delete && self.read
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operation {
+    Create,
+    Delete,
+    Read,
+}
+
+/// Reject requests for a disabled operation with `403` and code
+/// `operation_disabled`. Updates, appends, restores, reports and validation
+/// belong to none of them and always pass through.
+pub async fn reject_disabled(State(enabled): State<Arc<EnabledOperations>>, req: Request<Body>, next: Next) -> Response {
+    let (allowed, message) = match classify(&req) {
+        Some(Operation::Create) => (enabled.create, "Creates are disabled on this server"),
+        Some(Operation::Delete) => (enabled.delete, "Deletes are disabled on this server"),
+        Some(Operation::Read) => (enabled.read, "Reads are disabled on this server"),
+        None => (true, ""),
+    };
+    if allowed {
+        return next.run(req).await;
+    }
+
+    let mut body = json_error(message);
+    body["code"] = "operation_disabled".into();
+    (StatusCode::FORBIDDEN, Json(body)).into_response()
+}
+
+fn classify(req: &Request<Body>) -> Option<Operation> {
+    let path = req.uri().path().trim_end_matches('/');
+    let segments: Vec<&str> = path.strip_prefix("/v1/")?.split('/').collect();
+    match (req.method().clone(), segments.as_slice()) {
+        (Method::POST, ["pastes"] | ["pastes", "batch" | "upload"] | ["pastes", _, "fork"] | ["workspaces"]) => {
+            Some(Operation::Create)
+        }
+        (Method::PUT, ["pastes", _]) if is_text_body(req) => Some(Operation::Create),
+        (Method::DELETE, ["pastes", _] | ["workspaces", _]) => Some(Operation::Delete),
+        (Method::GET | Method::HEAD, ["pastes", ..] | ["workspaces", _]) => Some(Operation::Read),
+        // Minting a read token only serves to read the paste later
+        (Method::POST, ["pastes", _, "token"]) => Some(Operation::Read),
+        _ => None,
+    }
+}
+
+// Whether a PUT would create rather than update: `put_paste` treats a `text/plain`
+// or untyped body as a new paste
+pub fn is_text_body(req: &Request<Body>) -> bool {
+    let mime = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .unwrap_or_default()
+        .trim();
+    mime.is_empty() || mime.eq_ignore_ascii_case("text/plain")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, middleware, routing::{get, post}};
+    use tower::ServiceExt;
+
+    fn app(enabled: EnabledOperations) -> Router {
+        Router::new()
+            .route("/v1/pastes", post(|| async { StatusCode::CREATED }))
+            .route("/v1/pastes/{id}", get(|| async { "paste" }).put(|| async { "updated" }).delete(|| async { StatusCode::NO_CONTENT }))
+            .route("/v1/admin/pastes/{id}", axum::routing::delete(|| async { StatusCode::NO_CONTENT }))
+            .layer(middleware::from_fn_with_state(Arc::new(enabled), reject_disabled))
+    }
+
+    async fn send(app: &Router, method: Method, path: &str, content_type: Option<&str>) -> Response {
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(content_type) = content_type {
+            request = request.header(header::CONTENT_TYPE, content_type);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn disabled_creates_are_refused_while_reads_still_work() {
+        let app = app(EnabledOperations { create: false, delete: true, read: true });
+
+        let response = send(&app, Method::POST, "/v1/pastes", None).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "operation_disabled");
+        // A text PUT creates a paste, a JSON one updates it
+        assert_eq!(send(&app, Method::PUT, "/v1/pastes/abc", Some("text/plain")).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(send(&app, Method::PUT, "/v1/pastes/abc", Some("application/json")).await.status(), StatusCode::OK);
+
+        assert_eq!(send(&app, Method::GET, "/v1/pastes/abc", None).await.status(), StatusCode::OK);
+        assert_eq!(send(&app, Method::DELETE, "/v1/pastes/abc", None).await.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn admin_routes_are_never_turned_off() {
+        let app = app(EnabledOperations { create: false, delete: false, read: false });
+        assert_eq!(send(&app, Method::GET, "/v1/pastes/abc", None).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(send(&app, Method::DELETE, "/v1/pastes/abc", None).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(send(&app, Method::DELETE, "/v1/admin/pastes/abc", None).await.status(), StatusCode::NO_CONTENT);
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::Body,
+    extract::State,
+    http::{HeaderName, HeaderValue, Method, Request, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tower::ServiceExt;
+
+/// Request headers browsers may send cross-origin, shared by the CORS layer and
+/// the per-route preflight answer.
+pub const ALLOWED_HEADERS: [HeaderName; 7] = [
+    header::CONTENT_TYPE,
+    header::AUTHORIZATION,
+    header::ACCEPT,
+    header::IF_NONE_MATCH,
+    header::ORIGIN,
+    HeaderName::from_static("idempotency-key"),
+    HeaderName::from_static("x-edit-key"),
+];
+
+// Longest preflight cache for admin routes, which come and go with ADMIN_SECRET
+const ADMIN_MAX_AGE_SECS: u64 = 60;
+
+/// What the preflight middleware needs: the API routes without the CORS layer,
+/// the origins CORS allows, and how long browsers may cache an answer.
+pub struct Preflight {
+    routes: Router,
+    origins: Vec<String>,
+    max_age_secs: u64,
+}
+
+impl Preflight {
+    pub fn new(routes: Router, origins: Vec<String>, max_age_secs: u64) -> Arc<Self> {
+        Arc::new(Self { routes, origins, max_age_secs })
+    }
+}
+
+/// Answer `OPTIONS` on `/v1` routes with the methods that route serves.
+///
+/// Goes outside the CORS layer, which would otherwise answer every `OPTIONS` with
+/// the global method list. The request is run through the routes without CORS,
+/// where no handler serves `OPTIONS`, so the router's 405 reports the route's
+/// methods in `Allow`. Known routes get `204` with that `Allow`, plus the CORS
+/// preflight headers when the request is a preflight from an allowed origin.
+/// Anything else goes on to CORS as before.
+pub async fn preflight(State(preflight): State<Arc<Preflight>>, req: Request<Body>, next: Next) -> Response {
+    let path = req.uri().path();
+    if req.method() != Method::OPTIONS || !path.starts_with("/v1/") {
+        return next.run(req).await;
+    }
+    let max_age_secs = if path.starts_with("/v1/admin/") {
+        preflight.max_age_secs.min(ADMIN_MAX_AGE_SECS)
+    } else {
+        preflight.max_age_secs
+    };
+
+    let (parts, body) = req.into_parts();
+    let probe = Request::from_parts(parts.clone(), Body::empty());
+    let routed = match preflight.routes.clone().oneshot(probe).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    };
+    let Some(allow) = routed.headers().get(header::ALLOW).cloned() else {
+        return next.run(Request::from_parts(parts, body)).await;
+    };
+
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::ALLOW, allow.clone());
+    headers.insert(
+        header::VARY,
+        HeaderValue::from_static("origin, access-control-request-method, access-control-request-headers"),
+    );
+
+    let origin = parts
+        .headers
+        .get(header::ORIGIN)
+        .filter(|origin| preflight.origins.iter().any(|allowed| allowed.as_bytes() == origin.as_bytes()));
+    if let Some(origin) = origin
+        && parts.headers.contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+    {
+        let allowed_headers = ALLOWED_HEADERS.iter().map(HeaderName::as_str).collect::<Vec<_>>().join(",");
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+        headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, allow);
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_HEADERS,
+            HeaderValue::from_str(&allowed_headers).expect("header names are valid header values"),
+        );
+        headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age_secs));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get};
+
+    const ORIGIN: &str = "https://rustybin.net";
+
+    fn app() -> Router {
+        let routes = Router::new()
+            .route("/v1/pastes", axum::routing::post(|| async {}).get(|| async {}))
+            .route("/v1/pastes/{id}", get(|| async {}).put(|| async {}).delete(|| async {}))
+            .route("/v1/admin/stats", get(|| async {}));
+        let preflight = Preflight::new(routes.clone(), vec![ORIGIN.to_string()], 600);
+        routes.layer(middleware::from_fn_with_state(preflight, super::preflight))
+    }
+
+    async fn options(path: &str, origin: &str) -> Response {
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri(path)
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(Body::empty())
+            .unwrap();
+        app().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn preflights_advertise_each_routes_own_methods() {
+        for (path, methods, max_age) in [
+            ("/v1/pastes", "POST,GET,HEAD", "600"),
+            ("/v1/pastes/abc123", "GET,HEAD,PUT,DELETE", "600"),
+            ("/v1/admin/stats", "GET,HEAD", "60"),
+        ] {
+            let response = options(path, ORIGIN).await;
+            assert_eq!(response.status(), StatusCode::NO_CONTENT, "{path}");
+            let headers = response.headers();
+            assert_eq!(headers[header::ALLOW], methods, "{path}");
+            assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], methods, "{path}");
+            assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], ORIGIN);
+            assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], max_age, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn other_origins_get_only_the_allow_header() {
+        let response = options("/v1/pastes", "https://evil.example").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[header::ALLOW], "POST,GET,HEAD");
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_MAX_AGE));
+
+        // Unknown paths aren't answered here
+        let response = options("/v1/bogus", ORIGIN).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,155 @@
+use axum::{
+    body::Body,
+    http::{Request, header},
+    middleware::Next,
+    response::Response,
+};
+
+/// Re-indent JSON responses when the request asks for `?pretty=true` (or `1`).
+///
+/// Handlers keep returning compact JSON; only bodies with an `application/json`
+/// content type are rewritten, and their headers (including `Content-Type`) are
+/// kept apart from the recomputed length. Key order is preserved.
+pub async fn pretty_json(req: Request<Body>, next: Next) -> Response {
+    let pretty = req.uri().query().is_some_and(wants_pretty);
+    let response = next.run(req).await;
+    if !pretty || !is_json(&response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("Failed to buffer JSON response for pretty-printing: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(indent(&bytes)))
+}
+
+fn wants_pretty(query: &str) -> bool {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .any(|(key, value)| key == "pretty" && (value == "true" || value == "1"))
+}
+
+fn is_json(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"))
+}
+
+// Lay out compact JSON the way `serde_json::to_string_pretty` does: two-space
+// indent, `": "` after keys, and empty objects or arrays kept on one line.
+// Whitespace outside strings is dropped, so already-indented input is re-laid out.
+fn indent(json: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(json.len() * 2);
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    let newline = |out: &mut Vec<u8>, depth: usize| {
+        out.push(b'\n');
+        out.extend(std::iter::repeat_n(b' ', depth * 2));
+    };
+
+    let mut bytes = json.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        if in_string {
+            out.push(byte);
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => {
+                in_string = true;
+                out.push(byte);
+            }
+            b'{' | b'[' => {
+                out.push(byte);
+                while bytes.next_if(u8::is_ascii_whitespace).is_some() {}
+                if let Some(close @ (b'}' | b']')) = bytes.peek().copied() {
+                    out.push(close);
+                    bytes.next();
+                } else {
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            b'}' | b']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut out, depth);
+                out.push(byte);
+            }
+            b',' => {
+                out.push(byte);
+                newline(&mut out, depth);
+            }
+            b':' => out.extend_from_slice(b": "),
+            b' ' | b'\t' | b'\n' | b'\r' => {}
+            _ => out.push(byte),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::db::Database;
+    use axum::{Json, Router, middleware, routing::get};
+    use tower::ServiceExt;
+
+    async fn fetch(app: &Router, uri: &str) -> (Option<String>, String) {
+        let response = app.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+        let content_type = response.headers().get(header::CONTENT_TYPE).map(|value| value.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (content_type, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn pretty_output_matches_serde_json_for_a_paste() {
+        let options = Config { allow_plaintext_pastes: true, ..Config::default() }.paste_options();
+        let db = Database::in_memory(options).unwrap();
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({
+                "data": "{\"quoted\": [1, 2]}\n\\ end",
+                "encryption_version": 0,
+                "tags": [],
+            })).unwrap())
+            .unwrap();
+        let expected_compact = serde_json::to_string(&paste).unwrap();
+        let expected_pretty = serde_json::to_string_pretty(&paste).unwrap();
+
+        let app = Router::new()
+            .route("/paste", get(move || async move { Json(paste) }))
+            .route("/text", get(|| async { "{\"a\":1}" }))
+            .layer(middleware::from_fn(pretty_json));
+
+        let (content_type, compact) = fetch(&app, "/paste").await;
+        assert_eq!(compact, expected_compact, "compact by default");
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+        assert_eq!(fetch(&app, "/paste?pretty=false").await.1, expected_compact);
+
+        for uri in ["/paste?pretty=true", "/paste?x=1&pretty=1"] {
+            let (content_type, pretty) = fetch(&app, uri).await;
+            assert_eq!(pretty, expected_pretty, "{}", uri);
+            assert_eq!(content_type.as_deref(), Some("application/json"));
+        }
+        assert_eq!(fetch(&app, "/text?pretty=true").await.1, "{\"a\":1}", "only JSON is re-indented");
+    }
+}
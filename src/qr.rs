@@ -0,0 +1,509 @@
+/// A QR code symbol: `size` x `size` modules, row by row, `true` for dark.
+#[derive(Debug)]
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+// Light modules around the symbol, as the standard requires for scanners
+const QUIET_ZONE: usize = 4;
+
+// Blocks in a group and data codewords in each of them
+type BlockGroup = (usize, usize);
+
+// Error correction at level M for versions 1-10: codewords per block, then the
+// short and long block groups. Level M recovers about 15% damage; version 10
+// holds 213 bytes, enough for any paste URL a sane PUBLIC_BASE_URL gives.
+const LEVEL_M_BLOCKS: [(usize, BlockGroup, BlockGroup); 10] = [
+    (10, (1, 16), (0, 0)),
+    (16, (1, 28), (0, 0)),
+    (26, (1, 44), (0, 0)),
+    (18, (2, 32), (0, 0)),
+    (24, (2, 43), (0, 0)),
+    (16, (4, 27), (0, 0)),
+    (18, (4, 31), (0, 0)),
+    (22, (2, 38), (2, 39)),
+    (22, (3, 36), (2, 37)),
+    (26, (4, 43), (1, 44)),
+];
+
+// Centre rows and columns of the alignment patterns, by version
+const ALIGNMENT_POSITIONS: [&[usize]; 10] = [
+    &[],
+    &[6, 18],
+    &[6, 22],
+    &[6, 26],
+    &[6, 30],
+    &[6, 34],
+    &[6, 22, 38],
+    &[6, 24, 42],
+    &[6, 26, 46],
+    &[6, 28, 50],
+];
+
+impl QrCode {
+    /// Encode `data` in byte mode at the smallest version that fits, or None when
+    /// it's longer than version 10 holds.
+    pub fn encode(data: &[u8]) -> Option<Self> {
+        let version = (1..=LEVEL_M_BLOCKS.len()).find(|&version| 4 + count_bits(version) + data.len() * 8 <= data_codewords(version) * 8)?;
+        let codewords = add_error_correction(version, &bit_stream(version, data));
+
+        let mut symbol = Symbol::new(version);
+        symbol.draw_function_patterns();
+        symbol.draw_codewords(&codewords);
+
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                symbol.apply_mask(mask);
+                symbol.draw_format(mask);
+                let penalty = symbol.penalty();
+                symbol.apply_mask(mask);
+                penalty
+            })
+            .expect("there are eight masks");
+        symbol.apply_mask(mask);
+        symbol.draw_format(mask);
+
+        Some(Self { size: symbol.size, modules: symbol.modules })
+    }
+
+    /// The symbol as a square SVG image `pixels` wide, quiet zone included.
+    pub fn svg(&self, pixels: u32) -> String {
+        let side = self.size + 2 * QUIET_ZONE;
+        let mut path = String::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.modules[y * self.size + x] {
+                    path.push_str(&format!("M{},{}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE));
+                }
+            }
+        }
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{pixels}\" height=\"{pixels}\" viewBox=\"0 0 {side} {side}\" shape-rendering=\"crispEdges\">\
+             <rect width=\"100%\" height=\"100%\" fill=\"#fff\"/><path d=\"{path}\" fill=\"#000\"/></svg>\n"
+        )
+    }
+}
+
+fn count_bits(version: usize) -> usize {
+    if version < 10 { 8 } else { 16 }
+}
+
+fn data_codewords(version: usize) -> usize {
+    let (_, (short_blocks, short_len), (long_blocks, long_len)) = LEVEL_M_BLOCKS[version - 1];
+    short_blocks * short_len + long_blocks * long_len
+}
+
+// Mode indicator, length, data, terminator and padding, packed into codewords
+fn bit_stream(version: usize, data: &[u8]) -> Vec<u8> {
+    let capacity = data_codewords(version) * 8;
+    let mut bits: Vec<bool> = Vec::with_capacity(capacity);
+    let mut push = |value: usize, len: usize| bits.extend((0..len).rev().map(|i| (value >> i) & 1 == 1));
+    push(0b0100, 4);
+    push(data.len(), count_bits(version));
+    for &byte in data {
+        push(byte as usize, 8);
+    }
+
+    let terminator = (capacity - bits.len()).min(4);
+    bits.extend(std::iter::repeat_n(false, terminator));
+    bits.resize(bits.len().div_ceil(8) * 8, false);
+
+    let mut codewords: Vec<u8> = bits.chunks(8).map(|byte| byte.iter().fold(0, |acc, &bit| (acc << 1) | bit as u8)).collect();
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if codewords.len() * 8 >= capacity {
+            break;
+        }
+        codewords.push(pad);
+    }
+    codewords
+}
+
+// Split the data into blocks, append each block's Reed-Solomon codewords, and
+// interleave the blocks codeword by codeword
+fn add_error_correction(version: usize, data: &[u8]) -> Vec<u8> {
+    let (ecc_len, (short_blocks, short_len), (long_blocks, long_len)) = LEVEL_M_BLOCKS[version - 1];
+    let divisor = rs_divisor(ecc_len);
+
+    let mut blocks: Vec<(&[u8], Vec<u8>)> = Vec::new();
+    let mut rest = data;
+    for len in std::iter::repeat_n(short_len, short_blocks).chain(std::iter::repeat_n(long_len, long_blocks)) {
+        let (block, tail) = rest.split_at(len);
+        blocks.push((block, rs_remainder(block, &divisor)));
+        rest = tail;
+    }
+
+    let mut out = Vec::new();
+    for i in 0..short_len.max(long_len) {
+        out.extend(blocks.iter().filter_map(|(block, _)| block.get(i)));
+    }
+    for i in 0..ecc_len {
+        out.extend(blocks.iter().map(|(_, ecc)| ecc[i]));
+    }
+    out
+}
+
+// Coefficients of the degree-`degree` generator polynomial, highest power first
+// with its leading 1 left out
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0u8; degree];
+    result[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_mul(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    result
+}
+
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0u8; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (coefficient, &d) in result.iter_mut().zip(divisor) {
+            *coefficient ^= gf_mul(d, factor);
+        }
+    }
+    result
+}
+
+// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z = 0u8;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x1D);
+        z ^= ((y >> i) & 1) * x;
+    }
+    z
+}
+
+// A symbol being drawn, remembering which modules belong to function patterns so
+// data and masks leave them alone
+struct Symbol {
+    version: usize,
+    size: usize,
+    modules: Vec<bool>,
+    function: Vec<bool>,
+}
+
+impl Symbol {
+    fn new(version: usize) -> Self {
+        let size = version * 4 + 17;
+        Self { version, size, modules: vec![false; size * size], function: vec![false; size * size] }
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        // Finders with their separators, in three corners
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4isize..=4 {
+                for dx in -4isize..=4 {
+                    let (x, y) = (cx as isize + dx, cy as isize + dy);
+                    if (0..size as isize).contains(&x) && (0..size as isize).contains(&y) {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set_function(x as usize, y as usize, distance != 2 && distance != 4);
+                    }
+                }
+            }
+        }
+
+        // Alignment patterns everywhere but on top of the finders
+        let positions = ALIGNMENT_POSITIONS[self.version - 1];
+        let last = positions.len().saturating_sub(1);
+        for (i, &cx) in positions.iter().enumerate() {
+            for (j, &cy) in positions.iter().enumerate() {
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in -2isize..=2 {
+                    for dx in -2isize..=2 {
+                        let (x, y) = ((cx as isize + dx) as usize, (cy as isize + dy) as usize);
+                        self.set_function(x, y, dx.abs().max(dy.abs()) != 1);
+                    }
+                }
+            }
+        }
+
+        // Reserve the format areas before data goes in; the real bits come later
+        self.draw_format(0);
+
+        if self.version >= 7 {
+            let mut remainder = self.version;
+            for _ in 0..12 {
+                remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+            }
+            let bits = (self.version << 12) | remainder;
+            for i in 0..18 {
+                let dark = (bits >> i) & 1 == 1;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
+            }
+        }
+    }
+
+    // Both copies of the 15-bit format information for level M and `mask`, plus
+    // the module that is always dark
+    fn draw_format(&mut self, mask: usize) {
+        let data = mask; // level M's two bits are 00
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = ((data << 10) | remainder) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 == 1;
+        let size = self.size;
+
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    // Codeword bits in the zigzag order: two-column strips from the right,
+    // alternating up and down, stepping over the vertical timing pattern
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size;
+        let total_bits = codewords.len() * 8;
+        let mut i = 0;
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vert in 0..size {
+                let y = if upward { size - 1 - vert } else { vert };
+                for x in [right, right - 1] {
+                    if !self.function[y * size + x] && i < total_bits {
+                        self.modules[y * size + x] = (codewords[i / 8] >> (7 - i % 8)) & 1 == 1;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    // XOR the data modules with mask pattern `mask`; applying it twice undoes it
+    fn apply_mask(&mut self, mask: usize) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => (x * y) % 2 + (x * y) % 3 == 0,
+                    6 => ((x * y) % 2 + (x * y) % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + (x * y) % 3) % 2 == 0,
+                };
+                let index = y * self.size + x;
+                if invert && !self.function[index] {
+                    self.modules[index] = !self.modules[index];
+                }
+            }
+        }
+    }
+
+    // The standard's mask penalty: long runs, 2x2 blocks, finder-like patterns
+    // and dark/light imbalance, lower is better
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let at = |x: usize, y: usize| self.modules[y * size + x];
+        let mut penalty = 0;
+
+        for line in 0..size {
+            let row: Vec<bool> = (0..size).map(|x| at(x, line)).collect();
+            let column: Vec<bool> = (0..size).map(|y| at(line, y)).collect();
+            penalty += line_penalty(&row) + line_penalty(&column);
+        }
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let dark = at(x, y);
+                if at(x + 1, y) == dark && at(x, y + 1) == dark && at(x + 1, y + 1) == dark {
+                    penalty += 3;
+                }
+            }
+        }
+
+        let dark = self.modules.iter().filter(|&&dark| dark).count();
+        let percent = dark * 100 / self.modules.len();
+        penalty + percent.abs_diff(50) / 5 * 10
+    }
+}
+
+// Runs of five or more same-coloured modules, and finder-like 1:1:3:1:1 patterns
+// with four light modules on either side
+fn line_penalty(line: &[bool]) -> usize {
+    const FINDER_LIKE: [bool; 11] = [true, false, true, true, true, false, true, false, false, false, false];
+    let mut penalty = 0;
+
+    let mut run = 1;
+    for i in 1..=line.len() {
+        if i < line.len() && line[i] == line[i - 1] {
+            run += 1;
+            continue;
+        }
+        if run >= 5 {
+            penalty += run - 2;
+        }
+        run = 1;
+    }
+
+    for window in line.windows(FINDER_LIKE.len()) {
+        if window == FINDER_LIKE || window.iter().rev().eq(FINDER_LIKE.iter()) {
+            penalty += 40;
+        }
+    }
+    penalty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &[u8] = b"https://paste.example.com/v1/pastes/abc123";
+
+    // Read the 15 format bits back from the copy around the top-left finder
+    fn format_bits(code: &QrCode) -> usize {
+        let at = |x: usize, y: usize| code.modules[y * code.size + x] as usize;
+        let mut positions: Vec<(usize, usize)> = (0..=5).map(|i| (8, i)).collect();
+        positions.extend([(8, 7), (8, 8), (7, 8)]);
+        positions.extend((9..15).map(|i| (14 - i, 8)));
+        positions.iter().enumerate().fold(0, |bits, (i, &(x, y))| bits | (at(x, y) << i))
+    }
+
+    // Codewords read back in the zigzag order from the right, skipping function modules
+    fn read_codewords(symbol: &Symbol) -> Vec<u8> {
+        let size = symbol.size;
+        let mut bits = Vec::new();
+        let mut right = size as isize - 1;
+        let mut upward = true;
+        while right > 0 {
+            if right == 6 {
+                right -= 1;
+            }
+            let rows: Vec<usize> = if upward { (0..size).rev().collect() } else { (0..size).collect() };
+            for y in rows {
+                for x in [right as usize, right as usize - 1] {
+                    if !symbol.function[y * size + x] {
+                        bits.push(symbol.modules[y * size + x]);
+                    }
+                }
+            }
+            upward = !upward;
+            right -= 2;
+        }
+        bits.chunks_exact(8).map(|byte| byte.iter().fold(0, |acc, &bit| (acc << 1) | bit as u8)).collect()
+    }
+
+    #[test]
+    fn reed_solomon_matches_the_standards_example() {
+        // HELLO WORLD as 1-M in the standard's annex: data codewords and their ECC
+        let data = [32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17];
+        assert_eq!(rs_remainder(&data, &rs_divisor(10)), [196, 35, 39, 119, 235, 215, 231, 226, 93, 23]);
+    }
+
+    #[test]
+    fn picks_the_smallest_version_that_fits() {
+        assert_eq!(QrCode::encode(&[b'a'; 14]).unwrap().size, 21);
+        assert_eq!(QrCode::encode(&[b'a'; 15]).unwrap().size, 25);
+        assert_eq!(QrCode::encode(&[b'a'; 213]).unwrap().size, 57);
+        assert!(QrCode::encode(&[b'a'; 214]).is_none());
+    }
+
+    #[test]
+    fn bit_stream_holds_mode_length_data_and_padding() {
+        let codewords = bit_stream(1, b"hi");
+        // 0100, length 00000010, 'h', 'i', terminator 0000, then alternating pads
+        assert_eq!(&codewords[..4], [0x40, 0x26, 0x86, 0x90]);
+        assert_eq!(&codewords[4..8], [0xEC, 0x11, 0xEC, 0x11]);
+        assert_eq!(codewords.len(), data_codewords(1));
+    }
+
+    #[test]
+    fn symbol_has_finders_and_valid_format_information() {
+        let code = QrCode::encode(URL).unwrap();
+        let at = |x: usize, y: usize| code.modules[y * code.size + x];
+        for (left, top) in [(0, 0), (code.size - 7, 0), (0, code.size - 7)] {
+            for dy in 0..7 {
+                for dx in 0..7 {
+                    let ring = dx.min(dy).min(6 - dx).min(6 - dy);
+                    assert_eq!(at(left + dx, top + dy), ring != 1, "finder at ({left}, {top})");
+                }
+            }
+        }
+        assert!(at(8, code.size - 8), "the always-dark module");
+
+        let bits = format_bits(&code) ^ 0x5412;
+        assert_eq!(bits >> 13, 0b00, "error correction level M");
+        let mut remainder = bits >> 10;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        assert_eq!(bits & 0x3FF, remainder, "format BCH code");
+    }
+
+    #[test]
+    fn codewords_read_back_after_unmasking() {
+        for data in [&b"hi"[..], URL, &[b'x'; 200][..]] {
+            let code = QrCode::encode(data).unwrap();
+            let version = (code.size - 17) / 4;
+            let mask = (format_bits(&code) ^ 0x5412) >> 10 & 0b111;
+
+            let mut symbol = Symbol::new(version);
+            symbol.draw_function_patterns();
+            symbol.modules = code.modules.clone();
+            symbol.apply_mask(mask);
+
+            let expected = add_error_correction(version, &bit_stream(version, data));
+            assert_eq!(&read_codewords(&symbol)[..expected.len()], expected, "{} bytes", data.len());
+        }
+    }
+
+    #[test]
+    fn svg_is_the_requested_size_with_a_quiet_zone() {
+        let code = QrCode::encode(b"hi").unwrap();
+        let svg = code.svg(256);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("width=\"256\" height=\"256\""));
+        assert!(svg.contains("viewBox=\"0 0 29 29\""));
+        // The top-left finder's corner module sits just inside the quiet zone
+        assert!(svg.contains("M4,4h1v1h-1z"));
+    }
+}
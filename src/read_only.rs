@@ -0,0 +1,93 @@
+use axum::{
+    Json,
+    body::Body,
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::error::json_error;
+
+/// Reject requests that would write with `503` and code `read_only`, for
+/// `READ_ONLY_MODE`. Reads, and the few non-GET routes that never touch the
+/// database (validation, admin login and logout, read tokens), pass through.
+pub async fn reject_writes(req: Request<Body>, next: Next) -> Response {
+    if !is_write(req.method(), req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let mut body = json_error("Server is in read-only mode");
+    body["code"] = "read_only".into();
+    (StatusCode::SERVICE_UNAVAILABLE, Json(body)).into_response()
+}
+
+fn is_write(method: &Method, path: &str) -> bool {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return false;
+    }
+    let path = path.trim_end_matches('/');
+    let writes_nothing = matches!(path, "/v1/pastes/validate" | "/v1/admin/login" | "/v1/admin/logout")
+        || (path.starts_with("/v1/pastes/") && path.ends_with("/token"));
+    !writes_nothing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::db::Database;
+    use axum::{Router, extract::{Path, State}, middleware, routing::{get, post}};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn read_only_mode_serves_reads_and_refuses_creates() {
+        let dir = std::env::temp_dir().join(format!("rustybin-test-{}", uuid::Uuid::new_v4()));
+        let options = || Config { allow_plaintext_pastes: true, ..Config::default() }.paste_options();
+        let db = Database::new_with_path(&dir, options()).unwrap();
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": "kept", "encryption_version": 0 })).unwrap())
+            .unwrap();
+        drop(db);
+
+        let db = Arc::new(Database::open_read_only(&dir, options(), false).unwrap());
+        let created = Arc::new(AtomicBool::new(false));
+        let app = Router::new()
+            .route(
+                "/v1/pastes/{id}",
+                get(|State(db): State<Arc<Database>>, Path(id): Path<String>| async move {
+                    db.get_paste(&id).unwrap().map(|paste| paste.data).ok_or(StatusCode::NOT_FOUND)
+                }),
+            )
+            .route("/v1/pastes", post({
+                let created = created.clone();
+                move || async move {
+                    created.store(true, Ordering::SeqCst);
+                    StatusCode::CREATED
+                }
+            }))
+            .route("/v1/pastes/validate", post(|| async { StatusCode::OK }))
+            .with_state(db.clone())
+            .layer(middleware::from_fn(reject_writes));
+        let send = |method: Method, uri: String| app.clone().oneshot(Request::builder().method(method).uri(uri).body(Body::empty()).unwrap());
+
+        let read = send(Method::GET, format!("/v1/pastes/{}", paste.id)).await.unwrap();
+        assert_eq!(read.status(), StatusCode::OK);
+        assert_eq!(axum::body::to_bytes(read.into_body(), usize::MAX).await.unwrap(), "kept");
+
+        let refused = send(Method::POST, "/v1/pastes".to_string()).await.unwrap();
+        assert_eq!(refused.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(refused.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["code"], "read_only");
+        assert!(!created.load(Ordering::SeqCst), "the handler never ran");
+        assert_eq!(send(Method::POST, "/v1/pastes/validate".to_string()).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(send(Method::DELETE, format!("/v1/pastes/{}", paste.id)).await.unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // The database itself refuses writes too
+        assert!(db.create_paste(serde_json::from_value(serde_json::json!({ "data": "new", "encryption_version": 0 })).unwrap()).is_err());
+        drop(app);
+        drop(db);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+// Audience of read tokens. Admin session tokens carry none (and read tokens carry
+// no `iat`), so neither kind verifies as the other even under the same secret.
+const READ_TOKEN_AUDIENCE: &str = "paste-read";
+
+/// Errors from checking a read token.
+#[derive(Debug, thiserror::Error)]
+pub enum ReadTokenError {
+    #[error("Invalid read token")]
+    Invalid,
+
+    #[error("Read token has expired")]
+    Expired,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ReadTokenClaims {
+    sub: String,
+    aud: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exp: Option<i64>,
+}
+
+/// Sign a token that grants reading the paste `id`, until `expires_at` if given.
+///
+/// The token is an HMAC-SHA256 JWT keyed by `READ_TOKEN_SECRET`.
+pub fn sign_read_token(secret: &str, id: &str, expires_at: Option<DateTime<Utc>>) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = ReadTokenClaims {
+        sub: id.to_string(),
+        aud: READ_TOKEN_AUDIENCE.to_string(),
+        exp: expires_at.map(|expires_at| expires_at.timestamp()),
+    };
+
+    encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(secret.as_bytes()))
+}
+
+/// Check a read token's signature, then its expiry, and return the paste ID it grants.
+pub fn verify_read_token(secret: &str, token: &str) -> Result<String, ReadTokenError> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_required_spec_claims(&["sub", "aud"]);
+    validation.set_audience(&[READ_TOKEN_AUDIENCE]);
+    validation.leeway = 0;
+
+    match decode::<ReadTokenClaims>(token, &DecodingKey::from_secret(secret.as_bytes()), &validation) {
+        Ok(data) => Ok(data.claims.sub),
+        Err(e) if matches!(e.kind(), ErrorKind::ExpiredSignature) => Err(ReadTokenError::Expired),
+        Err(_) => Err(ReadTokenError::Invalid),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_token_grants_its_paste() {
+        let token = sign_read_token("secret", "abc123", Some(Utc::now() + chrono::Duration::minutes(5))).unwrap();
+        assert_eq!(verify_read_token("secret", &token).unwrap(), "abc123");
+
+        let forever = sign_read_token("secret", "abc123", None).unwrap();
+        assert_eq!(verify_read_token("secret", &forever).unwrap(), "abc123");
+    }
+
+    #[test]
+    fn tampered_or_foreign_token_is_invalid() {
+        let token = sign_read_token("secret", "abc123", None).unwrap();
+        // Another paste's claims under this token's signature
+        let other = sign_read_token("secret", "zzz999", None).unwrap();
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let (claims, _) = other.rsplit_once('.').unwrap();
+        let tampered = format!("{}.{}", claims, signature);
+
+        assert!(matches!(verify_read_token("secret", &tampered), Err(ReadTokenError::Invalid)));
+        assert!(matches!(verify_read_token("other-secret", &token), Err(ReadTokenError::Invalid)));
+        assert!(matches!(verify_read_token("secret", "not.a.token"), Err(ReadTokenError::Invalid)));
+    }
+
+    #[test]
+    fn expired_token_is_expired() {
+        let token = sign_read_token("secret", "abc123", Some(Utc::now() - chrono::Duration::minutes(1))).unwrap();
+        assert!(matches!(verify_read_token("secret", &token), Err(ReadTokenError::Expired)));
+    }
+}
@@ -0,0 +1,96 @@
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderValue, Request, header},
+    middleware::Next,
+    response::Response,
+};
+
+/// CSP for the bundled SPA: its own scripts only, inline styles for the editor and
+/// highlighting, `data:` images, and API calls to this origin or the public API hosts.
+pub const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; script-src 'self'; \
+    style-src 'self' 'unsafe-inline'; img-src 'self' data:; font-src 'self' data:; \
+    connect-src 'self' https://api.rustybin.net https://api.rustyb.in; object-src 'none'; \
+    base-uri 'self'; form-action 'self'; frame-ancestors 'none'";
+
+/// Add browser security headers to static and SPA responses.
+///
+/// `X-Content-Type-Options` and `Referrer-Policy` go on every file; the CSP (when
+/// configured) and `X-Frame-Options` only on HTML documents, where they apply. Only
+/// the static fallback is wrapped, so API JSON responses are left alone.
+pub async fn security_headers(
+    State(content_security_policy): State<Option<HeaderValue>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+
+    let headers = response.headers_mut();
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("strict-origin-when-cross-origin"));
+    if is_html {
+        headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+        if let Some(policy) = content_security_policy {
+            headers.insert(header::CONTENT_SECURITY_POLICY, policy);
+        }
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, middleware, response::Html, routing::get};
+    use tower::ServiceExt;
+
+    // The API plus a static fallback wrapped like the production SPA
+    fn app(policy: Option<HeaderValue>) -> Router {
+        let spa = Router::new()
+            .route("/app.css", get(|| async { ([(header::CONTENT_TYPE, "text/css")], "body {}") }))
+            .fallback(|| async { Html("<!doctype html><div id=\"root\"></div>") })
+            .layer(middleware::from_fn_with_state(policy, security_headers));
+        Router::new()
+            .route("/v1/pastes/{id}", get(|| async { Json(serde_json::json!({ "id": "abc" })) }))
+            .fallback_service(spa)
+    }
+
+    async fn get_headers(app: Router, path: &str) -> axum::http::HeaderMap {
+        app.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap().headers().clone()
+    }
+
+    #[tokio::test]
+    async fn spa_fallback_gets_every_security_header() {
+        let headers = get_headers(app(Some(HeaderValue::from_static(DEFAULT_CONTENT_SECURITY_POLICY))), "/abc123").await;
+        assert_eq!(headers[header::CONTENT_SECURITY_POLICY], DEFAULT_CONTENT_SECURITY_POLICY);
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::REFERRER_POLICY], "strict-origin-when-cross-origin");
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+    }
+
+    #[tokio::test]
+    async fn non_html_files_and_the_api_get_no_document_policy() {
+        let policy = Some(HeaderValue::from_static("default-src 'self'"));
+        let css = get_headers(app(policy.clone()), "/app.css").await;
+        assert_eq!(css[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert!(!css.contains_key(header::CONTENT_SECURITY_POLICY) && !css.contains_key(header::X_FRAME_OPTIONS));
+
+        let api = get_headers(app(policy), "/v1/pastes/abc").await;
+        for name in [header::CONTENT_SECURITY_POLICY, header::X_FRAME_OPTIONS, header::X_CONTENT_TYPE_OPTIONS] {
+            assert!(!api.contains_key(&name), "{} on an API response", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn an_unset_policy_sends_no_csp() {
+        let headers = get_headers(app(None), "/").await;
+        assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+    }
+}
@@ -0,0 +1,101 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    http::{HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+
+tokio::task_local! {
+    // Database time spent by the request being handled on this task
+    static DB_TIME: Cell<Duration>;
+}
+
+/// Add a database operation's duration to the current request's `db` timing.
+/// Does nothing outside a request timed by `server_timing`.
+pub fn record_db_time(elapsed: Duration) {
+    let _ = DB_TIME.try_with(|db_time| db_time.set(db_time.get() + elapsed));
+}
+
+/// Report where a request's time went in a `Server-Timing` header:
+/// `db;dur=1.234, total;dur=5.678`, in milliseconds.
+///
+/// `db` covers the database operations the handler ran itself; work handed to a
+/// blocking thread (maintenance) and streamed bodies read after the handler
+/// returns aren't counted. `total` runs until the response headers are ready.
+pub async fn server_timing(req: Request<Body>, next: Next) -> Response {
+    let started = Instant::now();
+    let (mut response, db_time) = DB_TIME
+        .scope(Cell::new(Duration::ZERO), async {
+            let response = next.run(req).await;
+            (response, DB_TIME.with(Cell::get))
+        })
+        .await;
+
+    let value = format!("db;dur={:.3}, total;dur={:.3}", millis(db_time), millis(started.elapsed()));
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        response.headers_mut().insert(HeaderName::from_static("server-timing"), value);
+    }
+    response
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::db::Database;
+    use axum::{Router, extract::{Path, State}, http::StatusCode, middleware, routing::get};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    // Each `name;dur=<ms>` entry of a Server-Timing header
+    fn durations(header: &str) -> Vec<(String, f64)> {
+        header
+            .split(", ")
+            .map(|entry| {
+                let (name, duration) = entry.split_once(";dur=").unwrap();
+                (name.to_string(), duration.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn get_requests_report_db_and_total_time() {
+        let db = Arc::new(Database::in_memory(Config { allow_plaintext_pastes: true, ..Config::default() }.paste_options()).unwrap());
+        let paste = db
+            .create_paste(serde_json::from_value(serde_json::json!({ "data": "timed", "encryption_version": 0 })).unwrap())
+            .unwrap();
+        let app = Router::new()
+            .route(
+                "/v1/pastes/{id}",
+                get(|State(db): State<Arc<Database>>, Path(id): Path<String>| async move {
+                    db.get_paste(&id).unwrap().map(|paste| paste.data).ok_or(StatusCode::NOT_FOUND)
+                }),
+            )
+            .route("/v1/health", get(|| async { "ok" }))
+            .with_state(db)
+            .layer(middleware::from_fn(server_timing));
+        let header = |uri: String| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                response.headers()["server-timing"].to_str().unwrap().to_string()
+            }
+        };
+
+        let timings = durations(&header(format!("/v1/pastes/{}", paste.id)).await);
+        assert_eq!(timings.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), ["db", "total"]);
+        let (db_ms, total_ms) = (timings[0].1, timings[1].1);
+        assert!(db_ms > 0.0 && total_ms >= db_ms, "db {} total {}", db_ms, total_ms);
+
+        let timings = durations(&header("/v1/health".to_string()).await);
+        assert_eq!(timings[0], ("db".to_string(), 0.0), "no database work");
+    }
+}